@@ -0,0 +1,70 @@
+//! Compares resolving the same arguments for many steps from a layered type
+//! map with and without a `ResolutionCache`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use imperat_common::{Dep, DepMut, FromTypeMap, ResolutionCache, TypeMap};
+use std::hint::black_box;
+
+struct Client;
+struct Config;
+struct Metrics;
+struct Secrets;
+struct Store;
+struct Tracer;
+
+type Args = (
+    Dep<Client>,
+    Dep<Config>,
+    Dep<Metrics>,
+    Dep<Secrets>,
+    Dep<Store>,
+    Dep<Tracer>,
+    DepMut<Vec<u8>>,
+    Option<Dep<u64>>,
+);
+
+// How many steps resolve the same arguments.
+const STEPS: usize = 500;
+
+/// A run's dependencies, padded with unrelated ones, with a group's
+/// dependencies layered over them.
+fn type_map() -> TypeMap {
+    let mut run = TypeMap::new();
+    run.bind(Dep::new(Client));
+    run.bind(Dep::new(Config));
+    run.bind(Dep::new(Metrics));
+    run.bind(DepMut::new(Vec::<u8>::new()));
+    run.bind(Dep::new(0_u8));
+    run.bind(Dep::new(0_u16));
+    run.bind(Dep::new(0_u32));
+    let mut group = TypeMap::new();
+    group.bind(Dep::new(Secrets));
+    group.bind(Dep::new(Store));
+    group.bind(Dep::new(Tracer));
+    group.layer_over(&run)
+}
+
+fn resolution(c: &mut Criterion) {
+    let tm = type_map();
+    let mut group = c.benchmark_group(format!("resolve {STEPS} steps"));
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for _ in 0..STEPS {
+                black_box(Args::retrieve_from_map(black_box(&tm)));
+            }
+        });
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let cache = ResolutionCache::new();
+            for _ in 0..STEPS {
+                cache.set_version(tm.version());
+                black_box(cache.resolve::<Args>(black_box(&tm)));
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, resolution);
+criterion_main!(benches);
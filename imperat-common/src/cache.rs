@@ -0,0 +1,137 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Mutex,
+};
+
+/// Caches values resolved from a type map by their type, so resolving the
+/// same types over and over, such as for many steps with the same
+/// arguments, skips looking up and downcasting each of them again. Only
+/// values which can be shared are cached; see `FromTypeMap::share`.
+///
+/// Everything cached is dropped once the map's version changes. See
+/// `TypeMap::version`.
+#[derive(Default)]
+pub struct ResolutionCache(Mutex<Cached>);
+
+#[derive(Default)]
+struct Cached {
+    // the version of the type map `values` were resolved from
+    version: u64,
+    values: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl std::fmt::Debug for ResolutionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cached = self.0.lock().expect("resolution cache mutex poisoned");
+        f.debug_struct("ResolutionCache")
+            .field("version", &cached.version)
+            .field("values", &cached.values.len())
+            .finish()
+    }
+}
+
+impl ResolutionCache {
+    /// Creates a new, empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves a `T` from `tm`, like `FromTypeMap::retrieve_from_map`, but
+    /// shares the value cached for `T` if there is one, and otherwise caches
+    /// it if it can be shared. Nothing is cached while `tm` records its
+    /// lookups, so every lookup is recorded.
+    ///
+    /// # Panics
+    /// If the cache's mutex is poisoned.
+    pub fn resolve<T: FromTypeMap>(&self, tm: &TypeMap) -> Option<T> {
+        if tm.is_recording() {
+            return T::retrieve_from_map(tm);
+        }
+        let id = TypeId::of::<T>();
+        let cached = self.0.lock().expect("resolution cache mutex poisoned");
+        if let Some(val) = cached.values.get(&id).and_then(|v| v.downcast_ref::<T>()) {
+            return val.share();
+        }
+        drop(cached);
+
+        let val = T::retrieve_from_map(tm)?;
+        if let Some(shared) = val.share() {
+            self.0
+                .lock()
+                .expect("resolution cache mutex poisoned")
+                .values
+                .insert(id, Box::new(shared));
+        }
+        Some(val)
+    }
+
+    /// Drops everything cached unless it was resolved from `version` of a
+    /// type map. Call this with a map's version before resolving from it, or
+    /// from a map layered over it which only binds values which are never
+    /// shared.
+    ///
+    /// # Panics
+    /// If the cache's mutex is poisoned.
+    pub fn set_version(&self, version: u64) {
+        let mut cached = self.0.lock().expect("resolution cache mutex poisoned");
+        if cached.version != version {
+            cached.version = version;
+            cached.values.clear();
+        }
+    }
+
+    /// Returns how many values are cached.
+    ///
+    /// # Panics
+    /// If the cache's mutex is poisoned.
+    pub fn len(&self) -> usize {
+        self.0
+            .lock()
+            .expect("resolution cache mutex poisoned")
+            .values
+            .len()
+    }
+
+    /// Returns whether nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dep, DepOrDefault};
+    use std::sync::Arc;
+
+    // shared values should be cached until the map changes, and others
+    // resolved afresh each time
+    #[test]
+    fn test_resolution_cache() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(1_i32));
+        let cache = ResolutionCache::new();
+        cache.set_version(tm.version());
+
+        let (a, _) = cache.resolve::<(Dep<i32>, Option<Dep<u8>>)>(&tm).unwrap();
+        assert_eq!(cache.len(), 1);
+        let (b, _) = cache.resolve::<(Dep<i32>, Option<Dep<u8>>)>(&tm).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        cache.resolve::<DepOrDefault<u32>>(&tm).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        cache.set_version(tm.version());
+        assert_eq!(cache.len(), 1);
+        tm.bind(Dep::new(2_i32));
+        cache.set_version(tm.version());
+        assert!(cache.is_empty());
+        assert_eq!(**cache.resolve::<Dep<i32>>(&tm).unwrap(), 2);
+
+        tm.record_accesses(true);
+        cache.resolve::<Dep<u8>>(&tm);
+        assert_eq!(tm.take_accesses(), vec![crate::DepInfo::of::<Dep<u8>>()]);
+    }
+}
@@ -2,7 +2,10 @@ use std::{
     any::{Any, TypeId},
     collections::HashMap,
     ops::Deref,
-    sync::Arc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 use variadics_please::all_tuples;
 
@@ -10,9 +13,37 @@ use variadics_please::all_tuples;
 /// <https://nickbryan.co.uk/software/using-a-type-map-for-dependency-injection-in-rust/>
 /// A `TypeMap` uniquely stores an arbitrary value by its type. No types
 /// can store more than one value.
+///
+/// Cloning a type map is cheap: clones share their bindings, and only copy
+/// them, shallowly, when one of them binds or removes a value. Bound values
+/// themselves are never copied.
+///
+/// Type maps may be layered over a parent with `layer_over`, falling back to
+/// the parent for any type they don't bind themselves.
 #[derive(Default, Debug)]
 pub struct TypeMap {
-    bindings: HashMap<TypeId, Box<dyn Any>>,
+    bindings: Arc<HashMap<TypeId, Binding>>,
+    // consulted for types not bound in this map
+    parent: Option<Arc<TypeMap>>,
+    // lookups since they were last taken, if recording
+    accesses: Option<Mutex<Vec<DepInfo>>>,
+    // changed whenever `bindings` is; see `version`
+    version: u64,
+}
+
+// Versions are unique across every type map, so a change to any of them is
+// never mistaken for another.
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+// A bound value, with its type's name for diagnostics.
+#[derive(Clone, Debug)]
+struct Binding {
+    name: &'static str,
+    value: Arc<dyn Any + Send + Sync>,
 }
 
 impl TypeMap {
@@ -23,25 +54,243 @@ impl TypeMap {
 
     /// Binds the given value to its type in the type map. If an
     /// existing value for this type exists, it's returned. An existing value
-    /// with an incorrect type is returned as none.
-    pub fn bind<T: Any>(&mut self, val: T) -> Option<Box<T>> {
-        self.bindings
-            .insert(val.type_id(), Box::new(val))
-            .and_then(|v| v.downcast().ok())
+    /// with an incorrect type is returned as none. Values may still be shared
+    /// with clones of this type map, so they're returned behind an `Arc`.
+    /// Type maps are sent to whichever thread runs a step, so values must be
+    /// `Send + Sync`.
+    pub fn bind<T: Any + Send + Sync>(&mut self, val: T) -> Option<Arc<T>> {
+        self.bind_arc(Arc::new(val))
+    }
+
+    /// Like `bind`, but binds a value which may already be shared, such as
+    /// one returned by `bind` or `remove`, so it can be put back as it was.
+    pub fn bind_arc<T: Any + Send + Sync>(&mut self, val: Arc<T>) -> Option<Arc<T>> {
+        let binding = Binding {
+            name: std::any::type_name::<T>(),
+            value: val,
+        };
+        self.version = next_version();
+        Arc::make_mut(&mut self.bindings)
+            .insert(TypeId::of::<T>(), binding)
+            .and_then(|b| b.value.downcast().ok())
+    }
+
+    /// Removes and returns the value for this unique type, if present. Values
+    /// in a parent map are never removed, and may still be returned by `get`.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<Arc<T>> {
+        self.version = next_version();
+        Arc::make_mut(&mut self.bindings)
+            .remove(&TypeId::of::<T>())
+            .and_then(|b| b.value.downcast().ok())
+    }
+
+    /// Removes and returns the value for this unique type by value, if this
+    /// map is its only owner. If a clone of this map still shares it, it stays
+    /// bound and none is returned; use `remove` to share it instead.
+    pub fn take<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        let value = &self.bindings.get(&TypeId::of::<T>())?.value;
+        // A clone may share the whole map, or just this value.
+        if Arc::strong_count(&self.bindings) > 1 || Arc::strong_count(value) > 1 {
+            return None;
+        }
+        Arc::try_unwrap(self.remove()?).ok()
+    }
+
+    /// Binds `val` only while `f` runs with this map, such as to swap in a
+    /// transaction for one group, then puts back whatever was bound to its
+    /// type before, if anything.
+    pub fn scoped<T: Any + Send + Sync, R>(
+        &mut self,
+        val: T,
+        f: impl FnOnce(&mut TypeMap) -> R,
+    ) -> R {
+        let id = TypeId::of::<T>();
+        let prev = self.bindings.get(&id).cloned();
+        self.bind(val);
+        let res = f(self);
+        self.version = next_version();
+        let bindings = Arc::make_mut(&mut self.bindings);
+        match prev {
+            Some(prev) => bindings.insert(id, prev),
+            None => bindings.remove(&id),
+        };
+        res
     }
 
     /// Returns the value in this type map for this unique type.
     pub fn get<T: Any>(&self) -> Option<&T> {
+        if let Some(accesses) = &self.accesses {
+            accesses
+                .lock()
+                .expect("typemap access mutex poisoned")
+                .push(DepInfo::of::<T>());
+        }
+        self.lookup()
+    }
+
+    /// Returns whether a `T` is bound in this map or any parent, without
+    /// recording an access.
+    pub fn contains<T: Any>(&self) -> bool {
+        self.contains_id(TypeId::of::<T>())
+    }
+
+    /// Returns whether a value with this type is bound in this map or any
+    /// parent, without recording an access.
+    pub fn contains_id(&self, id: TypeId) -> bool {
+        self.bindings.contains_key(&id) || self.parent.as_ref().is_some_and(|p| p.contains_id(id))
+    }
+
+    /// Returns how many types are bound in this map or any parent. Types
+    /// bound in both are counted once.
+    pub fn len(&self) -> usize {
+        self.keys().len()
+    }
+
+    /// Describes every type bound in this map or any parent, sorted by name.
+    /// Types bound in both are listed once.
+    pub fn keys(&self) -> Vec<DepInfo> {
+        let mut keys = self.parent.as_ref().map_or_else(Vec::new, |p| p.keys());
+        keys.retain(|k| !self.bindings.contains_key(&k.id));
+        keys.extend(
+            self.bindings
+                .iter()
+                .map(|(&id, b)| DepInfo { id, name: b.name }),
+        );
+        keys.sort_by_key(|k| k.name);
+        keys
+    }
+
+    /// Lists the name of every type bound in this map or any parent, one per
+    /// line and sorted, to diagnose why a dependency can't be resolved.
+    pub fn dump(&self) -> String {
+        self.keys()
+            .iter()
+            .map(|k| format!("{}\n", k.name))
+            .collect()
+    }
+
+    fn lookup<T: Any>(&self) -> Option<&T> {
         self.bindings
             .get(&TypeId::of::<T>())
-            .and_then(|boxed| boxed.downcast_ref())
+            .and_then(|b| b.value.downcast_ref())
+            .or_else(|| self.parent.as_ref()?.lookup())
+    }
+
+    /// Returns a type map which binds everything this one does, shadowing
+    /// any value of the same type in `parent`, and otherwise falls back to
+    /// `parent`. The returned map shares its bindings with both; binding or
+    /// removing values in it never changes them. It records accesses if
+    /// `parent` does.
+    #[must_use]
+    pub fn layer_over(&self, parent: &TypeMap) -> TypeMap {
+        let parent = match &self.parent {
+            Some(own) => own.layer_over(parent),
+            None => parent.clone(),
+        };
+        TypeMap {
+            bindings: self.bindings.clone(),
+            accesses: parent.accesses.as_ref().map(|_| Mutex::default()),
+            parent: Some(Arc::new(parent)),
+            version: self.version,
+        }
+    }
+
+    /// Returns a number which changes whenever a value is bound or removed in
+    /// this map or any parent, so values resolved from it may be cached until
+    /// it does. See `ResolutionCache`.
+    pub fn version(&self) -> u64 {
+        let own = self.version;
+        self.parent.as_ref().map_or(own, |p| own.max(p.version()))
+    }
+
+    /// Returns whether lookups are being recorded. See `record_accesses`.
+    pub(crate) fn is_recording(&self) -> bool {
+        self.accesses.is_some()
+    }
+
+    /// Returns whether nothing is bound in this map or any parent.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty() && self.parent.as_ref().is_none_or(|p| p.is_empty())
+    }
+
+    /// Starts or stops recording every lookup made with `get`, whether or not
+    /// the type was present. Stopping discards anything not yet taken.
+    pub fn record_accesses(&mut self, enabled: bool) {
+        self.accesses = enabled.then(Mutex::default);
+    }
+
+    /// Returns every lookup recorded since the last call, oldest first.
+    /// Always empty unless recording with `record_accesses`.
+    ///
+    /// # Panics
+    /// If the access mutex is poisoned.
+    pub fn take_accesses(&self) -> Vec<DepInfo> {
+        self.accesses.as_ref().map_or_else(Vec::new, |accesses| {
+            std::mem::take(&mut *accesses.lock().expect("typemap access mutex poisoned"))
+        })
+    }
+}
+
+impl Clone for TypeMap {
+    /// Clones this type map, sharing its bindings. Recording, if enabled,
+    /// starts afresh in the clone.
+    fn clone(&self) -> Self {
+        Self {
+            bindings: self.bindings.clone(),
+            parent: self.parent.clone(),
+            accesses: self.accesses.as_ref().map(|_| Mutex::default()),
+            version: self.version,
+        }
     }
 }
 
 /// A type which can be retrieved from a type map. Its type signature
-/// uniquely stores the type in the map.
-pub trait FromTypeMap: Any + Sized {
+/// uniquely stores the type in the map. It's sent along with its step to
+/// whichever thread runs it, so it must be `Send`.
+pub trait FromTypeMap: Any + Sized + Send {
     fn retrieve_from_map(tm: &TypeMap) -> Option<Self>;
+
+    /// Records every dependency this type resolves from a type map.
+    /// By default, that's just this type.
+    fn dependencies(deps: &mut Vec<DepInfo>) {
+        deps.push(DepInfo::of::<Self>());
+    }
+
+    /// Returns the first parameter which can't be retrieved from a type map,
+    /// by its position from 1. By default, this type is a single parameter.
+    fn missing(tm: &TypeMap) -> Option<(usize, DepInfo)> {
+        Self::retrieve_from_map(tm)
+            .is_none()
+            .then(|| (1, DepInfo::of::<Self>()))
+    }
+
+    /// Returns a copy of this value for anything else resolving this type
+    /// from the same type map, so a `ResolutionCache` can skip retrieving it
+    /// again. Values which must be retrieved afresh each time, such as
+    /// those specific to one step or created on retrieval, can't be shared.
+    /// By default, nothing is.
+    fn share(&self) -> Option<Self> {
+        None
+    }
+}
+
+/// Describes a single dependency resolved from a type map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DepInfo {
+    /// The unique type of the dependency.
+    pub id: TypeId,
+    /// The type's name, for diagnostics only. See `std::any::type_name`.
+    pub name: &'static str,
+}
+
+impl DepInfo {
+    /// Describes the dependency with type `T`.
+    pub fn of<T: Any>() -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
 }
 
 // Fans out an implementation for 0 to 16-tuple of generics of FromTypeMap. Allows
@@ -58,6 +307,7 @@ macro_rules! impl_fromtypemap_tuples {
         )]
         #[allow(
             unused_variables,
+            unused_mut,
             reason = "Zero-length tuples won't use some of the parameters."
         )]
         #[expect(
@@ -72,12 +322,55 @@ macro_rules! impl_fromtypemap_tuples {
                     )*))
                 )
             }
+
+            fn dependencies(deps: &mut Vec<DepInfo>) {
+                $(
+                    $param::dependencies(deps);
+                )*
+            }
+
+            fn missing(tm: &TypeMap) -> Option<(usize, DepInfo)> {
+                let mut index = 0;
+                $(
+                    index += 1;
+                    if $param::retrieve_from_map(tm).is_none() {
+                        return Some((index, DepInfo::of::<$param>()));
+                    }
+                )*
+
+                None
+            }
+
+            fn share(&self) -> Option<Self> {
+                let ($($param,)*) = self;
+                Some(($($param.share()?,)*))
+            }
         }
     }
 }
 
 all_tuples!(impl_fromtypemap_tuples, 0, 16, F);
 
+/// An optional parameter: always retrievable, and `None` when the inner type
+/// can't be retrieved. Its dependencies are still recorded, so a step taking
+/// one is still ordered after the step which binds it.
+impl<T: FromTypeMap> FromTypeMap for Option<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(T::retrieve_from_map(tm))
+    }
+
+    fn dependencies(deps: &mut Vec<DepInfo>) {
+        T::dependencies(deps);
+    }
+
+    fn share(&self) -> Option<Self> {
+        match self {
+            Some(val) => Some(Some(val.share()?)),
+            None => Some(None),
+        }
+    }
+}
+
 /// A dependency which can be automatically resolved at runtime
 /// by its unique type.
 pub struct Dep<T: ?Sized>(Arc<T>);
@@ -109,10 +402,159 @@ impl<T: ?Sized> Deref for Dep<T> {
     }
 }
 
-impl<T: ?Sized + 'static> FromTypeMap for Dep<T> {
+impl<T: ?Sized + Send + Sync + 'static> FromTypeMap for Dep<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+
+    fn share(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+/// Shared state which steps can modify, unlike a `Dep<T>`. Add one with
+/// `add_dep` and request it like any other dependency; every step gets
+/// the same state.
+///
+/// The state is behind an async-aware lock. In parallel groups, steps take
+/// turns holding it, so a read-modify-write under one lock is never
+/// interleaved with another step's. Holding the lock across an `.await`
+/// makes other steps wait for it; prefer `update` for short changes.
+pub struct DepMut<T>(Arc<tokio::sync::Mutex<T>>);
+
+impl<T> DepMut<T> {
+    /// Create new shared state for injection.
+    pub fn new(val: T) -> DepMut<T> {
+        DepMut(Arc::new(tokio::sync::Mutex::new(val)))
+    }
+
+    /// Waits for and takes the lock on this state, releasing it when the
+    /// guard is dropped.
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, T> {
+        self.0.lock().await
+    }
+
+    /// Modifies the state with `f` under the lock, returning its result.
+    pub async fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut *self.lock().await)
+    }
+
+    /// Returns a copy of the state as it is now.
+    pub async fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.lock().await.clone()
+    }
+}
+
+impl<T> Clone for DepMut<T> {
+    fn clone(&self) -> Self {
+        DepMut(self.0.clone())
+    }
+}
+
+impl<T: Send + 'static> FromTypeMap for DepMut<T> {
     fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
         tm.get::<Self>().cloned()
     }
+
+    fn share(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+}
+
+/// A dependency which falls back to `T::default()` when nothing is bound
+/// for it.
+pub struct DepOrDefault<T>(Dep<T>);
+
+impl<T> DepOrDefault<T> {
+    /// Yields the inner dependency, destroying the outer wrapper.
+    #[must_use]
+    pub fn inner(self) -> Dep<T> {
+        self.0
+    }
+}
+
+impl<T> Clone for DepOrDefault<T> {
+    fn clone(&self) -> Self {
+        DepOrDefault(self.0.clone())
+    }
+}
+
+impl<T> Deref for DepOrDefault<T> {
+    type Target = Arc<T>;
+
+    fn deref(&self) -> &Arc<T> {
+        &self.0
+    }
+}
+
+impl<T: Default + Send + Sync + 'static> FromTypeMap for DepOrDefault<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(DepOrDefault(
+            Dep::retrieve_from_map(tm).unwrap_or_else(|| Dep::new(T::default())),
+        ))
+    }
+
+    fn dependencies(deps: &mut Vec<DepInfo>) {
+        Dep::<T>::dependencies(deps);
+    }
+}
+
+/// A dependency which is preferred but not required: what's bound for it,
+/// or `T::default()` when nothing is. Like `DepOrDefault`, but it also
+/// says which it is, such as to only warm a cache which was configured.
+pub struct Preferred<T> {
+    dep: Dep<T>,
+    bound: bool,
+}
+
+impl<T> Preferred<T> {
+    /// Returns whether the dependency was bound, rather than defaulted.
+    #[must_use]
+    pub fn is_bound(&self) -> bool {
+        self.bound
+    }
+
+    /// Yields the inner dependency, destroying the outer wrapper.
+    #[must_use]
+    pub fn inner(self) -> Dep<T> {
+        self.dep
+    }
+}
+
+impl<T> Clone for Preferred<T> {
+    fn clone(&self) -> Self {
+        Preferred {
+            dep: self.dep.clone(),
+            bound: self.bound,
+        }
+    }
+}
+
+impl<T> Deref for Preferred<T> {
+    type Target = Arc<T>;
+
+    fn deref(&self) -> &Arc<T> {
+        &self.dep
+    }
+}
+
+impl<T: Default + Send + Sync + 'static> FromTypeMap for Preferred<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(match Dep::retrieve_from_map(tm) {
+            Some(dep) => Preferred { dep, bound: true },
+            None => Preferred {
+                dep: Dep::new(T::default()),
+                bound: false,
+            },
+        })
+    }
+
+    fn dependencies(deps: &mut Vec<DepInfo>) {
+        Dep::<T>::dependencies(deps);
+    }
 }
 
 #[cfg(test)]
@@ -145,5 +587,247 @@ mod tests {
     fn test_missing() {
         let tm = TypeMap::new();
         assert!(tm.get::<Dep<i32>>().is_none());
+        assert!(!tm.contains::<Dep<i32>>());
+        assert!(!tm.contains_id(TypeId::of::<Dep<i32>>()));
+    }
+
+    // removed values should be returned and then absent
+    #[test]
+    fn test_remove() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Config(2, 3)));
+
+        let cfg = tm.remove::<Dep<Config>>().unwrap();
+        assert_eq!(cfg.0.0, 2);
+        assert!(tm.get::<Dep<Config>>().is_none());
+        assert!(tm.remove::<Dep<Config>>().is_none());
+    }
+
+    // clones should share bindings until either changes
+    #[test]
+    fn test_clone_on_write() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Config(2, 3)));
+
+        let mut scoped = tm.clone();
+        assert!(Arc::ptr_eq(&tm.bindings, &scoped.bindings));
+
+        scoped.bind(Dep::new(Database));
+        scoped.bind(Dep::new(Config(4, 5)));
+        assert!(!Arc::ptr_eq(&tm.bindings, &scoped.bindings));
+        assert!(tm.get::<Dep<Database>>().is_none());
+        assert_eq!(tm.get::<Dep<Config>>().unwrap().0.0, 2);
+        assert_eq!(scoped.get::<Dep<Config>>().unwrap().0.0, 4);
+
+        // untouched bindings are never copied
+        let mut other = tm.clone();
+        other.bind(Dep::new(Database));
+        assert!(Arc::ptr_eq(
+            &tm.bindings[&TypeId::of::<Dep<Config>>()].value,
+            &other.bindings[&TypeId::of::<Dep<Config>>()].value,
+        ));
+    }
+
+    // tuples should report their first missing parameter
+    #[test]
+    fn test_missing_parameter() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Database));
+
+        assert_eq!(<(Dep<Database>,)>::missing(&tm), None);
+        assert_eq!(
+            <(Dep<Database>, Dep<Config>, Dep<i32>)>::missing(&tm),
+            Some((2, DepInfo::of::<Dep<Config>>()))
+        );
+    }
+
+    // optional parameters should always resolve, and still record their dependencies
+    #[test]
+    fn test_optional() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Database));
+
+        let (db, cfg) =
+            <(Option<Dep<Database>>, Option<Dep<Config>>)>::retrieve_from_map(&tm).unwrap();
+        assert!(db.is_some());
+        assert!(cfg.is_none());
+        assert_eq!(<(Option<Dep<Config>>,)>::missing(&tm), None);
+
+        let mut deps = vec![];
+        <Option<Dep<Config>>>::dependencies(&mut deps);
+        assert_eq!(deps, vec![DepInfo::of::<Dep<Config>>()]);
+    }
+
+    // layered maps should shadow their parent and otherwise fall back to it
+    #[test]
+    fn test_layer_over() {
+        let mut parent = TypeMap::new();
+        parent.bind(Dep::new(Database));
+        parent.bind(Dep::new(Config(2, 3)));
+        parent.record_accesses(true);
+        let mut group = TypeMap::new();
+        group.bind(Dep::new(Config(4, 5)));
+
+        let mut layered = group.layer_over(&parent);
+        assert!(layered.get::<Dep<Database>>().is_some());
+        assert_eq!(layered.get::<Dep<Config>>().unwrap().0.0, 4);
+        assert_eq!(
+            layered.take_accesses(),
+            vec![DepInfo::of::<Dep<Database>>(), DepInfo::of::<Dep<Config>>()]
+        );
+
+        // changes stay in their own layer
+        layered.bind(Dep::new(7));
+        assert!(layered.remove::<Dep<Config>>().is_some());
+        assert_eq!(layered.get::<Dep<Config>>().unwrap().0.0, 2);
+        assert!(parent.get::<Dep<i32>>().is_none());
+        assert_eq!(group.get::<Dep<Config>>().unwrap().0.0, 4);
+    }
+
+    // values should only be taken when no clone shares them
+    #[test]
+    fn test_take() {
+        let mut tm = TypeMap::new();
+        tm.bind(Config(2, 3));
+
+        let shared = tm.clone();
+        assert!(tm.take::<Config>().is_none());
+        assert!(tm.contains::<Config>());
+        drop(shared);
+
+        // the bindings themselves may be shared without sharing the value
+        let mut other = tm.clone();
+        other.bind(Dep::new(Database));
+        drop(tm);
+        assert_eq!(other.take::<Config>().unwrap().0, 2);
+        assert!(!other.contains::<Config>());
+        assert!(other.take::<Config>().is_none());
+    }
+
+    // scoped values should only be bound while the scope runs
+    #[test]
+    fn test_scoped() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Config(2, 3)));
+        let prev = tm.get::<Dep<Config>>().unwrap().clone();
+
+        let seen = tm.scoped(Dep::new(Config(4, 5)), |tm| {
+            tm.scoped(Dep::new(Database), |tm| tm.contains::<Dep<Database>>())
+                && tm.get::<Dep<Config>>().unwrap().0.0 == 4
+        });
+        assert!(seen);
+        assert!(!tm.contains::<Dep<Database>>());
+        // the previous value is put back, not a copy of it
+        assert!(Arc::ptr_eq(&tm.get::<Dep<Config>>().unwrap().0, &prev.0));
+
+        let old = tm.remove::<Dep<Config>>().unwrap();
+        tm.bind_arc(old.clone());
+        assert!(Arc::ptr_eq(&tm.get::<Dep<Config>>().unwrap().0, &old.0));
+    }
+
+    // keys should list every bound type once, including a parent's
+    #[test]
+    fn test_keys() {
+        let mut parent = TypeMap::new();
+        parent.bind(Dep::new(Database));
+        parent.bind(Dep::new(Config(2, 3)));
+        let mut group = TypeMap::new();
+        group.bind(Dep::new(Config(4, 5)));
+        group.bind(7_u8);
+
+        let layered = group.layer_over(&parent);
+        assert_eq!(layered.len(), 3);
+        assert!(layered.contains::<Dep<Database>>());
+        assert!(layered.contains::<u8>());
+        assert_eq!(
+            layered.keys(),
+            vec![
+                DepInfo::of::<Dep<Config>>(),
+                DepInfo::of::<Dep<Database>>(),
+                DepInfo::of::<u8>(),
+            ]
+        );
+        assert_eq!(
+            layered.dump(),
+            format!(
+                "{}\n{}\nu8\n",
+                std::any::type_name::<Dep<Config>>(),
+                std::any::type_name::<Dep<Database>>()
+            )
+        );
+        // nothing was looked up
+        assert!(layered.take_accesses().is_empty());
+    }
+
+    // mutable dependencies should share their state with every retrieval
+    #[test]
+    fn test_dep_mut() {
+        let mut tm = TypeMap::new();
+        tm.bind(DepMut::new(Config(2, 3)));
+
+        let first = DepMut::<Config>::retrieve_from_map(&tm).unwrap();
+        first.0.blocking_lock().0 = 4;
+        let second = DepMut::<Config>::retrieve_from_map(&tm).unwrap();
+        assert_eq!(second.0.blocking_lock().0, 4);
+    }
+
+    // defaulted dependencies should prefer what's bound
+    #[test]
+    fn test_dep_or_default() {
+        let mut tm = TypeMap::new();
+        assert_eq!(**DepOrDefault::<i32>::retrieve_from_map(&tm).unwrap(), 0);
+
+        tm.bind(Dep::new(5));
+        assert_eq!(**DepOrDefault::<i32>::retrieve_from_map(&tm).unwrap(), 5);
+    }
+
+    // preferred dependencies should say whether they were bound
+    #[test]
+    fn test_preferred() {
+        let mut tm = TypeMap::new();
+        let fallback = Preferred::<i32>::retrieve_from_map(&tm).unwrap();
+        assert_eq!((**fallback, fallback.is_bound()), (0, false));
+
+        tm.bind(Dep::new(5));
+        let bound = Preferred::<i32>::retrieve_from_map(&tm).unwrap();
+        assert_eq!((**bound, bound.is_bound()), (5, true));
+    }
+
+    // lookups should only be recorded while enabled
+    #[test]
+    fn test_record_accesses() {
+        let mut tm = TypeMap::new();
+        tm.bind(Dep::new(Database));
+        tm.get::<Dep<Database>>();
+        assert!(tm.take_accesses().is_empty());
+
+        tm.record_accesses(true);
+        tm.get::<Dep<Database>>();
+        tm.get::<Dep<i32>>();
+        assert_eq!(
+            tm.take_accesses(),
+            vec![DepInfo::of::<Dep<Database>>(), DepInfo::of::<Dep<i32>>()]
+        );
+        assert!(tm.take_accesses().is_empty());
+    }
+
+    // versions should change with any binding, in the map or its parent
+    #[test]
+    fn test_version() {
+        let mut parent = TypeMap::new();
+        parent.bind(Dep::new(Database));
+        let mut child = TypeMap::new();
+        child.bind(Dep::new(5));
+
+        let layered = child.layer_over(&parent);
+        let version = layered.version();
+        assert_eq!(child.layer_over(&parent).version(), version);
+        assert_eq!(parent.clone().version(), parent.version());
+
+        parent.bind(Dep::new(Config(1, 2)));
+        assert_ne!(child.layer_over(&parent).version(), version);
+        let version = child.layer_over(&parent).version();
+        child.remove::<Dep<i32>>();
+        assert_ne!(child.layer_over(&parent).version(), version);
     }
 }
@@ -1,3 +1,7 @@
+mod cache;
 mod dependencies;
+mod sync;
 
-pub use dependencies::{Dep, FromTypeMap, TypeMap};
+pub use cache::ResolutionCache;
+pub use dependencies::{Dep, DepInfo, DepMut, DepOrDefault, FromTypeMap, Preferred, TypeMap};
+pub use sync::SyncTypeMap;
@@ -0,0 +1,154 @@
+use crate::{DepInfo, TypeMap};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+/// A flat set of `Send + Sync` values, built once and shared by pipelines on
+/// several threads. Unlike `TypeMap`, it has no parent and no versions, so
+/// it's cheap to clone and hand to each pipeline.
+///
+/// Like `TypeMap`, it stores a single value per type, and clones share their
+/// bindings until one of them changes. Steps still resolve their
+/// dependencies from a `TypeMap`: build one from it with `to_type_map`, or
+/// add its values to a builder with `add_sync_deps`. Either way, the values
+/// are shared rather than cloned.
+#[derive(Clone, Default)]
+pub struct SyncTypeMap {
+    bindings: Arc<HashMap<TypeId, SyncBinding>>,
+}
+
+#[derive(Clone)]
+struct SyncBinding {
+    name: &'static str,
+    value: Arc<dyn Any + Send + Sync>,
+    // binds the shared value into a `TypeMap`
+    bind: fn(Arc<dyn Any + Send + Sync>, &mut TypeMap),
+}
+
+impl std::fmt::Debug for SyncTypeMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.bindings.values().map(|b| b.name))
+            .finish()
+    }
+}
+
+impl SyncTypeMap {
+    /// Creates a new, empty type map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds the given value to its type, returning any existing value for
+    /// it. Values may still be shared with clones of this map, so they're
+    /// returned behind an `Arc`.
+    pub fn bind<T: Any + Send + Sync>(&mut self, val: T) -> Option<Arc<T>> {
+        let binding = SyncBinding {
+            name: std::any::type_name::<T>(),
+            value: Arc::new(val),
+            bind: |val, tm| {
+                if let Ok(val) = val.downcast::<T>() {
+                    tm.bind_arc(val);
+                }
+            },
+        };
+        Arc::make_mut(&mut self.bindings)
+            .insert(TypeId::of::<T>(), binding)
+            .and_then(|b| b.value.downcast().ok())
+    }
+
+    /// Removes and returns the value for this unique type, if present.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<Arc<T>> {
+        Arc::make_mut(&mut self.bindings)
+            .remove(&TypeId::of::<T>())
+            .and_then(|b| b.value.downcast().ok())
+    }
+
+    /// Returns the value in this map for this unique type.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.bindings.get(&TypeId::of::<T>())?.value.downcast_ref()
+    }
+
+    /// Returns whether a `T` is bound in this map.
+    pub fn contains<T: Any>(&self) -> bool {
+        self.bindings.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns how many types are bound in this map.
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// Returns whether nothing is bound in this map.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// Describes every type bound in this map, sorted by name.
+    pub fn keys(&self) -> Vec<DepInfo> {
+        let mut keys: Vec<_> = self
+            .bindings
+            .iter()
+            .map(|(&id, b)| DepInfo { id, name: b.name })
+            .collect();
+        keys.sort_by_key(|k| k.name);
+        keys
+    }
+
+    /// Binds every value in this map into `tm`, replacing any of the same
+    /// type. The values are shared with this map, not cloned.
+    pub fn bind_into(&self, tm: &mut TypeMap) {
+        for b in self.bindings.values() {
+            (b.bind)(b.value.clone(), tm);
+        }
+    }
+
+    /// Builds a `TypeMap` sharing every value in this map, such as on the
+    /// thread a pipeline runs on.
+    pub fn to_type_map(&self) -> TypeMap {
+        let mut tm = TypeMap::new();
+        self.bind_into(&mut tm);
+        tm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dep;
+
+    #[derive(Clone, Debug)]
+    struct Config(i32);
+
+    // sync type maps should cross threads and rebuild a type map there,
+    // sharing their values
+    #[test]
+    fn test_sync_type_map() {
+        let mut tm = SyncTypeMap::new();
+        tm.bind(Dep::new(Config(2)));
+        tm.bind(7_u8);
+        assert!(tm.bind(8_u8).is_some_and(|prev| *prev == 7));
+        assert_eq!(tm.len(), 2);
+        assert_eq!(tm.keys()[0], DepInfo::of::<Dep<Config>>());
+
+        let shared = tm.clone();
+        let rebuilt = std::thread::spawn(move || {
+            let tm = shared.to_type_map();
+            (tm.get::<Dep<Config>>().unwrap().0, *tm.get::<u8>().unwrap())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(rebuilt, (2, 8));
+        let rebuilt = tm.to_type_map();
+        assert!(std::ptr::eq(
+            rebuilt.get::<Dep<Config>>().unwrap(),
+            tm.get::<Dep<Config>>().unwrap()
+        ));
+
+        assert!(tm.remove::<u8>().is_some());
+        assert!(!tm.contains::<u8>());
+        assert_eq!(tm.get::<Dep<Config>>().unwrap().0, 2);
+    }
+}
@@ -1,14 +1,58 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{DeriveInput, parse_macro_input};
+use syn::{
+    DeriveInput, GenericParam, Ident, Lifetime, parse_macro_input, parse_quote,
+    visit_mut::{self, VisitMut},
+};
 
 pub fn dependency_impl(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident;
-    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    // Dependencies are looked up by `TypeId`, so only `'static` instances of
+    // types with lifetimes are retrievable: each lifetime becomes `'static`.
+    let lifetimes: Vec<Ident> = input
+        .generics
+        .lifetimes()
+        .map(|l| l.lifetime.ident.clone())
+        .collect();
+    let args: Vec<_> = input
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(_) => quote!('static),
+            GenericParam::Type(ty) => {
+                let ident = &ty.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(c) => {
+                let ident = &c.ident;
+                quote!(#ident)
+            }
+        })
+        .collect();
+    let mut generics = input.generics;
+    generics.params = generics
+        .params
+        .into_iter()
+        .filter(|param| !matches!(param, GenericParam::Lifetime(_)))
+        .collect();
+    StaticLifetimes(&lifetimes).visit_generics_mut(&mut generics);
+    // generic dependencies are only retrievable when their parameters are
+    // `Send` and `'static`, as `FromTypeMap` requires, and are cloned out of
+    // the map, which derived `Clone` impls may only allow for some parameters
+    generics.make_where_clause().predicates.push(parse_quote!(
+        Self: ::core::clone::Clone + ::core::marker::Send + 'static
+    ));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let ty = if args.is_empty() {
+        quote!(#name)
+    } else {
+        quote!(#name<#(#args),*>)
+    };
 
     quote! {
-        impl #impl_generics ::imperat::FromTypeMap for #name #type_generics #where_clause {
+        impl #impl_generics ::imperat::FromTypeMap for #ty #where_clause {
             fn retrieve_from_map(tm: &::imperat::TypeMap) -> Option<Self> {
                 tm.get::<Self>().cloned()
             }
@@ -16,3 +60,15 @@ pub fn dependency_impl(input: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+/// Replaces every use of the given lifetimes with `'static`.
+struct StaticLifetimes<'a>(&'a [Ident]);
+
+impl VisitMut for StaticLifetimes<'_> {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if self.0.contains(&lifetime.ident) {
+            *lifetime = Lifetime::new("'static", lifetime.apostrophe);
+        }
+        visit_mut::visit_lifetime_mut(self, lifetime);
+    }
+}
@@ -1,4 +1,5 @@
 mod dependency;
+mod step;
 
 use proc_macro::TokenStream;
 
@@ -6,3 +7,28 @@ use proc_macro::TokenStream;
 pub fn dependency(input: TokenStream) -> TokenStream {
     dependency::dependency_impl(input)
 }
+
+/// Declares an async fn as a step carrying its name and options, so it can
+/// be added with `add` without repeating them. The fn moves into a type of
+/// the same name, and is then called as `name::call`. Two steps with the
+/// same name in one module fail to compile.
+///
+/// Options are all optional:
+///   * `name = "..."`: the step's name, defaulting to the fn's.
+///   * `retries = N`: retry the step up to `N` times. See `RetryPolicy::new`.
+///   * `timeout_ms = N`: time each attempt out after `N` milliseconds.
+///   * `tags("...", ...)`: tag the step. See `StepBuilder::tag`.
+///
+/// ```
+/// # use imperat::prelude::*;
+/// #[step(name = "load config", retries = 3, tags("setup"))]
+/// async fn load_config() -> Result<(), std::io::Error> {
+///     Ok(())
+/// }
+///
+/// let builder = new_imperative_builder().add(load_config);
+/// ```
+#[proc_macro_attribute]
+pub fn step(args: TokenStream, input: TokenStream) -> TokenStream {
+    step::step_impl(args, input)
+}
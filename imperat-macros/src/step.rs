@@ -0,0 +1,148 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    AttributeArgs, Error, ItemFn, Lit, Meta, NestedMeta, ReturnType, parse_macro_input, parse_quote,
+};
+
+/// Options parsed from `#[step(...)]`.
+#[derive(Default)]
+struct StepArgs {
+    name: Option<String>,
+    retries: Option<usize>,
+    timeout_ms: Option<u64>,
+    tags: Vec<String>,
+}
+
+impl StepArgs {
+    fn parse(args: AttributeArgs) -> syn::Result<Self> {
+        let mut parsed = StepArgs::default();
+        for arg in args {
+            match arg {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match nv.lit {
+                    Lit::Str(s) => parsed.name = Some(s.value()),
+                    lit => return Err(Error::new_spanned(lit, "expected a string")),
+                },
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("retries") => {
+                    match nv.lit {
+                        Lit::Int(n) => parsed.retries = Some(n.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "expected an integer")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("timeout_ms") => {
+                    match nv.lit {
+                        Lit::Int(n) => parsed.timeout_ms = Some(n.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "expected an integer")),
+                    }
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("tags") => {
+                    for tag in list.nested {
+                        match tag {
+                            NestedMeta::Lit(Lit::Str(s)) => parsed.tags.push(s.value()),
+                            tag => return Err(Error::new_spanned(tag, "expected a string")),
+                        }
+                    }
+                }
+                arg => {
+                    return Err(Error::new_spanned(
+                        arg,
+                        "expected `name`, `retries`, `timeout_ms`, or `tags`",
+                    ));
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+pub fn step_impl(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let func = parse_macro_input!(input as ItemFn);
+    match expand(args, func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: AttributeArgs, mut func: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let args = StepArgs::parse(args)?;
+    let sig = &func.sig;
+    if sig.asyncness.is_none() {
+        return Err(Error::new_spanned(sig.fn_token, "steps must be async fns"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(&sig.generics, "steps can't be generic"));
+    }
+    if let Some(receiver) = sig.receiver() {
+        return Err(Error::new_spanned(receiver, "steps can't take `self`"));
+    }
+
+    let ident = sig.ident.clone();
+    let out = match &sig.output {
+        ReturnType::Default => parse_quote!(()),
+        ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+    let name = args.name.unwrap_or_else(|| ident.to_string());
+    let retries = args
+        .retries
+        .map(|n| quote!(.retry(::imperat::RetryPolicy::new(#n))));
+    let timeout = args
+        .timeout_ms
+        .map(|ms| quote!(.timeout(::std::time::Duration::from_millis(#ms))));
+    let tags = &args.tags;
+    let unique = unique_name_guard(&name, &ident);
+
+    // The function moves into the step's type, which takes its name so it
+    // can be passed to `add`.
+    let vis = std::mem::replace(&mut func.vis, parse_quote!(pub));
+    let attrs = std::mem::take(&mut func.attrs);
+    func.sig.ident = syn::Ident::new("call", Span::call_site());
+
+    Ok(quote! {
+        #unique
+
+        #(#attrs)*
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug)]
+        #vis struct #ident;
+
+        impl #ident {
+            /// The step's name.
+            pub const NAME: &'static str = #name;
+
+            #func
+        }
+
+        impl ::core::convert::From<#ident> for ::imperat::StepBuilder<#out> {
+            fn from(_: #ident) -> Self {
+                ::imperat::new_step(#name, #ident::call)
+                    #retries
+                    #timeout
+                    #(.tag(#tags))*
+            }
+        }
+    })
+}
+
+/// Declares an item named after the step's name, so two steps with the
+/// same name in one module fail to compile as the item is defined twice.
+fn unique_name_guard(name: &str, ident: &syn::Ident) -> proc_macro2::TokenStream {
+    let readable: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    // Names which read the same, such as "a b" and "a_b", still differ.
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    let guard = syn::Ident::new(
+        &format!("__imperat_duplicate_step_name_{readable}_{hash:016x}"),
+        ident.span(),
+    );
+    quote! {
+        #[doc(hidden)]
+        #[allow(dead_code, non_upper_case_globals)]
+        const #guard: () = ();
+    }
+}
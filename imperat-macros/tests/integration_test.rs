@@ -28,6 +28,27 @@ enum Either<L: Clone + PartialEq + Eq + 'static, R: Clone + PartialEq + Eq + 'st
 #[derive(Clone, Dependency)]
 struct Result<T: Clone + 'static, E: Clone + 'static>(std::result::Result<T, E>);
 
+#[derive(Clone, Debug, Dependency)]
+struct Borrowed<'a> {
+    name: &'a str,
+}
+
+#[derive(Clone, Debug, Dependency)]
+struct BorrowedGeneric<'a, 'b: 'a, T: ?Sized + 'b>(&'a &'b T);
+
+#[derive(Clone, Debug, Dependency)]
+struct Bytes<const N: usize>([u8; N]);
+
+#[derive(Clone, Debug, Dependency)]
+struct Bounded<T, const N: usize = 4>
+where
+    T: Clone + IntoIterator<Item = usize>,
+    T::IntoIter: Clone,
+{
+    items: T,
+    sizes: [usize; N],
+}
+
 // a typemap bind and call should rt
 #[test]
 fn test_typemap_round_trips() {
@@ -48,3 +69,32 @@ fn test_typemap_round_trips() {
     let e = tm.get::<Either<usize, i32>>().unwrap();
     assert_eq!(&Either::Right(-1), e);
 }
+
+// types with lifetimes should be retrievable when 'static
+#[test]
+fn test_lifetimes_round_trip() {
+    let mut tm = imperat::TypeMap::new();
+    tm.bind(Borrowed { name: "static" });
+    tm.bind(BorrowedGeneric::<usize>(&&5));
+
+    let borrowed = <Borrowed<'static> as imperat::FromTypeMap>::retrieve_from_map(&tm).unwrap();
+    assert_eq!(borrowed.name, "static");
+    let nested = <BorrowedGeneric<usize> as imperat::FromTypeMap>::retrieve_from_map(&tm).unwrap();
+    assert_eq!(**nested.0, 5);
+}
+
+// const generics and where clauses should be retrievable
+#[test]
+fn test_const_generics_round_trip() {
+    let mut tm = imperat::TypeMap::new();
+    tm.bind(Bytes([1u8; 3]));
+    tm.bind(Bounded {
+        items: vec![1usize, 2],
+        sizes: [0; 4],
+    });
+
+    let sized = <Bytes<3> as imperat::FromTypeMap>::retrieve_from_map(&tm).unwrap();
+    assert_eq!(sized.0, [1, 1, 1]);
+    let bounded = <Bounded<Vec<usize>> as imperat::FromTypeMap>::retrieve_from_map(&tm).unwrap();
+    assert_eq!(bounded.items, [1, 2]);
+}
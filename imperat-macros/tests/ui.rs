@@ -0,0 +1,12 @@
+//! Derives which should fail to compile, with readable errors.
+
+// UI tests should fail with their expected errors. Compiler diagnostics
+// change between releases, so they only run on the release the expected
+// errors were written with, which rust-toolchain.toml pins; update them
+// alongside it with `TRYBUILD=overwrite`.
+#[rustversion::attr(not(stable(1.95)), ignore = "expected errors are from rustc 1.95")]
+#[test]
+fn test_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,14 @@
+use imperat::FromTypeMap;
+use imperat_macros::Dependency;
+
+#[derive(Clone, Dependency)]
+struct Borrowed<'a>(&'a str);
+
+fn retrieve<T: FromTypeMap>() {}
+
+// only 'static borrows are looked up
+fn main() {
+    fn local<'a>(_: &'a str) {
+        retrieve::<Borrowed<'a>>();
+    }
+}
@@ -0,0 +1,10 @@
+use imperat::prelude::*;
+
+#[step(name = "deploy")]
+async fn deploy_staging() {}
+
+// a copy-pasted step which wasn't renamed
+#[step(name = "deploy")]
+async fn deploy_production() {}
+
+fn main() {}
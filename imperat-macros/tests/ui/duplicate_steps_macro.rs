@@ -0,0 +1,14 @@
+use imperat::prelude::*;
+
+// steps per platform may share a name, but unguarded ones may not
+fn main() {
+    let _ = steps! {
+        new_imperative_builder(),
+        "build" => async || Ok::<_, &str>(()),
+        #[cfg(unix)]
+        "install" => async || Ok(()),
+        #[cfg(windows)]
+        "install" => async || Ok(()),
+        "build" => async || Ok(()),
+    };
+}
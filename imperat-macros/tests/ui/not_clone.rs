@@ -0,0 +1,7 @@
+use imperat_macros::Dependency;
+
+// dependencies are cloned out of the type map
+#[derive(Dependency)]
+struct NotClone;
+
+fn main() {}
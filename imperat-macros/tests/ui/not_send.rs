@@ -0,0 +1,8 @@
+use imperat_macros::Dependency;
+use std::rc::Rc;
+
+// dependencies are moved into steps running on any thread
+#[derive(Clone, Dependency)]
+struct NotSend(Rc<usize>);
+
+fn main() {}
@@ -0,0 +1,125 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+/// Whether a step gated on approval may run. See
+/// `StepBuilder::requires_approval`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Approval {
+    /// The step is waiting to be approved or rejected.
+    Pending,
+    Approved,
+    /// The step was rejected for this reason, failing it.
+    Rejected(String),
+}
+
+/// Approves or rejects the steps of a run which are gated on approval, by
+/// their key, such as from a chat command or web hook. Clones share their
+/// state. Set one with `ImperativeStepBuilder::approvals`.
+///
+/// Steps may be approved before they're reached, in which case they don't
+/// wait at all.
+#[derive(Clone, Debug)]
+pub struct Approvals(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    states: Mutex<BTreeMap<String, Approval>>,
+    // bumped whenever a step is approved or rejected
+    changed: watch::Sender<u64>,
+}
+
+impl Default for Approvals {
+    fn default() -> Self {
+        Self(Arc::new(Inner {
+            states: Mutex::default(),
+            changed: watch::Sender::new(0),
+        }))
+    }
+}
+
+impl Approvals {
+    /// Creates approvals where no step has been approved.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Approve the step with `key`, letting it run.
+    pub fn approve(&self, key: &str) {
+        self.decide(key, Approval::Approved);
+    }
+
+    /// Reject the step with `key` for `reason`, failing it.
+    pub fn reject(&self, key: &str, reason: &str) {
+        self.decide(key, Approval::Rejected(reason.to_string()));
+    }
+
+    /// Returns the state of the step with `key`, if it was reached or decided.
+    ///
+    /// # Panics
+    /// If the approvals mutex is poisoned.
+    #[must_use]
+    pub fn state(&self, key: &str) -> Option<Approval> {
+        self.lock().get(key).cloned()
+    }
+
+    /// Returns the keys of every step waiting for approval.
+    ///
+    /// # Panics
+    /// If the approvals mutex is poisoned.
+    #[must_use]
+    pub fn waiting(&self) -> Vec<String> {
+        self.lock()
+            .iter()
+            .filter(|(_, a)| **a == Approval::Pending)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Sets the state of the step with `key` as recorded by an earlier run,
+    /// unless it was already decided in this one.
+    pub(super) fn restore(&self, key: &str, approval: Approval) {
+        let mut states = self.lock();
+        if !states.contains_key(key) {
+            states.insert(key.to_string(), approval);
+        }
+    }
+
+    /// Marks the step with `key` as waiting, unless it was already decided,
+    /// returning its state.
+    pub(super) fn request(&self, key: &str) -> Approval {
+        self.lock()
+            .entry(key.to_string())
+            .or_insert(Approval::Pending)
+            .clone()
+    }
+
+    /// Waits until the step with `key` is approved or rejected.
+    pub(super) async fn decided(&self, key: &str) -> Approval {
+        let mut changed = self.0.changed.subscribe();
+        loop {
+            match self.state(key) {
+                Some(Approval::Pending) | None => {}
+                Some(approval) => return approval,
+            }
+            // the sender lives as long as this does
+            let _ = changed.changed().await;
+        }
+    }
+
+    fn decide(&self, key: &str, approval: Approval) {
+        self.lock().insert(key.to_string(), approval);
+        self.0.changed.send_modify(|n| *n += 1);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Approval>> {
+        self.0
+            .states
+            .lock()
+            .expect("imperat approvals mutex poisoned")
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Limits how much output fan-out groups retain at once, such as when many
+/// parallel steps each produce a large result. Attach one to groups with
+/// `GroupBuilder::output_budget`.
+///
+/// Each step of those groups which succeeds retains its output's size, as
+/// measured by `StepBuilder::output_size`. While more than the limit is
+/// retained, the groups start no new steps, letting running ones finish,
+/// until sinks `release` what they've drained, such as after writing outputs
+/// out with `StepBuilder::reduce_output`. If nothing releases output, the
+/// groups wait indefinitely, so release it even for discarded outputs.
+#[derive(Clone, Debug)]
+pub struct OutputBudget {
+    limit: usize,
+    retained: Arc<watch::Sender<usize>>,
+}
+
+impl OutputBudget {
+    /// Creates a budget which pauses groups while more than `limit` is
+    /// retained.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            retained: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// Returns how much output is retained.
+    #[must_use]
+    pub fn retained(&self) -> usize {
+        *self.retained.borrow()
+    }
+
+    /// Releases `size` of retained output once a sink has drained it, which
+    /// resumes paused groups once they're within the limit.
+    pub fn release(&self, size: usize) {
+        self.retained.send_modify(|r| *r = r.saturating_sub(size));
+    }
+
+    pub(super) fn retain(&self, size: usize) {
+        self.retained.send_modify(|r| *r = r.saturating_add(size));
+    }
+
+    /// Returns whether more than the limit is retained.
+    pub(super) fn exceeded(&self) -> bool {
+        self.retained() > self.limit
+    }
+
+    /// Waits until no more than the limit is retained.
+    pub(super) async fn drained(&self) {
+        let mut rx = self.retained.subscribe();
+        // The sender is held by `self`, so this can't fail.
+        let _ = rx.wait_for(|&r| r <= self.limit).await;
+    }
+}
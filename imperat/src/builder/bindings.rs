@@ -0,0 +1,110 @@
+use crate::DepInfo;
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+};
+
+/// Which steps bind which dependencies, and what every step depends on.
+/// Used to accept steps which depend on a binding before it exists, and to
+/// find steps which can never run because they depend on each other, through
+/// bindings or `depends_on`.
+#[derive(Debug, Default)]
+pub(super) struct BindingGraph {
+    binders: HashMap<TypeId, String>,
+    deps: Vec<(String, Vec<TypeId>)>,
+    after: HashMap<String, Vec<String>>,
+}
+
+impl BindingGraph {
+    /// Returns whether any step added so far binds this dependency.
+    pub(super) fn is_bound(&self, id: TypeId) -> bool {
+        self.binders.contains_key(&id)
+    }
+
+    /// Adds a step, returning the step which already binds what it binds,
+    /// if any. That step keeps the binding.
+    pub(super) fn add(
+        &mut self,
+        step: &str,
+        deps: &[DepInfo],
+        binds: Option<&DepInfo>,
+        after: &[String],
+    ) -> Option<String> {
+        let mut bound = None;
+        if let Some(dep) = binds {
+            match self.binders.get(&dep.id) {
+                Some(first) if first != step => bound = Some(first.clone()),
+                _ => {
+                    self.binders.insert(dep.id, step.to_string());
+                }
+            }
+        }
+        if !after.is_empty() {
+            self.after
+                .entry(step.to_string())
+                .or_default()
+                .extend(after.iter().cloned());
+        }
+        self.deps
+            .push((step.to_string(), deps.iter().map(|d| d.id).collect()));
+        bound
+    }
+
+    /// Returns the steps in a cycle of steps depending on each other's
+    /// bindings, starting and ending with the same step.
+    pub(super) fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (step, deps) in &self.deps {
+            let next = edges.entry(step).or_default();
+            next.extend(
+                deps.iter()
+                    .filter_map(|d| self.binders.get(d).map(String::as_str)),
+            );
+            next.extend(
+                self.after
+                    .get(step)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str),
+            );
+        }
+
+        let mut done = HashSet::new();
+        for (step, _) in &self.deps {
+            let mut path = vec![];
+            if let Some(cycle) = visit(step, &edges, &mut path, &mut done) {
+                return Some(cycle);
+            }
+        }
+
+        None
+    }
+}
+
+// Depth first search from `step`, tracking the current path to report cycles.
+fn visit<'a>(
+    step: &'a str,
+    edges: &HashMap<&'a str, Vec<&'a str>>,
+    path: &mut Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+) -> Option<Vec<String>> {
+    if let Some(start) = path.iter().position(|s| *s == step) {
+        let mut cycle: Vec<_> = path[start..].iter().map(ToString::to_string).collect();
+        cycle.push(step.to_string());
+        return Some(cycle);
+    }
+    if done.contains(step) {
+        return None;
+    }
+
+    path.push(step);
+    for next in edges.get(step).into_iter().flatten() {
+        if let Some(cycle) = visit(next, edges, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(step);
+
+    None
+}
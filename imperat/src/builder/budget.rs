@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+/// Limits on the resources a single step may use, protecting shared runners
+/// from runaway steps. Steps which exceed their budget fail with
+/// `Error::BudgetExceeded` and aren't retried; any tasks they spawned are
+/// aborted. Attach one with `StepBuilder::budget`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepBudget {
+    pub(super) duration: Option<Duration>,
+    pub(super) retries: Option<usize>,
+    pub(super) output_size: Option<usize>,
+}
+
+impl StepBudget {
+    /// Creates an unlimited budget.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the total time spent on the step across every attempt,
+    /// including delays between retries.
+    #[must_use]
+    pub fn max_duration(mut self, limit: Duration) -> Self {
+        self.duration = Some(limit);
+        self
+    }
+
+    /// Limit how many times the step is retried, regardless of its
+    /// group's retry policy. Running out of retries isn't an error in
+    /// itself; the step's last failure is returned as usual.
+    #[must_use]
+    pub fn max_retries(mut self, limit: usize) -> Self {
+        self.retries = Some(limit);
+        self
+    }
+
+    /// Limit the size of the step's output, as measured by the function
+    /// passed to `StepBuilder::output_size`. Steps with this limit must set
+    /// one, or `prepare` fails with `Error::NoOutputSize`.
+    #[must_use]
+    pub fn max_output_size(mut self, limit: usize) -> Self {
+        self.output_size = Some(limit);
+        self
+    }
+}
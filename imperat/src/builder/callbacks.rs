@@ -0,0 +1,119 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{Arc, Mutex},
+};
+
+use futures::FutureExt;
+
+use super::{Error, RedactFn, log::log_warn, step::panic_message};
+
+/// What happens when a callback, such as `before_step` or `on_group_end`,
+/// panics. See `ImperativeStepBuilder::on_callback_panic`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CallbackPanicPolicy {
+    /// The run fails with `Error::CallbackFailed` once the group the
+    /// callback ran in is done.
+    #[default]
+    Fatal,
+    /// The panic is logged as a warning, redacted like the run's errors, and
+    /// the run continues. Either way, it's listed in
+    /// `ExecutionReport::callback_failures`.
+    Warn,
+}
+
+/// Runs a run's callbacks, catching their panics so they can't take the
+/// run down with them.
+#[derive(Clone, Default)]
+pub(super) struct Callbacks {
+    pub(super) policy: CallbackPanicPolicy,
+    // the run's redactor, applied to panic messages before they're logged
+    pub(super) redact: Option<Arc<RedactFn>>,
+    // every callback which panicked, oldest first
+    failures: Arc<Mutex<Vec<Error>>>,
+    // the first fatal failure `check` hasn't returned yet
+    pending: Arc<Mutex<Option<Error>>>,
+}
+
+impl Callbacks {
+    /// Calls `f`, the `callback` for `target`, returning what it returns
+    /// unless it panicked.
+    pub(super) fn call<R>(&self, callback: &str, target: &str, f: impl FnOnce() -> R) -> Option<R> {
+        self.try_call(callback, target, f).ok()
+    }
+
+    /// Like `call`, but returns the callback's failure if it panicked.
+    pub(super) fn try_call<R>(
+        &self,
+        callback: &str,
+        target: &str,
+        f: impl FnOnce() -> R,
+    ) -> Result<R, Error> {
+        catch_unwind(AssertUnwindSafe(f))
+            .map_err(|panic| self.failed(callback, target, panic_message(panic.as_ref())))
+    }
+
+    /// Like `call`, for callbacks returning a future, which may panic too.
+    pub(super) async fn call_async<F: Future>(
+        &self,
+        callback: &str,
+        target: &str,
+        f: impl FnOnce() -> F,
+    ) -> Option<F::Output> {
+        let fut = self.call(callback, target, f)?;
+        AssertUnwindSafe(fut)
+            .catch_unwind()
+            .await
+            .map_err(|panic| self.failed(callback, target, panic_message(panic.as_ref())))
+            .ok()
+    }
+
+    /// Records that `callback` for `target` panicked with `msg`, returning
+    /// the error.
+    fn failed(&self, callback: &str, target: &str, msg: String) -> Error {
+        let e = Error::CallbackFailed(callback.to_string(), target.to_string(), msg);
+        match self.policy {
+            CallbackPanicPolicy::Fatal => {
+                self.pending
+                    .lock()
+                    .expect("callbacks mutex poisoned")
+                    .get_or_insert_with(|| e.copy());
+            }
+            CallbackPanicPolicy::Warn => log_warn!("{}", self.redacted(e.copy())),
+        }
+        self.failures
+            .lock()
+            .expect("callbacks mutex poisoned")
+            .push(e.copy());
+        e
+    }
+
+    /// Returns `e` after applying the run's redactor, if any, called
+    /// directly as it may be the callback which panicked.
+    fn redacted(&self, e: Error) -> Error {
+        let Some(redact) = &self.redact else {
+            return e;
+        };
+        e.redact(&|msg| {
+            catch_unwind(AssertUnwindSafe(|| redact(msg)))
+                .unwrap_or_else(|_| "<redaction failed>".to_string())
+        })
+    }
+
+    /// Returns the first fatal failure since the last check, if any.
+    pub(super) fn check(&self) -> Result<(), Error> {
+        match self
+            .pending
+            .lock()
+            .expect("callbacks mutex poisoned")
+            .take()
+        {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes every callback which panicked so far, oldest first.
+    pub(super) fn take(&self) -> Vec<Error> {
+        std::mem::take(&mut *self.failures.lock().expect("callbacks mutex poisoned"))
+    }
+}
@@ -0,0 +1,234 @@
+use super::Approval;
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::sync::Arc;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Records each step's output as it completes, so a later run can resume
+/// from where this one stopped with `ImperativeStepBuilder::resume_from`.
+/// Steps are recorded by their key. See `KeyStrategy`.
+///
+/// With the `serde` feature, `JsonCheckpointer` records them to a file.
+pub trait Checkpointer<O>: Send + Sync {
+    /// Records that the step with `key` completed with `output`.
+    ///
+    /// # Errors
+    /// If the output couldn't be recorded, which fails the step.
+    fn save(&self, key: &str, output: &O) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Records the approval state of the step with `key`, so a later run
+    /// returns to the same gate. See `StepBuilder::requires_approval`. By
+    /// default, approvals aren't recorded.
+    ///
+    /// # Errors
+    /// If the state couldn't be recorded, which fails the step.
+    fn save_approval(
+        &self,
+        key: &str,
+        approval: &Approval,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _ = (key, approval);
+        Ok(())
+    }
+
+    /// Returns every step recorded so far.
+    ///
+    /// # Errors
+    /// If the recorded steps couldn't be read.
+    fn load(&self) -> Result<Checkpoint<O>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// The outputs of every step which completed in an earlier run, and the
+/// approval state of every gated step it reached, by key. Get one from
+/// `Checkpointer::load`.
+#[derive(Clone, Debug)]
+pub struct Checkpoint<O> {
+    pub(super) outputs: HashMap<String, O>,
+    pub(super) approvals: HashMap<String, Approval>,
+}
+
+impl<O> Checkpoint<O> {
+    /// Returns the recorded output of the step with `key`, if it completed.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&O> {
+        self.outputs.get(key)
+    }
+
+    /// Returns the recorded approval state of the step with `key`, if it
+    /// was reached or decided.
+    #[must_use]
+    pub fn approval(&self, key: &str) -> Option<&Approval> {
+        self.approvals.get(key)
+    }
+
+    /// Adds the approval state of gated steps, by key.
+    #[must_use]
+    pub fn with_approvals(mut self, approvals: HashMap<String, Approval>) -> Self {
+        self.approvals.extend(approvals);
+        self
+    }
+
+    /// Returns how many steps completed.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+impl<O> Default for Checkpoint<O> {
+    fn default() -> Self {
+        Self::from(HashMap::new())
+    }
+}
+
+impl<O> From<HashMap<String, O>> for Checkpoint<O> {
+    fn from(outputs: HashMap<String, O>) -> Self {
+        Self {
+            outputs,
+            approvals: HashMap::new(),
+        }
+    }
+}
+
+/// Encrypts what a store writes to disk, such as with an AEAD cipher and a
+/// key the application provides, so step outputs are protected at rest.
+/// Set one on a store with, for example, `JsonCheckpointer::encrypted`.
+pub trait Cipher: Send + Sync {
+    /// Encrypts and authenticates `plaintext` along with `aad`, which isn't
+    /// encrypted but must be given again to open it.
+    ///
+    /// # Errors
+    /// If it couldn't be encrypted, which fails the write.
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BoxError>;
+
+    /// Decrypts `ciphertext` sealed with the same `aad`.
+    ///
+    /// # Errors
+    /// If it wasn't sealed with this key and `aad`, or was tampered with,
+    /// which fails the read.
+    fn open(&self, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, BoxError>;
+}
+
+/// Records steps to a JSON file as an object of outputs by key, rewriting
+/// it as each step completes. A missing file has no steps recorded.
+/// Approvals are recorded alongside it, in a file with the extension
+/// `approvals.json`.
+#[cfg(feature = "serde")]
+#[derive(Clone)]
+pub struct JsonCheckpointer {
+    path: std::path::PathBuf,
+    cipher: Option<Arc<dyn Cipher>>,
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Debug for JsonCheckpointer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonCheckpointer")
+            .field("path", &self.path)
+            .field("encrypted", &self.cipher.is_some())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl JsonCheckpointer {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cipher: None,
+        }
+    }
+
+    /// Encrypts both files with `cipher`. Each file is sealed whole, with
+    /// what it records as associated data, so neither can be swapped for the
+    /// other. Files written without it, or with another key, fail to load.
+    #[must_use]
+    pub fn encrypted(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    fn approvals_path(&self) -> std::path::PathBuf {
+        self.path.with_extension("approvals.json")
+    }
+
+    fn read(
+        &self,
+        path: &std::path::Path,
+        aad: &[u8],
+    ) -> Result<serde_json::Map<String, serde_json::Value>, BoxError> {
+        match std::fs::read(path) {
+            Ok(bytes) => match &self.cipher {
+                Some(cipher) => Ok(serde_json::from_slice(&cipher.open(&bytes, aad)?)?),
+                None => Ok(serde_json::from_slice(&bytes)?),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(serde_json::Map::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(
+        &self,
+        path: &std::path::Path,
+        aad: &[u8],
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<(), BoxError> {
+        let mut entries = self.read(path, aad)?;
+        entries.insert(key.to_string(), value);
+        let mut bytes = serde_json::to_vec_pretty(&entries)?;
+        if let Some(cipher) = &self.cipher {
+            bytes = cipher.seal(&bytes, aad)?;
+        }
+        // Write a copy first so a crash mid-write never loses earlier entries.
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+const OUTPUTS_AAD: &[u8] = b"imperat checkpoint outputs";
+#[cfg(feature = "serde")]
+const APPROVALS_AAD: &[u8] = b"imperat checkpoint approvals";
+
+#[cfg(feature = "serde")]
+impl<O: serde::Serialize + serde::de::DeserializeOwned> Checkpointer<O> for JsonCheckpointer {
+    fn save(&self, key: &str, output: &O) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.write(&self.path, OUTPUTS_AAD, key, serde_json::to_value(output)?)
+    }
+
+    fn save_approval(
+        &self,
+        key: &str,
+        approval: &Approval,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.write(
+            &self.approvals_path(),
+            APPROVALS_AAD,
+            key,
+            serde_json::to_value(approval)?,
+        )
+    }
+
+    fn load(&self) -> Result<Checkpoint<O>, Box<dyn std::error::Error + Send + Sync>> {
+        let outputs: HashMap<String, O> = self
+            .read(&self.path, OUTPUTS_AAD)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_value(value)?)))
+            .collect::<Result<_, Box<dyn std::error::Error + Send + Sync>>>()?;
+        let approvals = self
+            .read(&self.approvals_path(), APPROVALS_AAD)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_value(value)?)))
+            .collect::<Result<_, Box<dyn std::error::Error + Send + Sync>>>()?;
+        Ok(Checkpoint::from(outputs).with_approvals(approvals))
+    }
+}
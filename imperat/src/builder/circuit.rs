@@ -0,0 +1,227 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{DepInfo, step::short_type_name};
+use crate::Dep;
+
+/// Stops running steps which use a failing dependency, or which have a tag,
+/// until it recovers. Attach one with `ImperativeStepBuilder::circuit_breaker`.
+///
+/// The breaker starts `Closed`, counting consecutive failed attempts of the
+/// steps it covers. Once they reach its threshold, it opens: those steps are
+/// skipped if `StepBuilder::degradable` and fail with `Error::CircuitOpen`
+/// otherwise, and aren't retried. It also opens once the steps it covers have
+/// been retried `max_retries` times, if set. After its cooldown, it's half-open: the
+/// next covered step runs as a probe, closing the breaker if it succeeds or
+/// reopening it if not. Each change is sent as a
+/// `PipelineEvent::CircuitChanged`.
+///
+/// Clones share their state, so attaching the same breaker to every run of
+/// a pipeline keeps a downed service from being called by later runs, too.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    key: BreakerKey,
+    failures: usize,
+    cooldown: Duration,
+    retries: Option<usize>,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+/// The state of a `CircuitBreaker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Steps run as usual.
+    Closed,
+    /// Steps are stopped until the cooldown passes.
+    Open,
+    /// A single step may run as a probe.
+    HalfOpen,
+}
+
+#[derive(Clone, Debug)]
+enum BreakerKey {
+    Dep(DepInfo),
+    Tag(String),
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    // consecutive failed attempts while closed
+    failures: usize,
+    // retries of covered steps since the breaker last closed
+    retried: usize,
+    // when the breaker opened, or the last probe started
+    since: Instant,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker covering every step which depends on `T`, bound as
+    /// a `Dep<T>`. It opens after 5 consecutive failures, for 30 seconds.
+    #[must_use]
+    pub fn for_dep<T: 'static>() -> Self {
+        Self::new(BreakerKey::Dep(DepInfo::of::<Dep<T>>()))
+    }
+
+    /// Creates a breaker covering every step tagged `tag`, on its own or
+    /// through its group. It opens after 5 consecutive failures, for 30
+    /// seconds.
+    #[must_use]
+    pub fn for_tag(tag: &str) -> Self {
+        Self::new(BreakerKey::Tag(tag.to_string()))
+    }
+
+    fn new(key: BreakerKey) -> Self {
+        Self {
+            key,
+            failures: 5,
+            cooldown: Duration::from_secs(30),
+            retries: None,
+            state: Arc::new(Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                failures: 0,
+                retried: 0,
+                since: Instant::now(),
+                probing: false,
+            })),
+        }
+    }
+
+    /// Open after this many consecutive failed attempts.
+    #[must_use]
+    pub fn failures(mut self, failures: usize) -> Self {
+        self.failures = failures.max(1);
+        self
+    }
+
+    /// Stay open this long before probing whether the dependency recovered.
+    #[must_use]
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Open once covered steps have been retried this many times in all,
+    /// rather than letting them retry until their failures open it.
+    #[must_use]
+    pub fn max_retries(mut self, retries: usize) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Returns the breaker's current state.
+    ///
+    /// # Panics
+    /// If the breaker's mutex is poisoned.
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// Returns what the breaker covers, such as `Dep<Payments>` or a tag.
+    #[must_use]
+    pub fn name(&self) -> String {
+        match &self.key {
+            BreakerKey::Dep(dep) => short_type_name(dep.name),
+            BreakerKey::Tag(tag) => tag.clone(),
+        }
+    }
+
+    /// Returns whether this covers a step with `deps` and `tags`.
+    pub(super) fn covers(&self, deps: &[DepInfo], tags: &[&str]) -> bool {
+        match &self.key {
+            BreakerKey::Dep(dep) => deps.contains(dep),
+            BreakerKey::Tag(tag) => tags.contains(&tag.as_str()),
+        }
+    }
+
+    /// Returns whether a covered step may run, and the breaker's new state if
+    /// it became half-open to let the step probe.
+    pub(super) fn admit(&self) -> (bool, Option<CircuitState>) {
+        let mut st = self.lock();
+        let cooled = st.since.elapsed() >= self.cooldown;
+        let probe = match st.state {
+            CircuitState::Closed => return (true, None),
+            CircuitState::Open => cooled,
+            // A probe which never finished, such as when its run was
+            // cancelled, is replaced after another cooldown.
+            CircuitState::HalfOpen => !st.probing || cooled,
+        };
+        if !probe {
+            return (false, None);
+        }
+        let opened = st.state == CircuitState::Open;
+        st.state = CircuitState::HalfOpen;
+        st.since = Instant::now();
+        st.probing = true;
+        (true, opened.then_some(CircuitState::HalfOpen))
+    }
+
+    /// Lets another step probe, after the one admitted couldn't run.
+    pub(super) fn release(&self) {
+        self.lock().probing = false;
+    }
+
+    /// Consumes a retry of a covered step, returning whether the breaker
+    /// allows it, and its new state if it opened as it's out of retries.
+    pub(super) fn take_retry(&self) -> (bool, Option<CircuitState>) {
+        let mut st = self.lock();
+        if st.state == CircuitState::Open {
+            return (false, None);
+        }
+        if self.retries.is_some_and(|max| st.retried >= max) {
+            st.state = CircuitState::Open;
+            st.failures = 0;
+            st.since = Instant::now();
+            st.probing = false;
+            return (false, Some(CircuitState::Open));
+        }
+        st.retried += 1;
+        (true, None)
+    }
+
+    /// Gives back a retry consumed with `take_retry`.
+    pub(super) fn refund_retry(&self) {
+        let mut st = self.lock();
+        st.retried = st.retried.saturating_sub(1);
+    }
+
+    /// Records a covered step's attempt, returning the breaker's new state if
+    /// it changed.
+    pub(super) fn record(&self, success: bool) -> Option<CircuitState> {
+        let mut st = self.lock();
+        let next = match (st.state, success) {
+            (CircuitState::Closed, true) => {
+                st.failures = 0;
+                return None;
+            }
+            (CircuitState::Closed, false) => {
+                st.failures += 1;
+                if st.failures < self.failures {
+                    return None;
+                }
+                CircuitState::Open
+            }
+            (CircuitState::Open, _) => return None,
+            (CircuitState::HalfOpen, true) => CircuitState::Closed,
+            (CircuitState::HalfOpen, false) => CircuitState::Open,
+        };
+        st.state = next;
+        st.failures = 0;
+        if next == CircuitState::Closed {
+            st.retried = 0;
+        }
+        st.since = Instant::now();
+        st.probing = false;
+        Some(next)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state
+            .lock()
+            .expect("imperat circuit breaker mutex poisoned")
+    }
+}
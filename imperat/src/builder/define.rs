@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{
+    Error, GroupBuilder, ImperativeStepBuilder, IntoStepOutcome, Result, StepBuilder, new,
+};
+use crate::{Callable, FromTypeMap};
+
+/// Defines a runner with `define`, which may await between registrations,
+/// such as to list files and add a step for each. Clones register into the
+/// same runner.
+pub struct Registrar<O>(Rc<RefCell<Option<ImperativeStepBuilder<O>>>>);
+
+impl<O> Clone for Registrar<O> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<O: IntoStepOutcome + Send + 'static> Registrar<O> {
+    /// Add a dependency. See `ImperativeStepBuilder::add_dep`.
+    ///
+    /// # Panics
+    /// If used after `define` returned.
+    pub fn dep<T: Send + Sync + 'static>(&self, dep: T) {
+        self.with(|b| b.add_dep(dep));
+    }
+
+    /// Add a step to the default group. See `ImperativeStepBuilder::add_step`.
+    ///
+    /// # Panics
+    /// If used after `define` returned.
+    pub fn step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(&self, name: &str, func: C) {
+        self.with(|b| b.add_step(name, func));
+    }
+
+    /// Add a step built with `new_step`. See `ImperativeStepBuilder::add`.
+    ///
+    /// # Panics
+    /// If used after `define` returned.
+    pub fn add(&self, step: impl Into<StepBuilder<O>>) {
+        self.with(|b| b.add(step));
+    }
+
+    /// Add a group. See `ImperativeStepBuilder::new_group`.
+    ///
+    /// # Panics
+    /// If used after `define` returned.
+    pub fn group(&self, new_fn: impl Fn(GroupBuilder<O>) -> GroupBuilder<O>) {
+        self.with(|b| b.new_group(new_fn));
+    }
+
+    /// Configure the runner with any other builder method, such as
+    /// `|b| b.retry_budget(3)`.
+    ///
+    /// # Panics
+    /// If used after `define` returned.
+    pub fn with(&self, f: impl FnOnce(ImperativeStepBuilder<O>) -> ImperativeStepBuilder<O>) {
+        let mut builder = self.0.borrow_mut();
+        let b = builder
+            .take()
+            .expect("imperat registrar used after define returned");
+        *builder = Some(f(b));
+    }
+}
+
+/// Defines a runner by calling `f`, which registers dependencies and steps
+/// with the `Registrar` it's passed and may await while doing so. `f` may
+/// return anything a step may, such as a `Result`; if it fails, so does
+/// this, with `Error::Define`.
+///
+/// ```
+/// # async fn example() -> Result<(), imperat::BuilderError> {
+/// # struct Database;
+/// use imperat::{Dep, define};
+///
+/// let builder = define(async |reg| {
+///     reg.dep(Dep::new(Database));
+///     for table in ["users", "orders"] {
+///         // such as after listing the tables with `.await`
+///         reg.step(&format!("migrate {table}"), async |_: Dep<Database>| {});
+///     }
+///     Ok::<_, std::io::Error>(())
+/// })
+/// .await?;
+/// builder.execute().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Panics
+/// If `f` panicked within `Registrar::with` and recovered.
+pub async fn define<O, F, Fut>(f: F) -> Result<ImperativeStepBuilder<O>>
+where
+    O: IntoStepOutcome + Send + 'static,
+    F: FnOnce(Registrar<O>) -> Fut,
+    Fut: Future,
+    Fut::Output: IntoStepOutcome,
+{
+    let reg = Registrar(Rc::new(RefCell::new(Some(new()))));
+    let out = f(reg.clone()).await;
+    if !out.success() {
+        return Err(Error::Define(
+            out.error()
+                .unwrap_or_else(|| "definition failed without an error".into()),
+        ));
+    }
+
+    Ok(reg
+        .0
+        .borrow_mut()
+        .take()
+        .expect("imperat registrar poisoned by a panic"))
+}
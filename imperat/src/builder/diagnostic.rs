@@ -0,0 +1,77 @@
+use super::{Error, ExecutionReport, StepOutcome};
+use miette::{Diagnostic, LabeledSpan, SourceCode};
+use std::fmt::{self, Write};
+
+/// A failed run as a `miette::Diagnostic`, listing every step as its source
+/// with each step which didn't succeed labeled by its error. Render it with
+/// miette's `fancy` feature for CLI output. See `ExecutionReport::diagnostic`.
+#[derive(Clone, Debug)]
+pub struct RunDiagnostic {
+    message: String,
+    steps: String,
+    labels: Vec<LabeledSpan>,
+    help: String,
+}
+
+impl RunDiagnostic {
+    pub(super) fn new<O>(report: &ExecutionReport<O>, error: &Error) -> Self {
+        let mut entries: Vec<_> = report.steps.iter().collect();
+        entries.sort_by_key(|s| s.id);
+        let mut steps = String::new();
+        let mut labels = vec![];
+        for s in &entries {
+            let prefix = format!("[{}] ", s.group);
+            let label = match (s.outcome, &s.error) {
+                (StepOutcome::Succeeded, _) | (StepOutcome::Skipped, None) => None,
+                (_, Some(e)) => Some(e.clone()),
+                (StepOutcome::Cancelled, None) => Some("cancelled".to_string()),
+                (StepOutcome::Failed, None) => Some("failed".to_string()),
+            };
+            if let Some(label) = label {
+                let start = steps.len() + prefix.len();
+                labels.push(LabeledSpan::new(Some(label), start, s.name.len()));
+            }
+            let _ = writeln!(steps, "{prefix}{} ({:?})", s.name, s.outcome);
+        }
+        let succeeded = entries
+            .iter()
+            .filter(|s| s.outcome == StepOutcome::Succeeded)
+            .count();
+
+        Self {
+            message: error.to_string(),
+            steps,
+            labels,
+            help: format!("{succeeded} of {} steps succeeded", entries.len()),
+        }
+    }
+}
+
+impl fmt::Display for RunDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RunDiagnostic {}
+
+impl Diagnostic for RunDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new("imperat::run_failed"))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(&self.help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.steps)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(self.labels.iter().cloned()))
+    }
+}
+
+/// Lets runs return their errors through `miette::Result`.
+impl Diagnostic for Error {}
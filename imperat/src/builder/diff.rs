@@ -0,0 +1,144 @@
+use super::{ExecutionReport, StepOutcome};
+use std::{collections::BTreeMap, time::Duration};
+
+/// The outcome and duration of each step in a run, small enough to store
+/// between runs, such as scheduled runs of the same pipeline. Get one with
+/// `ExecutionReport::summary`, and compare two with `diff`. With the `serde`
+/// feature, it serializes for storage.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunSummary {
+    /// Each step's summary, by its group and then its name, separated by a
+    /// `/`, as with `KeyStrategy::GroupQualified`, so steps with the same
+    /// name in different groups are kept apart.
+    pub steps: BTreeMap<String, StepSummary>,
+}
+
+/// A single step's entry in a `RunSummary`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepSummary {
+    pub outcome: StepOutcome,
+    /// See `StepReport::duration`.
+    pub duration: Option<Duration>,
+}
+
+impl<O> From<&ExecutionReport<O>> for RunSummary {
+    fn from(report: &ExecutionReport<O>) -> Self {
+        let steps = report
+            .steps
+            .iter()
+            .map(|s| {
+                let summary = StepSummary {
+                    outcome: s.outcome,
+                    duration: s.duration,
+                };
+                (format!("{}/{}", s.group, s.name), summary)
+            })
+            .collect();
+        Self { steps }
+    }
+}
+
+impl RunSummary {
+    /// Compares this run to a `later` one, flagging steps which became more
+    /// than 20% slower.
+    #[must_use]
+    pub fn diff(&self, later: &RunSummary) -> RunDiff {
+        self.diff_with_threshold(later, 0.2)
+    }
+
+    /// Like `diff`, but flags steps which became slower by more than
+    /// `threshold`, as a fraction of their earlier duration.
+    #[must_use]
+    pub fn diff_with_threshold(&self, later: &RunSummary, threshold: f64) -> RunDiff {
+        let mut diff = RunDiff::default();
+        for (name, after) in &later.steps {
+            let Some(before) = self.steps.get(name) else {
+                diff.added.push(name.clone());
+                continue;
+            };
+            let failed =
+                |s: &StepSummary| matches!(s.outcome, StepOutcome::Failed | StepOutcome::Cancelled);
+            match (failed(before), failed(after)) {
+                (false, true) => diff.new_failures.push(name.clone()),
+                (true, false) => diff.fixed.push(name.clone()),
+                _ => {}
+            }
+            match (before.duration, after.duration) {
+                (Some(b), Some(a)) if a.as_secs_f64() > b.as_secs_f64() * (1.0 + threshold) => {
+                    diff.slower.push(SlowerStep {
+                        name: name.clone(),
+                        before: b,
+                        after: a,
+                    });
+                }
+                _ => {}
+            }
+        }
+        diff.removed = self
+            .steps
+            .keys()
+            .filter(|name| !later.steps.contains_key(*name))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+/// How a run changed from an earlier one, for triaging regressions in
+/// scheduled pipelines. Get one with `RunSummary::diff`. Steps are listed by
+/// their keys in `RunSummary::steps`, and every list is sorted by them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunDiff {
+    /// Steps which failed or were cancelled, but weren't before.
+    pub new_failures: Vec<String>,
+    /// Steps which failed before, but don't now.
+    pub fixed: Vec<String>,
+    /// Steps which ran slower than the threshold allows.
+    pub slower: Vec<SlowerStep>,
+    /// Steps which are only in the later run.
+    pub added: Vec<String>,
+    /// Steps which are only in the earlier run.
+    pub removed: Vec<String>,
+}
+
+/// A step which ran slower than before. See `RunDiff::slower`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowerStep {
+    /// The step's key in `RunSummary::steps`.
+    pub name: String,
+    pub before: Duration,
+    pub after: Duration,
+}
+
+impl RunDiff {
+    /// Returns whether any step newly failed or became slower.
+    #[must_use]
+    pub fn is_regression(&self) -> bool {
+        !self.new_failures.is_empty() || !self.slower.is_empty()
+    }
+}
+
+/// Lists each change on its own line, such as `new failure: release/deploy`.
+impl std::fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for name in &self.new_failures {
+            writeln!(f, "new failure: {name}")?;
+        }
+        for name in &self.fixed {
+            writeln!(f, "fixed: {name}")?;
+        }
+        for s in &self.slower {
+            writeln!(f, "slower: {} ({:?} -> {:?})", s.name, s.before, s.after)?;
+        }
+        for name in &self.added {
+            writeln!(f, "added: {name}")?;
+        }
+        for name in &self.removed {
+            writeln!(f, "removed: {name}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    Error, GroupBuilder, IntoStepOutcome, KeyStrategy, Result, RunContext, new_step,
+    step::{Bindings, CallbackKind, Group, GroupOptions},
+};
+use crate::{Callable, DynStep, FromTypeMap, StepInfo, TypeMap};
+
+/// How many steps may be added at runtime over a run, unless set with
+/// `ImperativeStepBuilder::max_dynamic_steps`.
+pub(super) const DEFAULT_LIMIT: usize = 1000;
+
+type BuildFn<O> = dyn FnOnce(GroupBuilder<O>) -> GroupBuilder<O> + Send;
+
+/// Adds steps to a run while it's running, such as one step for each work
+/// item another step discovers. Steps request a `DynamicSteps` of the
+/// builder's output type; requesting any other fails to resolve, like a
+/// missing dependency.
+///
+/// Added steps run once the adding step's group is done, before the next
+/// group: first every step added with `add_step` or `add_dyn_step` by the
+/// group's steps, in order, as one group, and then every group added with
+/// `add_group`. They may add more steps in turn, up to the run's limit; see
+/// `ImperativeStepBuilder::max_dynamic_steps`. A step which adds a step with
+/// its own name, or with the name of any step which led to it being added,
+/// fails the run with `Error::Cycle`, unless it opts in with
+/// `allow_recursion`.
+pub struct DynamicSteps<O> {
+    queue: Queue<O>,
+    // the step adding steps
+    step: String,
+    // see `allow_recursion`
+    recursive: bool,
+}
+
+impl<O> std::fmt::Debug for DynamicSteps<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynamicSteps")
+            .field("step", &self.step)
+            .field("recursive", &self.recursive)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<O: IntoStepOutcome + Send + 'static> DynamicSteps<O> {
+    /// Allows this step to add steps named after itself or the steps which
+    /// led to it being added, such as a crawler adding itself for each link
+    /// it finds. Only the run's limit stops them then.
+    #[must_use]
+    pub fn allow_recursion(mut self) -> Self {
+        self.recursive = true;
+        self
+    }
+
+    /// Adds a step with the provided name, to run once this step's group is
+    /// done. See `ImperativeStepBuilder::add_step`.
+    pub fn add_step<C, A>(&self, name: &str, func: C)
+    where
+        C: Callable<A, Out = O> + Send + 'static,
+        A: FromTypeMap,
+    {
+        let name = name.to_string();
+        self.push(false, Box::new(move |g| g.add(new_step(&name, func))));
+    }
+
+    /// Adds a step which is a trait object, to run once this step's group is
+    /// done. See `ImperativeStepBuilder::add_dyn_step`.
+    pub fn add_dyn_step(&self, step: Box<dyn DynStep<O>>) {
+        self.push(false, Box::new(move |g| g.add_dyn_step(step)));
+    }
+
+    /// Adds a group, to run once this step's group and the steps added with
+    /// `add_step` are done. See `ImperativeStepBuilder::new_group`.
+    pub fn add_group(
+        &self,
+        new_fn: impl FnOnce(GroupBuilder<O>) -> GroupBuilder<O> + Send + 'static,
+    ) {
+        self.push(true, Box::new(new_fn));
+    }
+
+    fn push(&self, group: bool, build: Box<BuildFn<O>>) {
+        self.queue
+            .0
+            .lock()
+            .expect("imperat dynamic steps mutex poisoned")
+            .push(Added {
+                by: self.step.clone(),
+                recursive: self.recursive,
+                group,
+                build,
+            });
+    }
+}
+
+impl<O: 'static> FromTypeMap for DynamicSteps<O> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(Self {
+            queue: tm.get::<Queue<O>>()?.clone(),
+            step: tm.get::<StepInfo>()?.name().to_string(),
+            recursive: false,
+        })
+    }
+}
+
+/// The steps added at runtime which haven't been turned into groups yet.
+/// It's bound by the builder's output type, so only `DynamicSteps` of that
+/// type resolve.
+pub(super) struct Queue<O>(Arc<Mutex<Vec<Added<O>>>>);
+
+impl<O> Clone for Queue<O> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<O> Default for Queue<O> {
+    fn default() -> Self {
+        Self(Arc::default())
+    }
+}
+
+struct Added<O> {
+    // the step which added it
+    by: String,
+    // whether it may be added in a cycle
+    recursive: bool,
+    // whether it's a group, rather than a step
+    group: bool,
+    build: Box<BuildFn<O>>,
+}
+
+/// Turns the steps added at runtime into groups, with the same defaults and
+/// callbacks as the builder's groups.
+pub(super) struct Expander<O> {
+    queue: Queue<O>,
+    tm: Arc<Mutex<TypeMap>>,
+    bindings: Bindings,
+    keys: KeyStrategy,
+    defaults: GroupOptions<O>,
+    pub(super) callbacks: Vec<CallbackKind<O>>,
+    pub(super) limit: usize,
+    // the position of the next group, and the id of the next step
+    pub(super) index: usize,
+    pub(super) ids: usize,
+    // how many steps were added so far
+    added: usize,
+    // the step which added each step, unless added recursively
+    parents: HashMap<String, String>,
+}
+
+impl<O: IntoStepOutcome + Send + 'static> Expander<O> {
+    pub(super) fn new(
+        tm: Arc<Mutex<TypeMap>>,
+        bindings: Bindings,
+        keys: KeyStrategy,
+        defaults: GroupOptions<O>,
+    ) -> Self {
+        let queue = tm
+            .lock()
+            .expect("imperat typemap mutex poisoned")
+            .get::<Queue<O>>()
+            .cloned()
+            .unwrap_or_default();
+        Self {
+            queue,
+            tm,
+            bindings,
+            keys,
+            defaults,
+            callbacks: vec![],
+            limit: DEFAULT_LIMIT,
+            index: 0,
+            ids: 0,
+            added: 0,
+            parents: HashMap::new(),
+        }
+    }
+
+    /// Takes every step added since the last call, returning the groups to
+    /// run next, in order.
+    pub(super) fn expand(&mut self, run: &RunContext) -> Result<Vec<Group<O>>> {
+        let added = std::mem::take(
+            &mut *self
+                .queue
+                .0
+                .lock()
+                .expect("imperat dynamic steps mutex poisoned"),
+        );
+        let mut steps = self.group().0;
+        let mut groups = vec![];
+        for a in added {
+            if a.group {
+                let new = (a.build)(self.group()).0.flatten();
+                for s in new.iter().flat_map(Group::steps) {
+                    self.track(&a.by, a.recursive, s.name())?;
+                }
+                groups.extend(new);
+            } else {
+                let before = steps.len();
+                steps = (a.build)(GroupBuilder(steps)).0;
+                for s in &steps.steps()[before..] {
+                    self.track(&a.by, a.recursive, s.name())?;
+                }
+            }
+        }
+        if !steps.steps().is_empty() {
+            groups.insert(0, steps);
+        }
+
+        let mut errors = vec![];
+        for g in &mut groups {
+            errors.extend(g.take_errors());
+        }
+        match errors.len() {
+            0 => {}
+            1 => return Err(errors.remove(0)),
+            _ => return Err(Error::Build(errors)),
+        }
+        for g in &mut groups {
+            for cb in &self.callbacks {
+                g.add_callback(cb.clone());
+            }
+            g.check_order()?;
+            self.ids = g.assign_keys(&self.keys, self.index, self.ids);
+            self.index += 1;
+            run.status.add_pending(g.len());
+        }
+
+        Ok(groups)
+    }
+
+    /// Records that step `by` added step `name`, failing if that's one step
+    /// too many or, unless `recursive`, would add steps in a cycle.
+    fn track(&mut self, by: &str, recursive: bool, name: &str) -> Result<()> {
+        self.added += 1;
+        if self.added > self.limit {
+            return Err(Error::TooManySteps(by.to_string(), self.limit));
+        }
+        // Recursive steps aren't parents, so chains never loop.
+        if recursive {
+            return Ok(());
+        }
+        let mut chain = vec![name.to_string()];
+        let mut parent = Some(by);
+        while let Some(p) = parent {
+            chain.push(p.to_string());
+            if p == name {
+                chain.reverse();
+                return Err(Error::Cycle(chain));
+            }
+            parent = self.parents.get(p).map(String::as_str);
+        }
+        self.parents.insert(name.to_string(), by.to_string());
+
+        Ok(())
+    }
+
+    fn group(&self) -> GroupBuilder<O> {
+        GroupBuilder::new(self.tm.clone(), self.bindings.clone()).inherit(&self.defaults)
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::Stream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use super::{CircuitState, StepOutcome, StepProgress};
+
+/// Something which happened during a run, sent as it happens. See
+/// `ImperativeStepBuilder::execute_streaming`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PipelineEvent {
+    /// A group with steps started, by its name or position. See
+    /// `StepReport::group`.
+    GroupStarted { group: String },
+    /// An attempt at a step started, counting from 1.
+    StepStarted {
+        name: String,
+        group: String,
+        attempt: usize,
+    },
+    /// A running step reported progress through `Progress`.
+    StepProgress {
+        name: String,
+        progress: StepProgress,
+    },
+    /// A step finished, including when it was skipped or cancelled. The
+    /// duration is zero for steps which never started.
+    StepFinished {
+        name: String,
+        group: String,
+        duration: Duration,
+        success: bool,
+        outcome: StepOutcome,
+    },
+    /// A circuit breaker changed state, by what it covers. See
+    /// `CircuitBreaker::name`.
+    CircuitChanged {
+        breaker: String,
+        state: CircuitState,
+    },
+    /// The run finished, with its error after redaction if it failed. This is
+    /// always the last event.
+    PipelineFinished {
+        success: bool,
+        error: Option<String>,
+    },
+}
+
+/// A live stream of a run's events, which ends after
+/// `PipelineEvent::PipelineFinished`.
+#[derive(Debug)]
+pub struct PipelineEvents {
+    rx: UnboundedReceiver<PipelineEvent>,
+    done: bool,
+}
+
+impl Stream for PipelineEvents {
+    type Item = PipelineEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let event = std::task::ready!(self.rx.poll_recv(cx));
+        // Steps may hold onto senders, such as in `Progress`, after the run.
+        self.done = matches!(event, None | Some(PipelineEvent::PipelineFinished { .. }));
+        Poll::Ready(event)
+    }
+}
+
+/// Sends a run's events, if anything is listening.
+#[derive(Clone, Debug, Default)]
+pub(super) struct EventSender(Option<UnboundedSender<PipelineEvent>>);
+
+impl EventSender {
+    pub(super) fn channel() -> (Self, PipelineEvents) {
+        let (tx, rx) = unbounded_channel();
+        (Self(Some(tx)), PipelineEvents { rx, done: false })
+    }
+
+    /// Sends the event built by `f`, only building it if there's a listener.
+    pub(super) fn send(&self, f: impl FnOnce() -> PipelineEvent) {
+        if let Some(tx) = &self.0 {
+            // The stream may have been dropped; the run goes on regardless.
+            let _ = tx.send(f());
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use futures::{
+    FutureExt,
+    channel::oneshot,
+    future::{self, AbortHandle, BoxFuture, Either},
+};
+use std::{
+    collections::BTreeMap,
+    panic::AssertUnwindSafe,
+    pin::{Pin, pin},
+    sync::{
+        Arc, Condvar, Mutex, Once,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// The async runtime a run spawns steps and waits on timers with. Steps in
+/// parallel groups are spawned onto it, and retry delays and timeouts sleep
+/// on it. Set one with `ImperativeStepBuilder::executor`; by default it's
+/// `TokioExecutor` with the `tokio` feature, or `ThreadExecutor` without.
+///
+/// Implementing it for another runtime takes a couple of lines:
+///
+/// ```
+/// use futures::future::BoxFuture;
+/// use imperat::Executor;
+/// use std::time::Duration;
+///
+/// struct Smol;
+///
+/// impl Executor for Smol {
+///     fn spawn(&self, fut: BoxFuture<'static, ()>) {
+///         // smol::spawn(fut).detach();
+///         # drop(fut);
+///     }
+///
+///     fn sleep(&self, limit: Duration) -> BoxFuture<'static, ()> {
+///         // Box::pin(async move { smol::Timer::after(limit).await; })
+///         # Box::pin(async move { std::thread::sleep(limit) })
+///     }
+/// }
+/// ```
+pub trait Executor: Send + Sync {
+    /// Runs `fut` as a detached task. Imperat awaits its result and aborts
+    /// it when no longer needed itself, so the task's handle isn't needed.
+    fn spawn(&self, fut: BoxFuture<'static, ()>);
+
+    /// Returns a future which completes after `limit`.
+    fn sleep(&self, limit: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// Spawns onto the current tokio runtime and sleeps with its timers. Runs
+/// must be executed within a tokio runtime with timers enabled.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioExecutor;
+
+#[cfg(feature = "tokio")]
+impl Executor for TokioExecutor {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        tokio::spawn(fut);
+    }
+
+    fn sleep(&self, limit: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(limit))
+    }
+}
+
+/// Needs no runtime at all: each spawned task is driven to completion on its
+/// own thread, while every timer is kept by a single timer thread, started
+/// on first use. Suited to occasional parallel steps and timeouts in
+/// applications without a runtime of their own, such as those driving a run
+/// with `futures::executor::block_on`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadExecutor;
+
+impl Executor for ThreadExecutor {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        std::thread::spawn(move || futures::executor::block_on(fut));
+    }
+
+    fn sleep(&self, limit: Duration) -> BoxFuture<'static, ()> {
+        static IDS: AtomicU64 = AtomicU64::new(0);
+        Box::pin(Sleep {
+            key: (Instant::now() + limit, IDS.fetch_add(1, Ordering::Relaxed)),
+        })
+    }
+}
+
+/// The timers of every `ThreadExecutor`, woken by one thread once their
+/// deadline passes.
+static TIMERS: Timers = Timers {
+    pending: Mutex::new(BTreeMap::new()),
+    changed: Condvar::new(),
+};
+
+struct Timers {
+    // pending timers by deadline, then id, and the waker of their sleep
+    pending: Mutex<BTreeMap<(Instant, u64), Waker>>,
+    // signalled when a timer is added before every other one
+    changed: Condvar,
+}
+
+impl Timers {
+    /// Starts the timer thread, unless it's already running.
+    fn start() {
+        static STARTED: Once = Once::new();
+        STARTED.call_once(|| {
+            std::thread::Builder::new()
+                .name("imperat-timers".to_string())
+                .spawn(|| TIMERS.run())
+                .expect("failed to spawn the imperat timer thread");
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<(Instant, u64), Waker>> {
+        self.pending.lock().expect("imperat timer mutex poisoned")
+    }
+
+    /// Wakes each timer once its deadline passes, sleeping until the next
+    /// deadline or until an earlier timer is added.
+    fn run(&self) {
+        let mut pending = self.lock();
+        loop {
+            let now = Instant::now();
+            let mut due = vec![];
+            while let Some(timer) = pending.first_entry() {
+                if timer.key().0 > now {
+                    break;
+                }
+                due.push(timer.remove());
+            }
+            if !due.is_empty() {
+                // wakers may poll their sleep, which takes the lock
+                drop(pending);
+                due.into_iter().for_each(Waker::wake);
+                pending = self.lock();
+                continue;
+            }
+            pending = match pending.keys().next() {
+                Some(&(deadline, _)) => {
+                    self.changed
+                        .wait_timeout(pending, deadline - now)
+                        .expect("imperat timer mutex poisoned")
+                        .0
+                }
+                None => self
+                    .changed
+                    .wait(pending)
+                    .expect("imperat timer mutex poisoned"),
+            };
+        }
+    }
+}
+
+/// Completes once its deadline passes. Its timer is removed when it's
+/// dropped, so abandoned sleeps, such as timeouts of steps which finished in
+/// time, don't linger.
+struct Sleep {
+    key: (Instant, u64),
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.key.0 {
+            return Poll::Ready(());
+        }
+        Timers::start();
+        let mut pending = TIMERS.lock();
+        pending.insert(self.key, cx.waker().clone());
+        if pending.keys().next() == Some(&self.key) {
+            TIMERS.changed.notify_one();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        TIMERS.lock().remove(&self.key);
+    }
+}
+
+/// The executor shared by every group over a single run.
+#[derive(Clone)]
+pub(crate) struct ExecutorHandle(Arc<dyn Executor>);
+
+impl Default for ExecutorHandle {
+    fn default() -> Self {
+        #[cfg(feature = "tokio")]
+        return Self::new(TokioExecutor);
+        #[cfg(not(feature = "tokio"))]
+        return Self::new(ThreadExecutor);
+    }
+}
+
+impl ExecutorHandle {
+    pub(crate) fn new(executor: impl Executor + 'static) -> Self {
+        Self(Arc::new(executor))
+    }
+
+    /// Runs `fut` as a detached task, which outlives the run.
+    pub(crate) fn detach(&self, fut: BoxFuture<'static, ()>) {
+        self.0.spawn(fut);
+    }
+
+    /// Waits for `limit` to pass.
+    pub(crate) async fn sleep(&self, limit: Duration) {
+        self.0.sleep(limit).await;
+    }
+
+    /// Runs `fut` within `limit`, yielding `None` if it timed out.
+    pub(crate) async fn timeout<F: Future>(&self, limit: Duration, fut: F) -> Option<F::Output> {
+        match future::select(pin!(fut), self.0.sleep(limit)).await {
+            Either::Left((out, _)) => Some(out),
+            Either::Right(_) => None,
+        }
+    }
+
+    /// Runs `fut` as a task, so steps in parallel groups can run on any of
+    /// the runtime's threads. The task is aborted if this is dropped, such as
+    /// when the step is cancelled or preempted.
+    pub(crate) async fn spawn<T: Send + 'static>(
+        &self,
+        fut: impl Future<Output = T> + Send + 'static,
+    ) -> std::thread::Result<T> {
+        let (tx, rx) = oneshot::channel();
+        let (task, handle) = future::abortable(AssertUnwindSafe(fut).catch_unwind());
+        let _abort = AbortOnDrop(handle);
+        self.0.spawn(Box::pin(async move {
+            if let Ok(out) = task.await {
+                let _ = tx.send(out);
+            }
+        }));
+        rx.await
+            .unwrap_or_else(|_| Err(Box::new("step task was dropped by its executor")))
+    }
+}
+
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,103 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc,
+};
+
+use super::{AnyOutput, ExecutionReport};
+
+type SerializeFn = dyn Fn(&dyn Any) -> Value + Send + Sync;
+
+/// How step outputs are serialized by `ExecutionReport::to_json`, by their
+/// type. For `AnyOutput`s, it's the type of the output they wrap, so runners
+/// built with `new_any_builder` can register a serializer per step type.
+/// Outputs without one are exported as their `Debug` representation.
+#[derive(Clone, Default)]
+pub struct OutputSerializers(HashMap<TypeId, Arc<SerializeFn>>);
+
+impl std::fmt::Debug for OutputSerializers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OutputSerializers")
+            .field(&self.0.len())
+            .finish()
+    }
+}
+
+impl OutputSerializers {
+    /// Creates serializers which export every output with `Debug`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Export outputs of type `T` with its `Serialize` implementation.
+    /// Outputs which fail to serialize are exported as `null`.
+    #[must_use]
+    pub fn register<T: Serialize + 'static>(self) -> Self {
+        self.register_with(|out: &T| serde_json::to_value(out).unwrap_or(Value::Null))
+    }
+
+    /// Export outputs of type `T` as `serialize` returns, such as to leave
+    /// out large or sensitive fields.
+    #[must_use]
+    pub fn register_with<T: 'static>(
+        mut self,
+        serialize: impl Fn(&T) -> Value + Send + Sync + 'static,
+    ) -> Self {
+        let serialize = move |out: &dyn Any| {
+            out.downcast_ref()
+                .map_or(Value::Null, |out: &T| serialize(out))
+        };
+        self.0.insert(TypeId::of::<T>(), Arc::new(serialize));
+        self
+    }
+
+    /// Serializes `out`, falling back to its `Debug` representation.
+    fn serialize<O: Debug + 'static>(&self, out: &O) -> Value {
+        let any: &dyn Any = out;
+        let inner = match any.downcast_ref::<AnyOutput>() {
+            Some(out) => out.value(),
+            None => any,
+        };
+        match self.0.get(&inner.type_id()) {
+            Some(serialize) => serialize(inner),
+            None => Value::String(format!("{out:?}")),
+        }
+    }
+}
+
+impl<O: Debug + 'static> ExecutionReport<O> {
+    /// Exports the run as JSON, with an entry for each step, including its
+    /// output, if it kept one, serialized by `serializers`.
+    #[must_use]
+    pub fn to_json(&self, serializers: &OutputSerializers) -> Value {
+        let steps: Vec<_> = self
+            .steps
+            .iter()
+            .map(|s| {
+                json!({
+                    "name": s.name,
+                    "key": s.key,
+                    "group": s.group,
+                    "short_id": s.short_id,
+                    "outcome": s.outcome,
+                    "duration": s.duration,
+                    "error": s.error,
+                    "output": self.output(s).map(|out| serializers.serialize(out)),
+                })
+            })
+            .collect();
+
+        json!({
+            "run_id": self.run_id,
+            "seed": self.seed,
+            "input_hash": self.input_hash,
+            "success": self.is_success(),
+            "error": self.error.as_ref().map(ToString::to_string),
+            "steps": steps,
+        })
+    }
+}
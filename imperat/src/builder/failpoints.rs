@@ -0,0 +1,108 @@
+#[cfg(feature = "failpoints")]
+use super::{Error, Result};
+#[cfg(feature = "failpoints")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// Triggers a fail point, evaluating to `Result<()>`. Compiled to `Ok(())`
+// without the `failpoints` feature or in release builds.
+macro_rules! fail_point {
+    ($run:expr, $point:ident, $step:expr) => {{
+        #[cfg(all(feature = "failpoints", debug_assertions))]
+        let res = $run.fail_points.trigger(super::FailPoint::$point, $step);
+        #[cfg(not(all(feature = "failpoints", debug_assertions)))]
+        let res: super::Result<()> = Ok(());
+        res
+    }};
+}
+
+pub(super) use fail_point;
+
+/// A point inside the executor where `FailPoints` can inject an error.
+#[cfg(feature = "failpoints")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FailPoint {
+    /// Before a step's dependencies are resolved.
+    BeforeResolve,
+    /// After dependencies are resolved, before the step is awaited.
+    BeforeAwait,
+    /// After the step finished, in place of its output.
+    AfterStep,
+}
+
+#[cfg(feature = "failpoints")]
+impl std::fmt::Display for FailPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FailPoint::BeforeResolve => "before_resolve",
+            FailPoint::BeforeAwait => "before_await",
+            FailPoint::AfterStep => "after_step",
+        })
+    }
+}
+
+/// Fail points which fail steps' attempts with `Error::FailPoint` to
+/// simulate internal errors in tests, such as to check that retries,
+/// rollbacks, or callbacks handle them. Attach them to a run with
+/// `ImperativeStepBuilder::fail_points`. Fail points are only checked in
+/// debug builds; in release builds they're compiled out.
+#[cfg(feature = "failpoints")]
+#[derive(Clone, Debug, Default)]
+pub struct FailPoints(Arc<Mutex<Triggers>>);
+
+// how many more times each point triggers for each step, if limited
+#[cfg(feature = "failpoints")]
+type Triggers = HashMap<(FailPoint, String), Option<usize>>;
+
+#[cfg(feature = "failpoints")]
+impl FailPoints {
+    /// Creates fail points which don't trigger anywhere yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail every attempt of `step` at `point`.
+    ///
+    /// # Panics
+    /// If the fail point mutex is poisoned.
+    #[must_use]
+    pub fn fail(self, point: FailPoint, step: &str) -> Self {
+        self.insert(point, step, None)
+    }
+
+    /// Fail only the first `times` attempts of `step` at `point`, so that a
+    /// retry can succeed.
+    ///
+    /// # Panics
+    /// If the fail point mutex is poisoned.
+    #[must_use]
+    pub fn fail_times(self, point: FailPoint, step: &str, times: usize) -> Self {
+        self.insert(point, step, Some(times))
+    }
+
+    fn insert(self, point: FailPoint, step: &str, times: Option<usize>) -> Self {
+        self.0
+            .lock()
+            .expect("imperat fail point mutex poisoned")
+            .insert((point, step.to_string()), times);
+        self
+    }
+
+    /// Fails if `point` is set for `step` and has triggers left.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    pub(super) fn trigger(&self, point: FailPoint, step: &str) -> Result<()> {
+        let mut points = self.0.lock().expect("imperat fail point mutex poisoned");
+        match points.get_mut(&(point, step.to_string())) {
+            None | Some(Some(0)) => Ok(()),
+            Some(times) => {
+                if let Some(n) = times {
+                    *n -= 1;
+                }
+                Err(Error::FailPoint(step.to_string(), point))
+            }
+        }
+    }
+}
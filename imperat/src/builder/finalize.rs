@@ -0,0 +1,143 @@
+use super::{Error, Result, RunContext, log::log_warn, step::short_type_name};
+use crate::{Callable, FromTypeMap, IntoStepOutcome, TypeMap};
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+
+type FinalizeFn = dyn Fn(&TypeMap) -> Result<BoxFuture<'static, Result<()>>> + Send + Sync;
+
+/// Cleans up after a run, however it ends. See
+/// `ImperativeStepBuilder::finalizer`.
+pub(super) struct Finalizer {
+    name: String,
+    call: Box<FinalizeFn>,
+}
+
+impl Finalizer {
+    pub(super) fn new<C, A: FromTypeMap + Send + 'static>(name: &str, func: C) -> Self
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        let func = Arc::new(func);
+        let name = name.to_string();
+        Self {
+            name: name.clone(),
+            // resolved synchronously so it can be started from `Drop`
+            call: Box::new(move |map| {
+                let args = A::retrieve_from_map(map).ok_or_else(|| match A::missing(map) {
+                    Some((index, dep)) => {
+                        Error::MissingParam(name.clone(), index, short_type_name(dep.name))
+                    }
+                    None => Error::DepResolution(name.clone()),
+                })?;
+                let (func, name) = (func.clone(), name.clone());
+                Ok(Box::pin(async move {
+                    let out = func.call(args).await;
+                    if out.success() {
+                        return Ok(());
+                    }
+                    Err(match out.error() {
+                        Some(e) => Error::Step(name, e),
+                        None => Error::UnknownStep(name),
+                    })
+                }))
+            }),
+        }
+    }
+}
+
+/// A run's finalizers, which run once it's over. If the run is dropped
+/// before then, the one running and those yet to start are finished on its
+/// executor instead.
+pub(super) struct Finalizers {
+    pending: Vec<Finalizer>,
+    // the finalizer running, so it's finished if this is dropped
+    current: Option<BoxFuture<'static, Result<()>>>,
+    tm: Arc<Mutex<TypeMap>>,
+    run: RunContext,
+}
+
+impl Finalizers {
+    pub(super) fn new(pending: Vec<Finalizer>, tm: Arc<Mutex<TypeMap>>, run: RunContext) -> Self {
+        Self {
+            pending,
+            current: None,
+            tm,
+            run,
+        }
+    }
+
+    /// Runs every finalizer in the order they were added, even if earlier
+    /// ones fail, returning the first failure. If this is dropped partway,
+    /// the rest are still finished on the executor.
+    pub(super) async fn run(mut self) -> Result<()> {
+        let mut first = None;
+        loop {
+            if self.current.is_none() {
+                if self.pending.is_empty() {
+                    break;
+                }
+                let f = self.pending.remove(0);
+                if self.run.settings.verbose {
+                    eprintln!("running finalizer '{}'", f.name);
+                }
+                match (f.call)(&self.tm.lock().expect("imperat typemap mutex poisoned")) {
+                    Ok(fut) => self.current = Some(fut),
+                    Err(e) => {
+                        first.get_or_insert(e);
+                        continue;
+                    }
+                }
+            }
+            if let Some(fut) = &mut self.current {
+                let res = fut.await;
+                self.current = None;
+                if let Err(e) = res {
+                    first.get_or_insert(e);
+                }
+            }
+        }
+        first.map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for Finalizers {
+    fn drop(&mut self) {
+        if self.current.is_none() && self.pending.is_empty() {
+            return;
+        }
+        // a poisoned typemap still holds the dependencies bound so far
+        let tm = self
+            .tm
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let futs: Vec<_> = self
+            .current
+            .take()
+            .into_iter()
+            .chain(self.pending.drain(..).filter_map(|f| match (f.call)(&tm) {
+                Ok(fut) => Some(fut),
+                Err(e) => {
+                    log_warn!(
+                        "finalizer '{}' couldn't run: {}",
+                        f.name,
+                        self.run.redact_error(e)
+                    );
+                    None
+                }
+            }))
+            .collect();
+        drop(tm);
+        let run = self.run.clone();
+        self.run.executor.detach(Box::pin(async move {
+            for fut in futs {
+                if let Err(e) = fut.await {
+                    log_warn!(
+                        "finalizer failed after the run was dropped: {}",
+                        run.redact_error(e)
+                    );
+                }
+            }
+        }));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+
+type Flights = Arc<Mutex<HashMap<String, Box<dyn Any + Send + Sync>>>>;
+
+/// Deduplicates steps with the same idempotency key across concurrent
+/// runs, such as the same pipeline running for many requests in a server.
+/// While a step with a key is running, other steps with that key wait for
+/// and share its output rather than running themselves.
+///
+/// Share one between builders and opt steps in with `StepBuilder::single_flight`.
+#[derive(Clone, Debug, Default)]
+pub struct SingleFlight {
+    flights: Flights,
+}
+
+impl SingleFlight {
+    /// Creates a registry with no steps in flight.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Awaits `fut`, unless a step with the same key is already running, in
+    /// which case its output is shared instead. If that step doesn't finish,
+    /// such as when it times out, `fut` is awaited after all.
+    pub(super) async fn run<O, F>(&self, key: &str, fut: F) -> O
+    where
+        O: Clone + Send + Sync + 'static,
+        F: Future<Output = O>,
+    {
+        let leader = {
+            let mut flights = self.flights.lock().expect("imperat flight mutex poisoned");
+            if let Some(rx) = flights.get(key) {
+                Err(rx.downcast_ref::<watch::Receiver<Option<O>>>().cloned())
+            } else {
+                let (tx, rx) = watch::channel(None);
+                flights.insert(key.to_string(), Box::new(rx));
+                Ok(tx)
+            }
+        };
+
+        let tx = match leader {
+            Ok(tx) => tx,
+            Err(Some(mut rx)) => {
+                if let Ok(out) = rx.wait_for(Option::is_some).await {
+                    return out.clone().expect("waited for output");
+                }
+                return fut.await;
+            }
+            // The key's in flight with another output type, so don't share it.
+            Err(None) => return fut.await,
+        };
+
+        let landing = Landing {
+            flights: &self.flights,
+            key,
+        };
+        let out = fut.await;
+        drop(landing);
+        tx.send_replace(Some(out.clone()));
+
+        out
+    }
+}
+
+// Removes a key from the registry once its leader finishes or is dropped.
+struct Landing<'a> {
+    flights: &'a Flights,
+    key: &'a str,
+}
+
+impl Drop for Landing<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut flights) = self.flights.lock() {
+            flights.remove(self.key);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use super::{Error, Result, step::short_type_name};
+use crate::{Callable, Dep, DepInfo, FromTypeMap, IntoStepOutcome, TypeMap};
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+type HealthFuture = Pin<Box<dyn Future<Output = bool> + Send>>;
+type HealthFn = dyn Fn(&TypeMap) -> Result<HealthFuture> + Send + Sync;
+
+/// Checks whether a dependency is healthy when a run starts. See
+/// `ImperativeStepBuilder::health_check`.
+pub(super) struct HealthCheck {
+    /// The dependency this checks, as steps request it.
+    pub(super) dep: DepInfo,
+    call: Box<HealthFn>,
+}
+
+impl HealthCheck {
+    pub(super) fn new<T: 'static, C, A: FromTypeMap>(check: C) -> Self
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        let name = format!(
+            "health check of {}",
+            short_type_name(std::any::type_name::<Dep<T>>())
+        );
+        let check = Arc::new(check);
+        Self {
+            dep: DepInfo::of::<Dep<T>>(),
+            call: Box::new(move |map| {
+                let args = A::retrieve_from_map(map).ok_or_else(|| match A::missing(map) {
+                    Some((index, dep)) => {
+                        Error::MissingParam(name.clone(), index, short_type_name(dep.name))
+                    }
+                    None => Error::DepResolution(name.clone()),
+                })?;
+                let check = check.clone();
+                Ok(Box::pin(async move { check.call(args).await.success() }))
+            }),
+        }
+    }
+
+    /// Returns whether this dependency is healthy, checked with the
+    /// dependencies bound so far.
+    pub(super) async fn check(&self, tm: &Mutex<TypeMap>) -> Result<bool> {
+        let fut = (self.call)(&tm.lock().expect("imperat typemap mutex poisoned"))?;
+        Ok(fut.await)
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+/// A compact summary of step durations, bucketed by powers of two
+/// milliseconds, for exporting distributions without keeping every timing.
+/// See `ExecutionReport::durations`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DurationHistogram {
+    /// How many durations fell in each bucket. Bucket 0 holds durations under
+    /// 1ms, and bucket `i` those under `2^i` ms but at least half that. Empty
+    /// buckets past the last duration are left out.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: Duration,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl DurationHistogram {
+    /// Adds a duration to the histogram.
+    pub fn record(&mut self, duration: Duration) {
+        let ms = duration.as_millis();
+        let bucket = (u128::BITS - ms.leading_zeros()) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(duration);
+        self.min = Some(self.min.map_or(duration, |min| min.min(duration)));
+        self.max = Some(self.max.map_or(duration, |max| max.max(duration)));
+    }
+
+    /// Adds every duration in `other` to this histogram.
+    pub fn merge(&mut self, other: &DurationHistogram) {
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (total, n) in self.buckets.iter_mut().zip(&other.buckets) {
+            *total += n;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = self.max.max(other.max);
+    }
+
+    /// Returns the exclusive upper bound of the bucket at `index`.
+    #[must_use]
+    pub fn bucket_bound(index: usize) -> Duration {
+        let ms = u32::try_from(index)
+            .ok()
+            .and_then(|i| 1u64.checked_shl(i))
+            .unwrap_or(u64::MAX);
+        Duration::from_millis(ms)
+    }
+
+    /// Returns the average duration, unless nothing was recorded.
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|&n| n > 0)?;
+        Some(self.sum / count)
+    }
+
+    /// Returns an upper bound on the duration at quantile `q`, between 0 and
+    /// 1, such as 0.99 for p99: the bound of its bucket, capped at `max`.
+    #[must_use]
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let max = self.max?;
+        // rank of the duration at `q`, counting from 1
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Some(Self::bucket_bound(i).min(max));
+            }
+        }
+
+        Some(max)
+    }
+}
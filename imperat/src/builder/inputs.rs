@@ -0,0 +1,26 @@
+use super::rollout::fnv1a;
+use serde::Serialize;
+use std::{any::type_name, collections::BTreeMap};
+
+/// The serialized dependencies of a run, by type. Hashing them lets runs
+/// detect when they were given different inputs than another run.
+#[derive(Debug, Default)]
+pub(super) struct Inputs(BTreeMap<&'static str, Vec<u8>>);
+
+impl Inputs {
+    pub(super) fn record<T: Serialize>(&mut self, dep: &T) -> serde_json::Result<()> {
+        self.0.insert(type_name::<T>(), serde_json::to_vec(dep)?);
+        Ok(())
+    }
+
+    /// Hashes every recorded input, independent of the order they were
+    /// recorded in.
+    pub(super) fn hash(&self) -> u64 {
+        fnv1a(
+            self.0
+                .iter()
+                .flat_map(|(name, value)| [name.as_bytes(), &[0], value, &[0]])
+                .flatten(),
+        )
+    }
+}
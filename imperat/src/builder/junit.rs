@@ -0,0 +1,107 @@
+use std::{fmt::Write as _, time::Duration};
+
+use super::{ExecutionReport, StepOutcome, StepReport};
+
+impl<O> ExecutionReport<O> {
+    /// Exports the run as JUnit XML, so CI systems can show it like a test
+    /// run. Each group is a test suite, in the order its first step was
+    /// reported, with a test case per step. Failed steps are failures,
+    /// cancelled steps are errors, and skipped steps are skipped, each with
+    /// the step's error, if any, as the message.
+    #[must_use]
+    pub fn to_junit(&self) -> String {
+        let mut groups: Vec<(&str, Vec<&StepReport>)> = vec![];
+        for s in &self.steps {
+            match groups.iter_mut().find(|(g, _)| *g == s.group) {
+                Some((_, steps)) => steps.push(s),
+                None => groups.push((&s.group, vec![s])),
+            }
+        }
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            out,
+            "<testsuites name=\"run {}\" {}>",
+            self.run_id,
+            counts(&self.steps.iter().collect::<Vec<_>>())
+        );
+        for (group, steps) in groups {
+            let _ = writeln!(
+                out,
+                "  <testsuite name=\"{}\" {}>",
+                escape(group),
+                counts(&steps)
+            );
+            for s in steps {
+                let _ = write!(
+                    out,
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                    escape(&s.name),
+                    escape(&s.group),
+                    seconds(s.duration.unwrap_or_default())
+                );
+                let tag = match s.outcome {
+                    StepOutcome::Succeeded => {
+                        out.push_str("/>\n");
+                        continue;
+                    }
+                    StepOutcome::Failed => "failure",
+                    StepOutcome::Cancelled => "error",
+                    StepOutcome::Skipped => "skipped",
+                };
+                out.push_str(">\n");
+                match &s.error {
+                    Some(e) => {
+                        let e = escape(e);
+                        let _ = writeln!(out, "      <{tag} message=\"{e}\">{e}</{tag}>");
+                    }
+                    None => {
+                        let _ = writeln!(out, "      <{tag}/>");
+                    }
+                }
+                out.push_str("    </testcase>\n");
+            }
+            out.push_str("  </testsuite>\n");
+        }
+        out.push_str("</testsuites>\n");
+        out
+    }
+}
+
+/// The attributes counting `steps` by outcome, with their total duration.
+fn counts(steps: &[&StepReport]) -> String {
+    let count = |outcome| steps.iter().filter(|s| s.outcome == outcome).count();
+    let time = steps.iter().filter_map(|s| s.duration).sum();
+    format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\"",
+        steps.len(),
+        count(StepOutcome::Failed),
+        count(StepOutcome::Cancelled),
+        count(StepOutcome::Skipped),
+        seconds(time)
+    )
+}
+
+fn seconds(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64())
+}
+
+/// Escapes `s` for an XML attribute or text, dropping characters XML can't
+/// represent at all.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\t' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
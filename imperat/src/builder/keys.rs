@@ -0,0 +1,74 @@
+use super::rollout::fnv1a;
+use std::sync::Arc;
+
+/// How the keys of the results returned by `execute` are formed. Steps
+/// with the same key overwrite each other, and the last one to run wins.
+#[derive(Clone, Default)]
+pub enum KeyStrategy {
+    /// The step's name.
+    #[default]
+    Name,
+    /// The step's position in the run, counting from 0 in declaration order
+    /// across every group.
+    Id,
+    /// The step's group and then its name, separated by a `/`. Groups are
+    /// identified by `GroupBuilder::name`, or by their position otherwise.
+    /// The top-level group is position 0.
+    GroupQualified,
+    /// The step's short id. See `StepKey::short_id`.
+    ShortId,
+    /// Keys returned by a custom function.
+    Custom(Arc<dyn Fn(&StepKey) -> String + Send + Sync>),
+}
+
+impl std::fmt::Debug for KeyStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name => write!(f, "Name"),
+            Self::Id => write!(f, "Id"),
+            Self::GroupQualified => write!(f, "GroupQualified"),
+            Self::ShortId => write!(f, "ShortId"),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Everything identifying a step, for building its result key.
+#[derive(Clone, Debug)]
+pub struct StepKey<'a> {
+    /// The step's name.
+    pub name: &'a str,
+    /// The step's group's name, or its position if unnamed.
+    pub group: &'a str,
+    /// The step's position in the run.
+    pub id: usize,
+}
+
+impl StepKey<'_> {
+    /// Returns the step's short id: the first 8 hex digits of a hash of its
+    /// group and name. Unlike its position, it's stable as other steps are
+    /// added or removed, so operators can refer to it across runs.
+    #[must_use]
+    pub fn short_id(&self) -> String {
+        short_id(self.group, self.name)
+    }
+}
+
+/// Returns the short id of the step named `name` in the group labeled
+/// `group`. See `StepKey::short_id`.
+pub(super) fn short_id(group: &str, name: &str) -> String {
+    let hash = fnv1a([group.as_bytes(), &[0], name.as_bytes()].concat());
+    format!("{hash:016x}")[..8].to_string()
+}
+
+impl KeyStrategy {
+    pub(super) fn key(&self, step: &StepKey) -> String {
+        match self {
+            Self::Name => step.name.to_string(),
+            Self::Id => step.id.to_string(),
+            Self::GroupQualified => format!("{}/{}", step.group, step.name),
+            Self::ShortId => step.short_id(),
+            Self::Custom(f) => f(step),
+        }
+    }
+}
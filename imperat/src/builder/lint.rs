@@ -0,0 +1,82 @@
+use super::{ExecutionPlan, step::short_type_name};
+use crate::DepInfo;
+use std::collections::HashSet;
+
+/// A likely mistake in how a runner is built, which doesn't stop it from
+/// running. See `ImperativeStepBuilder::lint`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// More than one step has this name, so only the last one's output is
+    /// kept by default. See `KeyStrategy`.
+    DuplicateName(String),
+    /// A parallel group, by label, with only one step, which gains nothing
+    /// from running in parallel.
+    SingleStepParallel(String),
+    /// A step in a group which tolerates failure binds a dependency, so
+    /// steps using it may fail to resolve it if the step fails.
+    TolerantBinding { group: String, step: String },
+    /// A dependency which was added, but no step or provider requests.
+    UnusedDep(DepInfo),
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateName(name) => write!(f, "step name '{name}' is reused"),
+            Self::SingleStepParallel(group) => {
+                write!(f, "parallel group '{group}' has a single step")
+            }
+            Self::TolerantBinding { group, step } => write!(
+                f,
+                "step '{step}' binds a dependency in group '{group}', which tolerates failure"
+            ),
+            Self::UnusedDep(dep) => write!(
+                f,
+                "dependency {} is added but never used",
+                short_type_name(dep.name)
+            ),
+        }
+    }
+}
+
+/// Finds every `Lint` in `plan`, given the dependencies added to its runner.
+pub(super) fn lint(plan: &ExecutionPlan, added: &[DepInfo]) -> Vec<Lint> {
+    let mut lints = vec![];
+
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    for (_, step) in plan.steps() {
+        if !seen.insert(&step.name) && reported.insert(&step.name) {
+            lints.push(Lint::DuplicateName(step.name.clone()));
+        }
+    }
+
+    for group in &plan.groups {
+        if group.parallel && group.steps.len() == 1 {
+            lints.push(Lint::SingleStepParallel(group.label().to_string()));
+        }
+        if group.tolerate_failure {
+            lints.extend(group.steps.iter().filter(|s| s.binds.is_some()).map(|s| {
+                Lint::TolerantBinding {
+                    group: group.label().to_string(),
+                    step: s.name.clone(),
+                }
+            }));
+        }
+    }
+
+    let used: HashSet<_> = plan
+        .steps()
+        .flat_map(|(_, s)| &s.dependencies)
+        .chain(plan.providers.iter().flat_map(|p| &p.dependencies))
+        .map(|d| d.id)
+        .collect();
+    lints.extend(
+        added
+            .iter()
+            .filter(|d| !used.contains(&d.id))
+            .map(|d| Lint::UnusedDep(*d)),
+    );
+
+    lints
+}
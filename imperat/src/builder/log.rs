@@ -0,0 +1,72 @@
+#[cfg(feature = "tracing")]
+use super::{IntoStepOutcome, Result, RunContext, StepOutcome, StepReport};
+#[cfg(feature = "tracing")]
+use std::time::Duration;
+
+// Logs a warning with `tracing` when enabled, or to stderr otherwise.
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!($($arg)*);
+    }};
+}
+
+pub(super) use log_warn;
+
+/// Creates the span an attempt of a step runs in, and reports its start.
+#[cfg(feature = "tracing")]
+pub(super) fn step_span(
+    step: &str,
+    group: &str,
+    attempt: usize,
+    run: &RunContext,
+) -> tracing::Span {
+    let span = tracing::info_span!(
+        "step",
+        step,
+        group,
+        id = super::keys::short_id(group, step),
+        attempt,
+        run_id = run.id,
+        metadata = %run.redacted(&run.metadata.to_string()),
+        duration = tracing::field::Empty,
+    );
+    tracing::info!(parent: &span, "step started");
+    span
+}
+
+/// Reports how an attempt of a step ended in its span.
+#[cfg(feature = "tracing")]
+pub(super) fn attempt_finished<O: IntoStepOutcome>(
+    span: &tracing::Span,
+    res: &Result<O>,
+    elapsed: Duration,
+    run: &RunContext,
+) {
+    span.record("duration", tracing::field::debug(elapsed));
+    match res {
+        Ok(out) if out.success() => tracing::info!(parent: span, "step succeeded"),
+        Ok(_) => tracing::warn!(parent: span, "step attempt failed"),
+        Err(e) => tracing::warn!(
+            parent: span,
+            error = %run.redacted(&e.to_string()),
+            "step attempt failed",
+        ),
+    }
+}
+
+/// Reports how a step ended, unless it succeeded, which its last attempt
+/// already reported.
+#[cfg(feature = "tracing")]
+pub(super) fn step_finished(entry: &StepReport) {
+    let (step, group) = (entry.name.as_str(), entry.group.as_str());
+    let error = entry.error.as_deref();
+    match entry.outcome {
+        StepOutcome::Succeeded => {}
+        StepOutcome::Failed => tracing::error!(step, group, error, "step failed"),
+        StepOutcome::Skipped => tracing::info!(step, group, reason = error, "step skipped"),
+        StepOutcome::Cancelled => tracing::warn!(step, group, "step cancelled"),
+    }
+}
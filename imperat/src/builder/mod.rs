@@ -1,21 +1,127 @@
+mod approval;
+mod backpressure;
+mod bindings;
+mod budget;
+mod callbacks;
+mod checkpoint;
+mod circuit;
+mod define;
+#[cfg(feature = "miette")]
+mod diagnostic;
+mod diff;
+mod dynamic;
+mod events;
+mod executor;
+#[cfg(feature = "serde")]
+mod export;
+mod failpoints;
+mod finalize;
+mod flight;
+mod health;
+mod histogram;
+#[cfg(feature = "serde")]
+mod inputs;
+mod junit;
+mod keys;
+mod lint;
+mod log;
+mod order;
 mod outcome;
+mod outputs;
+mod pipes;
+mod plan;
+mod profile;
+mod providers;
+mod readiness;
+mod refresh;
+#[cfg(feature = "inventory")]
+mod registry;
+mod render;
+mod replay;
+mod report;
+mod result;
+mod retry;
+mod returns;
+mod rollback;
+mod rollout;
+mod scheduler;
+mod slots;
+mod stats;
+mod status;
 mod step;
+mod tags;
 
 use std::{
     any::TypeId,
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap, VecDeque},
+    hash::{BuildHasher, RandomState},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
-use crate::{FromTypeMap, TypeMap, prelude::*};
-pub use outcome::IntoStepOutcome;
-pub use step::{Group, GroupBuilder, Step};
+use crate::{
+    CancelHandle, DepInfo, DynStep, FromTypeMap, RunController, SyncTypeMap, TypeMap,
+    callable::WithArgs, extractors, prelude::*,
+};
+pub use approval::{Approval, Approvals};
+pub use backpressure::OutputBudget;
+pub use budget::StepBudget;
+pub use callbacks::CallbackPanicPolicy;
+#[cfg(feature = "serde")]
+pub use checkpoint::JsonCheckpointer;
+pub use checkpoint::{Checkpoint, Checkpointer, Cipher};
+pub use circuit::{CircuitBreaker, CircuitState};
+pub use define::{Registrar, define};
+#[cfg(feature = "miette")]
+pub use diagnostic::RunDiagnostic;
+pub use diff::{RunDiff, RunSummary, SlowerStep, StepSummary};
+pub use dynamic::DynamicSteps;
+pub use events::{PipelineEvent, PipelineEvents};
+pub(crate) use executor::ExecutorHandle;
+#[cfg(feature = "tokio")]
+pub use executor::TokioExecutor;
+pub use executor::{Executor, ThreadExecutor};
+#[cfg(feature = "serde")]
+pub use export::OutputSerializers;
+#[cfg(feature = "failpoints")]
+pub use failpoints::{FailPoint, FailPoints};
+pub use flight::SingleFlight;
+pub use histogram::DurationHistogram;
+pub use keys::{KeyStrategy, StepKey};
+pub use lint::Lint;
+pub use order::GroupOrder;
+pub use outcome::{IntoStepOutcome, Skipped};
+pub use outputs::{AnyOutput, AnyStep, Outputs, any_output};
+pub use plan::{ExecutionPlan, GroupPlan, ProviderPlan, StepPlan};
+pub use profile::{Profile, ProfileSettings};
+pub use refresh::Refreshable;
+#[cfg(feature = "inventory")]
+pub use registry::Registration;
+pub use replay::{Replay, ReplaySpeed};
+pub use report::{ExecutionReport, ExitCodes, StepOutcome, StepReport};
+pub use result::RunResult;
+use retry::RetryBudget;
+pub use retry::RetryPolicy;
+pub use returns::{StepExtras, StepReturn};
+pub use rollback::RollbackScope;
+pub use rollout::Rollout;
+pub use scheduler::{Adaptive, Bounded, Parallel, ScheduledStep, Scheduler, Sequential};
+pub use stats::StepStats;
+pub use status::{RunStatus, StatusHandle, StepProgress};
+pub use step::{
+    Group, GroupBuilder, PanicPolicy, Phase, Step, StepBuilder, SubPipeline, new as new_step,
+};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("failed to resolve at least one dependency in step '{0}'")]
     DepResolution(String),
+    #[error("step '{0}': parameter {1} ({2}) missing")]
+    MissingParam(String, usize, String),
     #[error("failed to add a dependency of type '{0:?}' as it was already present")]
     AddDep(TypeId),
     #[error("step '{0}' failed to execute: {1}")]
@@ -24,24 +130,478 @@ pub enum Error {
     UnknownStep(String),
     #[error("group '{0}' had an error: {1}")]
     Group(String, Box<dyn std::error::Error + Send + Sync>),
+    /// A step was stopped, or never started, because the run or its group
+    /// was cancelled, or because it ran past its own timeout.
+    #[error("{}", cancelled(during_step, reason))]
+    Cancelled {
+        reason: CancelReason,
+        during_step: String,
+    },
+    #[cfg(feature = "serde")]
+    #[error("failed to serialize a dependency of type '{0}' for hashing: {1}")]
+    InputHash(&'static str, Box<dyn std::error::Error + Send + Sync>),
+    #[error("{} preflight check(s) failed: {}", .0.len(), join_errors(.0))]
+    Preflight(Vec<Error>),
+    #[error("failed to define the runner: {0}")]
+    Define(Box<dyn std::error::Error + Send + Sync>),
+    #[error("failed to initialize a dependency of type '{0}': {1}")]
+    DepInit(&'static str, Box<dyn std::error::Error + Send + Sync>),
+    #[error("step '{0}' exceeded its budget: {1}")]
+    BudgetExceeded(String, String),
+    #[error("step '{0}' panicked: {1}")]
+    Panicked(String, String),
+    /// A callback panicked. See `ImperativeStepBuilder::on_callback_panic`.
+    #[error("{0} callback for '{1}' panicked: {2}")]
+    CallbackFailed(String, String, String),
+    #[error("steps depend on each other: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    /// Steps added more steps at runtime than the run allows. See
+    /// `ImperativeStepBuilder::max_dynamic_steps`.
+    #[error("step '{0}' added steps past the run's limit of {1}")]
+    TooManySteps(String, usize),
+    #[error(
+        "step '{0}' depends on '{1}', which isn't in the same or an earlier phase of its group"
+    )]
+    DependsOn(String, String),
+    /// A step limits its output's size without measuring it. See
+    /// `StepBudget::max_output_size`.
+    #[error("step '{0}' limits its output's size, but has no `output_size` to measure it")]
+    NoOutputSize(String),
+    /// A closure was added with `ImperativeStepBuilder::add_step_auto`,
+    /// which can't name it.
+    #[error("can't name a step after '{0}', as it's a closure; add it with a name")]
+    ClosureName(String),
+    /// Steps were added to the template of `group_defaults`, which only
+    /// sets options.
+    #[error("group defaults can't have steps, but have: {}", .0.join(", "))]
+    DefaultSteps(Vec<String>),
+    /// Two steps bind the same dependency. See `StepBuilder::produces`.
+    #[error("steps '{1}' and '{2}' both bind '{0}'")]
+    DuplicateBinding(&'static str, String, String),
+    #[error("{}, cancelling: {}", .0, .1.join(", "))]
+    FailFast(Box<Error>, Vec<String>),
+    #[error("step '{0}' was skipped as '{1}' didn't succeed")]
+    Skipped(String, String),
+    /// More than one error occurred while building: first those outside of
+    /// any group, then each group's, including top-level steps', in the
+    /// order they were added.
+    #[error("{} build error(s): {}", .0.len(), join_errors(.0))]
+    Build(Vec<Error>),
+    /// None of the steps of a group which succeeds if any of them does
+    /// succeeded, with each one's error. See `GroupBuilder::any_of`.
+    #[error("none of {} step(s) succeeded: {}", .0.len(), join_errors(.0))]
+    NoneSucceeded(Vec<Error>),
+    /// An error which occurred while building a group, by its name, or its
+    /// position if unnamed.
+    #[error("group '{0}': {1}")]
+    InGroup(String, Box<Error>),
+    #[error("failed to refresh a dependency of type '{0}': {1}")]
+    DepRefresh(&'static str, Box<dyn std::error::Error + Send + Sync>),
+    #[error("failed to checkpoint step '{0}': {1}")]
+    Checkpoint(String, Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "failpoints")]
+    #[error("step '{0}' failed at fail point {1}")]
+    FailPoint(String, FailPoint),
+    #[error("step '{0}' depends on '{1}', which is unhealthy")]
+    Unhealthy(String, String),
+    #[error("step '{0}' depends on '{1}', which wasn't ready after {2:?}")]
+    NotReady(String, String, Duration),
+    #[error("step '{0}' wasn't run as the circuit breaker for '{1}' is open")]
+    CircuitOpen(String, String),
+    #[error("step '{0}' was rejected: {1}")]
+    Rejected(String, String),
+    #[error("group order names '{0}', which isn't a group")]
+    UnknownGroup(String),
+    /// The run failed, and rolling back completed steps failed as well.
+    #[error("{}; {} rollback(s) failed: {}", .0, .1.len(), join_errors(.1))]
+    Rollback(Box<Error>, Vec<Error>),
+}
+
+/// Why a step was cancelled; see `Error::Cancelled`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    /// Cancellation was requested through a `CancelHandle`, such as from a
+    /// signal handler.
+    Requested,
+    /// The run's deadline passed.
+    Deadline,
+    /// Another step, named here, failed in a group which fails fast.
+    FailFast(String),
+    /// The step ran past its timeout. Unlike the other reasons, this is a
+    /// failure of the step: it's retried and may fall back like any other.
+    Timeout,
+}
+
+impl std::fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CancelReason::Requested => f.write_str("cancellation was requested"),
+            CancelReason::Deadline => f.write_str("the run's deadline passed"),
+            CancelReason::FailFast(step) => write!(f, "step '{step}' failed"),
+            CancelReason::Timeout => f.write_str("the step timed out"),
+        }
+    }
+}
+
+impl Error {
+    /// Applies `redact` to every message in this error which may come from a
+    /// step, replacing step errors with their redacted message.
+    fn redact(self, redact: &dyn Fn(&str) -> String) -> Self {
+        match self {
+            Error::Step(name, e) => Error::Step(name, redact(&e.to_string()).into()),
+            Error::Group(name, e) => Error::Group(name, redact(&e.to_string()).into()),
+            Error::Panicked(name, msg) => Error::Panicked(name, redact(&msg)),
+            Error::CallbackFailed(callback, target, msg) => {
+                Error::CallbackFailed(callback, target, redact(&msg))
+            }
+            Error::Rejected(name, reason) => Error::Rejected(name, redact(&reason)),
+            Error::Define(e) => Error::Define(redact(&e.to_string()).into()),
+            Error::DepInit(ty, e) => Error::DepInit(ty, redact(&e.to_string()).into()),
+            Error::DepRefresh(ty, e) => Error::DepRefresh(ty, redact(&e.to_string()).into()),
+            Error::Checkpoint(name, e) => Error::Checkpoint(name, redact(&e.to_string()).into()),
+            Error::Preflight(errors) => {
+                Error::Preflight(errors.into_iter().map(|e| e.redact(redact)).collect())
+            }
+            Error::Build(errors) => {
+                Error::Build(errors.into_iter().map(|e| e.redact(redact)).collect())
+            }
+            Error::NoneSucceeded(errors) => {
+                Error::NoneSucceeded(errors.into_iter().map(|e| e.redact(redact)).collect())
+            }
+            Error::FailFast(e, cancelled) => Error::FailFast(Box::new(e.redact(redact)), cancelled),
+            Error::InGroup(label, e) => Error::InGroup(label, Box::new(e.redact(redact))),
+            Error::Rollback(e, errors) => Error::Rollback(
+                Box::new(e.redact(redact)),
+                errors.into_iter().map(|e| e.redact(redact)).collect(),
+            ),
+            #[cfg(feature = "serde")]
+            Error::InputHash(ty, e) => Error::InputHash(ty, redact(&e.to_string()).into()),
+            e => e,
+        }
+    }
+}
+
+impl Error {
+    /// Copies this error, replacing any boxed errors with their message.
+    fn copy(&self) -> Self {
+        let msg = |e: &(dyn std::error::Error + Send + Sync)| e.to_string().into();
+        let all = |errors: &[Error]| errors.iter().map(Error::copy).collect();
+        match self {
+            Error::DepResolution(name) => Error::DepResolution(name.clone()),
+            Error::MissingParam(name, i, ty) => Error::MissingParam(name.clone(), *i, ty.clone()),
+            Error::AddDep(ty) => Error::AddDep(*ty),
+            Error::Step(name, e) => Error::Step(name.clone(), msg(e.as_ref())),
+            Error::UnknownStep(name) => Error::UnknownStep(name.clone()),
+            Error::Group(name, e) => Error::Group(name.clone(), msg(e.as_ref())),
+            Error::Cancelled {
+                reason,
+                during_step,
+            } => Error::Cancelled {
+                reason: reason.clone(),
+                during_step: during_step.clone(),
+            },
+            #[cfg(feature = "serde")]
+            Error::InputHash(ty, e) => Error::InputHash(ty, msg(e.as_ref())),
+            Error::Preflight(errors) => Error::Preflight(all(errors)),
+            Error::Define(e) => Error::Define(msg(e.as_ref())),
+            Error::DepInit(ty, e) => Error::DepInit(ty, msg(e.as_ref())),
+            Error::DepRefresh(ty, e) => Error::DepRefresh(ty, msg(e.as_ref())),
+            Error::Checkpoint(name, e) => Error::Checkpoint(name.clone(), msg(e.as_ref())),
+            Error::BudgetExceeded(name, msg) => Error::BudgetExceeded(name.clone(), msg.clone()),
+            Error::Unhealthy(name, dep) => Error::Unhealthy(name.clone(), dep.clone()),
+            Error::NotReady(name, dep, timeout) => {
+                Error::NotReady(name.clone(), dep.clone(), *timeout)
+            }
+            Error::CircuitOpen(name, dep) => Error::CircuitOpen(name.clone(), dep.clone()),
+            Error::Rejected(name, reason) => Error::Rejected(name.clone(), reason.clone()),
+            Error::UnknownGroup(label) => Error::UnknownGroup(label.clone()),
+            #[cfg(feature = "failpoints")]
+            Error::FailPoint(name, point) => Error::FailPoint(name.clone(), *point),
+            Error::Panicked(name, msg) => Error::Panicked(name.clone(), msg.clone()),
+            Error::CallbackFailed(callback, target, msg) => {
+                Error::CallbackFailed(callback.clone(), target.clone(), msg.clone())
+            }
+            Error::Cycle(cycle) => Error::Cycle(cycle.clone()),
+            Error::TooManySteps(name, limit) => Error::TooManySteps(name.clone(), *limit),
+            Error::DependsOn(name, dep) => Error::DependsOn(name.clone(), dep.clone()),
+            Error::NoOutputSize(name) => Error::NoOutputSize(name.clone()),
+            Error::ClosureName(ty) => Error::ClosureName(ty.clone()),
+            Error::DefaultSteps(names) => Error::DefaultSteps(names.clone()),
+            Error::DuplicateBinding(ty, first, second) => {
+                Error::DuplicateBinding(ty, first.clone(), second.clone())
+            }
+            Error::FailFast(e, cancelled) => Error::FailFast(Box::new(e.copy()), cancelled.clone()),
+            Error::Skipped(name, dep) => Error::Skipped(name.clone(), dep.clone()),
+            Error::Build(errors) => Error::Build(all(errors)),
+            Error::NoneSucceeded(errors) => Error::NoneSucceeded(all(errors)),
+            Error::InGroup(label, e) => Error::InGroup(label.clone(), Box::new(e.copy())),
+            Error::Rollback(e, errors) => Error::Rollback(Box::new(e.copy()), all(errors)),
+        }
+    }
+
+    /// Returns why the run was cancelled if this error, or the error it
+    /// wraps, is a cancellation, so aborts can be told apart from failures.
+    /// Steps which timed out have `CancelReason::Timeout`.
+    #[must_use]
+    pub fn cancel_reason(&self) -> Option<&CancelReason> {
+        match self {
+            Error::Cancelled { reason, .. } => Some(reason),
+            Error::InGroup(_, e) | Error::Rollback(e, _) => e.cancel_reason(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this is a cancellation which isn't a failure of the
+    /// step it happened during, unlike a timeout.
+    fn aborted(&self) -> bool {
+        matches!(self, Error::Cancelled { reason, .. } if *reason != CancelReason::Timeout)
+    }
+
+    /// Attributes this error to the group labeled `label`, if any.
+    fn in_group(self, label: Option<&str>) -> Self {
+        match label {
+            Some(label) => Error::InGroup(label.to_string(), Box::new(self)),
+            None => self,
+        }
+    }
+}
+
+fn cancelled(step: &str, reason: &CancelReason) -> String {
+    match reason {
+        CancelReason::Timeout => format!("step '{step}' timed out"),
+        _ => format!("run was cancelled at step '{step}': {reason}"),
+    }
+}
+
+fn join_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+type DepAccessFn = dyn Fn(&str, &DepInfo) + Send + Sync;
+type RedactFn = dyn Fn(&str) -> String + Send + Sync;
+
+/// State shared by every group over a single run.
+#[derive(Clone, Default)]
+struct RunContext {
+    // unique to this run; see `CurrentRun::run_id`
+    id: u64,
+    // see `RunRng`
+    seed: u64,
+    retry_budget: RetryBudget,
+    circuits: Vec<CircuitBreaker>,
+    cancel: CancelHandle,
+    // see `RunController::drain`
+    draining: Arc<AtomicBool>,
+    settings: ProfileSettings,
+    on_dep_access: Option<Arc<DepAccessFn>>,
+    callbacks: callbacks::Callbacks,
+    stats: Option<StepStats>,
+    status: StatusHandle,
+    deadline: Option<Instant>,
+    metadata: RunMetadata,
+    redact: Option<Arc<RedactFn>>,
+    pipes: pipes::Pipes,
+    log: report::StepLog,
+    rollbacks: rollback::Rollbacks,
+    rollback_scope: RollbackScope,
+    refreshers: refresh::Refreshers,
+    counters: report::StepCounters,
+    events: events::EventSender,
+    executor: ExecutorHandle,
+    approvals: Approvals,
+    // environment variables to snapshot into the report
+    env: Vec<String>,
+    // dependencies whose health checks failed as the run started
+    unhealthy: Vec<DepInfo>,
+    // see `ImperativeStepBuilder::wait_for_dep`
+    readiness: Vec<Arc<readiness::ReadinessProbe>>,
+    #[cfg(feature = "failpoints")]
+    fail_points: FailPoints,
+}
+
+impl RunContext {
+    /// Returns whether the run is draining, so no more steps should start.
+    fn draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Returns `msg` after applying the run's redactor, if any. If the
+    /// redactor panics, the message is withheld rather than leaked.
+    fn redacted(&self, msg: &str) -> String {
+        match &self.redact {
+            Some(redact) => self
+                .callbacks
+                .call("redact", "run", || redact(msg))
+                .unwrap_or_else(|| "<redaction failed>".to_string()),
+            None => msg.to_string(),
+        }
+    }
+
+    /// Returns `e` with every message which may contain step data redacted,
+    /// if the run has a redactor.
+    fn redact_error(&self, e: Error) -> Error {
+        if self.redact.is_some() {
+            e.redact(&|msg| self.redacted(msg))
+        } else {
+            e
+        }
+    }
+
+    /// Reads each of the environment variables `names` which is set,
+    /// redacting their values.
+    fn snapshot_env(&self, names: &[String]) -> BTreeMap<String, String> {
+        names
+            .iter()
+            .filter_map(|name| {
+                let value = std::env::var_os(name)?;
+                Some((name.clone(), self.redacted(&value.to_string_lossy())))
+            })
+            .collect()
+    }
+
+    /// Returns why step `name` can't run, if any of its `deps` failed its
+    /// health check.
+    fn unavailable(&self, name: &str, deps: &[DepInfo]) -> Option<Error> {
+        deps.iter()
+            .find(|d| self.unhealthy.contains(d))
+            .map(|dep| Error::Unhealthy(name.to_string(), step::short_type_name(dep.name)))
+    }
+
+    /// Lets step `name`, with `deps` and `tags`, through the run's circuit
+    /// breakers, or returns the error for the first which is open.
+    fn admit(&self, name: &str, deps: &[DepInfo], tags: &[&str]) -> Result<()> {
+        let covering: Vec<_> = self
+            .circuits
+            .iter()
+            .filter(|b| b.covers(deps, tags))
+            .collect();
+        for (i, breaker) in covering.iter().enumerate() {
+            let (admitted, changed) = breaker.admit();
+            self.circuit_changed(breaker, changed);
+            if !admitted {
+                covering[..i].iter().for_each(|b| b.release());
+                return Err(Error::CircuitOpen(name.to_string(), breaker.name()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records an attempt of a step with `deps` and `tags` in the circuit
+    /// breakers covering it.
+    fn record_attempt(&self, deps: &[DepInfo], tags: &[&str], success: bool) {
+        for breaker in self.circuits.iter().filter(|b| b.covers(deps, tags)) {
+            self.circuit_changed(breaker, breaker.record(success));
+        }
+    }
+
+    /// Consumes a retry for a step with `deps` and `tags`, returning whether
+    /// every circuit breaker covering it and the run's retry budget allow
+    /// it. Nothing is consumed unless all of them do.
+    fn take_retry(&self, deps: &[DepInfo], tags: &[&str]) -> bool {
+        let covering: Vec<_> = self
+            .circuits
+            .iter()
+            .filter(|b| b.covers(deps, tags))
+            .collect();
+        for (i, breaker) in covering.iter().enumerate() {
+            let (taken, changed) = breaker.take_retry();
+            self.circuit_changed(breaker, changed);
+            if !taken {
+                covering[..i].iter().for_each(|b| b.refund_retry());
+                return false;
+            }
+        }
+        if !self.retry_budget.take() {
+            for breaker in &covering {
+                breaker.refund_retry();
+            }
+            return false;
+        }
+        true
+    }
+
+    fn circuit_changed(&self, breaker: &CircuitBreaker, state: Option<CircuitState>) {
+        let Some(state) = state else {
+            return;
+        };
+        if self.settings.verbose {
+            eprintln!("circuit breaker for '{}' is {state:?}", breaker.name());
+        }
+        self.events.send(|| PipelineEvent::CircuitChanged {
+            breaker: breaker.name(),
+            state,
+        });
+    }
+
+    /// Records a step's entry in the run's report and streams it.
+    fn record(&self, entry: report::StepReport) {
+        self.events.send(|| PipelineEvent::StepFinished {
+            name: entry.name.clone(),
+            group: entry.group.clone(),
+            duration: entry.duration.unwrap_or_default(),
+            success: entry.outcome == StepOutcome::Succeeded,
+            outcome: entry.outcome,
+        });
+        self.log.record(entry);
+    }
+}
+
 /// The primary entrypoint to building out an imperative runner. Initialize
 /// with default and then chain calls to each other.
 #[must_use]
-pub fn new<O>() -> ImperativeStepBuilder<O> {
+pub fn new<O: 'static>() -> ImperativeStepBuilder<O> {
     ImperativeStepBuilder::<O>::default()
 }
 
+/// Like `new`, but for runners whose steps only run effects and return `()`,
+/// so the output type never needs to be annotated.
+#[must_use]
+pub fn new_unit() -> ImperativeStepBuilder {
+    new()
+}
+
+/// Like `new`, but for runners whose steps return different types. Wrap
+/// each step with `any_output` and query the results with `Outputs`.
+#[must_use]
+pub fn new_any() -> ImperativeStepBuilder<AnyOutput> {
+    new()
+}
+
 /// A builder which returns an output `O` on execution. Create one
 /// by calling `new`.
-pub struct ImperativeStepBuilder<O> {
+pub struct ImperativeStepBuilder<O = ()> {
     tm: Arc<Mutex<TypeMap>>,
     default: Group<O>,
     groups: Vec<Group<O>>,
-    errors: Arc<Mutex<Vec<Error>>>,
+    // created by the first preflight check
+    preflight: Option<Group<O>>,
+    // errors accumulated at build time outside of any group's steps
+    errors: Vec<Error>,
+    bindings: step::Bindings,
+    keys: KeyStrategy,
+    order: GroupOrder,
+    group_defaults: step::GroupOptions<O>,
+    providers: Vec<providers::Provider>,
+    health: Vec<health::HealthCheck>,
+    finalizers: Vec<finalize::Finalizer>,
+    run: RunContext,
+    checkpointer: Option<Arc<dyn Checkpointer<O>>>,
+    resume: Option<Checkpoint<O>>,
+    tags: Option<tags::TagFilter>,
+    // every dependency added with `add_dep`, for `lint`
+    added: Vec<DepInfo>,
+    // see `max_dynamic_steps`
+    max_dynamic_steps: usize,
+    // set with `default_step_timeout`, which profiles don't override
+    step_timeout: Option<Duration>,
+    #[cfg(feature = "serde")]
+    inputs: inputs::Inputs,
 }
 
 #[allow(clippy::missing_fields_in_debug)]
@@ -49,35 +609,236 @@ impl<O> std::fmt::Debug for ImperativeStepBuilder<O> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ImperativeStepBuilder")
             .field("tm", &self.tm.lock().unwrap())
-            .field("errors", &self.errors.lock().unwrap())
+            .field("errors", &self.errors)
+            .field("steps", &self.default)
+            .field("groups", &self.groups)
+            .field("preflight", &self.preflight)
             .finish()
     }
 }
 
-impl<O> Default for ImperativeStepBuilder<O> {
+/// Lists every step in the order its group runs, with its dependencies and
+/// any options set on it or its group. Groups are labeled by name, or by
+/// their position if unnamed.
+impl<O> std::fmt::Display for ImperativeStepBuilder<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(preflight) = &self.preflight {
+            preflight.fmt_tree(f, "preflight")?;
+        }
+        self.default.fmt_tree(f, "steps")?;
+        for (i, group) in self.groups.iter().enumerate() {
+            let label = match group.name() {
+                Some(name) => format!("group '{name}'"),
+                None => format!("group {}", i + 1),
+            };
+            group.fmt_tree(f, &label)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<O: 'static> Default for ImperativeStepBuilder<O> {
     fn default() -> Self {
         let tm: Arc<Mutex<TypeMap>> = Arc::default();
-        let errors: Arc<Mutex<Vec<Error>>> = Arc::default();
+        tm.lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(RunMetadata::default());
+        tm.lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(Barriers::default());
+        tm.lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(dynamic::Queue::<O>::default());
+        let bindings = step::Bindings::default();
+        let id = RandomState::new().hash_one(Instant::now());
+        tm.lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(RunRng::new(id));
 
         ImperativeStepBuilder::<O> {
             tm: tm.clone(),
             groups: vec![],
-            errors: errors.clone(),
-            default: Group::new(tm, errors),
+            preflight: None,
+            errors: vec![],
+            bindings: bindings.clone(),
+            keys: KeyStrategy::default(),
+            order: GroupOrder::default(),
+            group_defaults: step::GroupOptions::default(),
+            providers: vec![],
+            health: vec![],
+            finalizers: vec![],
+            default: Group::new(tm, bindings),
+            run: RunContext {
+                id,
+                seed: id,
+                ..RunContext::default()
+            },
+            checkpointer: None,
+            resume: None,
+            tags: None,
+            added: vec![],
+            max_dynamic_steps: dynamic::DEFAULT_LIMIT,
+            step_timeout: None,
+            #[cfg(feature = "serde")]
+            inputs: inputs::Inputs::default(),
         }
     }
 }
 
-impl<O: IntoStepOutcome + 'static> ImperativeStepBuilder<O> {
+impl<O: IntoStepOutcome + Send + 'static> ImperativeStepBuilder<O> {
     /// Add a step with the provided name. To the default top-level group.
     /// See `Group::add_step`.
     #[must_use]
     pub fn add_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self {
+        self.add(new_step(name, func))
+    }
+
+    /// Add a step named after `func`, a fn item's own name, such as
+    /// `load_config` for `app::setup::load_config`. Closures have no name of
+    /// their own, so adding one fails the build with `Error::ClosureName`;
+    /// add them with `add_step`. Steps declared with `#[step]` already carry
+    /// their name; add them with `add`.
+    #[must_use]
+    pub fn add_step_auto<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        mut self,
+        func: C,
+    ) -> Self {
+        match step::auto_name::<C>() {
+            Ok(name) => self.add_step(&name, func),
+            Err(e) => {
+                self.errors.push(e);
+                self
+            }
+        }
+    }
+
+    /// Add a step which calls `func` with a clone of `args` followed by its
+    /// dependencies. Lets the same function be added once per argument, such
+    /// as once per table, without wrapping it in a closure. Pass a tuple to
+    /// bind more than one argument.
+    #[must_use]
+    pub fn add_step_with_args<F, X, A: FromTypeMap>(self, name: &str, func: F, args: X) -> Self
+    where
+        WithArgs<F, X>: Callable<A, Out = O> + 'static,
+    {
+        self.add_step(name, WithArgs { func, args })
+    }
+
+    /// Add a step which runs `fut`, a future built outside of imperat, such
+    /// as by a framework wrapping it, rather than a `Callable` resolving its
+    /// dependencies. The future is only built once, so it can only run once:
+    /// if the step is retried, later attempts panic.
+    #[must_use]
+    pub fn add_boxed_step(self, name: &str, fut: futures::future::BoxFuture<'static, O>) -> Self {
+        self.add(step::new_boxed(name, fut))
+    }
+
+    /// Add a step which is a trait object, such as one loaded at runtime by
+    /// a plugin system, named by its `DynStep::name`. Unlike `add_boxed_step`,
+    /// it's run again if it's retried.
+    #[must_use]
+    pub fn add_dyn_step(self, step: Box<dyn DynStep<O>>) -> Self {
+        self.add(step::new_dyn(step))
+    }
+
+    /// Add a step whose successful output is bound as a `Dep<T>`, so steps
+    /// in later groups, or later in a sequential group, can depend on it. The
+    /// step's own result is `Ok(())`, or its error.
+    ///
+    /// Steps depending on a binding aren't checked for missing dependencies
+    /// until they run. Steps in the same group as a binding step which depend
+    /// on it always run after it. See `StepBuilder::depends_on`.
+    #[must_use]
+    pub fn add_step_binding<T: Send + Sync + 'static, E: Send + 'static, C, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self
+    where
+        C: Callable<A, Out = std::result::Result<T, E>> + 'static,
+        O: From<std::result::Result<(), E>>,
+    {
+        let step = step::new_binding(name, func);
+        self.add(step)
+    }
+
+    /// Add a step whose successful output is kept in the results and also
+    /// bound as a `Dep<O>`, so later steps can depend on it. Shorthand for
+    /// adding `new_step(name, func).produces::<O>()`, so only one such step
+    /// may be added; see `StepBuilder::produces`. The same caveats as
+    /// `add_step_binding` apply to steps depending on it.
+    #[must_use]
+    pub fn add_producing_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self
+    where
+        O: Clone + Sync,
+    {
+        self.add(step::new(name, func).produces::<O>())
+    }
+
+    /// Add a step which only runs if `predicate` returns true. Otherwise,
+    /// its result is `O::from(Skipped)`. See `StepBuilder::run_if`.
+    #[must_use]
+    pub fn add_step_if<P, B, C, A>(self, name: &str, predicate: P, func: C) -> Self
+    where
+        P: Callable<B, Out = bool> + 'static,
+        B: FromTypeMap,
+        C: Callable<A, Out = O> + 'static,
+        A: FromTypeMap,
+        O: From<Skipped>,
+    {
+        self.add(step::new(name, func).run_if(predicate))
+    }
+
+    /// Add a step which `undo` rolls back if a later step fails. See
+    /// `StepBuilder::rollback`.
+    #[must_use]
+    pub fn add_step_with_rollback<C, A, U, B>(self, name: &str, func: C, undo: U) -> Self
+    where
+        C: Callable<A, Out = O> + 'static,
+        A: FromTypeMap,
+        U: Callable<B> + 'static,
+        U::Out: IntoStepOutcome,
+        B: FromTypeMap,
+    {
+        self.add(step::new(name, func).rollback(undo))
+    }
+
+    /// Add a step built with `new_step` to the default top-level group.
+    #[must_use]
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, step: impl Into<StepBuilder<O>>) -> Self {
+        self.default.add(step.into().0);
+        self
+    }
+
+    /// Add a preflight check which verifies an external precondition. Every
+    /// check runs in parallel before any other step, and all of them must
+    /// succeed for the run to continue. Otherwise, `execute` returns
+    /// `Error::Preflight` with every failed check.
+    ///
+    /// Preflight checks are not included in the results.
+    #[must_use]
+    pub fn add_preflight<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
         mut self,
         name: &str,
         func: C,
     ) -> Self {
-        self.default.add_step(name, func);
+        self.preflight
+            .get_or_insert_with(|| {
+                GroupBuilder::new(self.tm.clone(), self.bindings.clone())
+                    .parallel()
+                    .0
+            })
+            .add(new_step(name, func).0);
         self
     }
 
@@ -90,16 +851,188 @@ impl<O: IntoStepOutcome + 'static> ImperativeStepBuilder<O> {
     /// # Panics
     /// If the typemap mutex is poisoned.
     #[must_use]
-    pub fn add_dep<T: 'static>(self, dep: T) -> Self {
+    pub fn add_dep<T: Send + Sync + 'static>(mut self, dep: T) -> Self {
         let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
         if tm.get::<T>().is_some() {
-            self.default.add_error(Error::AddDep(TypeId::of::<T>()));
             drop(tm);
+            self.errors.push(Error::AddDep(TypeId::of::<T>()));
             return self;
         }
         tm.bind(dep);
         drop(tm);
+        self.added.push(DepInfo::of::<T>());
+
+        self
+    }
+
+    /// Add a provider of credentials scoped to each step, which steps request
+    /// as an `AuthContext<P::Token>`, such as to resolve a token per tenant
+    /// from a step's tags rather than share one credential. Like any
+    /// dependency, only one provider of each token type may be added.
+    #[must_use]
+    pub fn auth_provider<P: AuthProvider>(self, provider: P) -> Self {
+        self.add_dep::<Arc<dyn AuthProvider<Token = P::Token>>>(Arc::new(provider))
+    }
+
+    /// Add the way steps ask for input, which they request as an
+    /// `Interaction`, such as a `TerminalInteract` for a CLI or a
+    /// `test::ScriptedInteract` in tests, with the `test-util` feature. Only
+    /// one may be added.
+    #[must_use]
+    pub fn interact(self, interact: impl Interact) -> Self {
+        self.add_dep::<Arc<dyn Interact>>(Arc::new(interact))
+    }
+
+    /// Add every dependency in `deps`, sharing them rather than cloning, such as
+    /// ones built once and shared by pipelines on several threads. If any have
+    /// the same type as a dependency already added, none are added and each
+    /// records an error.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn add_sync_deps(mut self, deps: &SyncTypeMap) -> Self {
+        let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+        let added = deps.keys();
+        let existing: Vec<_> = added
+            .iter()
+            .filter(|d| tm.contains_id(d.id))
+            .map(|d| Error::AddDep(d.id))
+            .collect();
+        if existing.is_empty() {
+            deps.bind_into(&mut tm);
+        }
+        drop(tm);
+        if existing.is_empty() {
+            self.added.extend(added);
+        } else {
+            self.errors.extend(existing);
+        }
+
+        self
+    }
+
+    /// Internal API to change the dependencies added to this builder so far,
+    /// such as to substitute them in tests. See `test::TestHarness`.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn with_typemap<R>(&self, f: impl FnOnce(&mut TypeMap) -> R) -> R {
+        f(&mut self.tm.lock().expect("imperat typemap mutex poisoned"))
+    }
+
+    /// Run steps in `dir`, which they can request as a `WorkDir` to resolve
+    /// paths or start commands in. Groups may override it with
+    /// `GroupBuilder::work_dir`, such as to run each project of a monorepo in
+    /// its own folder. The process's current directory is never changed.
+    #[must_use]
+    pub fn work_dir(self, dir: impl AsRef<std::path::Path>) -> Self {
+        self.add_dep(WorkDir::new(dir))
+    }
+
+    /// Add a dependency which is refreshed during the run when it goes
+    /// stale, such as an auth token. Request it as a `Dep<T>`: before each
+    /// step which does, `Refreshable::refresh_if_stale` is called and any
+    /// fresh copy replaces it for that step and later ones.
+    ///
+    /// If refreshing fails, the step's attempt fails with
+    /// `Error::DepRefresh`, and may be retried.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn add_refreshable_dep<T: Refreshable + 'static>(mut self, dep: T) -> Self {
+        self = self.add_dep(Dep::new(dep));
+        self.run.refreshers.add::<T>();
+        self
+    }
 
+    /// Add a dependency built by `func` when the run starts, such as a
+    /// database pool which must be opened asynchronously. `func` may depend
+    /// on any other dependency, including ones from earlier providers.
+    /// Providers run in the order they're added, before any step, and their
+    /// output is bound as a `Dep<T>`.
+    ///
+    /// If `func` fails, the run fails with `Error::DepInit`.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    #[must_use]
+    pub fn add_dep_with<T: Send + Sync + 'static, E, C, A: FromTypeMap>(mut self, func: C) -> Self
+    where
+        C: Callable<A, Out = std::result::Result<T, E>> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let provider = providers::Provider::new(func, self.tm.clone());
+        // Steps depending on the provided type are accepted like those
+        // depending on a binding step.
+        self.bindings
+            .lock()
+            .expect("imperat bindings mutex poisoned")
+            .add(&provider.name, &provider.deps, Some(&provider.binds), &[]);
+        self.providers.push(provider);
+        self
+    }
+
+    /// Check the health of dependency `T`, bound as a `Dep<T>`, with `check`
+    /// when the run starts, after providers and before preflight checks.
+    /// `check` may depend on anything a step may. If it doesn't succeed,
+    /// steps depending on `T` which are `StepBuilder::degradable` are
+    /// skipped, while the rest fail with `Error::Unhealthy`, so runs can
+    /// continue partially through partial outages. Failed checks are listed
+    /// in `ExecutionReport::unhealthy`.
+    #[must_use]
+    pub fn health_check<T: 'static, C, A: FromTypeMap>(mut self, check: C) -> Self
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        self.health.push(health::HealthCheck::new::<T, C, A>(check));
+        self
+    }
+
+    /// Wait for dependency `T`, bound as a `Dep<T>`, to become ready before
+    /// the first step depending on it runs, such as until a service it
+    /// connects to is reachable. `probe` may depend on anything a step may,
+    /// and is called until it succeeds, backing off between calls. If it
+    /// doesn't succeed within `timeout`, steps depending on `T` which are
+    /// `StepBuilder::degradable` are skipped, while the rest fail with
+    /// `Error::NotReady`. The dependency is only waited for once per run.
+    #[must_use]
+    pub fn wait_for_dep<T: 'static, C, A: FromTypeMap>(
+        mut self,
+        timeout: Duration,
+        probe: C,
+    ) -> Self
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        self.run
+            .readiness
+            .push(readiness::ReadinessProbe::new::<T, C, A>(timeout, probe));
+        self
+    }
+
+    /// Run `func` once the run is over, after every group and any rollback,
+    /// whether the run succeeded or failed, such as to tear down what its
+    /// steps set up. `func` may depend on anything a step may, including
+    /// what steps bound. Finalizers run in the order they're added, and
+    /// every one runs even if earlier ones fail. If one fails, so does the
+    /// run, unless it had already failed.
+    ///
+    /// Finalizers still run if the run's future is dropped partway, such as
+    /// when `execute` is cancelled: the one running is finished, and those
+    /// yet to start are resolved with the dependencies bound so far, all
+    /// together, detached, on the run's `Executor`, where their failures are
+    /// only logged, redacted like the run's errors. They don't
+    /// run if the run never starts, or if the executor drops the task, as
+    /// when its runtime shuts down.
+    #[must_use]
+    pub fn finalizer<C, A: FromTypeMap + Send + 'static>(mut self, name: &str, func: C) -> Self
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        self.finalizers.push(finalize::Finalizer::new(name, func));
         self
     }
 
@@ -107,10 +1040,212 @@ impl<O: IntoStepOutcome + 'static> ImperativeStepBuilder<O> {
     /// Return the group builder when done and the group will be added.
     #[must_use]
     pub fn new_group(mut self, new_fn: impl Fn(GroupBuilder<O>) -> GroupBuilder<O>) -> Self {
-        let gb = new_fn(GroupBuilder::new(self.tm.clone(), self.errors.clone()));
+        let gb = new_fn(
+            GroupBuilder::new(self.tm.clone(), self.bindings.clone()).inherit(&self.group_defaults),
+        );
         // I've decided to not include a finalize() fn on GroupBuilder to avoid
         // confusion when in the closure.
-        self.groups.push(gb.0);
+        self.groups.extend(gb.0.flatten());
+        self
+    }
+
+    /// Create a group with this name to configure outside of a closure, such
+    /// as in a helper function, and then add with `attach`. It starts from
+    /// this builder's group defaults, like groups from `new_group`.
+    ///
+    /// Its steps are checked against the dependencies added to this builder
+    /// so far, so add dependencies first.
+    pub fn group(&self, name: &str) -> GroupBuilder<O> {
+        GroupBuilder::new(self.tm.clone(), self.bindings.clone())
+            .inherit(&self.group_defaults)
+            .name(name)
+    }
+
+    /// Add a group created with `group`. Groups run in the order they're
+    /// attached or created with `new_group`.
+    #[must_use]
+    pub fn attach(mut self, group: GroupBuilder<O>) -> Self {
+        if !group.0.is_from(&self.tm) {
+            let name = group.0.name().unwrap_or_default().to_string();
+            self.errors.push(Error::Group(
+                name,
+                "group was created by another builder".into(),
+            ));
+            return self;
+        }
+
+        self.groups.extend(group.0.flatten());
+        self
+    }
+
+    /// Turn this builder into a group named `name` to embed in another
+    /// pipeline with `add_pipeline`, such as a reusable sub-pipeline defined
+    /// in another crate. Top-level steps become the group's own steps, and
+    /// groups become its child groups. Dependencies added to this builder,
+    /// including providers, are only seen by its steps, shadowing those of
+    /// the pipeline it's added to. Callbacks added to this builder apply to
+    /// every group in it.
+    ///
+    /// Only steps, groups, and dependencies are kept. Run-wide settings, such
+    /// as profiles, preflight checks, barriers, and checkpointing, come from
+    /// the pipeline it's added to.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    pub fn into_group(mut self, name: &str) -> SubPipeline<O> {
+        let mut deps = self
+            .tm
+            .lock()
+            .expect("imperat typemap mutex poisoned")
+            .clone();
+        // bound by every builder, and replaced by the outer run's
+        deps.remove::<RunMetadata>();
+        deps.remove::<Barriers>();
+        deps.remove::<RunRng>();
+        let cbs = self.default.callbacks().to_vec();
+        for group in &mut self.groups {
+            for cb in &cbs {
+                group.add_callback(cb.clone());
+            }
+        }
+        for e in std::mem::take(&mut self.errors) {
+            self.default.add_error(e);
+        }
+        let group = GroupBuilder(self.default).name(name).0;
+        let mut sub = SubPipeline::new(group, self.groups, deps);
+        sub.providers = self.providers;
+        sub
+    }
+
+    /// Add a pipeline turned into a group with `into_group`. It runs in the
+    /// order it's added, like groups added with `attach`, followed by its
+    /// child groups.
+    #[must_use]
+    pub fn add_pipeline(mut self, sub: SubPipeline<O>) -> Self {
+        let mut group = sub.group;
+        group.embed(&self.tm, &self.bindings, &sub.deps);
+        self.providers.extend(sub.providers);
+        self.groups.extend(group.flatten());
+        self
+    }
+
+    /// Set the options every group created after this call starts with, such
+    /// as `parallel` or `tolerate_failure`. Each group may still override
+    /// them in `new_group`. Top-level steps aren't a group and don't inherit
+    /// these. Names set here are ignored, and adding steps here fails the
+    /// build with `Error::DefaultSteps`.
+    ///
+    /// Callbacks set here run before the group's own. Unlike callbacks added
+    /// with `before_step` and `after_step` on this builder, they only apply
+    /// to later groups.
+    #[must_use]
+    pub fn group_defaults(mut self, f: impl FnOnce(GroupBuilder<O>) -> GroupBuilder<O>) -> Self {
+        // Steps aren't kept, so they mustn't bind anything for other steps.
+        let gb = GroupBuilder::new(Arc::default(), step::Bindings::default());
+        match f(gb.inherit(&self.group_defaults)).into_defaults() {
+            Ok(defaults) => self.group_defaults = defaults,
+            Err(e) => self.errors.push(e),
+        }
+        self
+    }
+
+    /// Approve or reject steps gated on approval with `approvals`, shared
+    /// with whatever grants them. By default, gated steps wait on a handle
+    /// nothing else can reach. See `StepBuilder::requires_approval`.
+    #[must_use]
+    pub fn approvals(mut self, approvals: Approvals) -> Self {
+        self.run.approvals = approvals;
+        self
+    }
+
+    /// Record each step's output with `checkpointer` as it completes, so
+    /// that a later run can resume after it with `resume_from`. Preflight
+    /// checks aren't recorded. Failing to record a step fails it with
+    /// `Error::Checkpoint`.
+    #[must_use]
+    pub fn checkpoint(mut self, checkpointer: impl Checkpointer<O> + 'static) -> Self {
+        self.checkpointer = Some(Arc::new(checkpointer));
+        self
+    }
+
+    /// Skip every step which completed in `checkpoint`, such as one loaded
+    /// from the `Checkpointer` of a run which stopped partway. Skipped steps
+    /// aren't ran and keep their recorded output, as if they'd just returned
+    /// it, including binding it for later steps. Steps are matched by key,
+    /// so the key strategy shouldn't change between runs. Steps gated on
+    /// approval which the earlier run reached return to the same gate:
+    /// they wait again unless they were approved or rejected since.
+    #[must_use]
+    pub fn resume_from(mut self, checkpoint: Checkpoint<O>) -> Self {
+        self.resume = Some(checkpoint);
+        self
+    }
+
+    /// Only run steps tagged with any of `include`, or every step if it's
+    /// empty, and none of `exclude`. A step's tags include its group's; see
+    /// `StepBuilder::tag` and `GroupBuilder::tag`. Steps filtered out aren't
+    /// ran and are reported as skipped, so steps depending on what they bind
+    /// should be filtered out alongside them. Preflight checks always run.
+    #[must_use]
+    pub fn filter_tags(mut self, include: &[&str], exclude: &[&str]) -> Self {
+        self.tags = Some(tags::TagFilter::new(include, exclude));
+        self
+    }
+
+    /// Spawn parallel steps and wait on timers, for retry delays and
+    /// timeouts, with `executor` rather than the default. See `Executor`.
+    #[must_use]
+    pub fn executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.run.executor = ExecutorHandle::new(executor);
+        self
+    }
+
+    /// Inject errors into steps at `points`, to test how the run handles
+    /// internal errors. Only checked in debug builds.
+    #[cfg(feature = "failpoints")]
+    #[must_use]
+    pub fn fail_points(mut self, points: FailPoints) -> Self {
+        self.run.fail_points = points;
+        self
+    }
+
+    /// Set which completed steps are rolled back when the run fails. By
+    /// default, only those in the failed step's group are. See
+    /// `StepBuilder::rollback`.
+    #[must_use]
+    pub fn rollback_scope(mut self, scope: RollbackScope) -> Self {
+        self.run.rollback_scope = scope;
+        self
+    }
+
+    /// Choose what happens when a callback, such as `before_step` or
+    /// `on_group_end`, panics. Panics are always caught, and by default the
+    /// run fails with `Error::CallbackFailed` once the group the callback
+    /// ran in is done. A panicking `redact_output` fails its step either
+    /// way, as the output is lost, and a panicking `redact` withholds the
+    /// message it was redacting.
+    #[must_use]
+    pub fn on_callback_panic(mut self, policy: CallbackPanicPolicy) -> Self {
+        self.run.callbacks.policy = policy;
+        self
+    }
+
+    /// Set how many steps may be added with `DynamicSteps` over the run, so
+    /// discovery which never ends can't run forever. Adding more fails the
+    /// run with `Error::TooManySteps`. Defaults to 1000.
+    #[must_use]
+    pub fn max_dynamic_steps(mut self, limit: usize) -> Self {
+        self.max_dynamic_steps = limit;
+        self
+    }
+
+    /// Adds a callback to top-level steps and all groups which runs before a
+    /// step is retried. It's passed the step's name and the attempt about to
+    /// run, counting from 1.
+    #[must_use]
+    pub fn on_retry(mut self, cb: impl Fn(&str, usize) + Send + Sync + 'static) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::Retry(Arc::new(cb)));
         self
     }
 
@@ -118,7 +1253,7 @@ impl<O: IntoStepOutcome + 'static> ImperativeStepBuilder<O> {
     /// Callbacks added by this method run after group-specific callbacks,
     /// though this is subject to change.
     #[must_use]
-    pub fn before_step(mut self, cb: impl Fn(&Step<O>) + 'static) -> Self {
+    pub fn before_step(mut self, cb: impl Fn(&Step<O>) + Send + Sync + 'static) -> Self {
         self.default
             .add_callback(step::CallbackKind::BeforeStep(Arc::new(cb)));
         self
@@ -128,45 +1263,839 @@ impl<O: IntoStepOutcome + 'static> ImperativeStepBuilder<O> {
     /// Callbacks added by this method run after group-specific callbacks,
     /// though this is subject to change.
     #[must_use]
-    pub fn after_step(mut self, cb: impl Fn(&str, &O) + 'static) -> Self {
+    pub fn after_step(mut self, cb: impl Fn(&str, &O) + Send + Sync + 'static) -> Self {
         self.default
             .add_callback(step::CallbackKind::AfterStep(Arc::new(cb)));
         self
     }
 
+    /// Adds a callback to top-level steps and all groups which runs once
+    /// each step is done, with how it ended. See `GroupBuilder::on_step_result`.
+    #[must_use]
+    pub fn on_step_result(
+        mut self,
+        cb: impl Fn(&str, StepOutcome, Option<&O>) + Send + Sync + 'static,
+    ) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::StepResult(Arc::new(cb)));
+        self
+    }
+
+    /// Adds a callback to top-level steps and all groups which runs once each
+    /// step which failed with an error is done. See
+    /// `GroupBuilder::on_step_error`.
+    #[must_use]
+    pub fn on_step_error(
+        mut self,
+        cb: impl Fn(&str, &(dyn std::error::Error + 'static)) + Send + Sync + 'static,
+    ) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::StepError(Arc::new(cb)));
+        self
+    }
+
+    /// Adds an async before step callback to top-level steps and all groups.
+    /// See `GroupBuilder::before_step_async`.
+    #[must_use]
+    pub fn before_step_async<F>(
+        mut self,
+        cb: impl Fn(&Step<O>) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.default
+            .add_callback(step::CallbackKind::BeforeStepAsync(Arc::new(move |s| {
+                Box::pin(cb(s))
+            })));
+        self
+    }
+
+    /// Adds an async callback to top-level steps and all groups which runs
+    /// once each step is done, whatever its outcome. See
+    /// `GroupBuilder::after_step_async`.
+    #[must_use]
+    pub fn after_step_async<F>(
+        mut self,
+        cb: impl Fn(&str, StepOutcome, Option<&O>) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.default
+            .add_callback(step::CallbackKind::AfterStepAsync(Arc::new(
+                move |name, outcome, res| Box::pin(cb(name, outcome, res)),
+            )));
+        self
+    }
+
+    /// Adds a callback which runs before every group with steps, including
+    /// top-level steps. See `GroupBuilder::on_group_start`.
+    #[must_use]
+    pub fn on_group_start(mut self, cb: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::GroupStart(Arc::new(cb)));
+        self
+    }
+
+    /// Adds a callback which runs once every group with steps is done,
+    /// including top-level steps. See `GroupBuilder::on_group_end`.
+    #[must_use]
+    pub fn on_group_end(
+        mut self,
+        cb: impl Fn(&str, Option<&Error>) + Send + Sync + 'static,
+    ) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::GroupEnd(Arc::new(cb)));
+        self
+    }
+
+    /// Limit the total number of retries across every group in this run.
+    /// Once spent, failed steps are no longer retried even if their group
+    /// allows more attempts. By default, retries are unlimited.
+    #[must_use]
+    pub fn retry_budget(mut self, max_retries: usize) -> Self {
+        self.run.retry_budget = RetryBudget::new(max_retries);
+        self
+    }
+
+    /// Limit the total number of retries across every step depending on
+    /// `T`, bound as a `Dep<T>`, so that a downed service isn't hammered.
+    /// Once spent, the dependency's circuit breaker opens for the rest of
+    /// the run: the failing step isn't retried, and later steps depending on
+    /// `T` are skipped if `StepBuilder::degradable` or fail with
+    /// `Error::CircuitOpen`.
+    ///
+    /// This is a `CircuitBreaker::for_dep` which only opens on running out
+    /// of retries; attach one with `circuit_breaker` to also open it on
+    /// failures.
+    #[must_use]
+    pub fn dep_retry_limit<T: 'static>(self, max_retries: usize) -> Self {
+        self.circuit_breaker(
+            CircuitBreaker::for_dep::<T>()
+                .failures(usize::MAX)
+                .cooldown(Duration::MAX)
+                .max_retries(max_retries),
+        )
+    }
+
+    /// Stop running the steps `breaker` covers while it's open. See
+    /// `CircuitBreaker`.
+    #[must_use]
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.run.circuits.push(breaker);
+        self
+    }
+
+    /// Apply `redact` to every message which may contain step data before it
+    /// leaves the run, such as step errors, panic messages, and logged
+    /// metadata, to keep secrets and personal data out of persisted run
+    /// history. Returned errors wrapping step errors are replaced by their
+    /// redacted message. Calling this again applies both redactors in order.
+    #[must_use]
+    pub fn redact(mut self, redact: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.run.redact = Some(match self.run.redact.take() {
+            Some(prev) => Arc::new(move |msg| redact(&prev(msg))),
+            None => Arc::new(redact),
+        });
+        self.run.callbacks.redact.clone_from(&self.run.redact);
+        self
+    }
+
+    /// Record the values of the environment variables `names` as the run
+    /// starts in `ExecutionReport::env`, so a failed run's configuration
+    /// can be seen afterwards. Unset variables are left out, and values pass
+    /// through the run's redactor. May be called more than once. See
+    /// `GroupBuilder::snapshot_env` for variables only one group reads.
+    #[must_use]
+    pub fn snapshot_env(mut self, names: &[&str]) -> Self {
+        self.run.env.extend(names.iter().map(ToString::to_string));
+        self
+    }
+
+    /// Apply `redact` to every step's output before it reaches after step
+    /// callbacks or the results.
+    #[must_use]
+    pub fn redact_output(mut self, redact: impl Fn(O) -> O + Send + Sync + 'static) -> Self {
+        self.default
+            .add_callback(step::CallbackKind::RedactOutput(Arc::new(redact)));
+        self
+    }
+
+    /// Describe the run with a key-value entry, such as `triggered_by` or
+    /// `git_sha`. Steps can request every entry as `RunMetadata`, and verbose
+    /// logs include them. Setting a key again replaces its value.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.run.metadata.insert(key, value);
+        self.tm
+            .lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(self.run.metadata.clone());
+        self
+    }
+
+    /// Seed the `RunRng` steps can request, such as with the seed a previous
+    /// run's report or verbose logs recorded to reproduce it. By default, the
+    /// seed is the run's id.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.run.seed = seed;
+        self.tm
+            .lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(RunRng::new(seed));
+        self
+    }
+
+    /// Add a barrier called `name` which parallel steps can wait on with
+    /// `Barriers::wait` until `parties` of them are waiting. It's reusable:
+    /// once released, the next `parties` steps to wait are released together.
+    /// Adding a barrier again replaces it.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn barrier(self, name: &str, parties: usize) -> Self {
+        let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+        let mut barriers = tm.get::<Barriers>().cloned().unwrap_or_default();
+        barriers.insert(name, parties);
+        tm.bind(barriers);
+        drop(tm);
+
+        self
+    }
+
+    /// Add a pipe which steps in a parallel group can stream values of type
+    /// `T` through, from steps requesting its `PipeSender<T>` to steps
+    /// requesting its `PipeReceiver<T>`. Senders wait while `capacity`
+    /// values are waiting to be received.
+    ///
+    /// The pipe closes once every step requesting its sender finishes, so
+    /// its receivers see the end of the values. In sequential groups, or if
+    /// no step receives from it, senders will wait forever once it's full.
+    ///
+    /// # Panics
+    /// If the typemap mutex is poisoned.
+    #[must_use]
+    pub fn pipe<T: Send + 'static>(self, capacity: usize) -> Self {
+        let (tx, rx) = extractors::pipe::<T>(capacity);
+        let tm = self.tm.clone();
+        self.run.pipes.add(TypeId::of::<PipeSender<T>>(), move || {
+            tm.lock()
+                .expect("imperat typemap mutex poisoned")
+                .remove::<PipeSender<T>>();
+        });
+        self.add_dep(tx).add_dep(rx)
+    }
+
+    /// Cancel any step still running at `deadline`, failing the run with
+    /// `CancelReason::Deadline`, and don't start or retry any steps after it.
+    #[must_use]
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.run.deadline = Some(deadline);
+        self
+    }
+
+    /// Cancel any step which runs longer than `limit`, with
+    /// `CancelReason::Timeout`.
+    /// Groups may override this with `GroupBuilder::step_timeout` and steps
+    /// with `StepBuilder::timeout`; the most specific timeout wins.
+    #[must_use]
+    pub fn default_step_timeout(mut self, limit: Duration) -> Self {
+        self.step_timeout = Some(limit);
+        self.run.settings.step_timeout = Some(limit);
+        self
+    }
+
+    /// Use the settings bundled by a named profile as defaults for every group
+    /// and step. Settings from calls such as `default_step_timeout`, whether
+    /// made before or after, and settings on groups and steps override it.
+    /// Calling this again replaces the earlier profile's settings.
+    #[must_use]
+    pub fn with_profile(self, profile: Profile) -> Self {
+        self.with_settings(profile.settings())
+    }
+
+    /// Like `with_profile`, but with custom settings. See `ProfileSettings`.
+    #[must_use]
+    pub fn with_settings(mut self, settings: ProfileSettings) -> Self {
+        self.run.settings = ProfileSettings {
+            step_timeout: self.step_timeout.or(settings.step_timeout),
+            ..settings
+        };
+        self
+    }
+
+    /// Returns a handle which cancels this run once it's executing. Steps
+    /// observe cancellation by requesting `Cancelled`; steps which haven't
+    /// started yet won't run and `execute` returns `Error::Cancelled` with
+    /// `CancelReason::Requested`.
+    #[must_use]
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.run.cancel.clone()
+    }
+
+    /// Returns a controller which stops this run once it's executing, either
+    /// at once like `cancel_handle` or with a warm shutdown through
+    /// `RunController::drain`.
+    #[must_use]
+    pub fn controller(&self) -> RunController {
+        RunController::new(self.run.cancel.clone(), self.run.draining.clone())
+    }
+
+    /// Choose how the keys of the results returned by `execute` are formed.
+    /// By default, results are keyed by step name.
+    #[must_use]
+    pub fn key_strategy(mut self, strategy: KeyStrategy) -> Self {
+        self.keys = strategy;
+        self
+    }
+
+    /// Choose the order groups run in, such as running top-level steps last
+    /// for teardown. By default, top-level steps run first, then every group
+    /// in the order they were added. Naming a label which isn't a group is
+    /// an error.
+    #[must_use]
+    pub fn group_order(mut self, order: GroupOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Record every step attempt in `stats`. Pass clones of the same
+    /// statistics to many runs to accumulate them over time.
+    #[must_use]
+    pub fn record_stats(mut self, stats: &StepStats) -> Self {
+        self.run.stats = Some(stats.clone());
+        self
+    }
+
+    /// Returns a handle to this run's progress, which can be polled from
+    /// another task while the run executes. See `RunStatus`.
+    #[must_use]
+    pub fn status_handle(&self) -> StatusHandle {
+        self.run.status.clone()
+    }
+
+    /// Adds a callback invoked for every dependency lookup made while resolving
+    /// a step's arguments, with the step's name. Lookups are reported whether
+    /// or not the dependency was present, and each retry looks its dependencies
+    /// up again. Useful for auditing which steps touch which dependencies, or
+    /// finding dependencies which are never used. Calling this again adds
+    /// another callback, which runs after the earlier ones.
+    #[must_use]
+    pub fn on_dep_access(mut self, cb: impl Fn(&str, &DepInfo) + Send + Sync + 'static) -> Self {
+        self.run.on_dep_access = Some(match self.run.on_dep_access.take() {
+            Some(prev) => Arc::new(move |step, dep| {
+                prev(step, dep);
+                cb(step, dep);
+            }),
+            None => Arc::new(cb),
+        });
+        self
+    }
+
+    /// Returns every error which occurred while building, as `prepare` would
+    /// without finishing the runner, so that misconfiguration such as
+    /// missing dependencies and duplicate bindings can be checked up front.
+    /// Errors are in the order they occurred, and errors wrapping other errors
+    /// are copied with only their message.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    pub fn validate(&self) -> std::result::Result<(), Vec<Error>> {
+        if let Some(cycle) = self.find_cycle() {
+            return Err(vec![cycle]);
+        }
+        let mut errors: Vec<_> = self.errors.iter().map(Error::copy).collect();
+        errors.extend(self.unknown_groups());
+        for (label, group) in self.declared_groups() {
+            errors.extend(
+                group
+                    .errors()
+                    .iter()
+                    .map(|e| e.copy().in_group(label.as_deref())),
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Every group in the order they were added, with its label, or none
+    /// for top-level steps. Groups are labeled like their keys: by name, or
+    /// by position if unnamed.
+    fn declared_groups(&self) -> impl Iterator<Item = (Option<String>, &Group<O>)> {
+        let groups = self.groups.iter().enumerate().map(|(i, g)| {
+            let label = g.name().map_or_else(|| (i + 1).to_string(), str::to_string);
+            (Some(label), g)
+        });
+        self.preflight
+            .iter()
+            .map(|g| (Some("preflight".to_string()), g))
+            .chain(std::iter::once((None, &self.default)))
+            .chain(groups)
+    }
+
+    /// Like `declared_groups`, but in the order they run. See `group_order`.
+    fn labeled_groups(&self) -> impl Iterator<Item = (Option<String>, &Group<O>)> {
+        let mut groups: Vec<_> = self.declared_groups().collect();
+        groups.sort_by_key(|(label, _)| self.order.rank(label.as_deref().unwrap_or("0")));
+        groups.into_iter()
+    }
+
+    /// Errors for every label `group_order` names which isn't a group.
+    fn unknown_groups(&self) -> Vec<Error> {
+        let labels: Vec<_> = self.declared_groups().map(|(label, _)| label).collect();
+        let labels: Vec<_> = labels.iter().map(|l| l.as_deref().unwrap_or("0")).collect();
+        self.order
+            .unknown(&labels)
+            .into_iter()
+            .map(|label| Error::UnknownGroup(label.to_string()))
+            .collect()
+    }
+
+    /// Describes every group and step in the order they'd run, with the
+    /// dependencies each step requests and any which can't be resolved,
+    /// without running anything. Steps are ordered as `execute` would start
+    /// them, though steps in parallel groups may finish in any order.
+    #[must_use]
+    pub fn plan(&self) -> ExecutionPlan {
+        ExecutionPlan {
+            providers: self
+                .providers
+                .iter()
+                .map(|p| ProviderPlan {
+                    name: p.name.clone(),
+                    dependencies: p.deps.clone(),
+                    binds: p.binds,
+                })
+                .collect(),
+            groups: self
+                .labeled_groups()
+                .map(|(label, g)| g.plan(label))
+                .collect(),
+        }
+    }
+
+    /// Returns likely mistakes in how this runner is built, such as reused
+    /// step names or dependencies no step uses, without running anything.
+    /// Unlike build errors, none stop it from running.
+    #[must_use]
+    pub fn lint(&self) -> Vec<Lint> {
+        lint::lint(&self.plan(), &self.added)
+    }
+
+    // Steps in a cycle fail to resolve their dependencies as well, but the
+    // cycle is the more useful error.
+    fn find_cycle(&self) -> Option<Error> {
+        self.bindings
+            .lock()
+            .expect("imperat bindings mutex poisoned")
+            .find_cycle()
+            .map(Error::Cycle)
+    }
+
     /// Execute this runner. All configured groups and steps will be ran.
     /// If any errors occurred during building or while executing,
     /// all execution stops (unless otherwise configured) and the error is returned.
+    /// If more than one error occurred while building, they're all returned
+    /// together as `Error::Build`.
     ///
-    /// The returned `HashMap` contains all results by their step name. In the case of
-    /// duplicate names, results for the last step by order definition order will
-    /// win.
+    /// The returned `HashMap` contains all results by their step name, or as
+    /// configured with `key_strategy`. In the case of duplicate names, results
+    /// for the last step by order definition order will win; use
+    /// `execute_all` to keep them all.
+    ///
+    /// Equivalent to `prepare` followed by `PreparedRun::run`.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    pub async fn execute(self) -> Result<HashMap<String, O>> {
+        self.prepare()?.run().await
+    }
+
+    /// Execute this runner like `execute`, but keep the output of every step
+    /// sharing a key, such as generated steps which intentionally share a
+    /// name, rather than only the last one's. Outputs sharing a key are in
+    /// the order their steps were defined.
     ///
     /// # Panics
-    /// If the errors mutex is poisoned.
-    pub async fn execute(mut self) -> Result<HashMap<String, O>> {
-        if let Some(e) = self.errors.lock().expect("errors mutex poisoned").pop() {
-            return Err(e);
+    /// If the bindings mutex is poisoned.
+    pub async fn execute_all(self) -> Result<HashMap<String, Vec<O>>> {
+        self.prepare()?.run_all().await
+    }
+
+    /// Execute this runner like `execute`, but return its outputs as a
+    /// `RunResult`, which can also be queried for outputs by type and for
+    /// how each step fared.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    pub async fn execute_result(self) -> Result<RunResult<O>> {
+        self.prepare()?.run_result().await
+    }
+
+    /// Execute this runner like `execute`, but report on every step instead
+    /// of only returning outputs. The report is returned even if the run
+    /// failed, with the (redacted) error alongside every step which ran,
+    /// failed, was skipped, or was cancelled.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    pub async fn execute_report(self) -> ExecutionReport<O> {
+        let run = self.run.clone();
+        match self.prepare() {
+            Ok(prepared) => prepared.run_report().await,
+            Err(e) => {
+                let mut report = ExecutionReport::new(run.id, run.metadata.clone());
+                report.seed = run.seed;
+                report.env = run.snapshot_env(&run.env);
+                report.error = Some(run.redact_error(e));
+                report
+            }
+        }
+    }
+
+    /// Execute this runner like `execute_report`, streaming events as groups
+    /// and steps start and finish so a UI or log can follow the run live.
+    /// The stream ends after `PipelineEvent::PipelineFinished`, which carries
+    /// the same (redacted) error as the report.
+    ///
+    /// The run only makes progress while the returned future is polled, so
+    /// poll it alongside the stream, such as with `tokio::join!`. Events are
+    /// buffered until they're read.
+    pub fn execute_streaming(
+        mut self,
+    ) -> (PipelineEvents, impl Future<Output = ExecutionReport<O>>) {
+        let (events, stream) = events::EventSender::channel();
+        self.run.events = events.clone();
+        self.run.status.set_events(events.clone());
+        let fut = async move {
+            let report = self.execute_report().await;
+            events.send(|| PipelineEvent::PipelineFinished {
+                success: report.is_success(),
+                error: report.error.as_ref().map(ToString::to_string),
+            });
+            report
+        };
+        (stream, fut)
+    }
+
+    /// Execute this runner like `execute_report`, drawing a
+    /// `tui::LiveView` of it on stderr as it runs.
+    #[cfg(feature = "tui")]
+    pub async fn execute_live(self) -> ExecutionReport<O> {
+        let view = crate::tui::LiveView::new(&self.plan());
+        let (events, run) = self.execute_streaming();
+        let (drawn, report) = futures::join!(view.follow(events, std::io::stderr()), run);
+        if let Err(e) = drawn {
+            log::log_warn!("imperat: failed to draw live view: {e}");
+        }
+        report
+    }
+
+    /// Finish building this runner without running any steps, returning
+    /// any error which occurred while building. Nothing is awaited, so this
+    /// is cheap enough to fail fast on misconfiguration before committing
+    /// to a run.
+    ///
+    /// # Panics
+    /// If the bindings mutex is poisoned.
+    pub fn prepare(mut self) -> Result<PreparedRun<O>> {
+        if let Some(cycle) = self.find_cycle() {
+            return Err(cycle);
+        }
+        let unknown = self.unknown_groups();
+        let labels: Vec<_> = self.declared_groups().map(|(label, _)| label).collect();
+        let groups = self
+            .preflight
+            .iter_mut()
+            .chain(std::iter::once(&mut self.default))
+            .chain(&mut self.groups);
+        let mut errors = std::mem::take(&mut self.errors);
+        errors.extend(unknown);
+        for (label, group) in labels.into_iter().zip(groups) {
+            errors.extend(
+                group
+                    .take_errors()
+                    .into_iter()
+                    .map(|e| e.in_group(label.as_deref())),
+            );
+        }
+        match errors.len() {
+            0 => {}
+            1 => return Err(errors.remove(0)),
+            _ => return Err(Error::Build(errors)),
+        }
+        if self.run.on_dep_access.is_some() {
+            self.tm
+                .lock()
+                .expect("imperat typemap mutex poisoned")
+                .record_accesses(true);
         }
 
         // The default group's callbacks apply to every child group.
         // For consistency, we populate those callbacks here so that
         // every subgroup gets them last.
         let cbs = self.default.callbacks();
-        for group in &mut self.groups {
+        for group in self.groups.iter_mut().chain(&mut self.preflight) {
             for cb in cbs {
                 group.add_callback(cb.clone());
             }
         }
+        let mut dynamic = self.expander();
 
-        let mut outputs = vec![];
         let mut groups = vec![self.default];
         groups.extend(self.groups);
-        for g in groups {
-            let res = g.execute().await?;
-            outputs.push(res);
+        for g in &groups {
+            g.check_order()?;
+        }
+        let mut ids = 0;
+        for (i, g) in groups.iter_mut().enumerate() {
+            ids = g.assign_keys(&self.keys, i, ids);
+        }
+        if let Some(preflight) = &mut self.preflight {
+            preflight.assign_preflight(ids);
+        }
+        dynamic.index = groups.len();
+        dynamic.ids = ids + self.preflight.as_ref().map_or(0, Group::len);
+        let mut enabled = Vec::with_capacity(groups.len());
+        for (i, mut g) in groups.into_iter().enumerate() {
+            if g.roll_out(&i.to_string(), &mut self.run.metadata) {
+                enabled.push(g);
+            }
+        }
+        let mut groups = enabled;
+        groups.sort_by_key(|g| self.order.rank(g.label()));
+        if let Some(filter) = &self.tags {
+            for g in &mut groups {
+                g.filter_tags(filter, &self.run);
+            }
+        }
+        let mut resume = self.resume.take().unwrap_or_default();
+        for (key, approval) in std::mem::take(&mut resume.approvals) {
+            self.run.approvals.restore(&key, approval);
+        }
+        for g in &mut groups {
+            g.checkpoint(self.checkpointer.clone(), &mut resume);
+        }
+        self.tm
+            .lock()
+            .expect("imperat typemap mutex poisoned")
+            .bind(self.run.metadata.clone());
+        for step in groups.iter().chain(&self.preflight).flat_map(Group::steps) {
+            self.run.pipes.add_sender(step.dependencies());
         }
+        self.run.pipes.close_unused();
+        let steps = groups.iter().chain(&self.preflight).map(Group::len).sum();
+        self.run.status.add_pending(steps);
 
-        Ok(outputs.into_iter().flatten().collect())
+        Ok(PreparedRun {
+            tm: self.tm,
+            providers: self.providers,
+            health: self.health,
+            finalizers: self.finalizers,
+            preflight: self.preflight,
+            groups,
+            dynamic,
+            run: self.run,
+            #[cfg(feature = "serde")]
+            input_hash: self.inputs.hash(),
+        })
     }
+
+    /// Turns steps added at runtime into groups like this builder's, with
+    /// the default group's callbacks.
+    fn expander(&self) -> dynamic::Expander<O> {
+        let mut dynamic = dynamic::Expander::new(
+            self.tm.clone(),
+            self.bindings.clone(),
+            self.keys.clone(),
+            self.group_defaults.clone(),
+        );
+        dynamic.callbacks = self.default.callbacks().to_vec();
+        dynamic.limit = self.max_dynamic_steps;
+        dynamic
+    }
+}
+
+/// A runner which has been built and checked for errors but not yet ran.
+/// Create one with `ImperativeStepBuilder::prepare`.
+pub struct PreparedRun<O> {
+    tm: Arc<Mutex<TypeMap>>,
+    providers: Vec<providers::Provider>,
+    health: Vec<health::HealthCheck>,
+    finalizers: Vec<finalize::Finalizer>,
+    preflight: Option<Group<O>>,
+    groups: Vec<Group<O>>,
+    dynamic: dynamic::Expander<O>,
+    run: RunContext,
+    #[cfg(feature = "serde")]
+    input_hash: u64,
+}
+
+impl<O: IntoStepOutcome + Send + 'static> PreparedRun<O> {
+    /// Run every group and step. See `ImperativeStepBuilder::execute`.
+    pub async fn run(self) -> Result<HashMap<String, O>> {
+        self.run_report().await.into_result()
+    }
+
+    /// Run every group and step, keeping every output sharing a key. See
+    /// `ImperativeStepBuilder::execute_all`.
+    pub async fn run_all(self) -> Result<HashMap<String, Vec<O>>> {
+        self.run_report().await.into_all_result()
+    }
+
+    /// Run every group and step, returning a `RunResult`. See
+    /// `ImperativeStepBuilder::execute_result`.
+    pub async fn run_result(self) -> Result<RunResult<O>> {
+        self.run_report().await.into_run_result()
+    }
+
+    /// Run every group and step, reporting on each of them. See
+    /// `ImperativeStepBuilder::execute_report`.
+    pub async fn run_report(mut self) -> ExecutionReport<O> {
+        let run = self.run.clone();
+        let finalizers = finalize::Finalizers::new(
+            std::mem::take(&mut self.finalizers),
+            self.tm.clone(),
+            run.clone(),
+        );
+        let mut report = ExecutionReport::new(run.id, run.metadata.clone());
+        report.seed = run.seed;
+        #[cfg(feature = "serde")]
+        {
+            report.input_hash = self.input_hash;
+        }
+        report.env = run.snapshot_env(&run.env);
+        report.group_env = self
+            .groups
+            .iter()
+            .filter_map(|g| g.snapshot_env(&run))
+            .collect();
+        let res = match self.run_unredacted(&mut report).await {
+            Err(e) if run.rollback_scope == RollbackScope::Run => {
+                Err(run.rollbacks.roll_back(None, e, run.settings.verbose).await)
+            }
+            res => res,
+        };
+        let res = match (res, finalizers.run().await) {
+            (Ok(()), Err(e)) => Err(e),
+            (Err(e), Err(f)) => {
+                log::log_warn!("{}", run.redact_error(f));
+                Err(e)
+            }
+            (res, Ok(())) => res,
+        };
+        run.status.end();
+        report.set_steps(run.log.take());
+        report.callback_failures = run
+            .callbacks
+            .take()
+            .into_iter()
+            .map(|e| run.redact_error(e))
+            .collect();
+        report.drained = run.draining();
+        report.error = res.err().map(|e| run.redact_error(e));
+
+        report
+    }
+
+    async fn run_unredacted(mut self, report: &mut ExecutionReport<O>) -> Result<()> {
+        if self.run.settings.verbose {
+            eprintln!("starting run with seed {}", self.run.seed);
+        }
+        if self.run.settings.verbose && !self.run.metadata.is_empty() {
+            eprintln!(
+                "starting run with {}",
+                self.run.redacted(&self.run.metadata.to_string())
+            );
+        }
+        for provider in &self.providers {
+            provider.provide(&self.tm).await?;
+        }
+        let checks = self.health.iter().map(|h| h.check(&self.tm));
+        for (h, healthy) in self
+            .health
+            .iter()
+            .zip(futures::future::join_all(checks).await)
+        {
+            if !healthy? {
+                let name = step::short_type_name(h.dep.name);
+                if self.run.settings.verbose {
+                    eprintln!("{name} is unhealthy");
+                }
+                report.unhealthy.push(name);
+                self.run.unhealthy.push(h.dep);
+            }
+        }
+        // Every failed check is collected, whether it failed, timed out, or
+        // panicked.
+        if let Some(preflight) = self.preflight {
+            match preflight.execute(&self.run).await {
+                Ok(_) => {}
+                Err(Error::Build(errors)) => return Err(Error::Preflight(errors)),
+                Err(e) => return Err(Error::Preflight(vec![e])),
+            }
+        }
+
+        // Steps added at runtime run right after the group which added them.
+        let mut groups: VecDeque<_> = self.groups.into();
+        while let Some(g) = groups.pop_front() {
+            if self.run.draining() {
+                g.skip_drained(&self.run);
+                continue;
+            }
+            for (id, key, out) in g.execute(&self.run).await? {
+                report.add_output(id, key, out);
+            }
+            self.run.callbacks.check()?;
+            for g in self.dynamic.expand(&self.run)?.into_iter().rev() {
+                groups.push_front(g);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<O: IntoStepOutcome + Send + 'static> ImperativeStepBuilder<O> {
+    /// Like `add_dep`, but the dependency is also included in `input_hash`.
+    #[must_use]
+    pub fn add_hashed_dep<T: serde::Serialize + Send + Sync + 'static>(mut self, dep: T) -> Self {
+        if let Err(e) = self.inputs.record(&dep) {
+            self.errors
+                .push(Error::InputHash(std::any::type_name::<T>(), Box::new(e)));
+        }
+        self.add_dep(dep)
+    }
+
+    /// Returns a stable hash of every dependency added with `add_hashed_dep`.
+    /// Runs given the same inputs have the same hash, regardless of the order
+    /// dependencies were added in, so it can be compared across runs to detect
+    /// configuration drift.
+    #[must_use]
+    pub fn input_hash(&self) -> u64 {
+        self.inputs.hash()
+    }
+}
+
+// Runs must be `Send`, so they can be spawned on multi-threaded runtimes.
+#[allow(dead_code)]
+fn assert_send<T: Send>(_: &T) {}
+
+#[allow(dead_code)]
+fn execute_is_send(builder: ImperativeStepBuilder<bool>) {
+    assert_send(&builder);
+    assert_send(&builder.execute());
 }
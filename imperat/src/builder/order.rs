@@ -0,0 +1,40 @@
+/// The order groups run in. Preflight checks always run first. Groups are
+/// identified by label: by name, or by position if unnamed, where top-level
+/// steps are position 0. See `ImperativeStepBuilder::group_order`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum GroupOrder {
+    /// Top-level steps, then every group in the order they were added.
+    #[default]
+    DefaultFirst,
+    /// Every group in the order they were added, then top-level steps, such
+    /// as for teardown.
+    DefaultLast,
+    /// The groups with these labels in this order, then any others in the
+    /// order they'd otherwise run.
+    Labels(Vec<String>),
+}
+
+impl GroupOrder {
+    /// Returns where the group labeled `label` runs. Groups of the same
+    /// rank run in the order they were added.
+    pub(super) fn rank(&self, label: &str) -> usize {
+        match self {
+            _ if label == "preflight" => 0,
+            Self::DefaultFirst => 0,
+            Self::DefaultLast => usize::from(label == "0"),
+            Self::Labels(order) => order.iter().position(|l| l == label).unwrap_or(order.len()),
+        }
+    }
+
+    /// Returns every label this order names which isn't in `labels`.
+    pub(super) fn unknown<'a>(&'a self, labels: &[&str]) -> Vec<&'a str> {
+        match self {
+            Self::Labels(order) => order
+                .iter()
+                .map(String::as_str)
+                .filter(|l| !labels.contains(l))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
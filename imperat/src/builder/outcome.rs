@@ -9,6 +9,34 @@ pub trait IntoStepOutcome {
 
     /// Return whether this step succeeded.
     fn success(&self) -> bool;
+
+    /// Returns the error from the step execution without taking it, if
+    /// it's an error type. Used to classify failures; see
+    /// `RetryPolicy::retry_on_error`.
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+
+    /// Takes what the output carries besides its value once the step
+    /// succeeds. Only a `StepReturn` carries anything.
+    fn take_extras(&mut self) -> Option<super::StepExtras> {
+        None
+    }
+}
+
+/// The output of a step which didn't run because its condition wasn't met.
+/// See `StepBuilder::run_if`. It isn't a failure, so the run continues.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Skipped;
+
+impl IntoStepOutcome for Skipped {
+    fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        None
+    }
+
+    fn success(&self) -> bool {
+        true
+    }
 }
 
 // Nightly:
@@ -23,6 +51,10 @@ impl IntoStepOutcome for std::io::Error {
     fn success(&self) -> bool {
         false
     }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self)
+    }
 }
 
 impl IntoStepOutcome for Box<dyn std::error::Error + Send + Sync> {
@@ -33,6 +65,10 @@ impl IntoStepOutcome for Box<dyn std::error::Error + Send + Sync> {
     fn success(&self) -> bool {
         false
     }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.as_ref())
+    }
 }
 
 impl IntoStepOutcome for bool {
@@ -54,6 +90,40 @@ impl IntoStepOutcome for anyhow::Error {
     fn success(&self) -> bool {
         false
     }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.as_ref())
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl IntoStepOutcome for eyre::Report {
+    fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        Some(self.into())
+    }
+
+    fn success(&self) -> bool {
+        false
+    }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.as_ref())
+    }
+}
+
+#[cfg(feature = "miette")]
+impl IntoStepOutcome for miette::Report {
+    fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        Some(self.into())
+    }
+
+    fn success(&self) -> bool {
+        false
+    }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(AsRef::<dyn std::error::Error>::as_ref(self))
+    }
 }
 
 impl<T, E: IntoStepOutcome + Into<Box<dyn std::error::Error + Send + Sync>>> IntoStepOutcome
@@ -70,6 +140,29 @@ impl<T, E: IntoStepOutcome + Into<Box<dyn std::error::Error + Send + Sync>>> Int
     fn success(&self) -> bool {
         self.is_ok()
     }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.as_ref().err().and_then(IntoStepOutcome::error_ref)
+    }
+}
+
+/// `Continue` succeeds. `Break` fails, halting the run unless tolerated, with
+/// the error from its value if it has one.
+impl<B: IntoStepOutcome, C> IntoStepOutcome for std::ops::ControlFlow<B, C> {
+    fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        self.break_value().and_then(IntoStepOutcome::error)
+    }
+
+    fn success(&self) -> bool {
+        self.is_continue()
+    }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            std::ops::ControlFlow::Break(b) => b.error_ref(),
+            std::ops::ControlFlow::Continue(_) => None,
+        }
+    }
 }
 
 // Enable blanket implementations for primitives which never fail.
@@ -0,0 +1,184 @@
+use super::{IntoStepOutcome, StepExtras, outcome::Skipped};
+use crate::{Callable, FromTypeMap};
+use std::{
+    any::{Any, type_name},
+    collections::HashMap,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A step's output of any type, for runners whose steps return different
+/// types. Create a runner for them with `new_any_builder`, wrap each step
+/// with `any_output`, and then query the results with `Outputs`.
+///
+/// Whether the step succeeded is still decided by its original type's
+/// `IntoStepOutcome`.
+pub struct AnyOutput {
+    value: Box<dyn Any + Send>,
+    type_name: &'static str,
+    success: bool,
+    // downcasts `value` and takes its error
+    error: fn(Box<dyn Any + Send>) -> Option<BoxError>,
+    // downcasts `value` and takes its extras
+    extras: fn(&mut (dyn Any + Send)) -> Option<StepExtras>,
+}
+
+impl AnyOutput {
+    /// Wraps a step's output.
+    pub fn new<T: IntoStepOutcome + Send + 'static>(value: T) -> Self {
+        Self {
+            success: value.success(),
+            value: Box::new(value),
+            type_name: type_name::<T>(),
+            error: |value| value.downcast::<T>().ok().and_then(|v| v.error()),
+            extras: |value| value.downcast_mut::<T>()?.take_extras(),
+        }
+    }
+
+    /// Returns the output if it's a `T`.
+    #[must_use]
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// Returns the output if it's a `T`, or this back otherwise.
+    pub fn downcast<T: 'static>(self) -> Result<T, Self> {
+        match self.value.downcast() {
+            Ok(value) => Ok(*value),
+            Err(value) => Err(Self { value, ..self }),
+        }
+    }
+
+    /// Returns the wrapped output.
+    #[cfg(feature = "serde")]
+    pub(super) fn value(&self) -> &dyn Any {
+        self.value.as_ref()
+    }
+
+    /// Returns the name of the output's type, for diagnostics only.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl std::fmt::Debug for AnyOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnyOutput")
+            .field("type_name", &self.type_name)
+            .field("success", &self.success)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<Skipped> for AnyOutput {
+    fn from(skipped: Skipped) -> Self {
+        AnyOutput::new(skipped)
+    }
+}
+
+impl IntoStepOutcome for AnyOutput {
+    fn error(self) -> Option<BoxError> {
+        (self.error)(self.value)
+    }
+
+    fn success(&self) -> bool {
+        self.success
+    }
+
+    fn take_extras(&mut self) -> Option<StepExtras> {
+        (self.extras)(self.value.as_mut())
+    }
+}
+
+/// A step whose output is wrapped in an `AnyOutput`. Create one with
+/// `any_output`.
+pub struct AnyStep<F>(F);
+
+/// Wraps a step function so it can be added to a runner built with
+/// `new_any_builder`, whatever its output type.
+pub fn any_output<F>(func: F) -> AnyStep<F> {
+    AnyStep(func)
+}
+
+#[async_trait::async_trait]
+impl<F, A> Callable<A> for AnyStep<F>
+where
+    F: Callable<A> + Send + Sync,
+    F::Out: IntoStepOutcome + Send + 'static,
+    A: FromTypeMap + Send + 'static,
+{
+    type Out = AnyOutput;
+
+    async fn call(&self, args: A) -> AnyOutput {
+        AnyOutput::new(self.0.call(args).await)
+    }
+}
+
+/// The results of a runner built with `new_any_builder`, queried by step
+/// name or by type. Create one from `execute`'s results.
+#[derive(Debug, Default)]
+pub struct Outputs(HashMap<String, AnyOutput>);
+
+impl From<HashMap<String, AnyOutput>> for Outputs {
+    fn from(outputs: HashMap<String, AnyOutput>) -> Self {
+        Self(outputs)
+    }
+}
+
+impl Outputs {
+    /// Returns the output of the step with this key if it's a `T`.
+    #[must_use]
+    pub fn get<T: 'static>(&self, key: &str) -> Option<&T> {
+        self.0.get(key)?.downcast_ref()
+    }
+
+    /// Removes and returns the output of the step with this key if it's a `T`.
+    pub fn take<T: 'static>(&mut self, key: &str) -> Option<T> {
+        let out = self.0.remove(key)?;
+        match out.downcast() {
+            Ok(out) => Some(out),
+            Err(out) => {
+                self.0.insert(key.to_string(), out);
+                None
+            }
+        }
+    }
+
+    /// Returns the only output which is a `T`, or `None` if there are none
+    /// or several.
+    #[must_use]
+    pub fn by_type<T: 'static>(&self) -> Option<&T> {
+        let mut outputs = self.of_type::<T>();
+        match (outputs.next(), outputs.next()) {
+            (Some((_, out)), None) => Some(out),
+            _ => None,
+        }
+    }
+
+    /// Returns every output which is a `T`, with its step's key, in no
+    /// particular order.
+    pub fn of_type<T: 'static>(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.0
+            .iter()
+            .filter_map(|(key, out)| Some((key.as_str(), out.downcast_ref()?)))
+    }
+
+    /// Returns the untyped output of the step with this key.
+    #[must_use]
+    pub fn get_any(&self, key: &str) -> Option<&AnyOutput> {
+        self.0.get(key)
+    }
+
+    /// Returns how many outputs there are.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether there are no outputs.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::DepInfo;
+
+/// The pipes in a run, by the type of their sender, with how many steps
+/// requesting each sender haven't finished yet.
+#[derive(Clone, Default)]
+pub(super) struct Pipes(Arc<Mutex<HashMap<TypeId, Pipe>>>);
+
+struct Pipe {
+    senders: usize,
+    close: Box<dyn Fn() + Send + Sync>,
+}
+
+impl Pipes {
+    /// Registers a pipe whose sender has type `sender`, closed by `close`.
+    pub(super) fn add(&self, sender: TypeId, close: impl Fn() + Send + Sync + 'static) {
+        self.lock().insert(
+            sender,
+            Pipe {
+                senders: 0,
+                close: Box::new(close),
+            },
+        );
+    }
+
+    /// Counts a step which will send with any of `deps`.
+    pub(super) fn add_sender(&self, deps: &[DepInfo]) {
+        let mut pipes = self.lock();
+        for dep in deps {
+            if let Some(pipe) = pipes.get_mut(&dep.id) {
+                pipe.senders += 1;
+            }
+        }
+    }
+
+    /// Records that a step, which may send with any of `deps`, finished. Pipes
+    /// without any unfinished senders left are closed.
+    pub(super) fn finish(&self, deps: &[DepInfo]) {
+        let mut pipes = self.lock();
+        for dep in deps {
+            if let Some(pipe) = pipes.get_mut(&dep.id) {
+                pipe.senders = pipe.senders.saturating_sub(1);
+                if pipe.senders == 0 {
+                    (pipe.close)();
+                }
+            }
+        }
+    }
+
+    /// Closes every pipe without any senders.
+    pub(super) fn close_unused(&self) {
+        for pipe in self.lock().values().filter(|p| p.senders == 0) {
+            (pipe.close)();
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<TypeId, Pipe>> {
+        self.0.lock().expect("imperat pipes mutex poisoned")
+    }
+}
@@ -0,0 +1,221 @@
+use super::step::short_type_name;
+use crate::DepInfo;
+use std::fmt::Write;
+
+/// Describes what a runner would do, without running anything. Get one with
+/// `ImperativeStepBuilder::plan`.
+#[derive(Clone, Debug)]
+pub struct ExecutionPlan {
+    /// Every dependency provider in the order they run, before any step. See
+    /// `ImperativeStepBuilder::add_dep_with`.
+    pub providers: Vec<ProviderPlan>,
+    /// Every group in the order they run: preflight checks, then top-level
+    /// steps and each group per `ImperativeStepBuilder::group_order`.
+    pub groups: Vec<GroupPlan>,
+}
+
+impl ExecutionPlan {
+    /// Returns every step in the order they run, with their group.
+    pub fn steps(&self) -> impl Iterator<Item = (&GroupPlan, &StepPlan)> {
+        self.groups
+            .iter()
+            .flat_map(|g| g.steps.iter().map(move |s| (g, s)))
+    }
+
+    /// Returns every step which won't run as its dependencies can't be
+    /// resolved.
+    pub fn unresolved(&self) -> impl Iterator<Item = &StepPlan> {
+        self.steps().map(|(_, s)| s).filter(|s| s.phase.is_none())
+    }
+
+    /// Returns whether every step's dependencies can be resolved.
+    #[must_use]
+    pub fn is_resolved(&self) -> bool {
+        self.unresolved().next().is_none()
+    }
+
+    /// Renders this plan as a Graphviz DOT graph. Groups are clusters and
+    /// steps are nodes, in the order they run. Solid edges run from each
+    /// provider or binding step to the steps using what it binds, and dashed
+    /// edges from each step to those depending on it by name. Steps which
+    /// won't run are red.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = "digraph pipeline {\n".to_string();
+        for (i, provider) in self.providers.iter().enumerate() {
+            let _ = writeln!(dot, "  p{i} [label={}, shape=box];", quote(&provider.name));
+        }
+        let mut n = 0;
+        for (i, group) in self.groups.iter().enumerate() {
+            let _ = writeln!(dot, "  subgraph cluster_{i} {{");
+            let _ = writeln!(dot, "    label={};", quote(group.label()));
+            for step in &group.steps {
+                let color = if step.phase.is_none() {
+                    ", color=red"
+                } else {
+                    ""
+                };
+                let _ = writeln!(dot, "    s{n} [label={}{color}];", quote(&step.name));
+                n += 1;
+            }
+            dot.push_str("  }\n");
+        }
+        for edge in self.edges() {
+            let _ = match edge {
+                Edge::Binds(from, to, dep) => {
+                    writeln!(dot, "  {from} -> s{to} [label={}];", quote(&dep))
+                }
+                Edge::After(from, to) => writeln!(dot, "  s{from} -> s{to} [style=dashed];"),
+            };
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Renders this plan as a Mermaid flowchart, like `to_dot`. Groups are
+    /// subgraphs and steps which won't run are marked `unresolved`.
+    #[must_use]
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid reads some characters in labels as markup.
+        let quote = |s: &str| {
+            let s = s
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;");
+            format!("\"{s}\"")
+        };
+        let mut mermaid = "flowchart TD\n".to_string();
+        for (i, provider) in self.providers.iter().enumerate() {
+            let _ = writeln!(mermaid, "  p{i}[({})]", quote(&provider.name));
+        }
+        let mut n = 0;
+        for (i, group) in self.groups.iter().enumerate() {
+            let _ = writeln!(mermaid, "  subgraph g{i} [{}]", quote(group.label()));
+            for step in &group.steps {
+                let class = if step.phase.is_none() {
+                    ":::unresolved"
+                } else {
+                    ""
+                };
+                let _ = writeln!(mermaid, "    s{n}[{}]{class}", quote(&step.name));
+                n += 1;
+            }
+            mermaid.push_str("  end\n");
+        }
+        for edge in self.edges() {
+            let _ = match edge {
+                Edge::Binds(from, to, dep) => {
+                    writeln!(mermaid, "  {from} -- {} --> s{to}", quote(&dep))
+                }
+                Edge::After(from, to) => writeln!(mermaid, "  s{from} -.-> s{to}"),
+            };
+        }
+        mermaid.push_str("  classDef unresolved stroke:red\n");
+
+        mermaid
+    }
+
+    /// Every edge between nodes, where steps are numbered in the order
+    /// they run.
+    fn edges(&self) -> Vec<Edge> {
+        let steps: Vec<_> = self.steps().collect();
+        // binding steps bind for every step, wherever they are
+        let mut binders: Vec<_> = self
+            .providers
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (format!("p{i}"), p.binds))
+            .collect();
+        binders.extend(
+            steps
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (_, s))| Some((format!("s{i}"), s.binds?))),
+        );
+
+        let mut edges = vec![];
+        for (to, (group, step)) in steps.iter().enumerate() {
+            for dep in &step.dependencies {
+                for (from, _) in binders.iter().filter(|(_, b)| b.id == dep.id) {
+                    edges.push(Edge::Binds(from.clone(), to, short_type_name(dep.name)));
+                }
+            }
+            for name in &step.after {
+                let from = steps
+                    .iter()
+                    .position(|(g, s)| std::ptr::eq(*g, *group) && &s.name == name);
+                if let Some(from) = from {
+                    edges.push(Edge::After(from, to));
+                }
+            }
+        }
+
+        edges
+    }
+}
+
+enum Edge {
+    /// From a provider or binding step's node to a step using its binding.
+    Binds(String, usize, String),
+    /// From a step to a step depending on it by name.
+    After(usize, usize),
+}
+
+/// A single dependency provider's entry in an `ExecutionPlan`.
+#[derive(Clone, Debug)]
+pub struct ProviderPlan {
+    /// The provider's name, after the type it provides.
+    pub name: String,
+    /// Every dependency the provider requests.
+    pub dependencies: Vec<DepInfo>,
+    /// The dependency it binds.
+    pub binds: DepInfo,
+}
+
+/// A single group's entry in an `ExecutionPlan`.
+#[derive(Clone, Debug)]
+pub struct GroupPlan {
+    /// The group's name, or its position if unnamed. Preflight checks are
+    /// labeled `preflight`, and top-level steps aren't labeled.
+    pub label: Option<String>,
+    pub parallel: bool,
+    /// Whether the group was set to tolerate its steps' failures. See
+    /// `GroupBuilder::tolerate_failure`.
+    pub tolerate_failure: bool,
+    pub deterministic: bool,
+    pub max_concurrency: Option<usize>,
+    /// The group's steps in the order they start. Steps which won't run come
+    /// last.
+    pub steps: Vec<StepPlan>,
+}
+
+impl GroupPlan {
+    /// Returns this group's label, or `steps` for top-level steps.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or("steps")
+    }
+}
+
+/// A single step's entry in an `ExecutionPlan`.
+#[derive(Clone, Debug)]
+pub struct StepPlan {
+    pub name: String,
+    /// The step's short id. See `StepKey::short_id`.
+    pub short_id: String,
+    /// The step's phase in its group, counting from 0. See `Phase`. Steps
+    /// which won't run as their dependencies can't be resolved have none.
+    pub phase: Option<usize>,
+    /// Steps in the same group this step waits for. See
+    /// `StepBuilder::depends_on`.
+    pub after: Vec<String>,
+    /// Every dependency the step requests.
+    pub dependencies: Vec<DepInfo>,
+    /// The dependency the step binds its output to, if any. See
+    /// `StepBuilder::produces`.
+    pub binds: Option<DepInfo>,
+    /// Dependencies which can't be resolved, so the step won't run.
+    pub unresolved: Vec<DepInfo>,
+}
@@ -0,0 +1,90 @@
+use super::retry::RetryPolicy;
+use std::time::Duration;
+
+/// Named presets of run-wide settings so the same pipeline behaves
+/// appropriately per environment. Select one with
+/// `ImperativeStepBuilder::with_profile`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Tolerates failures so every broken step shows up in one run, logs
+    /// each step, and runs parallel groups one step at a time for readable output.
+    Dev,
+    /// Fails fast, logs each step, and retries flaky steps a couple of times.
+    Ci,
+    /// Fails fast, runs quietly, and retries failed steps.
+    Prod,
+}
+
+impl Profile {
+    /// Returns the settings bundled by this profile.
+    #[must_use]
+    pub fn settings(self) -> ProfileSettings {
+        match self {
+            Profile::Dev => ProfileSettings {
+                tolerate_failure: true,
+                verbose: true,
+                allow_parallel: false,
+                ..ProfileSettings::default()
+            },
+            Profile::Ci => ProfileSettings {
+                verbose: true,
+                retries: 2,
+                retry_backoff: Duration::from_secs(1),
+                ..ProfileSettings::default()
+            },
+            Profile::Prod => ProfileSettings {
+                retries: 3,
+                retry_backoff: Duration::from_secs(1),
+                ..ProfileSettings::default()
+            },
+        }
+    }
+}
+
+/// Run-wide defaults for every group and step. Groups and steps which set
+/// an option themselves override these. Start from a profile and override
+/// fields as needed:
+///
+/// ```
+/// # use imperat::{Profile, ProfileSettings};
+/// let settings = ProfileSettings {
+///     verbose: false,
+///     ..Profile::Ci.settings()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProfileSettings {
+    /// Whether groups continue past failed steps.
+    pub tolerate_failure: bool,
+    /// Whether each step's start and outcome is logged to stderr.
+    pub verbose: bool,
+    /// How many times failed steps are retried.
+    pub retries: usize,
+    /// Base delay between retries. The actual delay is jittered.
+    pub retry_backoff: Duration,
+    /// How long a step may run before it's cancelled with
+    /// `CancelReason::Timeout`.
+    pub step_timeout: Option<Duration>,
+    /// Whether parallel groups run their steps concurrently. If not,
+    /// they run one at a time but otherwise behave as parallel groups.
+    pub allow_parallel: bool,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self {
+            tolerate_failure: false,
+            verbose: false,
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+            step_timeout: None,
+            allow_parallel: true,
+        }
+    }
+}
+
+impl ProfileSettings {
+    pub(super) fn retry<O>(&self) -> Option<RetryPolicy<O>> {
+        (self.retries > 0).then(|| RetryPolicy::new(self.retries).fixed(self.retry_backoff))
+    }
+}
@@ -0,0 +1,71 @@
+use super::{Error, Result, step::short_type_name};
+use crate::{Callable, Dep, DepInfo, FromTypeMap, TypeMap};
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+type ProviderFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type ProviderFn = dyn Fn(&TypeMap) -> Result<ProviderFuture> + Send + Sync;
+
+/// Builds a dependency when a run starts, rather than when it's added to
+/// the builder. See `ImperativeStepBuilder::add_dep_with`.
+pub(super) struct Provider {
+    /// The name of the type this provides, for reporting.
+    pub(super) name: String,
+    pub(super) deps: Vec<DepInfo>,
+    pub(super) binds: DepInfo,
+    call: Box<ProviderFn>,
+}
+
+impl Provider {
+    pub(super) fn new<T: Send + Sync + 'static, E, C, A: FromTypeMap>(
+        func: C,
+        tm: Arc<Mutex<TypeMap>>,
+    ) -> Self
+    where
+        C: Callable<A, Out = std::result::Result<T, E>> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let name = format!(
+            "provider of {}",
+            short_type_name(std::any::type_name::<Dep<T>>())
+        );
+        let mut deps = vec![];
+        A::dependencies(&mut deps);
+
+        let func = Arc::new(func);
+        let provider = name.clone();
+        Self {
+            name,
+            deps,
+            binds: DepInfo::of::<Dep<T>>(),
+            call: Box::new(move |map| {
+                let args = A::retrieve_from_map(map).ok_or_else(|| match A::missing(map) {
+                    Some((index, dep)) => {
+                        Error::MissingParam(provider.clone(), index, short_type_name(dep.name))
+                    }
+                    None => Error::DepResolution(provider.clone()),
+                })?;
+                let func = func.clone();
+                let tm = tm.clone();
+                Ok(Box::pin(async move {
+                    let dep = func
+                        .call(args)
+                        .await
+                        .map_err(|e| Error::DepInit(std::any::type_name::<T>(), e.into()))?;
+                    tm.lock()
+                        .expect("imperat typemap mutex poisoned")
+                        .bind(Dep::new(dep));
+                    Ok(())
+                }))
+            }),
+        }
+    }
+
+    /// Builds and binds this dependency from the dependencies bound so far.
+    pub(super) async fn provide(&self, tm: &Mutex<TypeMap>) -> Result<()> {
+        let fut = (self.call)(&tm.lock().expect("imperat typemap mutex poisoned"))?;
+        fut.await
+    }
+}
@@ -0,0 +1,111 @@
+use super::{Error, Result, executor::ExecutorHandle, step::short_type_name};
+use crate::{Callable, Dep, DepInfo, FromTypeMap, IntoStepOutcome, TypeMap};
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type ProbeFuture = Pin<Box<dyn Future<Output = bool> + Send>>;
+type ProbeFn = dyn Fn(&TypeMap) -> Result<ProbeFuture> + Send + Sync;
+
+// How long to wait between probes, at first and at most.
+const FIRST_DELAY: Duration = Duration::from_millis(50);
+const MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Waits for a dependency to become ready before the first step depending
+/// on it runs. See `ImperativeStepBuilder::wait_for_dep`.
+pub(super) struct ReadinessProbe {
+    /// The dependency this waits for, as steps request it.
+    pub(super) dep: DepInfo,
+    timeout: Duration,
+    call: Box<ProbeFn>,
+    // whether the dependency became ready, once it's been waited for
+    ready: futures::lock::Mutex<Option<bool>>,
+}
+
+impl ReadinessProbe {
+    pub(super) fn new<T: 'static, C, A: FromTypeMap>(timeout: Duration, probe: C) -> Arc<Self>
+    where
+        C: Callable<A> + 'static,
+        C::Out: IntoStepOutcome,
+    {
+        let name = format!(
+            "readiness probe of {}",
+            short_type_name(std::any::type_name::<Dep<T>>())
+        );
+        let probe = Arc::new(probe);
+        Arc::new(Self {
+            dep: DepInfo::of::<Dep<T>>(),
+            timeout,
+            call: Box::new(move |map| {
+                let args = A::retrieve_from_map(map).ok_or_else(|| match A::missing(map) {
+                    Some((index, dep)) => {
+                        Error::MissingParam(name.clone(), index, short_type_name(dep.name))
+                    }
+                    None => Error::DepResolution(name.clone()),
+                })?;
+                let probe = probe.clone();
+                Ok(Box::pin(async move { probe.call(args).await.success() }))
+            }),
+            ready: futures::lock::Mutex::new(None),
+        })
+    }
+
+    /// Waits for this dependency to become ready for step `step`, probing
+    /// it with the dependencies in `tm`. It's only probed until it's ready
+    /// or times out once per run, and steps which arrive meanwhile wait on
+    /// the same probes.
+    pub(super) async fn wait(
+        &self,
+        step: &str,
+        tm: &Mutex<TypeMap>,
+        executor: &ExecutorHandle,
+        verbose: bool,
+    ) -> Result<()> {
+        let mut state = self.ready.lock().await;
+        let ready = match *state {
+            Some(ready) => ready,
+            None => *state.insert(self.probe(tm, executor, verbose).await?),
+        };
+        if ready {
+            return Ok(());
+        }
+        Err(Error::NotReady(
+            step.to_string(),
+            short_type_name(self.dep.name),
+            self.timeout,
+        ))
+    }
+
+    /// Probes until this dependency is ready, backing off between probes,
+    /// and returns whether it became ready before the timeout.
+    async fn probe(
+        &self,
+        tm: &Mutex<TypeMap>,
+        executor: &ExecutorHandle,
+        verbose: bool,
+    ) -> Result<bool> {
+        let start = Instant::now();
+        let mut delay = FIRST_DELAY;
+        loop {
+            let fut = (self.call)(&tm.lock().expect("imperat typemap mutex poisoned"))?;
+            let left = self.timeout.saturating_sub(start.elapsed());
+            if executor.timeout(left, fut).await == Some(true) {
+                return Ok(true);
+            }
+            let left = self.timeout.saturating_sub(start.elapsed());
+            if left.is_zero() {
+                return Ok(false);
+            }
+            if verbose {
+                eprintln!(
+                    "waiting for {} to become ready",
+                    short_type_name(self.dep.name)
+                );
+            }
+            executor.sleep(delay.min(left)).await;
+            delay = (delay * 2).min(MAX_DELAY);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::{Error, Result};
+use crate::{Dep, DepInfo, TypeMap};
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type RefreshFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+type RefreshFn = dyn Fn(Arc<Mutex<TypeMap>>) -> RefreshFuture + Send + Sync;
+
+/// A dependency which can go stale during a run, such as an auth token.
+/// Add one with `ImperativeStepBuilder::add_refreshable_dep`; before each
+/// step which takes it as a `Dep<T>` runs, it's replaced with a fresh copy
+/// if it's stale.
+pub trait Refreshable: Sized + Send + Sync {
+    /// Whether this must be refreshed before the next step uses it.
+    fn is_stale(&self) -> bool;
+
+    /// Builds a fresh replacement for this dependency.
+    fn refresh(&self) -> impl Future<Output = std::result::Result<Self, BoxError>> + Send;
+
+    /// Returns a fresh replacement if this is stale, or `None` if it can be
+    /// used as is.
+    fn refresh_if_stale(
+        &self,
+    ) -> impl Future<Output = std::result::Result<Option<Self>, BoxError>> + Send {
+        async {
+            if self.is_stale() {
+                self.refresh().await.map(Some)
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+struct Refresher {
+    // held while refreshing, so steps starting together refresh only once
+    lock: tokio::sync::Mutex<()>,
+    call: Box<RefreshFn>,
+}
+
+/// Refreshes a run's `Refreshable` dependencies, by the `TypeId` of their
+/// `Dep<T>`.
+#[derive(Clone, Default)]
+pub(super) struct Refreshers(Arc<HashMap<TypeId, Arc<Refresher>>>);
+
+impl Refreshers {
+    pub(super) fn add<T: Refreshable + 'static>(&mut self) {
+        let call = Box::new(|tm: Arc<Mutex<TypeMap>>| -> RefreshFuture {
+            Box::pin(async move {
+                let current = tm
+                    .lock()
+                    .expect("imperat typemap mutex poisoned")
+                    .get::<Dep<T>>()
+                    .cloned();
+                let Some(current) = current else {
+                    return Ok(());
+                };
+                let fresh = current
+                    .refresh_if_stale()
+                    .await
+                    .map_err(|e| Error::DepRefresh(std::any::type_name::<T>(), e))?;
+                if let Some(fresh) = fresh {
+                    tm.lock()
+                        .expect("imperat typemap mutex poisoned")
+                        .bind(Dep::new(fresh));
+                }
+                Ok(())
+            })
+        });
+        Arc::make_mut(&mut self.0).insert(
+            TypeId::of::<Dep<T>>(),
+            Arc::new(Refresher {
+                lock: tokio::sync::Mutex::new(()),
+                call,
+            }),
+        );
+    }
+
+    /// Refreshes each of `deps` which is refreshable and stale.
+    pub(super) async fn refresh(&self, deps: &[DepInfo], tm: &Arc<Mutex<TypeMap>>) -> Result<()> {
+        for dep in deps {
+            if let Some(refresher) = self.0.get(&dep.id) {
+                let _guard = refresher.lock.lock().await;
+                (refresher.call)(tm.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use std::any::Any;
+
+use super::{Error, ImperativeStepBuilder};
+
+/// Steps contributed to a named pipeline from anywhere in a binary with
+/// `register_steps!`, so library crates don't need to be wired into `main`.
+/// Collect them with `ImperativeStepBuilder::add_registered`.
+pub struct Registration {
+    pipeline: &'static str,
+    module: &'static str,
+    line: u32,
+    register: fn(&mut dyn Any) -> bool,
+}
+
+inventory::collect!(Registration);
+
+impl Registration {
+    /// Internal API for `register_steps!`.
+    #[doc(hidden)]
+    #[must_use]
+    pub const fn new(
+        pipeline: &'static str,
+        module: &'static str,
+        line: u32,
+        register: fn(&mut dyn Any) -> bool,
+    ) -> Self {
+        Self {
+            pipeline,
+            module,
+            line,
+            register,
+        }
+    }
+
+    /// Internal API for `register_steps!`. Applies `f` to the builder in
+    /// `slot`, returning false if it's for a different output type.
+    ///
+    /// # Panics
+    /// If `slot` is empty.
+    #[doc(hidden)]
+    pub fn apply<O: 'static>(
+        slot: &mut dyn Any,
+        f: impl FnOnce(ImperativeStepBuilder<O>) -> ImperativeStepBuilder<O>,
+    ) -> bool {
+        let Some(slot) = slot.downcast_mut::<Option<ImperativeStepBuilder<O>>>() else {
+            return false;
+        };
+        let builder = slot.take().expect("imperat registration applied twice");
+        *slot = Some(f(builder));
+        true
+    }
+
+    /// The pipeline these steps are registered to.
+    #[must_use]
+    pub fn pipeline(&self) -> &'static str {
+        self.pipeline
+    }
+
+    /// The module which registered these steps.
+    #[must_use]
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+}
+
+impl<O: 'static> ImperativeStepBuilder<O> {
+    /// Add everything registered to `pipeline` with `register_steps!`, in
+    /// any crate linked into this binary. Registrations are applied in
+    /// order of their module path and then line, so runs are repeatable.
+    /// Registrations for a builder of another output type are recorded as
+    /// `Error::Define`.
+    #[must_use]
+    pub fn add_registered(self, pipeline: &str) -> Self {
+        let mut registered: Vec<_> = inventory::iter::<Registration>
+            .into_iter()
+            .filter(|r| r.pipeline == pipeline)
+            .collect();
+        registered.sort_by_key(|r| (r.module, r.line));
+
+        let mut slot = Some(self);
+        let mut mismatched = vec![];
+        for r in registered {
+            if !(r.register)(&mut slot) {
+                mismatched.push(r);
+            }
+        }
+        let Some(mut builder) = slot else {
+            unreachable!("registrations always return the builder");
+        };
+        builder.errors.extend(mismatched.into_iter().map(|r| {
+            Error::Define(
+                format!(
+                    "steps registered to '{pipeline}' in {} at line {} are for another output type",
+                    r.module, r.line
+                )
+                .into(),
+            )
+        }));
+        builder
+    }
+}
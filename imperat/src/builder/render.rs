@@ -0,0 +1,233 @@
+use std::{fmt::Write as _, time::Duration};
+
+use super::{ExecutionReport, StepOutcome, StepReport};
+
+// How many characters wide the longest bar in a Markdown timing chart is.
+const CHART_WIDTH: usize = 30;
+
+impl<O> ExecutionReport<O> {
+    /// Renders a Markdown summary of the run for people to review, such as
+    /// in a ticket or pull request comment: its status, a table of every
+    /// step, the errors of steps which didn't succeed, and a chart of how
+    /// long each step which ran took.
+    #[must_use]
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "## Run {} {}\n", self.run_id, self.status());
+        if let Some(e) = &self.error {
+            let _ = writeln!(out, "**Error:** {}\n", markdown_cell(&e.to_string()));
+        }
+
+        out.push_str("| Step | Group | Outcome | Duration |\n");
+        out.push_str("| --- | --- | --- | --- |\n");
+        for s in &self.steps {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                markdown_cell(&s.name),
+                markdown_cell(&s.group),
+                outcome(s.outcome),
+                duration(s.duration)
+            );
+        }
+
+        let failures = self.failures();
+        if !failures.is_empty() {
+            out.push_str("\n### Failures\n");
+            for (s, e) in failures {
+                let _ = writeln!(
+                    out,
+                    "\n#### {} ({})\n\n```text\n{}\n```",
+                    markdown_cell(&s.name),
+                    outcome(s.outcome),
+                    e.replace("```", "'''")
+                );
+            }
+        }
+
+        let timings = self.timings();
+        if !timings.is_empty() {
+            out.push_str("\n### Timing\n\n```text\n");
+            let width = timings.iter().map(|(s, _)| s.name.len()).max().unwrap_or(0);
+            for (s, fraction) in timings {
+                let _ = writeln!(
+                    out,
+                    "{:width$}  {:CHART_WIDTH$}  {}",
+                    s.name,
+                    "█".repeat(bar(fraction, CHART_WIDTH)),
+                    duration(s.duration)
+                );
+            }
+            out.push_str("```\n");
+        }
+        out
+    }
+
+    /// Renders the same summary as `to_markdown` as a standalone HTML page,
+    /// with its styles inline, so it can be attached or served as is.
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let title = format!("Run {} {}", self.run_id, self.status());
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(out, "<title>{}</title>", html(&title));
+        out.push_str(STYLE);
+        out.push_str("</head>\n<body>\n");
+        let _ = writeln!(out, "<h1>{}</h1>", html(&title));
+        if let Some(e) = &self.error {
+            let _ = writeln!(out, "<p class=\"error\">{}</p>", html(&e.to_string()));
+        }
+
+        out.push_str(
+            "<table>\n<tr><th>Step</th><th>Group</th><th>Outcome</th><th>Duration</th></tr>\n",
+        );
+        for s in &self.steps {
+            let _ = writeln!(
+                out,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class(s.outcome),
+                html(&s.name),
+                html(&s.group),
+                outcome(s.outcome),
+                duration(s.duration)
+            );
+        }
+        out.push_str("</table>\n");
+
+        let failures = self.failures();
+        if !failures.is_empty() {
+            out.push_str("<h2>Failures</h2>\n");
+            for (s, e) in failures {
+                let _ = writeln!(
+                    out,
+                    "<h3>{} ({})</h3>\n<pre>{}</pre>",
+                    html(&s.name),
+                    outcome(s.outcome),
+                    html(e)
+                );
+            }
+        }
+
+        let timings = self.timings();
+        if !timings.is_empty() {
+            out.push_str("<h2>Timing</h2>\n<table class=\"timing\">\n");
+            for (s, fraction) in timings {
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{}</td><td class=\"chart\"><div class=\"bar {}\" style=\"width: {}%\"></div></td><td>{}</td></tr>",
+                    html(&s.name),
+                    class(s.outcome),
+                    bar(fraction, 100),
+                    duration(s.duration)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn status(&self) -> &'static str {
+        match (&self.error, self.drained) {
+            (Some(_), _) => "failed",
+            (None, true) => "drained",
+            (None, false) => "succeeded",
+        }
+    }
+
+    /// Steps which didn't succeed and have an error, with it.
+    fn failures(&self) -> Vec<(&StepReport, &str)> {
+        self.steps
+            .iter()
+            .filter(|s| s.outcome != StepOutcome::Succeeded)
+            .filter_map(|s| Some((s, s.error.as_deref()?)))
+            .collect()
+    }
+
+    /// Steps which ran, with their duration as a fraction of the longest.
+    fn timings(&self) -> Vec<(&StepReport, f64)> {
+        let longest = self
+            .steps
+            .iter()
+            .filter_map(|s| s.duration)
+            .max()
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.steps
+            .iter()
+            .filter_map(|s| {
+                let d = s.duration?.as_secs_f64();
+                Some((s, if longest > 0.0 { d / longest } else { 0.0 }))
+            })
+            .collect()
+    }
+}
+
+const STYLE: &str = "<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; text-align: left; }
+tr.failed td, tr.cancelled td { background: #fde8e8; }
+tr.skipped td { color: #888; }
+.error { color: #b00; }
+pre { background: #f5f5f5; padding: 0.6em; white-space: pre-wrap; }
+.timing td { border: none; }
+.chart { width: 20em; }
+.bar { height: 1em; background: #4a8; }
+.bar.failed, .bar.cancelled { background: #d44; }
+</style>
+";
+
+fn outcome(outcome: StepOutcome) -> &'static str {
+    match outcome {
+        StepOutcome::Succeeded => "✅ succeeded",
+        StepOutcome::Failed => "❌ failed",
+        StepOutcome::Skipped => "⏭️ skipped",
+        StepOutcome::Cancelled => "🚫 cancelled",
+    }
+}
+
+fn class(outcome: StepOutcome) -> &'static str {
+    match outcome {
+        StepOutcome::Succeeded => "succeeded",
+        StepOutcome::Failed => "failed",
+        StepOutcome::Skipped => "skipped",
+        StepOutcome::Cancelled => "cancelled",
+    }
+}
+
+fn duration(d: Option<Duration>) -> String {
+    d.map_or_else(|| "-".to_string(), |d| format!("{d:.1?}"))
+}
+
+/// How many of `width` units a bar `fraction` of the longest is, showing
+/// any step which took time at all.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn bar(fraction: f64, width: usize) -> usize {
+    let units = (fraction * width as f64).round() as usize;
+    if fraction > 0.0 { units.max(1) } else { units }
+}
+
+/// Escapes `s` for a single line of a Markdown table.
+fn markdown_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Escapes `s` for HTML text or attributes.
+fn html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
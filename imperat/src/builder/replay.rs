@@ -0,0 +1,174 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::{Duration, Instant, SystemTime},
+};
+
+use futures::StreamExt;
+
+use super::{
+    ExecutionReport, PipelineEvent, PipelineEvents, StepOutcome,
+    executor::{Executor, ExecutorHandle},
+};
+
+/// How fast a `Replay` plays back its events.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReplaySpeed {
+    /// Wait between events as long as the run did.
+    #[default]
+    RealTime,
+    /// Wait between events as long as the run did, divided by this factor,
+    /// such as 10.0 to play back ten times faster.
+    FastForward(f64),
+    /// Never wait: each event is played back as soon as it's asked for, so
+    /// the caller advances the replay, such as on a key press.
+    StepByStep,
+}
+
+/// Plays back a past run's events with their recorded timings, such as to
+/// watch how a failed run unfolded. Get one from a finished run with
+/// `ExecutionReport::replay`, or record a live one with `Replay::record`.
+/// Pass its events to `tui::LiveView::update` to watch it in a terminal.
+pub struct Replay {
+    // by when they happened since the run started
+    events: VecDeque<(Duration, PipelineEvent)>,
+    at: Duration,
+    speed: ReplaySpeed,
+    executor: ExecutorHandle,
+}
+
+impl Replay {
+    /// Creates a replay of `events`, by when they happened since the run
+    /// started.
+    #[must_use]
+    pub fn new(mut events: Vec<(Duration, PipelineEvent)>) -> Self {
+        events.sort_by_key(|(at, _)| *at);
+        Self {
+            events: events.into(),
+            at: Duration::ZERO,
+            speed: ReplaySpeed::default(),
+            executor: ExecutorHandle::default(),
+        }
+    }
+
+    /// Records a live run's events as they happen, until it finishes.
+    pub async fn record(mut events: PipelineEvents) -> Self {
+        let start = Instant::now();
+        let mut recorded = vec![];
+        while let Some(event) = events.next().await {
+            recorded.push((start.elapsed(), event));
+        }
+        Self::new(recorded)
+    }
+
+    /// Plays back at `speed`. Defaults to `ReplaySpeed::RealTime`.
+    #[must_use]
+    pub fn speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Changes the speed partway through, such as to fast-forward to an
+    /// interesting step and then step through it.
+    pub fn set_speed(&mut self, speed: ReplaySpeed) {
+        self.speed = speed;
+    }
+
+    /// Waits between events on `executor`, rather than the default one. See
+    /// `ImperativeStepBuilder::executor`.
+    #[must_use]
+    pub fn executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.executor = ExecutorHandle::new(executor);
+        self
+    }
+
+    /// Returns the events which haven't been played back yet, by when they
+    /// happened since the run started.
+    pub fn remaining(&self) -> impl Iterator<Item = &(Duration, PipelineEvent)> {
+        self.events.iter()
+    }
+
+    /// Plays back the next event without waiting, whatever the speed.
+    pub fn advance(&mut self) -> Option<PipelineEvent> {
+        let (at, event) = self.events.pop_front()?;
+        self.at = at;
+        Some(event)
+    }
+
+    /// Waits until the next event is due at this replay's speed, then plays
+    /// it back. Returns `None` once every event has been.
+    pub async fn next_event(&mut self) -> Option<PipelineEvent> {
+        let gap = self.events.front()?.0.saturating_sub(self.at);
+        let wait = match self.speed {
+            ReplaySpeed::RealTime => gap,
+            ReplaySpeed::FastForward(factor) => {
+                Duration::try_from_secs_f64(gap.as_secs_f64() / factor).unwrap_or_default()
+            }
+            ReplaySpeed::StepByStep => Duration::ZERO,
+        };
+        if !wait.is_zero() {
+            self.executor.sleep(wait).await;
+        }
+        self.advance()
+    }
+}
+
+impl<O> ExecutionReport<O> {
+    /// Returns a replay of this run's events, rebuilt from when each step
+    /// started and how long it ran. Each step is replayed as a single
+    /// attempt, and steps which never started finish as soon as the step
+    /// reported before them did.
+    #[must_use]
+    pub fn replay(&self) -> Replay {
+        let Some(start) = self.steps.iter().filter_map(|s| s.started).min() else {
+            return Replay::new(vec![(Duration::ZERO, self.finished_event())]);
+        };
+        let since = |t: SystemTime| t.duration_since(start).unwrap_or_default();
+
+        let mut steps = vec![];
+        let mut cursor = Duration::ZERO;
+        for s in &self.steps {
+            let started = s.started.map(since);
+            let finished = match (started, s.duration) {
+                (Some(started), Some(duration)) => started + duration,
+                (started, _) => started.unwrap_or(cursor).max(cursor),
+            };
+            cursor = cursor.max(finished);
+            if let Some(started) = started {
+                let event = PipelineEvent::StepStarted {
+                    name: s.name.clone(),
+                    group: s.group.clone(),
+                    attempt: 1,
+                };
+                steps.push((started, s.group.clone(), event));
+            }
+            let event = PipelineEvent::StepFinished {
+                name: s.name.clone(),
+                group: s.group.clone(),
+                duration: s.duration.unwrap_or_default(),
+                success: s.outcome == StepOutcome::Succeeded,
+                outcome: s.outcome,
+            };
+            steps.push((finished, s.group.clone(), event));
+        }
+        steps.sort_by_key(|(at, _, _)| *at);
+
+        // Each group starts with its first event.
+        let mut started = HashSet::new();
+        let mut events = vec![];
+        for (at, group, event) in steps {
+            if started.insert(group.clone()) {
+                events.push((at, PipelineEvent::GroupStarted { group }));
+            }
+            events.push((at, event));
+        }
+        events.push((cursor, self.finished_event()));
+        Replay::new(events)
+    }
+
+    fn finished_event(&self) -> PipelineEvent {
+        PipelineEvent::PipelineFinished {
+            success: self.error.is_none(),
+            error: self.error.as_ref().map(ToString::to_string),
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use super::{DurationHistogram, Error, Result, log::log_warn};
+use crate::{Counters, DepInfo, RunMetadata};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// How a step ended. See `StepReport::outcome`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StepOutcome {
+    Succeeded,
+    /// The step failed, timed out, or panicked.
+    Failed,
+    /// The step didn't run, either as its condition wasn't met or as a step
+    /// it depends on didn't succeed.
+    Skipped,
+    /// The step was cancelled, including by a failure in a group which
+    /// fails fast.
+    Cancelled,
+}
+
+/// A single step's entry in an `ExecutionReport`.
+#[derive(Clone, Debug)]
+pub struct StepReport {
+    /// The step's position in the run. See `KeyStrategy::Id`.
+    pub id: usize,
+    pub name: String,
+    /// The step's key in the results. See `KeyStrategy`.
+    pub key: String,
+    /// The step's group's name, or its position if unnamed. Preflight checks
+    /// are in the `preflight` group.
+    pub group: String,
+    /// The step's short id. See `StepKey::short_id`.
+    pub short_id: String,
+    /// Deprecated names the step is also known by.
+    pub aliases: Vec<String>,
+    /// Every dependency the step requests.
+    pub dependencies: Vec<DepInfo>,
+    /// When the step started, unless it never did.
+    pub started: Option<SystemTime>,
+    /// How long the step ran for across every attempt, unless it never
+    /// started or was cancelled.
+    pub duration: Option<Duration>,
+    pub outcome: StepOutcome,
+    /// The step's error, if it failed with one, after redaction.
+    pub error: Option<String>,
+    /// What the step counted across every attempt, by name. See `Counters`.
+    pub counters: BTreeMap<String, u64>,
+    /// The files the step produced, by name. See `StepReturn::artifact`.
+    pub artifacts: BTreeMap<String, PathBuf>,
+    /// The step this step is a fallback for, if any. The pair succeeded if
+    /// either did. See `StepBuilder::fallback_for`.
+    pub fallback_for: Option<String>,
+}
+
+/// The process exit codes a run maps to, for CLIs to return from `main`.
+/// See `ExecutionReport::exit_code`. By default, runs which succeeded exit
+/// with 0, runs which failed with 1, and runs which succeeded despite
+/// tolerating a step's failure with 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExitCodes {
+    success: u8,
+    failure: u8,
+    partial: u8,
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        Self {
+            success: 0,
+            failure: 1,
+            partial: 2,
+        }
+    }
+}
+
+impl ExitCodes {
+    /// Creates the default exit codes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exit with `code` if the run succeeded and every step which ran did.
+    #[must_use]
+    pub fn success(mut self, code: u8) -> Self {
+        self.success = code;
+        self
+    }
+
+    /// Exit with `code` if the run failed.
+    #[must_use]
+    pub fn failure(mut self, code: u8) -> Self {
+        self.failure = code;
+        self
+    }
+
+    /// Exit with `code` if the run succeeded, but only as it tolerated at
+    /// least one step's failure. Set it to the success code to treat these
+    /// runs as successful, or to the failure code to treat them as failed.
+    #[must_use]
+    pub fn partial(mut self, code: u8) -> Self {
+        self.partial = code;
+        self
+    }
+}
+
+impl StepReport {
+    /// Returns when the step finished, if it started and ran to completion.
+    #[must_use]
+    pub fn finished(&self) -> Option<SystemTime> {
+        Some(self.started? + self.duration?)
+    }
+}
+
+/// Everything known about a run once it's over, successful or not: an
+/// entry for every step which ran or was skipped, and every output,
+/// including those of steps sharing a key. Get one with
+/// `ImperativeStepBuilder::execute_report`.
+#[derive(Debug)]
+pub struct ExecutionReport<O> {
+    /// The run's id. See `CurrentRun::run_id`.
+    pub run_id: u64,
+    pub metadata: RunMetadata,
+    /// The seed of the run's `RunRng`. See `ImperativeStepBuilder::seed`.
+    pub seed: u64,
+    /// The run's inputs' hash. See `ImperativeStepBuilder::input_hash`.
+    #[cfg(feature = "serde")]
+    pub input_hash: u64,
+    /// An entry for each step, in the order they finished.
+    pub steps: Vec<StepReport>,
+    /// Why the run failed, if it did, after redaction.
+    pub error: Option<Error>,
+    /// How long every step which ran took, across every attempt.
+    pub durations: DurationHistogram,
+    /// How long the steps in each group took, by the group's name or
+    /// position. See `StepReport::group`.
+    pub group_durations: BTreeMap<String, DurationHistogram>,
+    /// The environment variables snapshotted as the run started, after
+    /// redaction. See `ImperativeStepBuilder::snapshot_env`.
+    pub env: BTreeMap<String, String>,
+    /// The environment variables snapshotted for each group, by the group's
+    /// name or position. See `GroupBuilder::snapshot_env`.
+    pub group_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// The dependencies whose health checks failed as the run started. See
+    /// `ImperativeStepBuilder::health_check`.
+    pub unhealthy: Vec<String>,
+    /// Whether the run was drained with `RunController::drain`, so steps
+    /// which hadn't started yet were skipped.
+    pub drained: bool,
+    /// Every callback which panicked, oldest first, after redaction. See
+    /// `ImperativeStepBuilder::on_callback_panic`.
+    pub callback_failures: Vec<Error>,
+    // by step id and key, in the order `execute` keeps them
+    outputs: Vec<(usize, String, O)>,
+}
+
+impl<O> ExecutionReport<O> {
+    pub(super) fn new(run_id: u64, metadata: RunMetadata) -> Self {
+        Self {
+            run_id,
+            metadata,
+            seed: 0,
+            #[cfg(feature = "serde")]
+            input_hash: 0,
+            steps: vec![],
+            error: None,
+            durations: DurationHistogram::default(),
+            group_durations: BTreeMap::new(),
+            env: BTreeMap::new(),
+            group_env: BTreeMap::new(),
+            unhealthy: vec![],
+            drained: false,
+            callback_failures: vec![],
+            outputs: vec![],
+        }
+    }
+
+    /// Sets the run's steps, summarizing their durations.
+    pub(super) fn set_steps(&mut self, steps: Vec<StepReport>) {
+        for step in &steps {
+            if let Some(duration) = step.duration {
+                self.durations.record(duration);
+                self.group_durations
+                    .entry(step.group.clone())
+                    .or_default()
+                    .record(duration);
+            }
+        }
+        self.steps = steps;
+    }
+
+    pub(super) fn add_output(&mut self, id: usize, key: String, out: O) {
+        self.outputs.push((id, key, out));
+    }
+
+    /// Returns whether the run succeeded.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Returns the run's failure as a diagnostic which labels every step
+    /// which didn't succeed, if it failed.
+    #[cfg(feature = "miette")]
+    #[must_use]
+    pub fn diagnostic(&self) -> Option<super::RunDiagnostic> {
+        Some(super::RunDiagnostic::new(self, self.error.as_ref()?))
+    }
+
+    /// Returns the output with this key, as `execute`'s results would. Steps
+    /// may also be found by one of their deprecated names, with a warning.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&O> {
+        let key = self.resolve(key)?;
+        self.outputs
+            .iter()
+            .rev()
+            .find(|(_, k, _)| k == key)
+            .map(|(_, _, out)| out)
+    }
+
+    /// Returns the last entry of the step with this name or short id (see
+    /// `StepKey::short_id`), or one of its deprecated names, with a warning.
+    #[must_use]
+    pub fn step(&self, name: &str) -> Option<&StepReport> {
+        let last = |f: &dyn Fn(&StepReport) -> bool| self.steps.iter().rev().find(|s| f(s));
+        last(&|s| s.name == name || s.short_id == name).or_else(|| {
+            let step = last(&|s| s.aliases.iter().any(|a| a == name))?;
+            log_warn!("step '{name}' is deprecated, use '{}' instead", step.name);
+            Some(step)
+        })
+    }
+
+    /// Returns the output of this step, if it produced one which was kept.
+    #[must_use]
+    pub fn output(&self, step: &StepReport) -> Option<&O> {
+        self.outputs
+            .iter()
+            .find(|(id, _, _)| *id == step.id)
+            .map(|(_, _, out)| out)
+    }
+
+    /// Returns every step with this outcome, in the order they finished.
+    pub fn with_outcome(&self, outcome: StepOutcome) -> impl Iterator<Item = &StepReport> {
+        self.steps.iter().filter(move |s| s.outcome == outcome)
+    }
+
+    /// Returns the totals of every step's counters, or only those of the
+    /// steps in `group`, by its name or position. See `StepReport::group`.
+    #[must_use]
+    pub fn counters(&self, group: Option<&str>) -> BTreeMap<String, u64> {
+        let mut totals = BTreeMap::new();
+        let steps = self
+            .steps
+            .iter()
+            .filter(|s| group.is_none_or(|g| s.group == g));
+        for (name, n) in steps.flat_map(|s| &s.counters) {
+            let total: &mut u64 = totals.entry(name.clone()).or_default();
+            *total = total.saturating_add(*n);
+        }
+        totals
+    }
+
+    /// Returns every output by key, as `execute` does, losing any outputs
+    /// sharing a key with a later step.
+    #[must_use]
+    pub fn into_outputs(self) -> HashMap<String, O> {
+        self.outputs
+            .into_iter()
+            .map(|(_, key, out)| (key, out))
+            .collect()
+    }
+
+    /// Returns every output by key, as `execute_all` does, keeping outputs
+    /// which share a key in the order their steps were defined.
+    #[must_use]
+    pub fn into_all_outputs(mut self) -> HashMap<String, Vec<O>> {
+        self.outputs.sort_by_key(|(id, _, _)| *id);
+        let mut outputs: HashMap<_, Vec<_>> = HashMap::new();
+        for (_, key, out) in self.outputs {
+            outputs.entry(key).or_default().push(out);
+        }
+        outputs
+    }
+
+    /// Returns the exit code for how the run ended per `codes`, so a CLI's
+    /// `main` can end with `report.exit_code(ExitCodes::default())`.
+    #[must_use]
+    pub fn exit_code(&self, codes: ExitCodes) -> std::process::ExitCode {
+        let code = if !self.is_success() {
+            codes.failure
+        } else if self.with_outcome(StepOutcome::Failed).next().is_some() {
+            codes.partial
+        } else {
+            codes.success
+        };
+        code.into()
+    }
+
+    /// Returns each step's outcome and duration, to store and compare with
+    /// later runs. See `RunSummary::diff`.
+    #[must_use]
+    pub fn summary(&self) -> super::RunSummary {
+        self.into()
+    }
+
+    /// Returns the run's error if it failed, and otherwise `into_outputs`.
+    pub fn into_result(mut self) -> Result<HashMap<String, O>> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(self.into_outputs()),
+        }
+    }
+
+    /// Returns the run's error if it failed, and otherwise
+    /// `into_all_outputs`.
+    pub fn into_all_result(mut self) -> Result<HashMap<String, Vec<O>>> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(self.into_all_outputs()),
+        }
+    }
+
+    // Finds the key of the step which has `key` as a deprecated name,
+    // unless a step has it as its key.
+    fn resolve<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        if self.outputs.iter().any(|(_, k, _)| k == key) {
+            return Some(key);
+        }
+        let step = self
+            .steps
+            .iter()
+            .find(|s| s.aliases.iter().any(|a| a == key))?;
+        log_warn!("step '{key}' is deprecated, use '{}' instead", step.name);
+        Some(&step.key)
+    }
+}
+
+impl<O> std::ops::Index<&str> for ExecutionReport<O> {
+    type Output = O;
+
+    /// # Panics
+    /// If there's no output with this key.
+    fn index(&self, key: &str) -> &O {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no output for step '{key}'"))
+    }
+}
+
+/// Entries for every step in a run, as they finish.
+#[derive(Clone, Debug, Default)]
+pub(super) struct StepLog(Arc<Mutex<Vec<StepReport>>>);
+
+impl StepLog {
+    pub(super) fn record(&self, entry: StepReport) {
+        #[cfg(feature = "tracing")]
+        super::log::step_finished(&entry);
+        self.0
+            .lock()
+            .expect("imperat log mutex poisoned")
+            .push(entry);
+    }
+
+    /// Returns whether the step called `name` has finished without
+    /// succeeding, including being skipped as a step it needed didn't,
+    /// unless a fallback for it succeeded.
+    pub(super) fn failed(&self, name: &str) -> bool {
+        let log = self.0.lock().expect("imperat log mutex poisoned");
+        let failed = log
+            .iter()
+            .rev()
+            .find(|e| e.name == name)
+            .is_some_and(|e| match e.outcome {
+                StepOutcome::Succeeded => false,
+                StepOutcome::Skipped => e.error.is_some(),
+                StepOutcome::Failed | StepOutcome::Cancelled => true,
+            });
+        failed
+            && !log.iter().any(|e| {
+                e.fallback_for.as_deref() == Some(name) && e.outcome == StepOutcome::Succeeded
+            })
+    }
+
+    pub(super) fn take(&self) -> Vec<StepReport> {
+        std::mem::take(&mut *self.0.lock().expect("imperat log mutex poisoned"))
+    }
+}
+
+/// Each step's `Counters` in a run, by step id.
+#[derive(Clone, Default)]
+pub(super) struct StepCounters(Arc<Mutex<HashMap<usize, Counters>>>);
+
+impl StepCounters {
+    /// Returns the counters of the step with this id, shared by its attempts.
+    pub(super) fn get(&self, id: usize) -> Counters {
+        self.0
+            .lock()
+            .expect("imperat counters mutex poisoned")
+            .entry(id)
+            .or_default()
+            .clone()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{any::Any, collections::HashMap, time::Duration};
+
+use super::{AnyOutput, ExecutionReport, Result, StepOutcome, StepReport};
+
+/// The outputs of a successful run by key, as `execute` returns them, along
+/// with how each step fared. Get one with
+/// `ImperativeStepBuilder::execute_result`.
+#[derive(Debug)]
+pub struct RunResult<O> {
+    outputs: HashMap<String, O>,
+    steps: Vec<StepReport>,
+}
+
+impl<O> RunResult<O> {
+    /// Returns the output with this key.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&O> {
+        self.outputs.get(key)
+    }
+
+    /// Returns the output with this key if it's a `T`. For `AnyOutput`s,
+    /// it's the output they wrap, so runners built with `new_any_builder`
+    /// can query each step's own type.
+    #[must_use]
+    pub fn get_as<T: 'static>(&self, key: &str) -> Option<&T>
+    where
+        O: 'static,
+    {
+        let out: &dyn Any = self.outputs.get(key)?;
+        match out.downcast_ref::<AnyOutput>() {
+            Some(out) => out.downcast_ref(),
+            None => out.downcast_ref(),
+        }
+    }
+
+    /// Returns every step which succeeded, in the order they finished.
+    pub fn successes(&self) -> impl Iterator<Item = &StepReport> {
+        self.steps
+            .iter()
+            .filter(|s| s.outcome == StepOutcome::Succeeded)
+    }
+
+    /// Returns every step which failed or was cancelled, and so was
+    /// tolerated, in the order they finished.
+    pub fn failures(&self) -> impl Iterator<Item = &StepReport> {
+        self.steps
+            .iter()
+            .filter(|s| matches!(s.outcome, StepOutcome::Failed | StepOutcome::Cancelled))
+    }
+
+    /// Returns every step's entry, in the order they finished.
+    #[must_use]
+    pub fn steps(&self) -> &[StepReport] {
+        &self.steps
+    }
+
+    /// Returns how long the run's steps took, from when the first started
+    /// to when the last finished.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        let start = self.steps.iter().filter_map(|s| s.started).min();
+        let end = self.steps.iter().filter_map(StepReport::finished).max();
+        match (start, end) {
+            (Some(start), Some(end)) => end.duration_since(start).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns how many outputs there are.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns whether there are no outputs.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Returns every output by key, as `execute` does.
+    #[must_use]
+    pub fn into_map(self) -> HashMap<String, O> {
+        self.outputs
+    }
+}
+
+impl<O> std::ops::Index<&str> for RunResult<O> {
+    type Output = O;
+
+    /// # Panics
+    /// If there's no output with this key.
+    fn index(&self, key: &str) -> &O {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no output for step '{key}'"))
+    }
+}
+
+impl<O> ExecutionReport<O> {
+    /// Returns the run's error if it failed, and otherwise its outputs and
+    /// steps as a `RunResult`.
+    pub fn into_run_result(mut self) -> Result<RunResult<O>> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let steps = std::mem::take(&mut self.steps);
+        Ok(RunResult {
+            outputs: self.into_outputs(),
+            steps,
+        })
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use super::{Error, IntoStepOutcome};
+
+type RetryIfFn<O> = dyn Fn(Result<&O, &Error>) -> bool + Send + Sync;
+type RetryOnErrorFn = dyn Fn(&(dyn std::error::Error + 'static)) -> bool + Send + Sync;
+
+/// How failed steps are retried. Set one on a group with
+/// `GroupBuilder::retry_policy` or on a single step with `StepBuilder::retry`.
+///
+/// By default, every failure is retried immediately, up to the policy's
+/// number of retries. Retries also draw from the run-wide budget set with
+/// `ImperativeStepBuilder::retry_budget`.
+pub struct RetryPolicy<O> {
+    /// Number of additional attempts after the first failure.
+    pub(super) retries: usize,
+    backoff: Backoff,
+    jitter: bool,
+    retry_if: Option<Arc<RetryIfFn<O>>>,
+    retry_on_error: Option<Arc<RetryOnErrorFn>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Backoff {
+    Fixed(Duration),
+    Exponential { base: Duration, max: Duration },
+}
+
+// derive requires O: Clone
+impl<O> Clone for RetryPolicy<O> {
+    fn clone(&self) -> Self {
+        Self {
+            retries: self.retries,
+            backoff: self.backoff,
+            jitter: self.jitter,
+            retry_if: self.retry_if.clone(),
+            retry_on_error: self.retry_on_error.clone(),
+        }
+    }
+}
+
+impl<O> std::fmt::Debug for RetryPolicy<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .field("jitter", &self.jitter)
+            .field("retry_if", &self.retry_if.is_some())
+            .field("retry_on_error", &self.retry_on_error.is_some())
+            .finish()
+    }
+}
+
+impl<O> RetryPolicy<O> {
+    /// Retry failures up to `retries` more times, without waiting.
+    #[must_use]
+    pub fn new(retries: usize) -> Self {
+        Self {
+            retries,
+            backoff: Backoff::Fixed(Duration::ZERO),
+            jitter: true,
+            retry_if: None,
+            retry_on_error: None,
+        }
+    }
+
+    /// Wait `delay` before every retry.
+    #[must_use]
+    pub fn fixed(mut self, delay: Duration) -> Self {
+        self.backoff = Backoff::Fixed(delay);
+        self
+    }
+
+    /// Wait `base` before the first retry, doubling before each retry after
+    /// it up to `max`.
+    #[must_use]
+    pub fn exponential(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff = Backoff::Exponential { base, max };
+        self
+    }
+
+    /// Whether to spread each delay between half and one and a half times
+    /// its length, so steps hitting the same backend don't retry in
+    /// lockstep. Enabled by default.
+    #[must_use]
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Only retry failures for which `pred` returns true. It's passed the
+    /// step's output when the step returned a failed outcome, or the error
+    /// which ended the attempt, such as `CancelReason::Timeout`'s.
+    #[must_use]
+    pub fn retry_if(
+        mut self,
+        pred: impl Fn(Result<&O, &Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Some(Arc::new(pred));
+        self
+    }
+
+    /// Only retry failures with an error for which `classify` returns true,
+    /// such as network errors, so other errors fail immediately. It's
+    /// passed the error in the step's failed output, see
+    /// `IntoStepOutcome::error_ref`, or the error which ended the attempt,
+    /// such as `CancelReason::Timeout`'s. Failures without an error aren't
+    /// retried.
+    ///
+    /// May be combined with `retry_if`, in which case both must hold.
+    #[must_use]
+    pub fn retry_on_error(
+        mut self,
+        classify: impl Fn(&(dyn std::error::Error + 'static)) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_on_error = Some(Arc::new(classify));
+        self
+    }
+
+    /// Only retry failures caused by an error of type `E`, anywhere in
+    /// their chain of sources. See `retry_on_error`.
+    #[must_use]
+    pub fn retry_on<E: std::error::Error + 'static>(self) -> Self {
+        self.retry_on_error(|e| {
+            std::iter::successors(Some(e), |e| e.source()).any(<dyn std::error::Error>::is::<E>)
+        })
+    }
+
+    /// Returns whether this failure may be retried.
+    pub(super) fn should_retry(&self, res: Result<&O, &Error>) -> bool
+    where
+        O: IntoStepOutcome,
+    {
+        let classified = self.retry_on_error.as_ref().is_none_or(|classify| {
+            let error = match res {
+                Ok(out) => out.error_ref(),
+                Err(e) => Some(e as &(dyn std::error::Error + 'static)),
+            };
+            error.is_some_and(|e| classify(e))
+        });
+        classified && self.retry_if.as_ref().is_none_or(|pred| pred(res))
+    }
+
+    /// Returns the delay before `retry`, counting from 1.
+    pub(super) fn delay(&self, retry: usize) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { base, max } => {
+                let factor = 1u32.checked_shl(u32::try_from(retry - 1).unwrap_or(u32::MAX));
+                factor
+                    .and_then(|f| base.checked_mul(f))
+                    .map_or(max, |d| d.min(max))
+            }
+        };
+
+        if self.jitter { jitter(delay) } else { delay }
+    }
+}
+
+/// A count of retries shared by every group in a run. Once it's spent,
+/// failed steps are no longer retried regardless of their group's policy.
+#[derive(Clone, Debug, Default)]
+pub(super) struct RetryBudget(Option<Arc<AtomicUsize>>);
+
+impl RetryBudget {
+    pub(super) fn new(max_retries: usize) -> Self {
+        Self(Some(Arc::new(AtomicUsize::new(max_retries))))
+    }
+
+    /// Consumes a retry from the budget, returning whether one was available.
+    /// An unlimited budget always has retries available.
+    pub(super) fn take(&self) -> bool {
+        let Some(remaining) = &self.0 else {
+            return true;
+        };
+
+        remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+// Spreads a delay over [d/2, 3d/2) so steps hitting the same backend
+// don't retry in lockstep. RandomState is randomly seeded per instance,
+// which is plenty for this.
+fn jitter(d: Duration) -> Duration {
+    let nanos = u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+    if nanos == 0 {
+        return d;
+    }
+    let r = RandomState::new().hash_one(Instant::now());
+
+    Duration::from_nanos((nanos / 2).saturating_add(r % nanos))
+}
@@ -0,0 +1,123 @@
+use super::IntoStepOutcome;
+use crate::{Dep, TypeMap};
+use std::{collections::BTreeMap, path::PathBuf};
+
+type PublishFn = dyn FnOnce(&mut TypeMap) + Send;
+
+/// A step's output along with what else it produced: metrics, artifacts,
+/// and dependencies for later steps. Return one from a step instead of
+/// reaching for side channels; whether the step succeeded is decided by
+/// `value`, so wrap fallible values, as in `StepReturn<Result<T, E>>`.
+///
+/// Once the step succeeds, its metrics are added to its `Counters`, its
+/// artifacts are listed in `StepReport::artifacts`, and its published
+/// dependencies are bound for the steps after it. A failed step's are
+/// discarded.
+///
+/// ```
+/// # use imperat::{prelude::*, StepReturn};
+/// # struct Digest(String);
+/// let builder = new_imperative_builder().add_step("build", async || {
+///     StepReturn::new(true)
+///         .metric("files", 12)
+///         .artifact("binary", "target/release/app")
+///         .publish(Digest("abc123".to_string()))
+/// });
+/// ```
+pub struct StepReturn<T> {
+    pub value: T,
+    extras: StepExtras,
+}
+
+/// What a `StepReturn` carries besides its value. See
+/// `IntoStepOutcome::take_extras`.
+#[derive(Default)]
+pub struct StepExtras {
+    pub(super) metrics: BTreeMap<String, u64>,
+    pub(super) artifacts: BTreeMap<String, PathBuf>,
+    pub(super) publish: Vec<Box<PublishFn>>,
+}
+
+impl std::fmt::Debug for StepExtras {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StepExtras")
+            .field("metrics", &self.metrics)
+            .field("artifacts", &self.artifacts)
+            .field("publish", &self.publish.len())
+            .finish()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for StepReturn<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StepReturn")
+            .field("value", &self.value)
+            .field("extras", &self.extras)
+            .finish()
+    }
+}
+
+impl<T> StepReturn<T> {
+    /// Returns `value` without anything else.
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            extras: StepExtras::default(),
+        }
+    }
+
+    /// Add `n` to the step's counter `name`. See `Counters`.
+    #[must_use]
+    pub fn metric(mut self, name: &str, n: u64) -> Self {
+        let total = self.extras.metrics.entry(name.to_string()).or_default();
+        *total = total.saturating_add(n);
+        self
+    }
+
+    /// List the file at `path` as the step's artifact `name`.
+    #[must_use]
+    pub fn artifact(mut self, name: &str, path: impl Into<PathBuf>) -> Self {
+        self.extras.artifacts.insert(name.to_string(), path.into());
+        self
+    }
+
+    /// Bind `dep` as a `Dep<D>` for the steps after this one, replacing any
+    /// already bound. Steps added before it's bound can't depend on it
+    /// directly; they can request an `Option<Dep<D>>` instead.
+    #[must_use]
+    pub fn publish<D: Send + Sync + 'static>(mut self, dep: D) -> Self {
+        self.extras.publish.push(Box::new(move |tm| {
+            tm.bind(Dep::new(dep));
+        }));
+        self
+    }
+
+    /// Returns the value, discarding everything else.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> From<T> for StepReturn<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: IntoStepOutcome> IntoStepOutcome for StepReturn<T> {
+    fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        self.value.error()
+    }
+
+    fn success(&self) -> bool {
+        self.value.success()
+    }
+
+    fn error_ref(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.value.error_ref()
+    }
+
+    fn take_extras(&mut self) -> Option<StepExtras> {
+        Some(std::mem::take(&mut self.extras))
+    }
+}
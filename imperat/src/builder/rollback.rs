@@ -0,0 +1,79 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use super::Error;
+
+// Resolves to the rollback's error, if it failed.
+pub(super) type RollbackFuture = Pin<Box<dyn Future<Output = Result<(), Error>> + Send>>;
+
+/// Which steps are rolled back when a step fails. See
+/// `ImperativeStepBuilder::rollback_scope`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RollbackScope {
+    /// Roll back the completed steps in the failed step's group.
+    #[default]
+    Group,
+    /// Roll back every completed step in the run.
+    Run,
+}
+
+/// Rollbacks of steps which completed in a run, oldest first.
+#[derive(Clone, Default)]
+pub(super) struct Rollbacks(Arc<Mutex<Vec<Pending>>>);
+
+struct Pending {
+    group: String,
+    step: String,
+    // resolved once the step completes, so it sees the same dependencies
+    undo: Result<RollbackFuture, Error>,
+}
+
+impl Rollbacks {
+    /// Queues the rollback of a step in `group` which completed.
+    pub(super) fn push(&self, group: &str, step: &str, undo: Result<RollbackFuture, Error>) {
+        self.0
+            .lock()
+            .expect("rollbacks mutex poisoned")
+            .push(Pending {
+                group: group.to_string(),
+                step: step.to_string(),
+                undo,
+            });
+    }
+
+    /// Rolls back every completed step in `group`, or the whole run if
+    /// none, newest first, as `e` failed it. Every rollback runs even if
+    /// earlier ones fail, and their failures are returned with `e`.
+    pub(super) async fn roll_back(&self, group: Option<&str>, e: Error, verbose: bool) -> Error {
+        let pending = {
+            let mut all = self.0.lock().expect("rollbacks mutex poisoned");
+            let (pending, rest) = std::mem::take(&mut *all)
+                .into_iter()
+                .partition(|p| group.is_none_or(|g| g == p.group));
+            *all = rest;
+            pending
+        };
+
+        let mut failed = vec![];
+        for p in pending.into_iter().rev() {
+            if verbose {
+                eprintln!("rolling back step '{}'", p.step);
+            }
+            let res = match p.undo {
+                Ok(undo) => undo.await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                failed.push(e);
+            }
+        }
+
+        if failed.is_empty() {
+            e
+        } else {
+            Error::Rollback(Box::new(e), failed)
+        }
+    }
+}
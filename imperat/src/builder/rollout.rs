@@ -0,0 +1,50 @@
+/// Enables a step or group for a percentage of runs, to gradually roll out
+/// new steps. Whether a run is included depends only on its key, such as a
+/// tenant id or hostname, and the name of the step or group, so a key gets
+/// the same answer on every run while different steps are rolled out to
+/// different keys. Attach one with `StepBuilder::rollout` or
+/// `GroupBuilder::rollout`.
+///
+/// Each decision is recorded in the run's metadata as `rollout.<name>`,
+/// either `enabled` or `disabled`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rollout {
+    percent: u8,
+    key: String,
+}
+
+impl Rollout {
+    /// Enable for `percent` of keys, where anything above 100 is 100.
+    #[must_use]
+    pub fn new(percent: u8, key: &str) -> Self {
+        Self {
+            percent: percent.min(100),
+            key: key.to_string(),
+        }
+    }
+
+    /// Returns whether the step or group called `name` is enabled for this
+    /// rollout's key.
+    #[must_use]
+    pub fn enabled(&self, name: &str) -> bool {
+        let bucket = fnv1a([name.as_bytes(), &[0], self.key.as_bytes()].concat()) % 100;
+        bucket < u64::from(self.percent)
+    }
+}
+
+impl std::fmt::Display for Rollout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}% rollout for '{}'", self.percent, self.key)
+    }
+}
+
+/// Hashes `bytes` with FNV-1a as, unlike `DefaultHasher`, it's stable across
+/// processes and Rust versions.
+pub(super) fn fnv1a(bytes: impl IntoIterator<Item = impl std::borrow::Borrow<u8>>) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.into_iter().fold(OFFSET, |hash, b| {
+        (hash ^ u64::from(*b.borrow())).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,187 @@
+use std::{sync::Mutex, time::Duration};
+
+/// Decides when each step of a parallel group's phase starts. Set one with
+/// `GroupBuilder::scheduler`; `GroupBuilder::parallel` uses `Parallel`.
+///
+/// A step is only offered once every step it depends on has finished, and
+/// steps which depend on a failed step are skipped without being offered,
+/// so every scheduler runs a phase in dependency order. Whenever steps are
+/// ready or one finishes, `next` is asked which ready step to start, until
+/// it returns `None`.
+///
+/// ```
+/// use imperat::{ScheduledStep, Scheduler};
+///
+/// /// Starts the highest priority ready step, one at a time.
+/// struct ByPriority;
+///
+/// impl Scheduler for ByPriority {
+///     fn next(&self, ready: &[ScheduledStep<'_>], running: usize) -> Option<usize> {
+///         let (i, _) = ready.iter().enumerate().max_by_key(|(_, s)| s.priority)?;
+///         (running == 0).then_some(i)
+///     }
+/// }
+/// ```
+pub trait Scheduler: Send + Sync {
+    /// Returns the index in `ready` of the step to start next, if any, given
+    /// how many steps of the phase are `running`. If no step is running, the
+    /// first ready step is started regardless, so a phase always finishes.
+    fn next(&self, ready: &[ScheduledStep<'_>], running: usize) -> Option<usize>;
+
+    /// Called as each step this started finishes, with how long it ran for
+    /// across every attempt and whether it succeeded, so schedulers can
+    /// adapt to how steps are faring. Does nothing by default.
+    fn finished(&self, name: &str, duration: Duration, success: bool) {
+        let _ = (name, duration, success);
+    }
+}
+
+/// A step which is ready to start. See `Scheduler`.
+#[derive(Clone, Copy, Debug)]
+pub struct ScheduledStep<'a> {
+    pub name: &'a str,
+    pub tags: &'a [String],
+    /// See `StepBuilder::priority`.
+    pub priority: i32,
+    /// The step's position in its phase, after ordering by dependencies.
+    pub position: usize,
+}
+
+/// Starts one step at a time, in order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sequential;
+
+impl Scheduler for Sequential {
+    fn next(&self, _: &[ScheduledStep<'_>], running: usize) -> Option<usize> {
+        (running == 0).then_some(0)
+    }
+}
+
+/// Starts every step as soon as the steps it depends on have succeeded, so
+/// the phase runs as a graph of its dependencies.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Parallel;
+
+impl Scheduler for Parallel {
+    fn next(&self, _: &[ScheduledStep<'_>], _: usize) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// Like `Parallel`, but runs at most this many steps at once, starting
+/// waiting steps in order. Unlike `GroupBuilder::max_concurrency`, waiting
+/// steps aren't started until one finishes, so they can't preempt others.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounded(pub usize);
+
+impl Scheduler for Bounded {
+    fn next(&self, _: &[ScheduledStep<'_>], running: usize) -> Option<usize> {
+        (running < self.0).then_some(0)
+    }
+}
+
+/// Like `Bounded`, but adapts how many steps run at once to how they fare,
+/// so throughput stays high without tuning a limit by hand. The limit grows
+/// by about one step each time as many steps as it allows succeed, and
+/// halves whenever a step fails or runs longer than the latency target, if
+/// there is one (additive increase, multiplicative decrease).
+///
+/// ```
+/// # use imperat::{Adaptive, prelude::*};
+/// # use std::time::Duration;
+/// let builder = new_imperative_builder::<()>().new_group(|gb| {
+///     gb.scheduler(
+///         Adaptive::new(4)
+///             .max(32)
+///             .latency_target(Duration::from_millis(500)),
+///     )
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Adaptive {
+    min: usize,
+    max: usize,
+    target: Option<Duration>,
+    limit: Mutex<f64>,
+}
+
+impl Adaptive {
+    /// Starts out running at most `initial` steps at once, and never runs
+    /// fewer than one.
+    #[must_use]
+    pub fn new(initial: usize) -> Self {
+        Self {
+            min: 1,
+            max: usize::MAX,
+            target: None,
+            limit: Mutex::new(Self::as_f64(initial.max(1))),
+        }
+    }
+
+    /// Never run fewer than `min` steps at once.
+    #[must_use]
+    pub fn min(mut self, min: usize) -> Self {
+        self.min = min.max(1);
+        self.clamp();
+        self
+    }
+
+    /// Never run more than `max` steps at once.
+    #[must_use]
+    pub fn max(mut self, max: usize) -> Self {
+        self.max = max.max(1);
+        self.clamp();
+        self
+    }
+
+    /// Back off when a step runs longer than `target`, as well as when one
+    /// fails.
+    #[must_use]
+    pub fn latency_target(mut self, target: Duration) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Returns how many steps may currently run at once.
+    ///
+    /// # Panics
+    /// If the limit's mutex is poisoned.
+    #[must_use]
+    pub fn limit(&self) -> usize {
+        let limit = *self.limit.lock().expect("imperat adaptive mutex poisoned");
+        // Limits are kept between `min` and `max`, which fit in a `usize`.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let limit = limit.floor() as usize;
+        limit.clamp(self.min, self.max)
+    }
+
+    fn clamp(&mut self) {
+        let limit = self
+            .limit
+            .get_mut()
+            .expect("imperat adaptive mutex poisoned");
+        *limit = limit.clamp(Self::as_f64(self.min), Self::as_f64(self.max));
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn as_f64(n: usize) -> f64 {
+        n as f64
+    }
+}
+
+impl Scheduler for Adaptive {
+    fn next(&self, _: &[ScheduledStep<'_>], running: usize) -> Option<usize> {
+        (running < self.limit()).then_some(0)
+    }
+
+    fn finished(&self, _: &str, duration: Duration, success: bool) {
+        let mut limit = self.limit.lock().expect("imperat adaptive mutex poisoned");
+        let slow = self.target.is_some_and(|target| duration > target);
+        *limit = if success && !slow {
+            *limit + 1.0 / *limit
+        } else {
+            *limit / 2.0
+        }
+        .clamp(Self::as_f64(self.min), Self::as_f64(self.max));
+    }
+}
@@ -0,0 +1,125 @@
+use std::{pin::pin, sync::Mutex};
+use tokio::sync::{Notify, watch};
+
+/// Limits how many steps in a parallel group run at once. Waiting steps
+/// start in priority order, and a waiting step may preempt a running
+/// preemptible step with a lower priority.
+pub(super) struct Slots {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    free: usize,
+    next_id: usize,
+    // (id, priority) of steps waiting for a slot
+    waiting: Vec<(usize, i32)>,
+    running: Vec<Running>,
+}
+
+struct Running {
+    id: usize,
+    priority: i32,
+    preemptible: bool,
+    preempt: watch::Sender<bool>,
+}
+
+impl State {
+    // The next step to start is the highest priority, and then the first to wait.
+    fn is_next(&self, id: usize) -> bool {
+        let next = self
+            .waiting
+            .iter()
+            .max_by(|(a_id, a), (b_id, b)| a.cmp(b).then(b_id.cmp(a_id)));
+        next.is_some_and(|(next, _)| *next == id)
+    }
+}
+
+impl Slots {
+    pub(super) fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                free: limit.max(1),
+                ..State::default()
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Waits for a slot to run a step in. If none are free once this step
+    /// is next, the lowest priority preemptible step below `priority` is
+    /// asked to give up its slot.
+    pub(super) async fn acquire(&self, priority: i32, preemptible: bool) -> Slot<'_> {
+        let id = {
+            let mut state = self.state.lock().expect("imperat slots mutex poisoned");
+            let id = state.next_id;
+            state.next_id += 1;
+            state.waiting.push((id, priority));
+            id
+        };
+
+        loop {
+            let mut released = pin!(self.released.notified());
+            released.as_mut().enable();
+            {
+                let mut state = self.state.lock().expect("imperat slots mutex poisoned");
+                if state.is_next(id) {
+                    if state.free > 0 {
+                        state.free -= 1;
+                        state.waiting.retain(|(w, _)| *w != id);
+                        let (preempt, preempted) = watch::channel(false);
+                        state.running.push(Running {
+                            id,
+                            priority,
+                            preemptible,
+                            preempt,
+                        });
+                        return Slot {
+                            slots: self,
+                            id,
+                            preempted,
+                        };
+                    }
+
+                    let victim = state
+                        .running
+                        .iter()
+                        .filter(|r| r.preemptible && r.priority < priority)
+                        .min_by_key(|r| r.priority);
+                    if let Some(victim) = victim {
+                        victim.preempt.send_replace(true);
+                    }
+                }
+            }
+            released.await;
+        }
+    }
+}
+
+/// A running step's slot, released when dropped.
+pub(super) struct Slot<'a> {
+    slots: &'a Slots,
+    id: usize,
+    preempted: watch::Receiver<bool>,
+}
+
+impl Slot<'_> {
+    /// Resolves once this slot's step should give up its slot.
+    pub(super) async fn preempted(&self) {
+        let mut preempted = self.preempted.clone();
+        if preempted.wait_for(|p| *p).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.slots.state.lock() {
+            state.running.retain(|r| r.id != self.id);
+            state.free += 1;
+        }
+        self.slots.released.notify_waiters();
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Statistics about each step accumulated over many runs, such as its
+/// typical duration and how often it fails. Share one between builders with
+/// `ImperativeStepBuilder::record_stats` to spot steps trending slower or
+/// flakier. Every attempt of a step is recorded, including retries.
+///
+/// Only the most recent attempts of each step are kept; see `with_window`.
+#[derive(Clone, Debug)]
+pub struct StepStats {
+    samples: Arc<Mutex<HashMap<String, VecDeque<Sample>>>>,
+    window: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    elapsed: Duration,
+    success: bool,
+}
+
+impl Default for StepStats {
+    fn default() -> Self {
+        Self {
+            samples: Arc::default(),
+            window: 100,
+        }
+    }
+}
+
+impl StepStats {
+    /// Creates empty statistics which keep the last 100 attempts of each step.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the last `window` attempts of each step instead.
+    #[must_use]
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub(super) fn record(&self, step: &str, elapsed: Duration, success: bool) {
+        let mut samples = self.samples.lock().expect("imperat stats mutex poisoned");
+        let samples = samples.entry(step.to_string()).or_default();
+        if samples.len() == self.window {
+            samples.pop_front();
+        }
+        samples.push_back(Sample { elapsed, success });
+    }
+
+    /// Returns how many attempts of this step are recorded.
+    ///
+    /// # Panics
+    /// If the stats mutex is poisoned.
+    #[must_use]
+    pub fn attempts(&self, step: &str) -> usize {
+        self.with_samples(step, VecDeque::len).unwrap_or(0)
+    }
+
+    /// Returns the duration which `p` percent of this step's recorded
+    /// attempts finished within, or `None` if it has none.
+    ///
+    /// # Panics
+    /// If the stats mutex is poisoned.
+    #[must_use]
+    pub fn percentile(&self, step: &str, p: f64) -> Option<Duration> {
+        self.with_samples(step, |samples| {
+            let mut durations: Vec<_> = samples.iter().map(|s| s.elapsed).collect();
+            durations.sort_unstable();
+            // nearest rank
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let rank = ((p.clamp(0.0, 100.0) / 100.0) * durations.len() as f64).ceil() as usize;
+            durations[rank.clamp(1, durations.len()) - 1]
+        })
+    }
+
+    /// Returns this step's median duration. See `percentile`.
+    ///
+    /// # Panics
+    /// If the stats mutex is poisoned.
+    #[must_use]
+    pub fn p50(&self, step: &str) -> Option<Duration> {
+        self.percentile(step, 50.0)
+    }
+
+    /// Returns this step's 95th percentile duration. See `percentile`.
+    ///
+    /// # Panics
+    /// If the stats mutex is poisoned.
+    #[must_use]
+    pub fn p95(&self, step: &str) -> Option<Duration> {
+        self.percentile(step, 95.0)
+    }
+
+    /// Returns the fraction of this step's recorded attempts which failed or
+    /// timed out, from 0 to 1, or `None` if it has none.
+    ///
+    /// # Panics
+    /// If the stats mutex is poisoned.
+    #[must_use]
+    pub fn failure_rate(&self, step: &str) -> Option<f64> {
+        self.with_samples(step, |samples| {
+            let failed = samples.iter().filter(|s| !s.success).count();
+            #[allow(clippy::cast_precision_loss)]
+            let rate = failed as f64 / samples.len() as f64;
+            rate
+        })
+    }
+
+    fn with_samples<T>(&self, step: &str, f: impl FnOnce(&VecDeque<Sample>) -> T) -> Option<T> {
+        let samples = self.samples.lock().expect("imperat stats mutex poisoned");
+        samples.get(step).filter(|s| !s.is_empty()).map(f)
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use super::events::{EventSender, PipelineEvent};
+
+/// A snapshot of a run's progress. See `ImperativeStepBuilder::status_handle`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunStatus {
+    /// Steps which haven't started yet.
+    pub pending: usize,
+    /// Steps which are running, including any waiting to retry.
+    pub running: usize,
+    pub succeeded: usize,
+    /// Steps which didn't run because their condition wasn't met, or
+    /// because the run ended before reaching them.
+    pub skipped: usize,
+    /// Steps which failed, timed out, panicked, or were cancelled.
+    pub failed: usize,
+    /// The names of the running steps, in the order they started.
+    pub current: Vec<String>,
+    /// Progress reported by running steps through `Progress`, by name.
+    pub progress: BTreeMap<String, StepProgress>,
+}
+
+/// How far through its work a running step is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepProgress {
+    /// Items processed so far.
+    pub done: u64,
+    /// Items to process in total, if known.
+    pub total: Option<u64>,
+}
+
+impl RunStatus {
+    /// Returns whether every step has finished, including once a run which
+    /// failed early has ended.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.pending == 0 && self.running == 0
+    }
+}
+
+/// A cheap handle to a run's progress which can be sent to another task,
+/// such as one serving health endpoints for a background pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct StatusHandle(Arc<Mutex<RunStatus>>, EventSender);
+
+impl StatusHandle {
+    /// Returns the run's current progress.
+    ///
+    /// # Panics
+    /// If the status mutex is poisoned.
+    #[must_use]
+    pub fn snapshot(&self) -> RunStatus {
+        self.lock().clone()
+    }
+
+    pub(super) fn add_pending(&self, steps: usize) {
+        self.lock().pending += steps;
+    }
+
+    pub(super) fn start(&self, step: &str) {
+        let mut status = self.lock();
+        status.pending = status.pending.saturating_sub(1);
+        status.running += 1;
+        status.current.push(step.to_string());
+    }
+
+    pub(super) fn finish(&self, step: &str, success: bool) {
+        let mut status = self.lock();
+        status.running -= 1;
+        if let Some(i) = status.current.iter().position(|s| s == step) {
+            status.current.remove(i);
+        }
+        status.progress.remove(step);
+        if success {
+            status.succeeded += 1;
+        } else {
+            status.failed += 1;
+        }
+    }
+
+    /// Marks a step as failed whether or not it started.
+    pub(super) fn cancel(&self, step: &str) {
+        let running = self.lock().current.iter().any(|s| s == step);
+        if running {
+            self.finish(step, false);
+        } else {
+            self.skip();
+        }
+    }
+
+    pub(super) fn skip_unmet(&self) {
+        let mut status = self.lock();
+        status.pending = status.pending.saturating_sub(1);
+        status.skipped += 1;
+    }
+
+    pub(super) fn skip(&self) {
+        let mut status = self.lock();
+        status.pending = status.pending.saturating_sub(1);
+        status.failed += 1;
+    }
+
+    /// Ends the run: steps which never started are counted as skipped, and
+    /// any left running, such as ones dropped when the run failed, as failed.
+    pub(super) fn end(&self) {
+        let mut status = self.lock();
+        status.skipped += std::mem::take(&mut status.pending);
+        status.failed += std::mem::take(&mut status.running);
+        status.current.clear();
+        status.progress.clear();
+    }
+
+    pub(crate) fn update_progress(&self, step: &str, f: impl FnOnce(&mut StepProgress)) {
+        let mut status = self.lock();
+        // Steps may hold onto their handle after they've finished.
+        if status.current.iter().any(|s| s == step) {
+            let progress = status.progress.entry(step.to_string()).or_default();
+            f(progress);
+            let progress = *progress;
+            self.1.send(|| PipelineEvent::StepProgress {
+                name: step.to_string(),
+                progress,
+            });
+        }
+    }
+
+    /// Forwards progress to `events` as well.
+    pub(super) fn set_events(&mut self, events: EventSender) {
+        self.1 = events;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RunStatus> {
+        self.0.lock().expect("imperat status mutex poisoned")
+    }
+}
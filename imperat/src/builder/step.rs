@@ -1,16 +1,292 @@
-use super::{Error, IntoStepOutcome, Result};
-use crate::{FromTypeMap, TypeMap, prelude::*};
-use futures::{StreamExt, stream::FuturesOrdered};
+use super::{
+    Approval, CancelReason, Checkpoint, Checkpointer, Error, IntoStepOutcome, Result, RunContext,
+    Skipped,
+    backpressure::OutputBudget,
+    bindings::BindingGraph,
+    budget::StepBudget,
+    events::PipelineEvent,
+    executor::ExecutorHandle,
+    failpoints::fail_point,
+    flight::SingleFlight,
+    keys::{self, KeyStrategy, StepKey},
+    log::log_warn,
+    plan::{GroupPlan, StepPlan},
+    report::{StepOutcome, StepReport},
+    retry::RetryPolicy,
+    rollback::{RollbackFuture, RollbackScope},
+    rollout::Rollout,
+    scheduler::{Parallel, ScheduledStep, Scheduler, Sequential},
+    slots::Slots,
+    stats::StepStats,
+    status::StatusHandle,
+    tags::TagFilter,
+};
+use crate::{
+    CurrentRun, DepInfo, DynStep, FromTypeMap, ResolutionCache, TypeMap, callable::WithArgs,
+    prelude::*,
+};
+use futures::{
+    FutureExt, StreamExt,
+    future::{self, Either},
+    stream::FuturesUnordered,
+};
 use std::{
-    collections::HashMap,
-    pin::Pin,
+    any::{Any, TypeId},
+    collections::{BTreeMap, HashMap},
+    panic::AssertUnwindSafe,
+    pin::{Pin, pin},
     sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
 
-/// A resolved step which is ready to be ran.
+type StepFuture<O> = Pin<Box<dyn Future<Output = O> + Send>>;
+// Fails with the first parameter which couldn't be resolved, if known.
+type StepFn<O> = dyn Fn(&TypeMap, &ResolutionCache) -> std::result::Result<StepFuture<O>, Option<(usize, DepInfo)>>
+    + Send
+    + Sync;
+
+/// A step which is ready to be ran. Its dependencies are resolved
+/// each time it's called, so a step may be ran more than once.
 pub struct Step<O> {
     name: String,
-    fut: Pin<Box<dyn Future<Output = O>>>,
+    // this step's key in the results
+    key: String,
+    // this step's position in the run
+    id: usize,
+    deps: Vec<DepInfo>,
+    call: Box<StepFn<O>>,
+    opts: StepOptions<O>,
+}
+
+// Resolves a condition, which yields the step's output if it's skipped.
+type ConditionFn<O> = dyn Fn(
+        &TypeMap,
+    ) -> std::result::Result<
+        Pin<Box<dyn Future<Output = Option<O>> + Send>>,
+        Option<(usize, DepInfo)>,
+    > + Send
+    + Sync;
+// Resolves a rollback once its step completes.
+type UndoFn =
+    dyn Fn(&TypeMap) -> std::result::Result<RollbackFuture, Option<(usize, DepInfo)>> + Send + Sync;
+type ReduceFn<O> = dyn Fn(O) -> O + Send + Sync;
+type OutputSizeFn<O> = dyn Fn(&O) -> usize + Send + Sync;
+type PublishFn<O> = dyn Fn(&O, &mut TypeMap) + Send + Sync;
+// Binds a value and returns how to restore what it replaced.
+type ScopedFn = dyn Fn(&mut TypeMap) -> Box<dyn FnOnce(&mut TypeMap) + Send> + Send + Sync;
+
+/// Options which apply to a single step. Unset options fall back to
+/// the step's group, and then to the builder.
+struct StepOptions<O> {
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy<O>>,
+    aliases: Vec<String>,
+    tags: Vec<String>,
+    reduce: Option<Box<ReduceFn<O>>>,
+    phase: Option<Phase>,
+    binds: Option<DepInfo>,
+    priority: i32,
+    preemptible: bool,
+    scoped: Vec<Box<ScopedFn>>,
+    budget: StepBudget,
+    output_size: Option<Box<OutputSizeFn<O>>>,
+    publish: Option<Box<PublishFn<O>>>,
+    after: Vec<String>,
+    // steps which must not have failed, and the output if one has
+    requires: Vec<String>,
+    // the step this one runs in place of if it fails
+    fallback_for: Option<String>,
+    degradable: bool,
+    unmet: Option<fn() -> O>,
+    rollout: Option<Rollout>,
+    condition: Option<Box<ConditionFn<O>>>,
+    rollback: Option<Box<UndoFn>>,
+    panic: Option<PanicPolicy>,
+    approval: bool,
+}
+
+impl<O> Default for StepOptions<O> {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retry: None,
+            aliases: vec![],
+            tags: vec![],
+            reduce: None,
+            phase: None,
+            binds: None,
+            priority: 0,
+            preemptible: false,
+            scoped: vec![],
+            budget: StepBudget::default(),
+            output_size: None,
+            publish: None,
+            after: vec![],
+            requires: vec![],
+            fallback_for: None,
+            degradable: false,
+            unmet: None,
+            rollout: None,
+            condition: None,
+            rollback: None,
+            panic: None,
+            approval: false,
+        }
+    }
+}
+
+/// What happens when a step in a group panics. See `GroupBuilder::on_panic`
+/// and `StepBuilder::on_panic`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The run fails with `Error::Panicked`.
+    #[default]
+    Abort,
+    /// The step is left out of the results and the run continues, as if
+    /// its failure was tolerated.
+    Tolerate,
+    /// The step is retried under the group's retry policy like any other
+    /// failure. If it's out of retries, the run fails with `Error::Panicked`.
+    Retry,
+}
+
+/// What ends a step's attempt if it runs too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Limit {
+    Timeout,
+    Deadline,
+    Budget,
+}
+
+/// A label for a set of steps in a group which must all finish before
+/// any step in a later phase starts. Numbered phases run in numeric order,
+/// followed by labeled phases in the order each label first appears in the
+/// group. Steps without a phase are in phase 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Number(u32),
+    Label(String),
+}
+
+impl From<u32> for Phase {
+    fn from(n: u32) -> Self {
+        Phase::Number(n)
+    }
+}
+
+impl From<&str> for Phase {
+    fn from(label: &str) -> Self {
+        Phase::Label(label.to_string())
+    }
+}
+
+impl From<String> for Phase {
+    fn from(label: String) -> Self {
+        Phase::Label(label)
+    }
+}
+
+impl<O> std::fmt::Debug for Step<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Step")
+            .field("name", &self.name)
+            .field(
+                "deps",
+                &self.deps.iter().map(|d| d.name).collect::<Vec<_>>(),
+            )
+            .field("binds", &self.opts.binds.map(|d| d.name))
+            .field("aliases", &self.opts.aliases)
+            .field("tags", &self.opts.tags)
+            .field("after", &self.opts.after)
+            .field("requires", &self.opts.requires)
+            .field("fallback_for", &self.opts.fallback_for)
+            .field("degradable", &self.opts.degradable)
+            .field("phase", &self.opts.phase)
+            .field("timeout", &self.opts.timeout)
+            .field("retry", &self.opts.retry)
+            .field("budget", &self.opts.budget)
+            .field("priority", &self.opts.priority)
+            .field("preemptible", &self.opts.preemptible)
+            .field("rollout", &self.opts.rollout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats as the step's name, its dependencies, what it binds, and any
+/// options set on it, such as `deploy(Dep<Client>) -> Dep<Release> [timeout 5s]`.
+impl<O> std::fmt::Display for Step<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let deps: Vec<_> = self.deps.iter().map(|d| short_type_name(d.name)).collect();
+        write!(f, "{}({})", self.name, deps.join(", "))?;
+        if let Some(binds) = &self.opts.binds {
+            write!(f, " -> {}", short_type_name(binds.name))?;
+        }
+
+        let o = &self.opts;
+        let mut opts = vec![];
+        match &o.phase {
+            Some(Phase::Number(n)) => opts.push(format!("phase {n}")),
+            Some(Phase::Label(l)) => opts.push(format!("phase '{l}'")),
+            None => {}
+        }
+        if !o.after.is_empty() {
+            opts.push(format!("after {}", o.after.join(", ")));
+        }
+        if !o.requires.is_empty() {
+            opts.push(format!("requires {}", o.requires.join(", ")));
+        }
+        if let Some(primary) = &o.fallback_for {
+            opts.push(format!("fallback for {primary}"));
+        }
+        if !o.aliases.is_empty() {
+            opts.push(format!("formerly {}", o.aliases.join(", ")));
+        }
+        if !o.tags.is_empty() {
+            opts.push(format!("tagged {}", o.tags.join(", ")));
+        }
+        if let Some(limit) = o.timeout {
+            opts.push(format!("timeout {limit:?}"));
+        }
+        if let Some(retry) = &o.retry {
+            opts.push(format!("{} retries", retry.retries));
+        }
+        if o.budget != StepBudget::default() {
+            opts.push("budgeted".to_string());
+        }
+        if o.priority != 0 {
+            opts.push(format!("priority {}", o.priority));
+        }
+        if o.preemptible {
+            opts.push("preemptible".to_string());
+        }
+        if !o.scoped.is_empty() {
+            opts.push(format!("{} scoped deps", o.scoped.len()));
+        }
+        if let Some(rollout) = &o.rollout {
+            opts.push(rollout.to_string());
+        }
+        if o.condition.is_some() {
+            opts.push("conditional".to_string());
+        }
+        if o.degradable {
+            opts.push("degradable".to_string());
+        }
+        if o.rollback.is_some() {
+            opts.push("rollback".to_string());
+        }
+        if let Some(policy) = o.panic {
+            opts.push(format!("{policy:?} panics").to_lowercase());
+        }
+        write_options(f, &opts)
+    }
+}
+
+// Writes ` [a, b]`, or nothing without options.
+fn write_options(f: &mut std::fmt::Formatter<'_>, opts: &[String]) -> std::fmt::Result {
+    if opts.is_empty() {
+        return Ok(());
+    }
+    write!(f, " [{}]", opts.join(", "))
 }
 
 impl<O> Step<O> {
@@ -18,27 +294,306 @@ impl<O> Step<O> {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Returns every dependency this step is injected with, in argument order.
+    pub fn dependencies(&self) -> &[DepInfo] {
+        &self.deps
+    }
+
+    /// Returns the deprecated names this step was previously known by.
+    pub fn aliases(&self) -> &[String] {
+        &self.opts.aliases
+    }
+
+    /// Returns the labels this step was tagged with, such as `setup`.
+    pub fn tags(&self) -> &[String] {
+        &self.opts.tags
+    }
+
+    /// Returns the dependency this step binds its output to, if any.
+    pub fn binds(&self) -> Option<&DepInfo> {
+        self.opts.binds.as_ref()
+    }
+
+    /// Returns the names of the steps this step explicitly runs after.
+    pub fn depends_on(&self) -> &[String] {
+        &self.opts.after
+    }
+
+    /// Returns whether this step must run after `other`, either explicitly
+    /// or because it depends on what `other` binds.
+    fn runs_after(&self, other: &Step<O>) -> bool {
+        self.opts.after.contains(&other.name)
+            || self.is_fallback_for(other)
+            || other
+                .binds()
+                .is_some_and(|b| self.deps.iter().any(|d| d.id == b.id))
+    }
+
+    /// Returns this step's output if it should be skipped, either as a step
+    /// it requires didn't succeed, with why, or as the step it's a fallback
+    /// for didn't fail.
+    fn check_unmet(&self, run: &RunContext) -> Option<(O, Option<Error>)> {
+        let unmet = self.opts.unmet?;
+        if let Some(required) = self.opts.requires.iter().find(|r| run.log.failed(r)) {
+            if run.settings.verbose {
+                eprintln!(
+                    "skipping step '{}' as '{required}' didn't succeed",
+                    self.name
+                );
+            }
+            let e = Error::Skipped(self.name.clone(), required.clone());
+            return Some((unmet(), Some(e)));
+        }
+        let primary = self
+            .opts
+            .fallback_for
+            .as_ref()
+            .filter(|p| !run.log.failed(p))?;
+        if run.settings.verbose {
+            eprintln!("skipping step '{}' as '{primary}' didn't fail", self.name);
+        }
+        Some((unmet(), None))
+    }
+
+    /// Returns the first step this step depends on in an `earlier` phase of
+    /// its group which didn't succeed, if any.
+    fn failed_dependency(&self, earlier: &[String], run: &RunContext) -> Option<&String> {
+        self.opts.after.iter().find(|&name| {
+            earlier.contains(name)
+                && self.opts.fallback_for.as_ref() != Some(name)
+                && run.log.failed(name)
+        })
+    }
+
+    /// Returns whether this step only runs if `other` fails. See
+    /// `StepBuilder::fallback_for`.
+    fn is_fallback_for(&self, other: &Step<O>) -> bool {
+        self.opts.fallback_for.as_ref() == Some(&other.name)
+    }
+
+    /// Returns the phase this step was placed in, if any.
+    pub fn phase(&self) -> Option<&Phase> {
+        self.opts.phase.as_ref()
+    }
+
+    /// Binds this step's scoped dependencies into `tm` only while `f`
+    /// resolves the step from it.
+    fn with_scoped<R>(&self, tm: &mut TypeMap, f: impl FnOnce(&TypeMap) -> R) -> R {
+        let restore: Vec<_> = self.opts.scoped.iter().map(|bind| bind(tm)).collect();
+        let res = f(tm);
+        for restore in restore.into_iter().rev() {
+            restore(tm);
+        }
+
+        res
+    }
+
+    /// Describes why this step's arguments couldn't be resolved.
+    fn missing(&self, missing: Option<(usize, DepInfo)>) -> Error {
+        match missing {
+            Some((index, dep)) => {
+                Error::MissingParam(self.name.clone(), index, short_type_name(dep.name))
+            }
+            None => Error::DepResolution(self.name.clone()),
+        }
+    }
+
+    /// Returns the size of `out`, per `StepBuilder::output_size`.
+    fn output_size(&self, out: &O) -> usize {
+        match &self.opts.output_size {
+            Some(size) => size(out),
+            None => std::mem::size_of_val(out),
+        }
+    }
+
+    /// Fails outputs larger than this step's budget allows.
+    fn check_output_size(&self, out: O) -> Result<O> {
+        let Some(limit) = self.opts.budget.output_size else {
+            return Ok(out);
+        };
+        let size = self.output_size(&out);
+        if size > limit {
+            return Err(Error::BudgetExceeded(
+                self.name.clone(),
+                format!("output of size {size} is larger than {limit}"),
+            ));
+        }
+
+        Ok(out)
+    }
+
+    /// Returns what's kept of this step's output in the results.
+    fn reduce(&self, out: O) -> O {
+        match &self.opts.reduce {
+            Some(reduce) => reduce(out),
+            None => out,
+        }
+    }
+}
+
+/// Values which can only be injected while a specific step runs. They're
+/// bound into the type map right before the step's arguments are resolved.
+struct StepScope {
+    info: StepInfo,
+    attempt: Attempt,
+    spawner: StepSpawner,
+    cancel: CancelHandle,
+    cancelled: Cancelled,
+    progress: Progress,
+    counters: Counters,
+}
+
+impl StepScope {
+    fn new(
+        step: &str,
+        tags: &[&str],
+        attempt: usize,
+        run_cancel: &CancelHandle,
+        status: &StatusHandle,
+        counters: Counters,
+        executor: &ExecutorHandle,
+    ) -> Self {
+        let cancel = CancelHandle::default();
+        Self {
+            info: StepInfo::new(step, tags),
+            attempt: Attempt(attempt),
+            spawner: StepSpawner::new(step, executor.clone()),
+            progress: Progress::new(step, status),
+            counters,
+            cancelled: Cancelled::new(run_cancel, &cancel),
+            cancel,
+        }
+    }
+
+    /// Describes this step for `current_run`.
+    fn current(&self, run: &RunContext) -> CurrentRun {
+        CurrentRun::new(
+            run.id,
+            self.info.clone(),
+            self.attempt,
+            run.metadata.clone(),
+        )
+    }
+
+    fn bind(&self, tm: &mut TypeMap) {
+        tm.bind(self.info.clone());
+        tm.bind(self.attempt);
+        tm.bind(self.spawner.clone());
+        tm.bind(self.cancelled.clone());
+        tm.bind(self.progress.clone());
+        tm.bind(self.counters.clone());
+    }
+
+    /// Runs tasks spawned by the step in `span`.
+    #[cfg(feature = "tracing")]
+    fn in_span(mut self, span: &tracing::Span) -> Self {
+        self.spawner = self.spawner.in_span(span);
+        self
+    }
+
+    /// Cleans up after the step has finished. Anything still holding
+    /// its `Cancelled`, such as spawned tasks, sees it as cancelled.
+    fn finish(self) {
+        self.cancel.cancel();
+        self.spawner.abort_all();
+    }
 }
 
 /// Options which apply to a group and its steps.
-struct GroupOptions<O> {
+pub(super) struct GroupOptions<O> {
+    name: Option<String>,
     parallel: bool,
-    tolerate_failure: bool,
+    deterministic: bool,
+    cpu_bound: bool,
+    max_concurrency: Option<usize>,
+    panic: PanicPolicy,
+    tolerate_failure: Option<bool>,
+    retry: Option<RetryPolicy<O>>,
+    step_timeout: Option<Duration>,
+    rollout: Option<Rollout>,
+    history: Option<StepStats>,
     callbacks: Vec<CallbackKind<O>>,
+    tags: Vec<String>,
+    scheduler: Option<Arc<dyn Scheduler>>,
+    env: Vec<String>,
+    output_budget: Option<OutputBudget>,
+    escalate: Option<Arc<EscalateFn<O>>>,
+    success: GroupSuccess,
+}
+
+/// When a group succeeds. See `GroupBuilder::any_of`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum GroupSuccess {
+    #[default]
+    AllOf,
+    AnyOf,
+}
+
+impl<O> Clone for GroupOptions<O> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            parallel: self.parallel,
+            deterministic: self.deterministic,
+            cpu_bound: self.cpu_bound,
+            max_concurrency: self.max_concurrency,
+            panic: self.panic,
+            tolerate_failure: self.tolerate_failure,
+            retry: self.retry.clone(),
+            step_timeout: self.step_timeout,
+            rollout: self.rollout.clone(),
+            history: self.history.clone(),
+            callbacks: self.callbacks.clone(),
+            tags: self.tags.clone(),
+            scheduler: self.scheduler.clone(),
+            env: self.env.clone(),
+            output_budget: self.output_budget.clone(),
+            escalate: self.escalate.clone(),
+            success: self.success,
+        }
+    }
 }
 
 impl<O> Default for GroupOptions<O> {
     fn default() -> Self {
         Self {
+            name: None,
             parallel: false,
-            tolerate_failure: false,
+            deterministic: false,
+            cpu_bound: false,
+            max_concurrency: None,
+            panic: PanicPolicy::default(),
+            tolerate_failure: None,
+            retry: None,
+            step_timeout: None,
+            rollout: None,
+            history: None,
             callbacks: vec![],
+            tags: vec![],
+            scheduler: None,
+            env: vec![],
+            output_budget: None,
+            escalate: None,
+            success: GroupSuccess::default(),
         }
     }
 }
 
-pub type BeforeCallbackFn<O> = dyn Fn(&Step<O>);
-pub type AfterCallbackFn<O> = dyn Fn(&str, &O);
+pub type BeforeCallbackFn<O> = dyn Fn(&Step<O>) + Send + Sync;
+// Whether a tolerated failure should fail its group anyway.
+type EscalateFn<O> = dyn Fn(&str, &O) -> bool + Send + Sync;
+pub type AfterCallbackFn<O> = dyn Fn(&str, &O) + Send + Sync;
+pub type RetryCallbackFn = dyn Fn(&str, usize) + Send + Sync;
+pub type RedactOutputFn<O> = dyn Fn(O) -> O + Send + Sync;
+pub type StepResultFn<O> = dyn Fn(&str, StepOutcome, Option<&O>) + Send + Sync;
+pub type StepErrorFn = dyn Fn(&str, &(dyn std::error::Error + 'static)) + Send + Sync;
+pub type GroupStartFn = dyn Fn(&str) + Send + Sync;
+pub type GroupEndFn = dyn Fn(&str, Option<&Error>) + Send + Sync;
+pub type CallbackFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+pub type AsyncBeforeCallbackFn<O> = dyn Fn(&Step<O>) -> CallbackFuture + Send + Sync;
+pub type AsyncAfterCallbackFn<O> =
+    dyn Fn(&str, StepOutcome, Option<&O>) -> CallbackFuture + Send + Sync;
 
 /// A variant of a callback on a group.
 pub(super) enum CallbackKind<O> {
@@ -47,6 +602,26 @@ pub(super) enum CallbackKind<O> {
     /// Called after the step executes. Is passed the step's
     /// name and result.
     AfterStep(Arc<AfterCallbackFn<O>>),
+    /// Called before a step is retried. Is passed the step's name and
+    /// the attempt about to run.
+    Retry(Arc<RetryCallbackFn>),
+    /// Called on every step's output before any other callback sees it.
+    RedactOutput(Arc<RedactOutputFn<O>>),
+    /// Called once a step is done, after any retries. Is passed the step's
+    /// name, how it ended, and its output if it returned one.
+    StepResult(Arc<StepResultFn<O>>),
+    /// Called once a step is done if it failed with an error, after any
+    /// retries. Is passed the step's name and error.
+    StepError(Arc<StepErrorFn>),
+    /// Awaited before the step executes, after any `BeforeStep`.
+    BeforeStepAsync(Arc<AsyncBeforeCallbackFn<O>>),
+    /// Awaited once a step is done, like `StepResult`.
+    AfterStepAsync(Arc<AsyncAfterCallbackFn<O>>),
+    /// Called before a group with steps runs. Is passed the group's label.
+    GroupStart(Arc<GroupStartFn>),
+    /// Called once a group with steps is done. Is passed the group's label
+    /// and its error, if it failed.
+    GroupEnd(Arc<GroupEndFn>),
 }
 
 // derive fails for some reason
@@ -55,6 +630,14 @@ impl<O> Clone for CallbackKind<O> {
         match self {
             CallbackKind::BeforeStep(cb) => CallbackKind::BeforeStep(cb.clone()),
             CallbackKind::AfterStep(cb) => CallbackKind::AfterStep(cb.clone()),
+            CallbackKind::Retry(cb) => CallbackKind::Retry(cb.clone()),
+            CallbackKind::RedactOutput(cb) => CallbackKind::RedactOutput(cb.clone()),
+            CallbackKind::StepResult(cb) => CallbackKind::StepResult(cb.clone()),
+            CallbackKind::StepError(cb) => CallbackKind::StepError(cb.clone()),
+            CallbackKind::BeforeStepAsync(cb) => CallbackKind::BeforeStepAsync(cb.clone()),
+            CallbackKind::AfterStepAsync(cb) => CallbackKind::AfterStepAsync(cb.clone()),
+            CallbackKind::GroupStart(cb) => CallbackKind::GroupStart(cb.clone()),
+            CallbackKind::GroupEnd(cb) => CallbackKind::GroupEnd(cb.clone()),
         }
     }
 }
@@ -63,48 +646,340 @@ impl<O> Clone for CallbackKind<O> {
 /// of steps. Subgroups allow specific steps to have some behavior.
 pub struct Group<O> {
     tm: Arc<Mutex<TypeMap>>,
+    // dependencies only this group's steps see, shadowing `tm`
+    deps: TypeMap,
+    // arguments its steps share, resolved from `deps` over `tm`
+    resolved: ResolutionCache,
     steps: Vec<Step<O>>,
+    // this group's name, or its position if unnamed, once assigned
+    label: String,
     // errors accumulated at build time
-    errors: Arc<Mutex<Vec<Error>>>,
+    errors: Vec<Error>,
+    // steps which weren't added as their dependencies couldn't be resolved,
+    // with those dependencies
+    rejected: Vec<(Step<O>, Vec<DepInfo>)>,
+    // records completed steps; see `ImperativeStepBuilder::checkpoint`
+    checkpointer: Option<Arc<dyn Checkpointer<O>>>,
+    // outputs of steps which completed in an earlier run, by step id
+    resumed: Mutex<HashMap<usize, O>>,
+    // dependencies which steps added so far will bind once they succeed
+    bindings: Bindings,
     opts: GroupOptions<O>,
+    // groups which run after this one, flattened once it's added to a builder
+    children: Vec<Group<O>>,
+    // whether every failed step fails the group together, as `Error::Build`,
+    // rather than only the first; set for preflight checks
+    collect_errors: bool,
+}
+
+pub(super) type Bindings = Arc<Mutex<BindingGraph>>;
+
+impl<O> std::fmt::Debug for Group<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let o = &self.opts;
+        f.debug_struct("Group")
+            .field("name", &o.name)
+            .field("parallel", &o.parallel)
+            .field("deterministic", &o.deterministic)
+            .field("cpu_bound", &o.cpu_bound)
+            .field("max_concurrency", &o.max_concurrency)
+            .field("panic", &o.panic)
+            .field("tolerate_failure", &o.tolerate_failure)
+            .field("retry", &o.retry)
+            .field("step_timeout", &o.step_timeout)
+            .field("rollout", &o.rollout)
+            .field("order_by_history", &o.history.is_some())
+            .field("tags", &o.tags)
+            .field("custom_scheduler", &o.scheduler.is_some())
+            .field("output_budget", &o.output_budget)
+            .field("escalates", &o.escalate.is_some())
+            .field("deps", &self.deps)
+            .field("steps", &self.steps)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<O> Group<O> {
-    pub(super) fn new(tm: Arc<Mutex<TypeMap>>, errors: Arc<Mutex<Vec<Error>>>) -> Self {
+    /// Internal API to write this group as `label`, followed by any options
+    /// set on it, and then each of its steps on its own indented line.
+    pub(super) fn fmt_tree(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        label: &str,
+    ) -> std::fmt::Result {
+        let o = &self.opts;
+        let mut opts = vec![];
+        if o.parallel {
+            opts.push("parallel".to_string());
+        }
+        if o.deterministic {
+            opts.push("deterministic".to_string());
+        }
+        if o.cpu_bound {
+            opts.push("cpu bound".to_string());
+        }
+        if let Some(limit) = o.max_concurrency {
+            opts.push(format!("max concurrency {limit}"));
+        }
+        match o.tolerate_failure {
+            Some(true) => opts.push("tolerates failure".to_string()),
+            Some(false) => opts.push("fails fast".to_string()),
+            None => {}
+        }
+        if o.escalate.is_some() {
+            opts.push("escalates failures".to_string());
+        }
+        if o.panic != PanicPolicy::Abort {
+            opts.push(format!("{:?} panics", o.panic).to_lowercase());
+        }
+        if let Some(retry) = &o.retry {
+            opts.push(format!("{} retries", retry.retries));
+        }
+        if let Some(limit) = o.step_timeout {
+            opts.push(format!("step timeout {limit:?}"));
+        }
+        if let Some(rollout) = &o.rollout {
+            opts.push(rollout.to_string());
+        }
+        if o.history.is_some() {
+            opts.push("ordered by history".to_string());
+        }
+        if !o.tags.is_empty() {
+            opts.push(format!("tagged {}", o.tags.join(", ")));
+        }
+
+        write!(f, "{label}")?;
+        write_options(f, &opts)?;
+        writeln!(f, ":")?;
+        for step in &self.steps {
+            writeln!(f, "  {step}")?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn new(tm: Arc<Mutex<TypeMap>>, bindings: Bindings) -> Self {
         Self {
             steps: vec![],
-            errors,
+            label: String::new(),
+            errors: vec![],
+            rejected: vec![],
+            checkpointer: None,
+            resumed: Mutex::default(),
+            bindings,
             tm,
+            deps: TypeMap::new(),
+            resolved: ResolutionCache::new(),
             opts: GroupOptions::default(),
+            children: vec![],
+            collect_errors: false,
         }
     }
 
-    pub(super) fn add_error(&self, e: Error) {
-        self.errors
-            .lock()
-            .expect("imperat group mutex poisoned")
-            .push(e);
+    /// Internal API to take this group and its child groups, recursively, in
+    /// the order they run.
+    pub(super) fn flatten(mut self) -> Vec<Group<O>> {
+        let children = std::mem::take(&mut self.children);
+        let mut groups = vec![self];
+        groups.extend(children.into_iter().flat_map(Group::flatten));
+        groups
+    }
+
+    /// Internal API to move this group and its child groups into another
+    /// builder, whose dependencies `deps` shadow.
+    pub(super) fn embed(&mut self, tm: &Arc<Mutex<TypeMap>>, bindings: &Bindings, deps: &TypeMap) {
+        self.tm = tm.clone();
+        self.bindings = bindings.clone();
+        self.deps = self.deps.layer_over(deps);
+        for child in &mut self.children {
+            child.embed(tm, bindings, deps);
+        }
+    }
+
+    /// Returns this group's name, if it was given one.
+    pub fn name(&self) -> Option<&str> {
+        self.opts.name.as_deref()
+    }
+
+    /// Returns whether this group was created by the builder owning `tm`.
+    pub(super) fn is_from(&self, tm: &Arc<Mutex<TypeMap>>) -> bool {
+        Arc::ptr_eq(&self.tm, tm)
+    }
+
+    pub(super) fn add_error(&mut self, e: Error) {
+        self.errors.push(e);
+    }
+
+    /// Internal API for the errors which occurred while building this group.
+    pub(super) fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    pub(super) fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
     }
 }
 
-impl<O: IntoStepOutcome + 'static> Group<O> {
-    /// Adds a step to this group.
-    pub(super) fn add_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+impl<O: IntoStepOutcome + Send + 'static> Group<O> {
+    /// Adds a step to this group. Steps whose dependencies can't be
+    /// resolved are not added and record an error instead, unless they
+    /// depend on the output of an earlier binding step.
+    pub(super) fn add(&mut self, step: Step<O>) {
+        if step.opts.budget.output_size.is_some() && step.opts.output_size.is_none() {
+            self.add_error(Error::NoOutputSize(step.name.clone()));
+        }
+        let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+        StepScope::new(
+            &step.name,
+            &self.tags(&step),
+            1,
+            &CancelHandle::default(),
+            &StatusHandle::default(),
+            Counters::default(),
+            &ExecutorHandle::default(),
+        )
+        .bind(&mut tm);
+        // Calling a step only resolves its arguments and builds its future,
+        // it doesn't run until awaited.
+        let resolved = self.resolve(&step, &mut tm, None).0.map(drop);
+        let unresolved: Vec<_> = step
+            .deps
+            .iter()
+            .filter(|d| resolved.is_err() && !self.deps.contains_id(d.id) && !tm.contains_id(d.id))
+            .copied()
+            .collect();
+        drop(tm);
+        let mut bindings = self
+            .bindings
+            .lock()
+            .expect("imperat bindings mutex poisoned");
+        // Steps awaiting a binding are checked again when they run.
+        let pending = step.deps.iter().any(|d| bindings.is_bound(d.id));
+        let bound = bindings.add(&step.name, &step.deps, step.binds(), &step.opts.after);
+        drop(bindings);
+        if let (Some(first), Some(dep)) = (bound, step.binds()) {
+            self.add_error(Error::DuplicateBinding(dep.name, first, step.name.clone()));
+        }
+        if let (Err(e), false) = (resolved, pending) {
+            log_warn!("will not run step '{}': {e}", step.name);
+            self.add_error(e);
+            self.rejected.push((step, unresolved));
+            return;
+        }
+        for alias in step.aliases() {
+            if self.steps.iter().any(|s| &s.name == alias) {
+                log_warn!(
+                    "deprecated name '{alias}' of step '{}' is also the name of another step",
+                    step.name
+                );
+            }
+        }
+
+        self.steps.push(step);
+    }
+
+    /// Internal API to set the result key of every step in this group, where
+    /// `ids` is the id of this group's first step. Returns the next step's id.
+    pub(super) fn assign_keys(
         &mut self,
-        name: &str,
-        func: C,
+        strategy: &KeyStrategy,
+        index: usize,
+        ids: usize,
+    ) -> usize {
+        self.label = self.opts.name.clone().unwrap_or_else(|| index.to_string());
+        for (id, step) in (ids..).zip(&mut self.steps) {
+            step.id = id;
+            step.key = strategy.key(&StepKey {
+                name: &step.name,
+                group: &self.label,
+                id,
+            });
+        }
+
+        ids + self.steps.len()
+    }
+
+    /// Internal API to record this group's steps with `checkpointer`, and
+    /// to resume any of them which completed in `resume` instead of running
+    /// them again.
+    pub(super) fn checkpoint(
+        &mut self,
+        checkpointer: Option<Arc<dyn Checkpointer<O>>>,
+        resume: &mut Checkpoint<O>,
     ) {
-        let Some(args) =
-            A::retrieve_from_map(&self.tm.lock().expect("imperat typemap mutex poisoned"))
-        else {
-            eprintln!("will not run step '{name}' as at least one dependency was absent");
-            self.add_error(Error::DepResolution(name.to_string()));
-            return;
-        };
-        self.steps.push(Step {
-            name: name.to_string(),
-            fut: Box::pin(func.call(args)),
-        });
+        self.checkpointer = checkpointer;
+        let resumed = self
+            .resumed
+            .get_mut()
+            .expect("imperat resume mutex poisoned");
+        for step in &self.steps {
+            if let Some(out) = resume.outputs.remove(&step.key) {
+                resumed.insert(step.id, out);
+            }
+        }
+    }
+
+    /// Internal API to label this group as the preflight checks, where `ids`
+    /// is the id of its first step. Their keys are always their names, and
+    /// every failed check fails the group together.
+    pub(super) fn assign_preflight(&mut self, ids: usize) {
+        self.label = "preflight".to_string();
+        self.collect_errors = true;
+        for (id, step) in (ids..).zip(&mut self.steps) {
+            step.id = id;
+        }
+    }
+
+    /// Internal API to record every step of this group as skipped, as the
+    /// run was drained before it started.
+    pub(super) fn skip_drained(&self, run: &RunContext) {
+        self.skip_all(self.steps.iter(), run);
+    }
+
+    /// Records `steps` as skipped without running them, such as when the
+    /// run was drained before they started.
+    fn skip_all<'a>(&'a self, steps: impl IntoIterator<Item = &'a Step<O>>, run: &RunContext) {
+        for s in steps {
+            run.status.skip();
+            run.pipes.finish(&s.deps);
+            self.record(s, run, StepOutcome::Skipped, None);
+        }
+    }
+
+    /// Records a step which never ran to completion in the run's report.
+    fn record(&self, s: &Step<O>, run: &RunContext, outcome: StepOutcome, error: Option<&Error>) {
+        run.record(self.entry(s, run, outcome, error));
+    }
+
+    /// Describes a step for the run's report, without any timing.
+    fn entry(
+        &self,
+        s: &Step<O>,
+        run: &RunContext,
+        outcome: StepOutcome,
+        error: Option<&Error>,
+    ) -> StepReport {
+        StepReport {
+            id: s.id,
+            name: s.name.clone(),
+            key: s.key.clone(),
+            group: self.label.clone(),
+            short_id: keys::short_id(&self.label, &s.name),
+            aliases: s.opts.aliases.clone(),
+            dependencies: s.deps.clone(),
+            started: None,
+            duration: None,
+            outcome,
+            error: error.map(|e| run.redacted(&e.to_string())),
+            counters: run.counters.get(s.id).snapshot(),
+            artifacts: BTreeMap::new(),
+            fallback_for: s.opts.fallback_for.clone(),
+        }
+    }
+
+    /// Returns how many steps are in this group.
+    pub(super) fn len(&self) -> usize {
+        self.steps.len()
     }
 
     /// Internal API to add a callback to this group.
@@ -112,101 +987,1766 @@ impl<O: IntoStepOutcome + 'static> Group<O> {
         self.opts.callbacks.push(cb);
     }
 
+    /// Internal API to drop this group's steps which their rollouts don't
+    /// enable, recording each decision in `metadata`. `label` stands in for
+    /// the group's name if it has none. Returns whether the group itself is
+    /// enabled.
+    pub(super) fn roll_out(&mut self, label: &str, metadata: &mut RunMetadata) -> bool {
+        let mut decide = |name: &str, rollout: &Rollout| {
+            let enabled = rollout.enabled(name);
+            let decision = if enabled { "enabled" } else { "disabled" };
+            metadata.insert(&format!("rollout.{name}"), decision);
+            enabled
+        };
+        let name = self.opts.name.as_deref().unwrap_or(label);
+        if !self.opts.rollout.as_ref().is_none_or(|r| decide(name, r)) {
+            return false;
+        }
+        self.steps
+            .retain(|s| s.opts.rollout.as_ref().is_none_or(|r| decide(&s.name, r)));
+
+        true
+    }
+
+    /// Internal API to snapshot the environment variables this group reads,
+    /// by its label, unless it has none.
+    pub(super) fn snapshot_env(
+        &self,
+        run: &RunContext,
+    ) -> Option<(String, BTreeMap<String, String>)> {
+        if self.opts.env.is_empty() {
+            return None;
+        }
+        Some((self.label.clone(), run.snapshot_env(&self.opts.env)))
+    }
+
+    /// Internal API to drop this group's steps which `filter` doesn't
+    /// match, recording each as skipped.
+    pub(super) fn filter_tags(&mut self, filter: &TagFilter, run: &RunContext) {
+        let group: Vec<_> = self.opts.tags.iter().map(String::as_str).collect();
+        let (kept, dropped) =
+            std::mem::take(&mut self.steps)
+                .into_iter()
+                .partition(|s: &Step<O>| {
+                    let own = s.opts.tags.iter().map(String::as_str);
+                    filter.matches(&own.chain(group.iter().copied()).collect::<Vec<_>>())
+                });
+        self.steps = kept;
+        for s in dropped {
+            if run.settings.verbose {
+                eprintln!("skipping step '{}' as its tags were filtered out", s.name);
+            }
+            self.record(&s, run, StepOutcome::Skipped, None);
+        }
+    }
+
+    /// Resolves `step` with this group's dependencies, and then `scope`'s
+    /// values, layered over `tm`, returning every lookup recorded while
+    /// doing so. Arguments which can be shared are cached until `tm` or this
+    /// group's dependencies change.
+    fn resolve(
+        &self,
+        step: &Step<O>,
+        tm: &mut TypeMap,
+        scope: Option<&StepScope>,
+    ) -> (Result<StepFuture<O>>, Vec<DepInfo>) {
+        let mut layered;
+        let tm = if self.deps.is_empty() {
+            tm
+        } else {
+            layered = self.deps.layer_over(tm);
+            &mut layered
+        };
+        step.with_scoped(tm, |tm| {
+            // Step scopes only bind values which are never shared, so they
+            // don't change what's cached.
+            self.resolved.set_version(tm.version());
+            let scoped;
+            let tm = match scope {
+                Some(scope) => {
+                    let mut values = TypeMap::new();
+                    scope.bind(&mut values);
+                    scoped = values.layer_over(tm);
+                    &scoped
+                }
+                None => tm,
+            };
+            let fut = (step.call)(tm, &self.resolved).map_err(|missing| step.missing(missing));
+            (fut, tm.take_accesses())
+        })
+    }
+
+    /// Internal API to read this group's label, once assigned. See
+    /// `StepReport::group`.
+    pub(super) fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Internal API to read this group's steps.
+    pub(super) fn steps(&self) -> &[Step<O>] {
+        &self.steps
+    }
+
     /// Internal API to read callbacks from this group.
     pub(super) fn callbacks(&self) -> &[CallbackKind<O>] {
         &self.opts.callbacks
     }
 
-    /// Execute this group, returning all of the results. The results
-    /// are grouped by the step name. The last defined with a duplicate
-    /// step name will appear in the results.
-    pub(super) async fn execute(self) -> Result<HashMap<String, O>> {
-        let mut outputs = HashMap::with_capacity(self.steps.len());
+    /// Internal API to describe this group as labeled `label`, without
+    /// running anything. See `ImperativeStepBuilder::plan`.
+    pub(super) fn plan(&self, label: Option<String>) -> GroupPlan {
+        // Top-level steps run as the group at position 0.
+        let group = label.as_deref().unwrap_or("0");
+        let step = |s: &Step<O>, phase, unresolved: &[DepInfo]| StepPlan {
+            name: s.name.clone(),
+            short_id: keys::short_id(group, &s.name),
+            phase,
+            after: s.opts.after.clone(),
+            dependencies: s.deps.clone(),
+            binds: s.binds().copied(),
+            unresolved: unresolved.to_vec(),
+        };
+        let mut steps = vec![];
+        for (i, phase) in self.phases().into_iter().enumerate() {
+            steps.extend(phase.into_iter().map(|(s, _)| step(s, Some(i), &[])));
+        }
+        steps.extend(self.rejected.iter().map(|(s, deps)| step(s, None, deps)));
+
+        GroupPlan {
+            label,
+            parallel: self.opts.parallel,
+            tolerate_failure: self.opts.tolerate_failure == Some(true),
+            deterministic: self.opts.deterministic,
+            max_concurrency: self.opts.max_concurrency,
+            steps,
+        }
+    }
 
-        let exec_step = async |s, cbs: &[CallbackKind<O>]| {
-            for cb in cbs {
-                if let CallbackKind::BeforeStep(cb) = cb {
-                    cb(&s);
+    /// Checks every step's `depends_on` and `fallback_for` name a step in
+    /// the same or an earlier phase of this group.
+    pub(super) fn check_order(&self) -> Result<()> {
+        let phases = self.phases();
+        for (i, phase) in phases.iter().enumerate() {
+            for (step, _) in phase {
+                let earlier = phases[..=i].iter().flatten();
+                if let Some(name) = step
+                    .opts
+                    .after
+                    .iter()
+                    .chain(&step.opts.fallback_for)
+                    .find(|&name| !earlier.clone().any(|(s, _)| &s.name == name))
+                {
+                    return Err(Error::DependsOn(step.name.clone(), name.clone()));
                 }
             }
-            let Step { name, fut } = s;
-            let res = fut.await;
-            for cb in cbs {
-                if let CallbackKind::AfterStep(cb) = cb {
-                    cb(&name, &res);
+        }
+
+        Ok(())
+    }
+
+    /// Returns this group's steps split into phases, in the order they run.
+    /// Within a phase, steps run after the steps they depend on and otherwise
+    /// keep their declaration order, or their order by history if set. Each
+    /// step is paired with the positions of the steps it depends on in its
+    /// phase.
+    fn phases(&self) -> Vec<Vec<(&Step<O>, Vec<usize>)>> {
+        let mut labels = vec![];
+        let mut keyed: Vec<_> = self
+            .steps
+            .iter()
+            .map(|s| {
+                let key = match s.phase() {
+                    None => (0, 0),
+                    Some(Phase::Number(n)) => (0, *n as usize),
+                    Some(Phase::Label(l)) => {
+                        let i = labels.iter().position(|x| x == &l).unwrap_or_else(|| {
+                            labels.push(l);
+                            labels.len() - 1
+                        });
+                        (1, i)
+                    }
                 };
-            }
-            res
-        };
+                (key, s)
+            })
+            .collect();
+        keyed.sort_by_key(|(key, _)| *key);
 
-        let cbs = self.callbacks().to_vec();
-        // implies tolerate_failure for now. We'd need something special
-        // here to allow a single failure to interrupt all futures.
-        if self.opts.parallel {
-            return Ok(self
-                .steps
-                .into_iter()
-                .map(|s| async { (s.name.clone(), exec_step(s, &cbs).await) })
-                .collect::<FuturesOrdered<_>>()
-                .collect()
-                .await);
-        }
+        keyed
+            .chunk_by(|(a, _), (b, _)| a == b)
+            .map(|phase| {
+                let mut steps: Vec<_> = phase.iter().map(|(_, s)| *s).collect();
+                if let Some(stats) = &self.opts.history {
+                    by_history(&mut steps, stats);
+                }
+                order(steps)
+            })
+            .collect()
+    }
+
+    /// Runs a phase's steps as the group's scheduler starts them, returning
+    /// their results in the phase's order. Steps depending on another step in
+    /// their phase wait for it, and are skipped if it doesn't succeed, as are
+    /// steps depending on a step in an `earlier` phase which didn't. In
+    /// groups which fail fast, the first failure cancels every unfinished
+    /// step instead.
+    async fn run_parallel_phase<'a>(
+        &self,
+        phase: Vec<(&'a Step<O>, Vec<usize>)>,
+        earlier: &[String],
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        slots: Option<&Slots>,
+    ) -> Result<Vec<(&'a Step<O>, Result<O>)>> {
+        let scheduler: &dyn Scheduler = match &self.opts.scheduler {
+            _ if !run.settings.allow_parallel => &Sequential,
+            Some(scheduler) => scheduler.as_ref(),
+            None => &Parallel,
+        };
+        let names: Vec<_> = phase.iter().map(|(s, _)| s.name.clone()).collect();
+        // Whether each step in the phase succeeded, once finished.
+        let mut done: Vec<Option<bool>> = vec![None; phase.len()];
+        let mut pending: Vec<usize> = (0..phase.len()).collect();
+        let exec = async |i: usize, failed: Option<String>| {
+            let s = phase[i].0;
+            let res = match failed {
+                Some(dep) => {
+                    run.status.skip();
+                    run.pipes.finish(&s.deps);
+                    let e = Error::Skipped(names[i].clone(), dep);
+                    self.record(s, run, StepOutcome::Skipped, Some(&e));
+                    Err(e)
+                }
+                None => self.run_scheduled(s, cbs, run, slots, scheduler).await,
+            };
+            (i, s, res)
+        };
+        let mut running = FuturesUnordered::new();
+        let mut finished: Vec<_> = phase.iter().map(|_| None).collect();
 
-        for step in self.steps {
-            let name = step.name.clone();
-            let r = exec_step(step, &cbs).await;
-            if self.opts.tolerate_failure {
-                outputs.insert(name, r);
-                continue;
+        let fail_fast = self.opts.tolerate_failure == Some(false);
+        let budget = self.opts.output_budget.as_ref();
+        loop {
+            // Steps after a failed step are skipped straight away, while the
+            // rest wait for the scheduler once the steps they follow succeed.
+            pending.retain(|&i| {
+                let failed = failed_before(&phase, i, &done, earlier, run);
+                let skipped = failed.is_some();
+                if skipped {
+                    running.push(exec(i, failed));
+                }
+                !skipped
+            });
+            // While over the output budget, only running steps finish until
+            // sinks drain enough output to resume.
+            let paused = budget.filter(|b| b.exceeded() && !pending.is_empty() && !run.draining());
+            while paused.is_none() && !run.draining() {
+                let Some(next) = next_ready(scheduler, &phase, &pending, &done, running.len())
+                else {
+                    break;
+                };
+                pending.retain(|&i| i != next);
+                running.push(exec(next, None));
             }
 
-            if r.success() {
-                outputs.insert(name, r);
-            } else if let Some(e) = r.error() {
-                return Err(Error::Step(name, e));
-            } else {
-                return Err(Error::UnknownStep(name));
+            let next = match paused {
+                Some(budget) if running.is_empty() => {
+                    if run.settings.verbose {
+                        eprintln!("group '{}' is waiting for output to drain", self.label);
+                    }
+                    budget.drained().await;
+                    continue;
+                }
+                Some(budget) => {
+                    match future::select(running.next(), pin!(budget.drained())).await {
+                        Either::Left((next, _)) => next,
+                        Either::Right(_) => continue,
+                    }
+                }
+                None => running.next().await,
+            };
+            let Some((i, s, res)) = next else {
+                break;
+            };
+            done[i] = Some(res.as_ref().is_ok_and(IntoStepOutcome::success));
+            if let (Some(budget), Ok(out), Some(true)) = (budget, &res, done[i]) {
+                budget.retain(s.output_size(out));
+            }
+            let res = match res {
+                Ok(out) if fail_fast && !out.success() && !self.has_fallback(s) => {
+                    Err(failure(&s.name, out))
+                }
+                Err(Error::Panicked(..)) if self.panic_policy(s) == PanicPolicy::Tolerate => {
+                    finished[i] = Some((s, res));
+                    continue;
+                }
+                res => res,
+            };
+            match res {
+                Err(e) if fail_fast && !self.falls_back(s, &e) => {
+                    // Dropping the remaining futures cancels them.
+                    let cancelled: Vec<_> = (0..names.len())
+                        .filter(|&j| j != i && finished[j].is_none())
+                        .map(|j| {
+                            run.status.cancel(&names[j]);
+                            let reason = Error::Cancelled {
+                                reason: CancelReason::FailFast(s.name.clone()),
+                                during_step: names[j].clone(),
+                            };
+                            self.record(phase[j].0, run, StepOutcome::Cancelled, Some(&reason));
+                            names[j].clone()
+                        })
+                        .collect();
+                    return Err(Error::FailFast(Box::new(e), cancelled));
+                }
+                res => finished[i] = Some((s, res)),
             }
         }
+        // Only steps which never started are left once the run drains.
+        self.skip_all(pending.iter().map(|&i| phase[i].0), run);
 
-        Ok(outputs)
+        Ok(finished.into_iter().flatten().collect())
     }
-}
-
-/// Allows incrementally building groups with specific options.
-pub struct GroupBuilder<O>(pub(super) Group<O>);
 
-impl<O: IntoStepOutcome + 'static> GroupBuilder<O> {
-    pub(super) fn new(tm: Arc<Mutex<TypeMap>>, errors: Arc<Mutex<Vec<Error>>>) -> Self {
-        GroupBuilder(Group::new(tm, errors))
+    /// Runs a step started by `scheduler`, and tells it how the step fared.
+    async fn run_scheduled(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        slots: Option<&Slots>,
+        scheduler: &dyn Scheduler,
+    ) -> Result<O> {
+        let st = Instant::now();
+        let res = self.run_step(s, cbs, run, slots).await;
+        let success = res.as_ref().is_ok_and(IntoStepOutcome::success);
+        scheduler.finished(&s.name, st.elapsed(), success);
+        res
     }
 
-    /// Add a step with this name to the provided group.
-    pub fn add_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
-        mut self,
-        name: &str,
+    /// Runs a single step to completion, tracking it in the run's status.
+    async fn run_step(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        slots: Option<&Slots>,
+    ) -> Result<O> {
+        let resumed = self
+            .resumed
+            .lock()
+            .expect("imperat resume mutex poisoned")
+            .remove(&s.id);
+        if let Some(out) = resumed {
+            if run.settings.verbose {
+                eprintln!("resuming step '{}' from its checkpoint", s.name);
+            }
+            self.publish(s, &out);
+            return Ok(self.skip(s, cbs, run, out, None).await);
+        }
+        if let Some(e) = run.unavailable(&s.name, &s.deps) {
+            return self.unavailable(s, cbs, run, e).await;
+        }
+        if let Some((out, e)) = s.check_unmet(run) {
+            return Ok(self.skip(s, cbs, run, out, e.as_ref()).await);
+        }
+        if let Some(skipped) = self.check_condition(s).await? {
+            if run.settings.verbose {
+                eprintln!("skipping step '{}' as its condition wasn't met", s.name);
+            }
+            return Ok(self.skip(s, cbs, run, skipped, None).await);
+        }
+        if let Err(e) = self.await_admission(s, run).await {
+            return self.unavailable(s, cbs, run, e).await;
+        }
+        run.status.start(&s.name);
+        let (started, st) = (SystemTime::now(), Instant::now());
+        let mut res = match (
+            self.run_attempts(s, cbs, run, slots).await,
+            &self.checkpointer,
+        ) {
+            (Ok(out), Some(checkpointer)) if out.success() => checkpointer
+                .save(&s.key, &out)
+                .map(|()| out)
+                .map_err(|e| Error::Checkpoint(s.name.clone(), e)),
+            (res, _) => res,
+        };
+        let success = res.as_ref().is_ok_and(IntoStepOutcome::success);
+        let outcome = match &res {
+            Ok(_) if success => StepOutcome::Succeeded,
+            Err(e) if e.aborted() => StepOutcome::Cancelled,
+            _ => StepOutcome::Failed,
+        };
+        let artifacts = match &mut res {
+            Ok(out) if success => self.apply_extras(s, run, out),
+            _ => BTreeMap::new(),
+        };
+        let mut entry = self.entry(s, run, outcome, res.as_ref().err());
+        // Failed outputs which were tolerated carry their own error.
+        if let (Ok(out), false) = (&res, success) {
+            entry.error = out.error_ref().map(|e| run.redacted(&e.to_string()));
+        }
+        run.record(StepReport {
+            started: Some(started),
+            duration: Some(st.elapsed()),
+            artifacts,
+            ..entry
+        });
+        if let (Ok(out), true) = (&res, success) {
+            self.publish(s, out);
+        }
+        if let (true, Some(undo)) = (success, &s.opts.rollback) {
+            let undo = {
+                let tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+                if self.deps.is_empty() {
+                    undo(&tm)
+                } else {
+                    undo(&self.deps.layer_over(&tm))
+                }
+            };
+            run.rollbacks.push(
+                &self.label,
+                &s.name,
+                undo.map_err(|missing| s.missing(missing)),
+            );
+        }
+        run.status.finish(&s.name, success);
+        run.pipes.finish(&s.deps);
+        on_step_result(cbs, run, &s.name, outcome, res.as_ref().ok());
+        let error = match &res {
+            Ok(out) if !success => out.error_ref(),
+            Ok(_) => None,
+            Err(e) => Some(e as &(dyn std::error::Error + 'static)),
+        };
+        if let Some(e) = error {
+            on_step_error(cbs, run, &s.name, e);
+        }
+        after_step_async(cbs, run, &s.name, outcome, res.as_ref().ok()).await;
+
+        res
+    }
+
+    /// Waits until `s` is approved, if it requires approval, recording its
+    /// state with the group's checkpointer so a resumed run returns to the
+    /// same gate.
+    async fn await_approval(&self, s: &Step<O>, run: &RunContext) -> Result<()> {
+        if !s.opts.approval {
+            return Ok(());
+        }
+        let save = |approval: &Approval| match &self.checkpointer {
+            Some(checkpointer) => checkpointer
+                .save_approval(&s.key, approval)
+                .map_err(|e| Error::Checkpoint(s.name.clone(), e)),
+            None => Ok(()),
+        };
+        let mut approval = run.approvals.request(&s.key);
+        if approval == Approval::Pending {
+            if run.settings.verbose {
+                eprintln!("step '{}' is waiting for approval", s.name);
+            }
+            save(&approval)?;
+            approval = run.approvals.decided(&s.key).await;
+            save(&approval)?;
+        }
+        match approval {
+            Approval::Rejected(reason) => Err(Error::Rejected(s.name.clone(), reason)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Skips `s` if it's degradable, since a dependency it uses is
+    /// unavailable per `e`, or fails it otherwise.
+    async fn unavailable(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        e: Error,
+    ) -> Result<O> {
+        if let (true, Some(unmet)) = (s.opts.degradable, s.opts.unmet) {
+            if run.settings.verbose {
+                eprintln!("skipping step '{}' as it's degradable: {e}", s.name);
+            }
+            return Ok(self.skip(s, cbs, run, unmet(), Some(&e)).await);
+        }
+        run.status.skip();
+        run.pipes.finish(&s.deps);
+        self.record(s, run, StepOutcome::Failed, Some(&e));
+        Err(e)
+    }
+
+    /// Waits until `s` may start: the dependencies it waits for are ready
+    /// (see `ImperativeStepBuilder::wait_for_dep`), the circuit breakers
+    /// covering it let it through, and it's approved, if it must be.
+    async fn await_admission(&self, s: &Step<O>, run: &RunContext) -> Result<()> {
+        for probe in run.readiness.iter().filter(|p| s.deps.contains(&p.dep)) {
+            probe
+                .wait(&s.name, &self.tm, &run.executor, run.settings.verbose)
+                .await?;
+        }
+        run.admit(&s.name, &s.deps, &self.tags(s))?;
+        self.await_approval(s, run).await
+    }
+
+    /// Returns the tags of `s`, including its group's.
+    fn tags<'a>(&'a self, s: &'a Step<O>) -> Vec<&'a str> {
+        self.opts
+            .tags
+            .iter()
+            .chain(&s.opts.tags)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Fails with `out`'s error if it's a failure of `s` which this group's
+    /// escalation policy won't tolerate, and otherwise returns it. See
+    /// `GroupBuilder::escalate_if`.
+    fn escalate(&self, s: &Step<O>, out: O) -> Result<O> {
+        let escalate = self.opts.escalate.as_ref();
+        if out.success() || !escalate.is_some_and(|f| f(&s.name, &out)) {
+            return Ok(out);
+        }
+        Err(match out.error() {
+            Some(e) => Error::Step(s.name.clone(), e),
+            None => Error::UnknownStep(s.name.clone()),
+        })
+    }
+
+    /// Returns whether a step in this group runs in place of `s` if it
+    /// fails, so its failure doesn't fail the group.
+    fn has_fallback(&self, s: &Step<O>) -> bool {
+        self.steps.iter().any(|f| f.is_fallback_for(s))
+    }
+
+    /// Returns whether `s` failing with `e` is left to its fallback, rather
+    /// than failing the group. Cancellations other than timeouts never are.
+    fn falls_back(&self, s: &Step<O>, e: &Error) -> bool {
+        !e.aborted() && self.has_fallback(s)
+    }
+
+    /// Returns what happens when `s` panics, set on it or its group.
+    fn panic_policy(&self, s: &Step<O>) -> PanicPolicy {
+        s.opts.panic.unwrap_or(self.opts.panic)
+    }
+
+    /// Adds the metrics a successful step returned with its output to its
+    /// counters and binds the dependencies it published, returning its
+    /// artifacts. See `StepReturn`.
+    fn apply_extras(
+        &self,
+        s: &Step<O>,
+        run: &RunContext,
+        out: &mut O,
+    ) -> BTreeMap<String, std::path::PathBuf> {
+        let Some(extras) = out.take_extras() else {
+            return BTreeMap::new();
+        };
+        let counters = run.counters.get(s.id);
+        for (name, n) in &extras.metrics {
+            counters.add(name, *n);
+        }
+        if !extras.publish.is_empty() {
+            let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+            for publish in extras.publish {
+                publish(&mut tm);
+            }
+        }
+        extras.artifacts
+    }
+
+    /// Binds a step's successful output, if it's a binding step.
+    fn publish(&self, s: &Step<O>, out: &O) {
+        if let Some(publish) = &s.opts.publish {
+            publish(
+                out,
+                &mut self.tm.lock().expect("imperat typemap mutex poisoned"),
+            );
+        }
+    }
+
+    /// Skips a step which didn't meet its condition or requirements, as if
+    /// it returned `out`, recording why if it's because of `error`.
+    async fn skip(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        out: O,
+        error: Option<&Error>,
+    ) -> O {
+        run.status.skip_unmet();
+        run.pipes.finish(&s.deps);
+        self.record(s, run, StepOutcome::Skipped, error);
+        on_step_result(cbs, run, &s.name, StepOutcome::Skipped, Some(&out));
+        after_step_async(cbs, run, &s.name, StepOutcome::Skipped, Some(&out)).await;
+        out
+    }
+
+    /// Evaluates a step's condition, if any, returning its output if it
+    /// should be skipped.
+    async fn check_condition(&self, s: &Step<O>) -> Result<Option<O>> {
+        let Some(condition) = &s.opts.condition else {
+            return Ok(None);
+        };
+        let fut = {
+            let tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+            if self.deps.is_empty() {
+                condition(&tm)
+            } else {
+                condition(&self.deps.layer_over(&tm))
+            }
+        };
+
+        Ok(fut.map_err(|missing| s.missing(missing))?.await)
+    }
+
+    /// Runs a step, retrying it per the group's retry policy for as long
+    /// as the run's retry budget allows.
+    async fn run_attempts(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        slots: Option<&Slots>,
+    ) -> Result<O> {
+        // Retry policies are inherited from the group, and then the builder.
+        let retry = s
+            .opts
+            .retry
+            .clone()
+            .or_else(|| self.opts.retry.clone())
+            .or_else(|| run.settings.retry());
+        let budget = &s.opts.budget;
+        let max_retries = budget.retries.unwrap_or(usize::MAX);
+        let deadline = budget.duration.map(|limit| Instant::now() + limit);
+
+        let tags = self.tags(s);
+
+        let mut attempt = 0;
+        loop {
+            let res = self
+                .run_attempt(s, cbs, run, attempt + 1, slots, deadline)
+                .await?;
+            let res = res
+                .and_then(|r| s.check_output_size(r))
+                .and_then(|r| redact_output(cbs, run, &s.name, r));
+            if !res.as_ref().is_err_and(Error::aborted) {
+                let success = res.as_ref().is_ok_and(IntoStepOutcome::success);
+                run.record_attempt(&s.deps, &tags, success);
+            }
+            // In deterministic groups, after step callbacks are deferred until
+            // results are committed.
+            if let (Ok(res), false) = (&res, self.opts.deterministic) {
+                after_step(cbs, run, &s.name, res);
+            }
+
+            let failed = match &res {
+                Ok(r) => !r.success(),
+                Err(Error::Panicked(..)) => self.panic_policy(s) == PanicPolicy::Retry,
+                Err(Error::BudgetExceeded(..)) => false,
+                Err(e) => !e.aborted(),
+            };
+            match &retry {
+                Some(policy)
+                    if failed
+                        && attempt < policy.retries.min(max_retries)
+                        && policy.should_retry(res.as_ref())
+                        && run.take_retry(&s.deps, &tags) =>
+                {
+                    attempt += 1;
+                    run.executor.sleep(policy.delay(attempt)).await;
+                    on_retry(cbs, run, &s.name, attempt + 1);
+                }
+                _ => return res,
+            }
+        }
+    }
+
+    /// Returns the error for step `s` running past `by`.
+    fn exceeded(s: &Step<O>, by: Limit) -> Error {
+        match by {
+            Limit::Timeout => Error::Cancelled {
+                reason: CancelReason::Timeout,
+                during_step: s.name.clone(),
+            },
+            Limit::Deadline => Error::Cancelled {
+                reason: CancelReason::Deadline,
+                during_step: s.name.clone(),
+            },
+            Limit::Budget => Error::BudgetExceeded(
+                s.name.clone(),
+                format!(
+                    "ran longer than {:?}",
+                    s.opts.budget.duration.unwrap_or_default()
+                ),
+            ),
+        }
+    }
+
+    /// Returns how long a step's next attempt may run, and what ends it
+    /// then: its timeout, the run's deadline, or the step's budget, which
+    /// ends `deadline`. Fails once the run's deadline has passed.
+    fn attempt_limit(
+        &self,
+        s: &Step<O>,
+        run: &RunContext,
+        deadline: Option<Instant>,
+    ) -> Result<(Option<Duration>, Limit)> {
+        let left = |d: Instant| d.saturating_duration_since(Instant::now());
+        // Timeouts are inherited from the group, and then the builder. The
+        // run's deadline caps them all.
+        let run_left = run.deadline.map(left);
+        if run_left == Some(Duration::ZERO) {
+            return Err(Error::Cancelled {
+                reason: CancelReason::Deadline,
+                during_step: s.name.clone(),
+            });
+        }
+        let timeout = match (
+            s.opts
+                .timeout
+                .or(self.opts.step_timeout)
+                .or(run.settings.step_timeout),
+            run_left,
+        ) {
+            (Some(limit), Some(left)) if limit <= left => (Some(limit), Limit::Timeout),
+            (_, Some(left)) => (Some(left), Limit::Deadline),
+            (limit, None) => (limit, Limit::Timeout),
+        };
+
+        // A step's budget ends it like a timeout, but with its own error.
+        Ok(match (timeout, deadline.map(left)) {
+            ((Some(limit), by), Some(left)) if limit <= left => (Some(limit), by),
+            (_, Some(left)) => (Some(left), Limit::Budget),
+            (timeout, None) => timeout,
+        })
+    }
+
+    /// Runs a step's future within `limit`, yielding `None` if it timed out.
+    /// Steps in parallel groups are spawned, so they may run on any thread.
+    async fn run_body(
+        &self,
+        fut: impl Future<Output = O> + Send + 'static,
+        run: &RunContext,
+        limit: Option<Duration>,
+    ) -> std::thread::Result<Option<O>> {
+        let cpu_bound = self.opts.cpu_bound;
+        let executor = run.executor.clone();
+        let fut = async move {
+            let fut = async move {
+                match limit {
+                    Some(limit) => executor.timeout(limit, fut).await,
+                    None => Some(fut.await),
+                }
+            };
+            if cpu_bound {
+                offload(fut).await
+            } else {
+                fut.await
+            }
+        };
+        if self.opts.parallel && run.settings.allow_parallel {
+            run.executor.spawn(fut).await
+        } else {
+            AssertUnwindSafe(fut).catch_unwind().await
+        }
+    }
+
+    /// Runs a single attempt of a step. Errors which end only this attempt,
+    /// such as timeouts and panics, are returned in the inner result. If the
+    /// step is preempted, it waits for another slot and starts over.
+    async fn run_attempt(
+        &self,
+        s: &Step<O>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        attempt: usize,
+        slots: Option<&Slots>,
+        deadline: Option<Instant>,
+    ) -> Result<Result<O>> {
+        loop {
+            if run.cancel.is_cancelled() {
+                return Err(Error::Cancelled {
+                    reason: CancelReason::Requested,
+                    during_step: s.name.clone(),
+                });
+            }
+            let (limit, by) = self.attempt_limit(s, run, deadline)?;
+            let slot = match slots {
+                Some(slots) => Some(slots.acquire(s.opts.priority, s.opts.preemptible).await),
+                None => None,
+            };
+            if let Err(e) = run.refreshers.refresh(&s.deps, &self.tm).await {
+                return Ok(Err(e));
+            }
+            before_step(cbs, run, s);
+            before_step_async(cbs, run, s).await;
+            run.events.send(|| PipelineEvent::StepStarted {
+                name: s.name.clone(),
+                group: self.label.clone(),
+                attempt,
+            });
+            #[cfg(feature = "tracing")]
+            let span = super::log::step_span(&s.name, &self.label, attempt, run);
+            let scope = StepScope::new(
+                &s.name,
+                &self.tags(s),
+                attempt,
+                &run.cancel,
+                &run.status,
+                run.counters.get(s.id),
+                &run.executor,
+            );
+            #[cfg(feature = "tracing")]
+            let scope = scope.in_span(&span);
+            if let Err(e) = fail_point!(run, BeforeResolve, &s.name) {
+                scope.finish();
+                return Ok(Err(e));
+            }
+            let (fut, accesses) = {
+                let mut tm = self.tm.lock().expect("imperat typemap mutex poisoned");
+                self.resolve(s, &mut tm, Some(&scope))
+            };
+            if let Some(cb) = &run.on_dep_access {
+                for dep in &accesses {
+                    run.callbacks
+                        .call("on_dep_access", &s.name, || cb(&s.name, dep));
+                }
+            }
+            let fut = scope.current(run).scope(fut?);
+            #[cfg(feature = "tracing")]
+            let fut = tracing::Instrument::instrument(fut, span.clone());
+            if run.settings.verbose {
+                eprintln!("running step '{}'", s.name);
+            }
+
+            let st = Instant::now();
+            let fut = async {
+                fail_point!(run, BeforeAwait, &s.name)?;
+                let out = self
+                    .run_body(fut, run, limit)
+                    .await
+                    .map_err(|panic| Error::Panicked(s.name.clone(), panic_message(&*panic)))?
+                    .ok_or_else(|| Self::exceeded(s, by))?;
+                fail_point!(run, AfterStep, &s.name)?;
+                Ok(out)
+            };
+            let res = match &slot {
+                Some(slot) => match future::select(pin!(fut), pin!(slot.preempted())).await {
+                    Either::Left((res, _)) => res,
+                    Either::Right(_) => {
+                        scope.finish();
+                        if run.settings.verbose {
+                            eprintln!("step '{}' was preempted", s.name);
+                        }
+                        continue;
+                    }
+                },
+                None => fut.await,
+            };
+            scope.finish();
+            #[cfg(feature = "tracing")]
+            super::log::attempt_finished(&span, &res, st.elapsed(), run);
+            Self::attempt_finished(s, run, &res, st.elapsed());
+
+            return Ok(res);
+        }
+    }
+
+    /// Records an attempt's result in the run's stats, and reports it when
+    /// verbose.
+    fn attempt_finished(s: &Step<O>, run: &RunContext, res: &Result<O>, elapsed: Duration) {
+        if let Some(stats) = &run.stats {
+            let success = res.as_ref().is_ok_and(IntoStepOutcome::success);
+            stats.record(&s.name, elapsed, success);
+        }
+        if run.settings.verbose {
+            let outcome = match res {
+                Ok(r) if r.success() => "succeeded",
+                Ok(_) => "failed",
+                Err(Error::Panicked(..)) => "panicked",
+                Err(e) if e.aborted() => "was cancelled",
+                Err(_) => "timed out",
+            };
+            eprintln!("step '{}' {outcome} after {elapsed:?}", s.name);
+        }
+    }
+
+    /// Execute this group, returning all of the results with their step's id
+    /// and key, in the order they're committed. Later results replace
+    /// earlier ones with the same key in `execute`'s results.
+    ///
+    /// Steps run phase by phase; see `Phase`. No steps start once the run
+    /// is cancelled.
+    pub(super) async fn execute(self, run: &RunContext) -> Result<Vec<(usize, String, O)>> {
+        // Empty groups, such as an unused default group, aren't reported.
+        let reported = !self.steps.is_empty();
+        if reported {
+            run.events.send(|| PipelineEvent::GroupStarted {
+                group: self.label.clone(),
+            });
+            on_group_start(self.callbacks(), run, &self.label);
+        }
+        let fut = async {
+            match self.run_phases(run).await {
+                Err(e) if run.rollback_scope == RollbackScope::Group => {
+                    let verbose = run.settings.verbose;
+                    Err(run.rollbacks.roll_back(Some(&self.label), e, verbose).await)
+                }
+                res => res,
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(
+            fut,
+            tracing::info_span!("group", group = %self.label, run_id = run.id),
+        );
+        let res = fut.await;
+        if reported {
+            on_group_end(self.callbacks(), run, &self.label, res.as_ref().err());
+        }
+
+        res
+    }
+
+    /// Runs `steps` in order until one succeeds, skipping the rest. See
+    /// `GroupBuilder::any_of`.
+    async fn run_any_of(
+        &self,
+        steps: &[&Step<O>],
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+    ) -> Result<Vec<(usize, String, O)>> {
+        let mut failures = vec![];
+        for (i, s) in steps.iter().enumerate() {
+            if run.draining() {
+                self.skip_all(steps[i..].iter().copied(), run);
+                return Ok(vec![]);
+            }
+            match self.run_step(s, cbs, run, None).await {
+                Ok(out) if out.success() => {
+                    self.skip_all(steps[i + 1..].iter().copied(), run);
+                    return Ok(vec![(s.id, s.key.clone(), s.reduce(out))]);
+                }
+                Ok(out) => failures.push(failure(&s.name, out)),
+                Err(e) if e.aborted() => return Err(e),
+                Err(e) => failures.push(e),
+            }
+        }
+
+        Err(Error::NoneSucceeded(failures))
+    }
+
+    /// Runs a parallel group's phases in order. Parallel groups tolerate
+    /// failures unless they fail fast. Steps skipped as a step they depend on
+    /// failed only fail the group if it doesn't tolerate failure.
+    ///
+    /// Results are always committed in phase then declaration order,
+    /// regardless of which step finishes first.
+    async fn run_parallel(
+        &self,
+        phases: Vec<Vec<(&Step<O>, Vec<usize>)>>,
+        cbs: &[CallbackKind<O>],
+        run: &RunContext,
+        tolerate_failure: bool,
+    ) -> Result<Vec<(usize, String, O)>> {
+        let mut outputs = Vec::with_capacity(self.steps.len());
+        let slots = self.opts.max_concurrency.map(Slots::new);
+        // failed steps, with their names
+        let mut errors = vec![];
+        // failed steps, in groups which succeed if any step does
+        let mut failures = vec![];
+        // the steps in phases which already ran
+        let mut earlier = vec![];
+        for phase in phases {
+            let names: Vec<_> = phase.iter().map(|(s, _)| s.name.clone()).collect();
+            let results = self
+                .run_parallel_phase(phase, &earlier, cbs, run, slots.as_ref())
+                .await?;
+            earlier.extend(names);
+            for (s, res) in results {
+                if self.opts.success == GroupSuccess::AnyOf {
+                    match res {
+                        Ok(out) if out.success() => {
+                            if self.opts.deterministic {
+                                after_step(cbs, run, &s.name, &out);
+                            }
+                            outputs.push((s.id, s.key.clone(), s.reduce(out)));
+                        }
+                        Ok(out) => failures.push(failure(&s.name, out)),
+                        Err(e) => failures.push(e),
+                    }
+                    continue;
+                }
+                match res.and_then(|res| self.escalate(s, res)) {
+                    Ok(res) if self.collect_errors && !res.success() => {
+                        errors.push((&s.name, failure(&s.name, res)));
+                    }
+                    Ok(res) => {
+                        if self.opts.deterministic {
+                            after_step(cbs, run, &s.name, &res);
+                        }
+                        outputs.push((s.id, s.key.clone(), s.reduce(res)));
+                    }
+                    Err(Error::Panicked(..)) if self.panic_policy(s) == PanicPolicy::Tolerate => {}
+                    Err(Error::Skipped(..)) if tolerate_failure => {}
+                    Err(e) if self.falls_back(s, &e) => {}
+                    Err(e) => errors.push((&s.name, e)),
+                }
+            }
+        }
+
+        if outputs.is_empty() && !failures.is_empty() {
+            return Err(Error::NoneSucceeded(failures));
+        }
+        if self.collect_errors && !errors.is_empty() {
+            errors.sort_by_key(|(name, _)| *name);
+            return Err(Error::Build(errors.into_iter().map(|(_, e)| e).collect()));
+        }
+        errors
+            .into_iter()
+            .next()
+            .map_or(Ok(outputs), |(_, e)| Err(e))
+    }
+
+    async fn run_phases(&self, run: &RunContext) -> Result<Vec<(usize, String, O)>> {
+        let cbs = self.callbacks().to_vec();
+        let phases = self.phases();
+        let tolerate_failure = self
+            .opts
+            .tolerate_failure
+            .unwrap_or(run.settings.tolerate_failure);
+        if self.opts.parallel {
+            return self.run_parallel(phases, &cbs, run, tolerate_failure).await;
+        }
+
+        if self.opts.success == GroupSuccess::AnyOf {
+            let steps: Vec<_> = phases.into_iter().flatten().map(|(s, _)| s).collect();
+            return self.run_any_of(&steps, &cbs, run).await;
+        }
+        let mut outputs = Vec::with_capacity(self.steps.len());
+        // the steps in phases which already ran
+        let mut earlier = vec![];
+        for (p, phase) in phases.iter().enumerate() {
+            // Whether each step in the phase succeeded.
+            let mut succeeded: Vec<bool> = Vec::with_capacity(phase.len());
+            for (k, (step, after)) in phase.iter().enumerate() {
+                if run.draining() {
+                    let rest = phase[k..].iter().chain(phases[p + 1..].iter().flatten());
+                    self.skip_all(rest.map(|(s, _)| *s), run);
+                    return Ok(outputs);
+                }
+                if let Some(dep) = after
+                    .iter()
+                    .find(|&&j| !succeeded[j] && !step.is_fallback_for(phase[j].0))
+                    .map(|&j| &phase[j].0.name)
+                    .or_else(|| step.failed_dependency(&earlier, run))
+                {
+                    run.status.skip();
+                    run.pipes.finish(&step.deps);
+                    let e = Error::Skipped(step.name.clone(), dep.clone());
+                    self.record(step, run, StepOutcome::Skipped, Some(&e));
+                    if !tolerate_failure {
+                        return Err(e);
+                    }
+                    succeeded.push(false);
+                    continue;
+                }
+                let name = step.name.clone();
+                let r = match self.run_step(step, &cbs, run, None).await {
+                    Err(Error::Panicked(..))
+                        if self.panic_policy(step) == PanicPolicy::Tolerate =>
+                    {
+                        succeeded.push(false);
+                        continue;
+                    }
+                    Err(e) if self.falls_back(step, &e) => {
+                        succeeded.push(false);
+                        continue;
+                    }
+                    r => r?,
+                };
+                succeeded.push(r.success());
+                if tolerate_failure || self.has_fallback(step) {
+                    let r = self.escalate(step, r)?;
+                    outputs.push((step.id, step.key.clone(), step.reduce(r)));
+                    continue;
+                }
+
+                if !r.success() {
+                    return Err(failure(&name, r));
+                }
+                outputs.push((step.id, step.key.clone(), step.reduce(r)));
+            }
+            earlier.extend(phase.iter().map(|(s, _)| s.name.clone()));
+        }
+
+        Ok(outputs)
+    }
+}
+
+/// The error of step `name`, which returned the failed output `out`.
+fn failure<O: IntoStepOutcome>(name: &str, out: O) -> Error {
+    match out.error() {
+        Some(e) => Error::Step(name.to_string(), e),
+        None => Error::UnknownStep(name.to_string()),
+    }
+}
+
+// Panics usually carry a string message, but may carry anything.
+pub(super) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+/// Returns the name of a step which step `i` of `phase` follows and which
+/// failed, in its phase or an `earlier` one, if any, unless step `i` is its
+/// fallback.
+fn failed_before<O>(
+    phase: &[(&Step<O>, Vec<usize>)],
+    i: usize,
+    done: &[Option<bool>],
+    earlier: &[String],
+    run: &RunContext,
+) -> Option<String> {
+    let (s, after) = &phase[i];
+    after
+        .iter()
+        .find(|&&j| done[j] == Some(false) && !s.is_fallback_for(phase[j].0))
+        .map(|&j| &phase[j].0.name)
+        .or_else(|| s.failed_dependency(earlier, run))
+        .cloned()
+}
+
+/// Returns which of the `pending` steps of `phase` to start next, if any,
+/// offering those whose steps they follow succeeded to `scheduler`.
+fn next_ready<O>(
+    scheduler: &dyn Scheduler,
+    phase: &[(&Step<O>, Vec<usize>)],
+    pending: &[usize],
+    done: &[Option<bool>],
+    running: usize,
+) -> Option<usize> {
+    let ready: Vec<_> = pending
+        .iter()
+        .copied()
+        .filter(|&i| {
+            phase[i].1.iter().all(|&j| {
+                done[j] == Some(true)
+                    || (done[j].is_some() && phase[i].0.is_fallback_for(phase[j].0))
+            })
+        })
+        .collect();
+    let offered: Vec<_> = ready
+        .iter()
+        .map(|&i| ScheduledStep {
+            name: &phase[i].0.name,
+            tags: &phase[i].0.opts.tags,
+            priority: phase[i].0.opts.priority,
+            position: i,
+        })
+        .collect();
+    match scheduler.next(&offered, running) {
+        Some(k) if k < ready.len() => Some(ready[k]),
+        _ if running == 0 => ready.first().copied(),
+        _ => None,
+    }
+}
+
+/// Sorts a phase's steps so every step follows the steps it depends on, and
+/// pairs each with their positions. Steps which are free to run keep their
+/// declaration order. Cycles are rejected before running, but any left are
+/// broken in declaration order.
+fn order<O>(mut steps: Vec<&Step<O>>) -> Vec<(&Step<O>, Vec<usize>)> {
+    let mut ordered: Vec<&Step<O>> = Vec::with_capacity(steps.len());
+    while !steps.is_empty() {
+        let ready = steps
+            .iter()
+            .position(|s| {
+                steps
+                    .iter()
+                    .all(|other| std::ptr::eq(*s, *other) || !s.runs_after(other))
+            })
+            .unwrap_or(0);
+        ordered.push(steps.remove(ready));
+    }
+
+    ordered
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let after = (0..i).filter(|&j| s.runs_after(ordered[j])).collect();
+            (*s, after)
+        })
+        .collect()
+}
+
+/// Sorts steps which have failed more often first, then faster steps first,
+/// keeping the order of ties. Steps without history haven't failed and run
+/// after faster steps which have it.
+fn by_history<O>(steps: &mut [&Step<O>], stats: &StepStats) {
+    steps.sort_by_cached_key(|s| {
+        let failures = stats.failure_rate(&s.name).unwrap_or_default();
+        // rates are never negative, so their bits sort the same way
+        (
+            std::cmp::Reverse(failures.to_bits()),
+            stats.p50(&s.name).unwrap_or(Duration::MAX),
+        )
+    });
+}
+
+/// Names a step after the type of its function `C`, a fn item's own name.
+/// Generic arguments are dropped. Closures have no name of their own, and
+/// every closure in a fn has the same type name, so they're rejected.
+pub(super) fn auto_name<C>() -> Result<String> {
+    let name = std::any::type_name::<C>();
+    // generic arguments may contain paths of their own
+    let path = name.split('<').next().unwrap_or(name);
+    match path.rsplit("::").next() {
+        Some(segment) if !segment.starts_with('{') => Ok(segment.to_string()),
+        _ => Err(Error::ClosureName(name.to_string())),
+    }
+}
+
+/// Shortens a type name by dropping its module paths, such as
+/// `Dep<HttpClient>` for `imperat_common::Dep<app::HttpClient>`.
+pub(super) fn short_type_name(name: &str) -> String {
+    let mut short = String::with_capacity(name.len());
+    // where the current path started in `short`
+    let mut start = 0;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            short.truncate(start);
+            continue;
+        }
+        short.push(c);
+        // paths include `{{closure}}` for types declared in closures
+        if !c.is_alphanumeric() && !matches!(c, '_' | '{' | '}') {
+            start = short.len();
+        }
+    }
+
+    short
+}
+
+/// Runs a future on tokio's blocking pool, so CPU-heavy steps don't starve
+/// the runtime's workers. Panics are resumed here, to be caught like any
+/// other step's. Outside a tokio runtime, such as under `ThreadExecutor`,
+/// this just awaits the future.
+async fn offload<F>(fut: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return fut.await;
+    };
+    match tokio::task::spawn_blocking(move || handle.block_on(fut)).await {
+        Ok(out) => out,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn before_step<O>(cbs: &[CallbackKind<O>], run: &RunContext, step: &Step<O>) {
+    for cb in cbs {
+        if let CallbackKind::BeforeStep(cb) = cb {
+            run.callbacks.call("before_step", &step.name, || cb(step));
+        }
+    }
+}
+
+fn after_step<O>(cbs: &[CallbackKind<O>], run: &RunContext, name: &str, res: &O) {
+    for cb in cbs {
+        if let CallbackKind::AfterStep(cb) = cb {
+            run.callbacks.call("after_step", name, || cb(name, res));
+        }
+    }
+}
+
+// A redactor which panics takes the output with it, so the step fails
+// whatever the run's callback panic policy.
+fn redact_output<O>(
+    cbs: &[CallbackKind<O>],
+    run: &RunContext,
+    name: &str,
+    mut out: O,
+) -> Result<O> {
+    for cb in cbs {
+        if let CallbackKind::RedactOutput(cb) = cb {
+            out = run.callbacks.try_call("redact_output", name, || cb(out))?;
+        }
+    }
+
+    Ok(out)
+}
+
+fn on_step_result<O>(
+    cbs: &[CallbackKind<O>],
+    run: &RunContext,
+    name: &str,
+    outcome: StepOutcome,
+    res: Option<&O>,
+) {
+    for cb in cbs {
+        if let CallbackKind::StepResult(cb) = cb {
+            run.callbacks
+                .call("on_step_result", name, || cb(name, outcome, res));
+        }
+    }
+}
+
+fn on_step_error<O>(
+    cbs: &[CallbackKind<O>],
+    run: &RunContext,
+    name: &str,
+    e: &(dyn std::error::Error + 'static),
+) {
+    for cb in cbs {
+        if let CallbackKind::StepError(cb) = cb {
+            run.callbacks.call("on_step_error", name, || cb(name, e));
+        }
+    }
+}
+
+async fn before_step_async<O>(cbs: &[CallbackKind<O>], run: &RunContext, step: &Step<O>) {
+    for cb in cbs {
+        if let CallbackKind::BeforeStepAsync(cb) = cb {
+            run.callbacks
+                .call_async("before_step_async", &step.name, || cb(step))
+                .await;
+        }
+    }
+}
+
+async fn after_step_async<O>(
+    cbs: &[CallbackKind<O>],
+    run: &RunContext,
+    name: &str,
+    outcome: StepOutcome,
+    res: Option<&O>,
+) {
+    for cb in cbs {
+        if let CallbackKind::AfterStepAsync(cb) = cb {
+            run.callbacks
+                .call_async("after_step_async", name, || cb(name, outcome, res))
+                .await;
+        }
+    }
+}
+
+fn on_group_start<O>(cbs: &[CallbackKind<O>], run: &RunContext, label: &str) {
+    for cb in cbs {
+        if let CallbackKind::GroupStart(cb) = cb {
+            run.callbacks.call("on_group_start", label, || cb(label));
+        }
+    }
+}
+
+fn on_group_end<O>(cbs: &[CallbackKind<O>], run: &RunContext, label: &str, error: Option<&Error>) {
+    for cb in cbs {
+        if let CallbackKind::GroupEnd(cb) = cb {
+            run.callbacks
+                .call("on_group_end", label, || cb(label, error));
+        }
+    }
+}
+
+fn on_retry<O>(cbs: &[CallbackKind<O>], run: &RunContext, name: &str, attempt: usize) {
+    for cb in cbs {
+        if let CallbackKind::Retry(cb) = cb {
+            run.callbacks.call("on_retry", name, || cb(name, attempt));
+        }
+    }
+}
+
+/// A whole pipeline turned into a group, to embed in another pipeline with
+/// `ImperativeStepBuilder::add_pipeline`. Create one with
+/// `ImperativeStepBuilder::into_group`.
+#[must_use = "pipelines do nothing until added to a builder"]
+pub struct SubPipeline<O> {
+    pub(super) group: Group<O>,
+    // dependencies added to the pipeline's builder
+    pub(super) deps: TypeMap,
+    pub(super) providers: Vec<super::providers::Provider>,
+}
+
+impl<O> SubPipeline<O> {
+    pub(super) fn new(mut group: Group<O>, children: Vec<Group<O>>, deps: TypeMap) -> Self {
+        group.children = children;
+        Self {
+            group,
+            deps,
+            providers: vec![],
+        }
+    }
+}
+
+/// Allows incrementally building groups with specific options.
+#[must_use = "groups do nothing until added to a builder"]
+pub struct GroupBuilder<O>(pub(super) Group<O>);
+
+impl<O: IntoStepOutcome + Send + 'static> GroupBuilder<O> {
+    pub(super) fn new(tm: Arc<Mutex<TypeMap>>, bindings: Bindings) -> Self {
+        GroupBuilder(Group::new(tm, bindings))
+    }
+
+    /// Internal API to start this group from a copy of `defaults`.
+    pub(super) fn inherit(mut self, defaults: &GroupOptions<O>) -> Self {
+        self.0.opts = defaults.clone();
+        self
+    }
+
+    /// Internal API to take this group's options, without any name, as
+    /// defaults for other groups. Fails if it has any steps.
+    pub(super) fn into_defaults(self) -> Result<GroupOptions<O>> {
+        let steps: Vec<_> = self.0.steps.iter().map(|s| s.name.clone()).collect();
+        if !steps.is_empty() {
+            return Err(Error::DefaultSteps(steps));
+        }
+
+        Ok(GroupOptions {
+            name: None,
+            ..self.0.opts
+        })
+    }
+
+    /// Add a step with this name to the provided group.
+    pub fn add_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self {
+        self.add(new(name, func))
+    }
+
+    /// Add a step named after `func` to the provided group. See
+    /// `ImperativeStepBuilder::add_step_auto`.
+    pub fn add_step_auto<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        mut self,
         func: C,
     ) -> Self {
-        self.0.add_step(name, func);
+        match auto_name::<C>() {
+            Ok(name) => self.add_step(&name, func),
+            Err(e) => {
+                self.0.add_error(e);
+                self
+            }
+        }
+    }
+
+    /// Add a step which runs an already built future to the provided group.
+    /// See `ImperativeStepBuilder::add_boxed_step`.
+    pub fn add_boxed_step(self, name: &str, fut: future::BoxFuture<'static, O>) -> Self {
+        self.add(new_boxed(name, fut))
+    }
+
+    /// Add a step which is a trait object to the provided group. See
+    /// `ImperativeStepBuilder::add_dyn_step`.
+    pub fn add_dyn_step(self, step: Box<dyn DynStep<O>>) -> Self {
+        self.add(new_dyn(step))
+    }
+
+    /// Add a step which calls `func` with a clone of `args` followed by its
+    /// dependencies to the provided group.
+    /// See `ImperativeStepBuilder::add_step_with_args`.
+    pub fn add_step_with_args<F, X, A: FromTypeMap>(self, name: &str, func: F, args: X) -> Self
+    where
+        WithArgs<F, X>: Callable<A, Out = O> + 'static,
+    {
+        self.add_step(name, WithArgs { func, args })
+    }
+
+    /// Add a step whose successful output is bound as a `Dep<T>` for later
+    /// steps to the provided group. See `ImperativeStepBuilder::add_step_binding`.
+    pub fn add_step_binding<T: Send + Sync + 'static, E: Send + 'static, C, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self
+    where
+        C: Callable<A, Out = std::result::Result<T, E>> + 'static,
+        O: From<std::result::Result<(), E>>,
+    {
+        let step = new_binding(name, func);
+        self.add(step)
+    }
+
+    /// Add a step whose successful output is kept in the results and also
+    /// bound as a `Dep<O>` for later steps to the provided group.
+    /// See `ImperativeStepBuilder::add_producing_step`.
+    pub fn add_producing_step<C: Callable<A, Out = O> + 'static, A: FromTypeMap>(
+        self,
+        name: &str,
+        func: C,
+    ) -> Self
+    where
+        O: Clone + Sync,
+    {
+        self.add(new(name, func).produces::<O>())
+    }
+
+    /// Add a step which only runs if `predicate` returns true to the provided
+    /// group. See `StepBuilder::run_if`.
+    pub fn add_step_if<P, B, C, A>(self, name: &str, predicate: P, func: C) -> Self
+    where
+        P: Callable<B, Out = bool> + 'static,
+        B: FromTypeMap,
+        C: Callable<A, Out = O> + 'static,
+        A: FromTypeMap,
+        O: From<Skipped>,
+    {
+        self.add(new(name, func).run_if(predicate))
+    }
+
+    /// Add a step which `undo` rolls back if a later step fails to the
+    /// provided group. See `StepBuilder::rollback`.
+    pub fn add_step_with_rollback<C, A, U, B>(self, name: &str, func: C, undo: U) -> Self
+    where
+        C: Callable<A, Out = O> + 'static,
+        A: FromTypeMap,
+        U: Callable<B> + 'static,
+        U::Out: IntoStepOutcome,
+        B: FromTypeMap,
+    {
+        self.add(new(name, func).rollback(undo))
+    }
+
+    /// Add a step built with `new_step` to the provided group.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, step: impl Into<StepBuilder<O>>) -> Self {
+        self.0.add(step.into().0);
+        self
+    }
+
+    /// Add a dependency only this group's steps see, shadowing any dependency
+    /// of the same type added to the builder. Add it before the steps using it.
+    ///
+    /// Binding steps, anywhere in the run, still bind their output for every
+    /// step, but a group's own dependency of the same type shadows it.
+    pub fn add_dep<T: Send + Sync + 'static>(mut self, dep: T) -> Self {
+        if self.0.deps.bind(dep).is_some() {
+            self.0.add_error(Error::AddDep(TypeId::of::<T>()));
+        }
         self
     }
 
-    /// Run all the steps in this group in parallel. Currently,
-    /// this implies `GroupOptions::tolerate_failure` but that may change in the future;
-    /// set both if both are desired.
+    /// Run this group's steps in `dir`: steps requesting a `WorkDir` get
+    /// this one rather than the builder's. See `ImperativeStepBuilder::work_dir`.
+    pub fn work_dir(self, dir: impl AsRef<std::path::Path>) -> Self {
+        self.add_dep(WorkDir::new(dir))
+    }
+
+    /// Pass a closure to define a child group, which runs after this group's
+    /// own steps as a group of its own. It starts with this group's options,
+    /// including callbacks, and the dependencies added to it so far, but not
+    /// its name. Unnamed child groups are labeled by their position in the
+    /// run, like any other group.
+    pub fn new_group(mut self, new_fn: impl FnOnce(GroupBuilder<O>) -> GroupBuilder<O>) -> Self {
+        let mut child = Group::new(self.0.tm.clone(), self.0.bindings.clone());
+        child.opts = GroupOptions {
+            name: None,
+            ..self.0.opts.clone()
+        };
+        child.deps = self.0.deps.clone();
+        self.0.children.push(new_fn(GroupBuilder(child)).0);
+        self
+    }
+
+    /// Name this group, for identifying it in results. See `KeyStrategy`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.0.opts.name = Some(name.to_string());
+        self
+    }
+
+    /// Run all the steps in this group in parallel. Unless this group fails
+    /// fast, this implies `GroupOptions::tolerate_failure` but that may change
+    /// in the future; set both if both are desired.
+    ///
+    /// Each step runs as its own task, so on a multi-threaded runtime steps
+    /// may run on different threads at once. Callbacks still run on the task
+    /// executing the group.
+    ///
+    /// Results are committed in phase and then declaration order, so the last
+    /// defined step wins duplicate names. Callbacks run as steps finish; see `deterministic`.
     pub fn parallel(mut self) -> Self {
         self.0.opts.parallel = true;
-        self.tolerate_failure()
+        self.0.opts.tolerate_failure.get_or_insert(true);
+        self
+    }
+
+    /// Cancel steps in this group which run longer than `limit`, with
+    /// `CancelReason::Timeout`. Overrides the builder's default step timeout.
+    pub fn step_timeout(mut self, limit: Duration) -> Self {
+        self.0.opts.step_timeout = Some(limit);
+        self
+    }
+
+    /// Run all the steps in this group in parallel, but invoke after step
+    /// callbacks in declaration order rather than as each step finishes.
+    /// Steps still start, and before step callbacks still run, in declaration
+    /// order. Implies `parallel`.
+    ///
+    /// Retried steps only invoke after step callbacks with their final result.
+    pub fn deterministic(mut self) -> Self {
+        self.0.opts.deterministic = true;
+        self.parallel()
+    }
+
+    /// Run this group in parallel, starting each step when `scheduler` says
+    /// to, such as with `Bounded` or a priority queue of its own. See
+    /// `Scheduler`. Without one, parallel groups use `Parallel`, or
+    /// `Sequential` if the profile doesn't allow parallelism.
+    pub fn scheduler(mut self, scheduler: impl Scheduler + 'static) -> Self {
+        self.0.opts.scheduler = Some(Arc::new(scheduler));
+        self.parallel()
+    }
+
+    /// Run at most `limit` steps of this parallel group at once. Waiting steps
+    /// start in order of `StepBuilder::priority`, and then declaration order.
+    pub fn max_concurrency(mut self, limit: usize) -> Self {
+        self.0.opts.max_concurrency = Some(limit);
+        self
+    }
+
+    /// Pause starting steps of this parallel group while more output is
+    /// retained than `budget` allows, such as in fan-outs with large outputs.
+    /// Share a budget between groups to limit their outputs together. See
+    /// `OutputBudget`.
+    pub fn output_budget(mut self, budget: &OutputBudget) -> Self {
+        self.0.opts.output_budget = Some(budget.clone());
+        self
+    }
+
+    /// Stop this parallel group at its first failed step, cancelling every
+    /// step still running or waiting to run, and fail with
+    /// `Error::FailFast`. By default, parallel groups run every step
+    /// regardless of failures. Tolerated panics don't count as failures.
+    pub fn fail_fast(mut self) -> Self {
+        self.0.opts.tolerate_failure = Some(false);
+        self
+    }
+
+    /// Choose what happens when a step in this group panics. By default,
+    /// the run fails with `Error::Panicked`.
+    pub fn on_panic(mut self, policy: PanicPolicy) -> Self {
+        self.0.opts.panic = policy;
+        self
+    }
+
+    /// Mark this group's steps as CPU-bound. Each step runs on tokio's
+    /// blocking pool rather than a runtime worker, so long computations
+    /// don't block other tasks, on current-thread runtimes too. Steps in a
+    /// parallel CPU-bound group each get their own blocking thread. A
+    /// cancelled step keeps its thread until it returns.
+    ///
+    /// Outside a tokio runtime, CPU-bound steps run like any other step.
+    pub fn cpu_bound(mut self) -> Self {
+        self.0.opts.cpu_bound = true;
+        self
+    }
+
+    /// Only run this group for the runs `rollout` enables, by this group's
+    /// name, or its position if unnamed. Otherwise it's left out of the run,
+    /// as if it was never added.
+    pub fn rollout(mut self, rollout: Rollout) -> Self {
+        self.0.opts.rollout = Some(rollout);
+        self
+    }
+
+    /// Run this group's steps which have failed most often in `history`
+    /// first, and then its fastest steps, so that suites of independent
+    /// checks, such as validation or smoke tests, fail as early as possible.
+    /// Pass the same stats given to `ImperativeStepBuilder::record_stats` so
+    /// each run's order follows the runs before it.
+    ///
+    /// Steps still run after the steps they depend on and in their phases,
+    /// but otherwise lose their declaration order, including in results and
+    /// callbacks of `deterministic` groups.
+    pub fn order_by_history(mut self, history: &StepStats) -> Self {
+        self.0.opts.history = Some(history.clone());
+        self
+    }
+
+    /// Label every step in this group, on top of their own tags. See
+    /// `ImperativeStepBuilder::filter_tags`. May be called more than once.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.0.opts.tags.push(tag.to_string());
+        self
+    }
+
+    /// Record the values of the environment variables `names` as the run
+    /// starts in `ExecutionReport::group_env`, under this group's label. See
+    /// `ImperativeStepBuilder::snapshot_env`.
+    pub fn snapshot_env(mut self, names: &[&str]) -> Self {
+        self.0
+            .opts
+            .env
+            .extend(names.iter().map(ToString::to_string));
+        self
+    }
+
+    /// Succeed this group if any of its steps succeeds, such as to try a
+    /// mirror and then another. Sequential groups run their steps in order
+    /// until one succeeds, skipping the rest, while parallel groups run every
+    /// step. Only the outputs of steps which succeeded are kept. If none
+    /// does, the group fails with `Error::NoneSucceeded`.
+    pub fn any_of(mut self) -> Self {
+        self.0.opts.success = GroupSuccess::AnyOf;
+        self
+    }
+
+    /// Succeed this group only if every step succeeds, unless it tolerates
+    /// failures. This is the default; it undoes `any_of`.
+    pub fn all_of(mut self) -> Self {
+        self.0.opts.success = GroupSuccess::AllOf;
+        self
     }
 
     /// Don't exit on the first failure.
     pub fn tolerate_failure(mut self) -> Self {
-        self.0.opts.tolerate_failure = true;
+        self.0.opts.tolerate_failure = Some(true);
+        self
+    }
+
+    /// Fail the group with a step's error, even though it tolerates
+    /// failures, when `pred` returns true for the step's name and failed
+    /// output, such as for data corruption which must never be ignored.
+    /// Only failures the group would otherwise tolerate are checked.
+    pub fn escalate_if(mut self, pred: impl Fn(&str, &O) -> bool + Send + Sync + 'static) -> Self {
+        self.0.opts.escalate = Some(Arc::new(pred));
+        self
+    }
+
+    /// Retry failed steps in this group up to `retries` more times, waiting
+    /// a jittered `backoff` between attempts. Retries also draw from the
+    /// run-wide budget set with `ImperativeStepBuilder::retry_budget`.
+    pub fn retry(self, retries: usize, backoff: Duration) -> Self {
+        self.retry_policy(RetryPolicy::new(retries).fixed(backoff))
+    }
+
+    /// Retry failed steps in this group per `policy`. Steps may override it
+    /// with `StepBuilder::retry`.
+    pub fn retry_policy(mut self, policy: RetryPolicy<O>) -> Self {
+        self.0.opts.retry = Some(policy);
+        self
+    }
+
+    /// Pass a callback to run for this group before a step is retried. It's
+    /// passed the step's name and the attempt about to run, counting from 1.
+    pub fn on_retry(mut self, cb: impl Fn(&str, usize) + Send + Sync + 'static) -> Self {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::Retry(Arc::new(cb)));
         self
     }
 
     /// Pass a callback to run for this group before every step.
-    pub fn before_step(mut self, cb: impl Fn(&Step<O>) + 'static) -> Self {
+    pub fn before_step(mut self, cb: impl Fn(&Step<O>) + Send + Sync + 'static) -> Self {
         self.0
             .opts
             .callbacks
@@ -214,12 +2754,596 @@ impl<O: IntoStepOutcome + 'static> GroupBuilder<O> {
         self
     }
 
-    /// Pass a callback to run for this group after every step.
-    pub fn after_step(mut self, cb: impl Fn(&str, &O) + 'static) -> Self {
+    /// Pass a callback to run for this group after every step which returns
+    /// an output, including failed ones, before a failure ends the run. Use
+    /// `after_step_async` or `on_step_result` to run after every step.
+    pub fn after_step(mut self, cb: impl Fn(&str, &O) + Send + Sync + 'static) -> Self {
         self.0
             .opts
             .callbacks
             .push(CallbackKind::AfterStep(Arc::new(cb)));
         self
     }
+
+    /// Pass a callback to run for this group once each step is done, after
+    /// any retries. It's passed the step's name, how it ended, and its output
+    /// if it returned one, as failed steps which time out or panic don't.
+    /// Steps skipped as their condition isn't met are passed their skipped
+    /// output.
+    ///
+    /// Unlike after step callbacks, it runs as soon as the step is done,
+    /// even in `deterministic` groups, and before a failure ends the run.
+    pub fn on_step_result(
+        mut self,
+        cb: impl Fn(&str, StepOutcome, Option<&O>) + Send + Sync + 'static,
+    ) -> Self {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::StepResult(Arc::new(cb)));
+        self
+    }
+
+    /// Pass a callback to run for this group once each step which failed
+    /// with an error is done, after any retries. It's passed the step's name
+    /// and either the error its output carries or the run's error, such as
+    /// for timeouts and panics, before any redaction.
+    pub fn on_step_error(
+        mut self,
+        cb: impl Fn(&str, &(dyn std::error::Error + 'static)) + Send + Sync + 'static,
+    ) -> Self {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::StepError(Arc::new(cb)));
+        self
+    }
+
+    /// Like `before_step`, but awaits the returned future before the step
+    /// runs, such as to write an audit record.
+    pub fn before_step_async<F>(
+        mut self,
+        cb: impl Fn(&Step<O>) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::BeforeStepAsync(Arc::new(move |s| {
+                Box::pin(cb(s))
+            })));
+        self
+    }
+
+    /// Like `on_step_result`, but awaits the returned future once each step
+    /// is done. Unlike `after_step`, it runs whatever the step's outcome.
+    pub fn after_step_async<F>(
+        mut self,
+        cb: impl Fn(&str, StepOutcome, Option<&O>) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::AfterStepAsync(Arc::new(
+                move |name, outcome, res| Box::pin(cb(name, outcome, res)),
+            )));
+        self
+    }
+
+    /// Pass a callback to run before this group's first step. It's passed
+    /// the group's name, or its position if unnamed.
+    pub fn on_group_start(mut self, cb: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::GroupStart(Arc::new(cb)));
+        self
+    }
+
+    /// Pass a callback to run once this group is done, whether or not it
+    /// succeeded. It's passed the group's name, or its position if unnamed,
+    /// and the group's error if it failed.
+    pub fn on_group_end(
+        mut self,
+        cb: impl Fn(&str, Option<&Error>) + Send + Sync + 'static,
+    ) -> Self {
+        self.0
+            .opts
+            .callbacks
+            .push(CallbackKind::GroupEnd(Arc::new(cb)));
+        self
+    }
+}
+
+/// Create a step with the provided name which calls `func`. Configure
+/// the returned builder and then add it to a group or builder with `add`.
+pub fn new<C, A>(name: &str, func: C) -> StepBuilder<C::Out>
+where
+    C: Callable<A> + Send + Sync + 'static,
+    A: FromTypeMap + Send,
+    C::Out: Send + 'static,
+{
+    let mut deps = vec![];
+    A::dependencies(&mut deps);
+
+    let func = Arc::new(func);
+    StepBuilder(Step {
+        name: name.to_string(),
+        key: name.to_string(),
+        id: 0,
+        deps,
+        call: Box::new(move |tm, cache| {
+            let args = cache.resolve::<A>(tm).ok_or_else(|| A::missing(tm))?;
+            let func = func.clone();
+            Ok(Box::pin(async move { func.call(args).await }))
+        }),
+        opts: StepOptions::default(),
+    })
+}
+
+/// Like `new`, but the step runs a future which was already built. It can
+/// only run once, so any retry panics. See
+/// `ImperativeStepBuilder::add_boxed_step`.
+pub(super) fn new_boxed<O: Send + 'static>(
+    name: &str,
+    fut: future::BoxFuture<'static, O>,
+) -> StepBuilder<O> {
+    let fut = Arc::new(Mutex::new(Some(fut)));
+    let step = name.to_string();
+    StepBuilder(Step {
+        name: name.to_string(),
+        key: name.to_string(),
+        id: 0,
+        deps: vec![],
+        // Steps are resolved when they're added, so the future is only taken
+        // once it's awaited.
+        call: Box::new(move |_, _| {
+            let fut = fut.clone();
+            let step = step.clone();
+            Ok(Box::pin(async move {
+                let fut = fut
+                    .lock()
+                    .expect("imperat boxed step mutex poisoned")
+                    .take();
+                match fut {
+                    Some(fut) => fut.await,
+                    None => panic!("boxed step '{step}' can't run more than once"),
+                }
+            }))
+        }),
+        opts: StepOptions::default(),
+    })
+}
+
+/// Like `new`, but the step is a trait object whose type is only known at
+/// runtime. See `ImperativeStepBuilder::add_dyn_step`.
+pub(super) fn new_dyn<O: Send + 'static>(step: Box<dyn DynStep<O>>) -> StepBuilder<O> {
+    let step: Arc<dyn DynStep<O>> = step.into();
+    let name = step.name().to_string();
+    new(&name, move || {
+        let step = step.clone();
+        async move { step.run().await }
+    })
+}
+
+/// Like `new`, but the step's successful output is bound into the type map
+/// as a `Dep<T>` and the step's result becomes `Ok(())`.
+pub(super) fn new_binding<T, E, C, A, O>(name: &str, func: C) -> StepBuilder<O>
+where
+    T: Send + Sync + 'static,
+    E: Send + 'static,
+    C: Callable<A, Out = std::result::Result<T, E>> + Send + Sync + 'static,
+    A: FromTypeMap + Send,
+    O: From<std::result::Result<(), E>> + Send + 'static,
+{
+    let mut deps = vec![];
+    A::dependencies(&mut deps);
+
+    let func = Arc::new(func);
+    // the step may run on another thread, so its output is bound once it
+    // returns to the executor
+    let bound = Arc::new(Mutex::new(None));
+    let publish = bound.clone();
+    StepBuilder(Step {
+        name: name.to_string(),
+        key: name.to_string(),
+        id: 0,
+        deps,
+        call: Box::new(move |map, cache| {
+            let args = cache.resolve::<A>(map).ok_or_else(|| A::missing(map))?;
+            let func = func.clone();
+            let bound = bound.clone();
+            Ok(Box::pin(async move {
+                let res = func.call(args).await.map(|out| {
+                    *bound.lock().expect("imperat binding mutex poisoned") = Some(out);
+                });
+                O::from(res)
+            }))
+        }),
+        opts: StepOptions {
+            binds: Some(DepInfo::of::<Dep<T>>()),
+            publish: Some(Box::new(move |_, tm| {
+                let out = publish
+                    .lock()
+                    .expect("imperat binding mutex poisoned")
+                    .take();
+                if let Some(out) = out {
+                    tm.bind(Dep::new(out));
+                }
+            })),
+            ..StepOptions::default()
+        },
+    })
+}
+
+/// Allows building a single step with specific options. Create one
+/// by calling `new_step`.
+pub struct StepBuilder<O>(pub(super) Step<O>);
+
+impl<O> StepBuilder<O> {
+    /// Cancel this step with `CancelReason::Timeout` if it runs longer than
+    /// `limit`. Overrides any timeout set on its group or builder.
+    #[must_use]
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.0.opts.timeout = Some(limit);
+        self
+    }
+
+    /// Declare a deprecated name this step was previously known by so
+    /// anything referencing the old name keeps working while it migrates.
+    /// May be called more than once.
+    #[must_use]
+    pub fn alias(mut self, old_name: &str) -> Self {
+        self.0.opts.aliases.push(old_name.to_string());
+        self
+    }
+
+    /// Label this step, such as `setup` or `slow`, for callbacks to filter or
+    /// group steps by, or to select which steps run with
+    /// `ImperativeStepBuilder::filter_tags`. See `Step::tags`. May be called
+    /// more than once.
+    #[must_use]
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.0.opts.tags.push(tag.to_string());
+        self
+    }
+
+    /// Replace this step's output with a reduced representation, such as a
+    /// sample or summary, before it's kept in the results. `reduce` takes
+    /// ownership of the full output, so it can also hand it off to a sink
+    /// rather than holding onto large values for the rest of the run.
+    ///
+    /// Reduction happens after after step callbacks, which see the full output.
+    #[must_use]
+    pub fn reduce_output(mut self, reduce: impl Fn(O) -> O + Send + Sync + 'static) -> Self {
+        self.0.opts.reduce = Some(Box::new(reduce));
+        self
+    }
+
+    /// Deduplicate this step with steps in concurrent runs sharing `flight`
+    /// and the same idempotency `key`. Only one of them runs at a time, and
+    /// the others wait for and share its output. See `SingleFlight`.
+    #[must_use]
+    pub fn single_flight(mut self, flight: &SingleFlight, key: &str) -> Self
+    where
+        O: Clone + Send + Sync + 'static,
+    {
+        let call = self.0.call;
+        let flight = flight.clone();
+        let key: Arc<str> = key.into();
+        self.0.call = Box::new(move |tm, cache| {
+            let fut = call(tm, cache)?;
+            let flight = flight.clone();
+            let key = key.clone();
+            Ok(Box::pin(async move { flight.run(&key, fut).await }))
+        });
+        self
+    }
+
+    /// Prioritize this step when its parallel group is limited by
+    /// `GroupBuilder::max_concurrency`. Higher priorities start first, and
+    /// the default priority is 0.
+    #[must_use]
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.0.opts.priority = priority;
+        self
+    }
+
+    /// Allow this step to be cancelled and queued again when a step with a
+    /// higher priority is waiting and its group is at its concurrency limit.
+    /// Preempted steps start over from scratch once a slot frees up, without
+    /// counting as a retry, so they should be safe to restart.
+    #[must_use]
+    pub fn preemptible(mut self) -> Self {
+        self.0.opts.preemptible = true;
+        self
+    }
+
+    /// Bind `dep` as a dependency only this step sees, shadowing any
+    /// dependency of the same type. Request it by its type like any other
+    /// dependency, such as `Dep<T>` when passed a `Dep<T>`.
+    #[must_use]
+    pub fn scoped_dep<T: Clone + Send + Sync + 'static>(mut self, dep: T) -> Self {
+        self.0.opts.scoped.push(Box::new(move |tm| {
+            let prev = tm.bind(dep.clone());
+            Box::new(move |tm| match prev {
+                Some(prev) => {
+                    tm.bind_arc(prev);
+                }
+                None => {
+                    tm.remove::<T>();
+                }
+            })
+        }));
+        self
+    }
+
+    /// Internal API for `matrix!` to hide any `Dep` of `value`'s type from
+    /// this step, as several of its axes have that type.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn __hide_dep<T: Send + Sync + 'static>(mut self, _: &T) -> Self {
+        self.0.opts.scoped.push(Box::new(|tm| {
+            let prev = tm.remove::<Dep<T>>();
+            Box::new(move |tm| {
+                if let Some(prev) = prev {
+                    tm.bind_arc(prev);
+                }
+            })
+        }));
+        self
+    }
+
+    /// Bind this step's successful output, converted to a `T`, as a
+    /// `Dep<T>`, so later steps can depend on it. Use `produces::<O>()` to
+    /// bind the output as is. Unlike `add_step_binding`, the output is also
+    /// kept in the results.
+    ///
+    /// Only one step in a run may bind each type, so steps producing the same
+    /// kind of output should each choose their own `T`, such as a newtype.
+    /// Otherwise, `prepare` fails with `Error::DuplicateBinding`.
+    #[must_use]
+    pub fn produces<T>(mut self) -> Self
+    where
+        O: Clone,
+        T: From<O> + Send + Sync + 'static,
+    {
+        self.0.opts.binds = Some(DepInfo::of::<Dep<T>>());
+        self.0.opts.publish = Some(Box::new(|out, tm| {
+            tm.bind(Dep::new(T::from(out.clone())));
+        }));
+        self
+    }
+
+    /// Run this step only after every named step in its group has succeeded.
+    /// Steps which also depend on what another step in their group binds run
+    /// after it as well. In parallel groups, steps without any dependency on
+    /// each other still run concurrently.
+    ///
+    /// Named steps must be in the same or an earlier phase, otherwise
+    /// `prepare` fails with `Error::DependsOn`. If a named step doesn't
+    /// succeed, this step is skipped with `Error::Skipped`, which fails its
+    /// group unless the group tolerates failure.
+    #[must_use]
+    pub fn depends_on<S: Into<String>>(mut self, steps: impl IntoIterator<Item = S>) -> Self {
+        self.0.opts.after.extend(steps.into_iter().map(Into::into));
+        self
+    }
+
+    /// Retry this step per `policy` when it fails. Overrides any retry
+    /// policy set on its group or builder.
+    #[must_use]
+    pub fn retry(mut self, policy: RetryPolicy<O>) -> Self {
+        self.0.opts.retry = Some(policy);
+        self
+    }
+
+    /// Choose what happens when this step panics. Overrides its group's
+    /// policy; see `GroupBuilder::on_panic`.
+    #[must_use]
+    pub fn on_panic(mut self, policy: PanicPolicy) -> Self {
+        self.0.opts.panic = Some(policy);
+        self
+    }
+
+    /// Apply the settings recommended for production steps in one call: a
+    /// 5 minute timeout, 3 retries with jittered exponential backoff from
+    /// 1 second up to 30 seconds, and retrying panics like any other
+    /// failure. Settings already made on this step are kept, and settings
+    /// made after override these.
+    #[must_use]
+    pub fn hardened(mut self) -> Self {
+        let o = &mut self.0.opts;
+        o.timeout.get_or_insert(Duration::from_mins(5));
+        o.retry.get_or_insert_with(|| {
+            RetryPolicy::new(3).exponential(Duration::from_secs(1), Duration::from_secs(30))
+        });
+        o.panic.get_or_insert(PanicPolicy::Retry);
+        self
+    }
+
+    /// Limit the resources this step may use. See `StepBudget`.
+    #[must_use]
+    pub fn budget(mut self, budget: StepBudget) -> Self {
+        self.0.opts.budget = budget;
+        self
+    }
+
+    /// Measure this step's output with `size` when enforcing
+    /// `StepBudget::max_output_size`, which requires it, or
+    /// `GroupBuilder::output_budget`, such as by its length in bytes.
+    #[must_use]
+    pub fn output_size(mut self, size: impl Fn(&O) -> usize + Send + Sync + 'static) -> Self {
+        self.0.opts.output_size = Some(Box::new(size));
+        self
+    }
+
+    /// Place this step in a phase. Every step in a phase finishes before any
+    /// step in a later phase of the same group starts, even in parallel groups.
+    /// Accepts a number, such as `phase(1)`, or a label, such as `phase("deploy")`.
+    /// See `Phase` for how phases are ordered.
+    #[must_use]
+    pub fn phase(mut self, phase: impl Into<Phase>) -> Self {
+        self.0.opts.phase = Some(phase.into());
+        self
+    }
+
+    /// Only run this step if `predicate` returns true when it would start.
+    /// Like a step, `predicate` may request any dependency; wrap synchronous
+    /// predicates with `sync_fn`. Otherwise, the step's result is
+    /// `O::from(Skipped)` and the run continues.
+    #[must_use]
+    pub fn run_if<C: Callable<A, Out = bool> + 'static, A: FromTypeMap>(self, predicate: C) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        self.condition(predicate, true)
+    }
+
+    /// Wait until this step is approved, by its key, before running it. See
+    /// `ImperativeStepBuilder::approvals`. If it's rejected, it fails with
+    /// `Error::Rejected`, or is skipped if it's `degradable`.
+    ///
+    /// With a `Checkpointer`, the step's approval state is recorded as it
+    /// starts waiting and once it's decided, so a run resumed after a restart
+    /// returns to the same gate rather than running earlier steps again.
+    #[must_use]
+    pub fn requires_approval(mut self) -> Self {
+        self.0.opts.approval = true;
+        self
+    }
+
+    /// Skip this step, rather than failing it, if a dependency it requests
+    /// fails its health check or a circuit breaker covering it is open. See
+    /// `ImperativeStepBuilder::health_check` and
+    /// `ImperativeStepBuilder::circuit_breaker`.
+    /// The step's result is then `O::from(Skipped)` and the run continues.
+    #[must_use]
+    pub fn degradable(mut self) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        self.0.opts.degradable = true;
+        self.0.opts.unmet = Some(|| O::from(Skipped));
+        self
+    }
+
+    /// Skip this step if any of `steps` failed, was cancelled, or was itself
+    /// skipped as a step it needed didn't succeed, even in groups which
+    /// tolerate failure. The step's result is then `O::from(Skipped)` and
+    /// the run continues. Unlike `depends_on`, this doesn't order anything:
+    /// only steps which finished before this one would start are checked,
+    /// such as those in earlier groups or earlier in a sequential group.
+    #[must_use]
+    pub fn requires_success_of<S: Into<String>>(
+        mut self,
+        steps: impl IntoIterator<Item = S>,
+    ) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        self.0
+            .opts
+            .requires
+            .extend(steps.into_iter().map(Into::into));
+        self.0.opts.unmet = Some(|| O::from(Skipped));
+        self
+    }
+
+    /// Only run this step if the step called `primary` fails, in place of
+    /// it. Otherwise this step is skipped and its result is
+    /// `O::from(Skipped)`. The pair counts as one: `primary` failing
+    /// doesn't fail its group, and steps which require its success are
+    /// satisfied if either succeeded. If both fail, the group fails with
+    /// this step's error.
+    ///
+    /// Like `depends_on`, this runs after `primary`, which must be in the
+    /// same or an earlier phase of this step's group, otherwise `prepare`
+    /// fails with `Error::DependsOn`.
+    #[must_use]
+    pub fn fallback_for(mut self, primary: &str) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        self.0.opts.fallback_for = Some(primary.to_string());
+        self.0.opts.unmet = Some(|| O::from(Skipped));
+        self
+    }
+
+    /// Skip this step if `predicate` returns true when it would start.
+    /// The inverse of `run_if`.
+    #[must_use]
+    pub fn skip_if<C: Callable<A, Out = bool> + 'static, A: FromTypeMap>(self, predicate: C) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        self.condition(predicate, false)
+    }
+
+    /// Roll this step back with `undo` if it completes but a later step
+    /// fails its group, or the run with `RollbackScope::Run`. Completed steps
+    /// are rolled back newest first, and every rollback runs even if an
+    /// earlier one fails. Failed rollbacks are returned with the run's error
+    /// in `Error::Rollback`.
+    ///
+    /// `undo` may depend on anything the step may, resolved once the step
+    /// completes. Failures a group tolerates don't roll anything back.
+    #[must_use]
+    pub fn rollback<C: Callable<A> + 'static, A: FromTypeMap>(mut self, undo: C) -> Self
+    where
+        C::Out: IntoStepOutcome,
+    {
+        A::dependencies(&mut self.0.deps);
+        let name = self.0.name.clone();
+        let undo = Arc::new(undo);
+        self.0.opts.rollback = Some(Box::new(move |tm| {
+            let args = A::retrieve_from_map(tm).ok_or_else(|| A::missing(tm))?;
+            let (undo, name) = (undo.clone(), name.clone());
+            Ok(Box::pin(async move {
+                let out = undo.call(args).await;
+                if out.success() {
+                    return Ok(());
+                }
+                Err(match out.error() {
+                    Some(e) => Error::Step(name, e),
+                    None => Error::UnknownStep(name),
+                })
+            }))
+        }));
+        self
+    }
+
+    fn condition<C: Callable<A, Out = bool> + 'static, A: FromTypeMap>(
+        mut self,
+        predicate: C,
+        run_if: bool,
+    ) -> Self
+    where
+        O: From<Skipped> + 'static,
+    {
+        A::dependencies(&mut self.0.deps);
+        let predicate = Arc::new(predicate);
+        self.0.opts.condition = Some(Box::new(move |tm| {
+            let args = A::retrieve_from_map(tm).ok_or_else(|| A::missing(tm))?;
+            let predicate = predicate.clone();
+            Ok(Box::pin(async move {
+                (predicate.call(args).await != run_if).then(|| O::from(Skipped))
+            }))
+        }));
+        self
+    }
+
+    /// Only run this step for the runs `rollout` enables, by this step's
+    /// name. Otherwise it's left out of the run and its results, as if it
+    /// was never added. Steps depending on it run without waiting for it.
+    #[must_use]
+    pub fn rollout(mut self, rollout: Rollout) -> Self {
+        self.0.opts.rollout = Some(rollout);
+        self
+    }
 }
@@ -0,0 +1,24 @@
+/// Selects which steps run by their tags, and those of their group. See
+/// `ImperativeStepBuilder::filter_tags`.
+#[derive(Clone, Debug, Default)]
+pub(super) struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub(super) fn new(include: &[&str], exclude: &[&str]) -> Self {
+        let owned = |tags: &[&str]| tags.iter().map(ToString::to_string).collect();
+        Self {
+            include: owned(include),
+            exclude: owned(exclude),
+        }
+    }
+
+    /// Returns whether a step tagged with `tags` runs: it has any included
+    /// tag, or nothing is included, and no excluded tag.
+    pub(super) fn matches(&self, tags: &[&str]) -> bool {
+        let has = |filter: &[String]| tags.iter().any(|t| filter.iter().any(|f| f == t));
+        (self.include.is_empty() || has(&self.include)) && !has(&self.exclude)
+    }
+}
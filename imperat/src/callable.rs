@@ -1,12 +1,14 @@
 use crate::FromTypeMap;
+use futures::future::BoxFuture;
 use variadics_please::all_tuples;
 
-/// Something that is callable with a specific interface.
+/// Something that is callable with a specific interface. Callables
+/// may be called more than once, such as when a step is retried.
 #[async_trait::async_trait]
-pub trait Callable<Args: FromTypeMap> {
+pub trait Callable<Args: FromTypeMap>: Send + Sync {
     type Out;
 
-    async fn call(self, args: Args) -> Self::Out;
+    async fn call(&self, args: Args) -> Self::Out;
 }
 
 // Fans out an implementation for 0 to 16-tuple of generics of Callable.
@@ -35,7 +37,7 @@ macro_rules! impl_callable_tuples {
             type Out = O;
 
             #[inline]
-            async fn call(self, ($($param,)*): ($($param,)*)) -> Self::Out {
+            async fn call(&self, ($($param,)*): ($($param,)*)) -> Self::Out {
                 (self)($($param,)*).await
             }
 
@@ -44,3 +46,114 @@ macro_rules! impl_callable_tuples {
 }
 
 all_tuples!(impl_callable_tuples, 0, 16, F);
+
+/// A synchronous function, such as a predicate, usable wherever an async
+/// function is expected. Create one with `sync_fn`.
+pub struct SyncFn<F>(F);
+
+/// Wraps a synchronous function so it can be used as a step or predicate.
+/// It runs inline when awaited, so it shouldn't block for long.
+pub fn sync_fn<F>(func: F) -> SyncFn<F> {
+    SyncFn(func)
+}
+
+// Fans out an implementation for 0 to 16-tuple of generics of Callable for
+// synchronous functions wrapped in `SyncFn`.
+macro_rules! impl_callable_sync_tuples {
+    ($($param: ident),*) => {
+        #[allow(
+            non_snake_case,
+            reason = "Certain variable names are provided by the caller, not by us."
+        )]
+        #[allow(
+            unused_variables,
+            reason = "Zero-length tuples won't use some of the parameters."
+        )]
+        #[expect(
+            clippy::allow_attributes,
+            reason = "This is in a macro, and as such, the below lints may not always apply."
+        )]
+        #[async_trait::async_trait]
+        impl<Func, O, $($param: FromTypeMap + Send + Sync),*> Callable<($($param,)*)> for SyncFn<Func>
+        where Func: Fn($($param,)*) -> O + Send + Sync,
+        {
+            type Out = O;
+
+            #[inline]
+            async fn call(&self, ($($param,)*): ($($param,)*)) -> Self::Out {
+                (self.0)($($param,)*)
+            }
+        }
+    }
+}
+
+all_tuples!(impl_callable_sync_tuples, 0, 16, F);
+
+/// A function with its leading argument bound ahead of time. Every call
+/// passes a clone of the bound argument followed by the resolved arguments.
+pub struct WithArgs<F, X> {
+    pub(crate) func: F,
+    pub(crate) args: X,
+}
+
+// Fans out an implementation for 0 to 15-tuple of generics of Callable for
+// functions whose first argument is bound by `WithArgs`.
+macro_rules! impl_callable_with_args_tuples {
+    ($($param: ident),*) => {
+        #[allow(
+            non_snake_case,
+            reason = "Certain variable names are provided by the caller, not by us."
+        )]
+        #[expect(
+            clippy::allow_attributes,
+            reason = "This is in a macro, and as such, the below lints may not always apply."
+        )]
+        #[async_trait::async_trait]
+        impl<Func, X, Fut, O, $($param: FromTypeMap + Send + Sync),*> Callable<($($param,)*)>
+            for WithArgs<Func, X>
+        where Func: Fn(X, $($param,)*) -> Fut + Send + Sync,
+              X: Clone + Send + Sync,
+              Fut: Future<Output = O> + Send,
+        {
+            type Out = O;
+
+            #[inline]
+            async fn call(&self, ($($param,)*): ($($param,)*)) -> Self::Out {
+                (self.func)(self.args.clone(), $($param,)*).await
+            }
+        }
+    }
+}
+
+all_tuples!(impl_callable_with_args_tuples, 0, 15, F);
+
+/// A step whose type is only known at runtime, such as one loaded by a
+/// plugin system from a registry or a dynamic library. Unlike a `Callable`,
+/// it's object safe, so steps can be passed around as `Box<dyn DynStep<O>>`
+/// and added with `ImperativeStepBuilder::add_dyn_step`.
+///
+/// It isn't passed any dependencies, so it should hold whatever it needs.
+///
+/// ```
+/// # use futures::future::BoxFuture;
+/// # use imperat::DynStep;
+/// struct Plugin(String);
+///
+/// impl DynStep<bool> for Plugin {
+///     fn name(&self) -> &str {
+///         &self.0
+///     }
+///
+///     fn run(&self) -> BoxFuture<'_, bool> {
+///         Box::pin(async { true })
+///     }
+/// }
+/// ```
+pub trait DynStep<O>: Send + Sync {
+    /// The step's name.
+    fn name(&self) -> &str;
+
+    /// Runs the step. Called once per attempt, so it may be called again
+    /// if the step is retried.
+    fn run(&self) -> BoxFuture<'_, O>;
+}
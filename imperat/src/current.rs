@@ -0,0 +1,68 @@
+use crate::{Attempt, RunMetadata, StepInfo};
+
+tokio::task_local! {
+    static CURRENT_RUN: CurrentRun;
+}
+
+/// The run and step being executed, for code deep inside a step which can't
+/// request extractors, such as logging helpers. Get it with `current_run`.
+#[derive(Clone, Debug)]
+pub struct CurrentRun {
+    run_id: u64,
+    step: StepInfo,
+    attempt: Attempt,
+    metadata: RunMetadata,
+}
+
+impl CurrentRun {
+    pub(crate) fn new(
+        run_id: u64,
+        step: StepInfo,
+        attempt: Attempt,
+        metadata: RunMetadata,
+    ) -> Self {
+        Self {
+            run_id,
+            step,
+            attempt,
+            metadata,
+        }
+    }
+
+    /// Returns an id for the run, unique to it among every run in practice.
+    #[must_use]
+    pub fn run_id(&self) -> u64 {
+        self.run_id
+    }
+
+    /// Returns the step being executed.
+    #[must_use]
+    pub fn step(&self) -> &StepInfo {
+        &self.step
+    }
+
+    /// Returns which attempt of the step this is.
+    #[must_use]
+    pub fn attempt(&self) -> Attempt {
+        self.attempt
+    }
+
+    /// Returns the run's metadata.
+    #[must_use]
+    pub fn metadata(&self) -> &RunMetadata {
+        &self.metadata
+    }
+
+    /// Runs `fut` with this as the current run.
+    pub(crate) fn scope<F: Future>(self, fut: F) -> impl Future<Output = F::Output> {
+        CURRENT_RUN.scope(self, fut)
+    }
+}
+
+/// Returns the run and step being executed when called from within a step,
+/// like tracing's current span. Tasks spawned by a step, including with
+/// `StepSpawner`, don't inherit it; outside of a step, it's `None`.
+#[must_use]
+pub fn current_run() -> Option<CurrentRun> {
+    CURRENT_RUN.try_with(Clone::clone).ok()
+}
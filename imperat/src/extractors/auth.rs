@@ -0,0 +1,81 @@
+use super::StepInfo;
+use crate::{FromTypeMap, TypeMap};
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Resolves a credential scoped to a single step, such as a token for the
+/// tenant named by one of its tags. Add one with
+/// `ImperativeStepBuilder::auth_provider`, and steps request an
+/// `AuthContext` for its token type.
+///
+/// ```
+/// # use futures::future::BoxFuture;
+/// # use imperat::{AuthProvider, StepInfo};
+/// # type BoxError = Box<dyn std::error::Error + Send + Sync>;
+/// struct TenantTokens;
+///
+/// impl AuthProvider for TenantTokens {
+///     type Token = String;
+///
+///     fn token<'a>(&'a self, step: &'a StepInfo) -> BoxFuture<'a, Result<String, BoxError>> {
+///         Box::pin(async move {
+///             let tenant = step
+///                 .tags()
+///                 .iter()
+///                 .find_map(|t| t.strip_prefix("tenant:"))
+///                 .ok_or("step has no tenant")?;
+///             Ok(format!("token for {tenant}"))
+///         })
+///     }
+/// }
+/// ```
+pub trait AuthProvider: Send + Sync + 'static {
+    type Token: Send + 'static;
+
+    /// Returns a credential for `step`. Called each time a step asks its
+    /// `AuthContext` for one, so providers which fetch them should cache.
+    fn token<'a>(&'a self, step: &'a StepInfo) -> BoxFuture<'a, Result<Self::Token, BoxError>>;
+}
+
+/// The credential provider for the running step, yielding tokens of type
+/// `T` scoped to it. Resolves if an `AuthProvider` with this token type was
+/// added to the builder.
+pub struct AuthContext<T> {
+    provider: Arc<dyn AuthProvider<Token = T>>,
+    step: StepInfo,
+}
+
+impl<T> Clone for AuthContext<T> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            step: self.step.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for AuthContext<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthContext")
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + 'static> AuthContext<T> {
+    /// Resolves a credential for the running step from the provider.
+    pub async fn token(&self) -> Result<T, BoxError> {
+        self.provider.token(&self.step).await
+    }
+}
+
+impl<T: Send + 'static> FromTypeMap for AuthContext<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(Self {
+            provider: tm.get::<Arc<dyn AuthProvider<Token = T>>>()?.clone(),
+            step: tm.get::<StepInfo>()?.clone(),
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Barrier;
+
+/// The named barriers in a run, which parallel steps wait on to rendezvous
+/// partway through, such as every shard finishing one stage before any of
+/// them starts the next. Add one with `ImperativeStepBuilder::barrier`.
+///
+/// A step which fails or is skipped before waiting leaves the rest waiting
+/// until they're cancelled or time out, so pair barriers with `fail_fast` or
+/// a timeout.
+#[derive(Clone, Debug, Default)]
+pub struct Barriers(Arc<HashMap<String, Arc<Barrier>>>);
+
+impl Barriers {
+    pub(crate) fn insert(&mut self, name: &str, parties: usize) {
+        Arc::make_mut(&mut self.0).insert(name.to_string(), Arc::new(Barrier::new(parties)));
+    }
+
+    /// Waits until every party is waiting on the barrier called `name`, and
+    /// then releases them all. Exactly one party of each release is its
+    /// leader, for which this returns true.
+    ///
+    /// # Panics
+    /// If there's no barrier called `name`.
+    pub async fn wait(&self, name: &str) -> bool {
+        let Some(barrier) = self.0.get(name) else {
+            panic!("no barrier called '{name}'");
+        };
+        barrier.wait().await.is_leader()
+    }
+}
+
+impl FromTypeMap for Barriers {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
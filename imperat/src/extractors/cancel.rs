@@ -0,0 +1,125 @@
+use crate::{FromTypeMap, TypeMap};
+use futures::future;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use tokio::sync::watch;
+
+/// Cancels a run, or a single step, from outside of it. Get one for a run
+/// with `ImperativeStepBuilder::cancel_handle` and call `cancel` from,
+/// for example, a Ctrl-C handler.
+///
+/// Cancellation is cooperative: steps which are already running are only
+/// notified through `Cancelled`, but no further steps will start.
+#[derive(Clone, Debug)]
+pub struct CancelHandle(Arc<watch::Sender<bool>>);
+
+impl Default for CancelHandle {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+}
+
+impl CancelHandle {
+    /// Requests cancellation.
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Returns whether cancellation was requested.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+}
+
+/// Stops a run from outside of it, either at once with `cancel` or
+/// gracefully with `drain`. Get one for a run with
+/// `ImperativeStepBuilder::controller`.
+#[derive(Clone, Debug)]
+pub struct RunController {
+    cancel: CancelHandle,
+    draining: Arc<AtomicBool>,
+}
+
+impl RunController {
+    pub(crate) fn new(cancel: CancelHandle, draining: Arc<AtomicBool>) -> Self {
+        Self { cancel, draining }
+    }
+
+    /// Requests cancellation, as `CancelHandle::cancel` does.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Requests a warm shutdown: steps which are already running finish,
+    /// but every step which hasn't started yet is skipped. Finalizers still
+    /// run, and the run succeeds with the outputs of the steps which did,
+    /// with `ExecutionReport::drained` set.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether a drain was requested.
+    #[must_use]
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether cancellation was requested.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+/// Lets a step poll for, or wait on, cancellation of itself or its run so
+/// long-running steps can clean up rather than be dropped mid-await.
+/// Request it as a step argument like any other dependency.
+#[derive(Clone, Debug)]
+pub struct Cancelled {
+    run: watch::Receiver<bool>,
+    step: watch::Receiver<bool>,
+}
+
+impl Cancelled {
+    pub(crate) fn new(run: &CancelHandle, step: &CancelHandle) -> Self {
+        Self {
+            run: run.subscribe(),
+            step: step.subscribe(),
+        }
+    }
+
+    /// Returns whether this step or its run was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        *self.run.borrow() || *self.step.borrow()
+    }
+
+    /// Resolves once this step or its run is cancelled. Never resolves
+    /// if neither can be cancelled anymore.
+    pub async fn cancelled(&self) {
+        let wait = async |mut rx: watch::Receiver<bool>| {
+            if rx.wait_for(|c| *c).await.is_err() {
+                future::pending::<()>().await;
+            }
+        };
+
+        future::select(
+            Box::pin(wait(self.run.clone())),
+            Box::pin(wait(self.step.clone())),
+        )
+        .await;
+    }
+}
+
+impl FromTypeMap for Cancelled {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
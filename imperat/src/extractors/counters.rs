@@ -0,0 +1,55 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// Lets a step count what it did, such as rows processed or bytes uploaded.
+/// Counts are kept across the step's attempts and are included in its
+/// `StepReport`. See `ExecutionReport::counters` for totals.
+#[derive(Clone, Debug, Default)]
+pub struct Counters(Arc<Mutex<BTreeMap<String, u64>>>);
+
+impl Counters {
+    /// Adds `n` to the counter `name`.
+    ///
+    /// # Panics
+    /// If the counters mutex is poisoned.
+    pub fn add(&self, name: &str, n: u64) {
+        let mut counters = self.0.lock().expect("imperat counters mutex poisoned");
+        let count = counters.entry(name.to_string()).or_default();
+        *count = count.saturating_add(n);
+    }
+
+    /// Adds one to the counter `name`.
+    pub fn increment(&self, name: &str) {
+        self.add(name, 1);
+    }
+
+    /// Returns the count of `name` so far, which is 0 if it was never added to.
+    ///
+    /// # Panics
+    /// If the counters mutex is poisoned.
+    #[must_use]
+    pub fn get(&self, name: &str) -> u64 {
+        self.snapshot().get(name).copied().unwrap_or_default()
+    }
+
+    /// Returns every count so far, by name.
+    ///
+    /// # Panics
+    /// If the counters mutex is poisoned.
+    #[must_use]
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .expect("imperat counters mutex poisoned")
+            .clone()
+    }
+}
+
+impl FromTypeMap for Counters {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{FromTypeMap, TypeMap};
+use futures::{
+    channel::oneshot,
+    future::{self, BoxFuture},
+};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+};
+
+/// Asks whoever is running a pipeline for input, such as a name, a yes or
+/// no, or a choice between options. Add one with
+/// `ImperativeStepBuilder::interact`, and steps request an `Interaction`.
+///
+/// Use `TerminalInteract` to ask on a terminal, and
+/// `test::ScriptedInteract`, with the `test-util` feature, to answer from a
+/// script in tests or automation, with the same step code.
+pub trait Interact: Send + Sync + 'static {
+    /// Asks for a line of text, without its line ending.
+    fn prompt<'a>(&'a self, message: &'a str) -> BoxFuture<'a, io::Result<String>>;
+
+    /// Asks a yes or no question, answering `default` if nothing is entered.
+    fn confirm<'a>(&'a self, message: &'a str, default: bool) -> BoxFuture<'a, io::Result<bool>>;
+
+    /// Asks to choose one of `options`, returning its index.
+    fn select<'a>(
+        &'a self,
+        message: &'a str,
+        options: &'a [&'a str],
+    ) -> BoxFuture<'a, io::Result<usize>>;
+}
+
+/// The way to ask for input while the running step runs. Resolves if an
+/// `Interact` was added to the builder.
+#[derive(Clone)]
+pub struct Interaction(Arc<dyn Interact>);
+
+impl std::fmt::Debug for Interaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Interaction").finish_non_exhaustive()
+    }
+}
+
+impl Interaction {
+    /// Asks for a line of text. See `Interact::prompt`.
+    pub async fn prompt(&self, message: &str) -> io::Result<String> {
+        self.0.prompt(message).await
+    }
+
+    /// Asks a yes or no question. See `Interact::confirm`.
+    pub async fn confirm(&self, message: &str, default: bool) -> io::Result<bool> {
+        self.0.confirm(message, default).await
+    }
+
+    /// Asks to choose one of `options`. See `Interact::select`.
+    pub async fn select(&self, message: &str, options: &[&str]) -> io::Result<usize> {
+        self.0.select(message, options).await
+    }
+}
+
+impl FromTypeMap for Interaction {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        Some(Self(tm.get::<Arc<dyn Interact>>()?.clone()))
+    }
+}
+
+type Reader = Box<dyn BufRead + Send>;
+type Writer = Box<dyn Write + Send>;
+
+/// Asks for input on a terminal: questions are written to stderr, so
+/// stdout stays free for output, and answers are read from stdin. Questions
+/// from steps running at once are asked one at a time.
+///
+/// Reading blocks, so each question is asked on its own thread.
+#[derive(Clone)]
+pub struct TerminalInteract(Arc<Mutex<(Reader, Writer)>>);
+
+impl std::fmt::Debug for TerminalInteract {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TerminalInteract").finish_non_exhaustive()
+    }
+}
+
+impl Default for TerminalInteract {
+    fn default() -> Self {
+        Self::with_io(io::BufReader::new(io::stdin()), io::stderr())
+    }
+}
+
+impl TerminalInteract {
+    /// Asks on stdin and stderr.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks by writing to `output` and reading answers from `input`, such as
+    /// a pseudo-terminal or a socket.
+    pub fn with_io(
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        Self(Arc::new(Mutex::new((Box::new(input), Box::new(output)))))
+    }
+
+    /// Asks `message` on a thread, repeating it until `parse` accepts an
+    /// answer.
+    fn ask<T: Send + 'static>(
+        &self,
+        message: String,
+        parse: impl Fn(&str) -> Option<T> + Send + 'static,
+    ) -> BoxFuture<'static, io::Result<T>> {
+        let io = self.0.clone();
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || {
+            let mut io = io.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            let (input, output) = &mut *io;
+            let res = loop {
+                if let Err(e) = write!(output, "{message}").and_then(|()| output.flush()) {
+                    break Err(e);
+                }
+                let mut line = String::new();
+                match input.read_line(&mut line) {
+                    Ok(0) => break Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(_) => {
+                        if let Some(answer) = parse(line.trim_end_matches(['\n', '\r'])) {
+                            break Ok(answer);
+                        }
+                    }
+                    Err(e) => break Err(e),
+                }
+            };
+            let _ = tx.send(res);
+        });
+        Box::pin(async move {
+            rx.await
+                .unwrap_or_else(|_| Err(io::Error::other("interaction thread panicked")))
+        })
+    }
+}
+
+impl Interact for TerminalInteract {
+    fn prompt<'a>(&'a self, message: &'a str) -> BoxFuture<'a, io::Result<String>> {
+        self.ask(format!("{message}: "), |line| Some(line.to_string()))
+    }
+
+    fn confirm<'a>(&'a self, message: &'a str, default: bool) -> BoxFuture<'a, io::Result<bool>> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        self.ask(format!("{message} [{hint}]: "), move |line| {
+            match line.trim().to_ascii_lowercase().as_str() {
+                "" => Some(default),
+                "y" | "yes" => Some(true),
+                "n" | "no" => Some(false),
+                _ => None,
+            }
+        })
+    }
+
+    fn select<'a>(
+        &'a self,
+        message: &'a str,
+        options: &'a [&'a str],
+    ) -> BoxFuture<'a, io::Result<usize>> {
+        let count = options.len();
+        if count == 0 {
+            let e = io::Error::new(io::ErrorKind::InvalidInput, "no options to select from");
+            return Box::pin(future::ready(Err(e)));
+        }
+        let mut question = format!("{message}\n");
+        for (i, option) in options.iter().enumerate() {
+            let _ = writeln!(question, "  {}) {option}", i + 1);
+        }
+        let _ = write!(question, "choose 1-{count}: ");
+        self.ask(question, move |line| {
+            let choice: usize = line.trim().parse().ok()?;
+            (1..=count).contains(&choice).then(|| choice - 1)
+        })
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
+
+/// The values of a step added by `matrix!`, by axis name. Axes are also
+/// bound as a `Dep` of their type, except axes which share a type with
+/// another axis, which are only found here.
+#[derive(Clone, Default)]
+pub struct Matrix(Arc<Vec<(&'static str, Arc<dyn Any + Send + Sync>)>>);
+
+impl Matrix {
+    /// Returns the value of `axis`, if it's an axis with values of type `T`.
+    #[must_use]
+    pub fn get<T: Any>(&self, axis: &str) -> Option<&T> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == axis)
+            .and_then(|(_, value)| value.downcast_ref())
+    }
+
+    /// Returns the axes' names, in the order they were declared.
+    pub fn axes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.iter().map(|(name, _)| *name)
+    }
+
+    /// Internal API for `matrix!` to add `axis` with its `value`.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn __with<T: Any + Send + Sync>(mut self, axis: &'static str, value: T) -> Self {
+        Arc::make_mut(&mut self.0).push((axis, Arc::new(value)));
+        self
+    }
+
+    /// Internal API for `matrix!`: returns whether `value`'s type is only
+    /// used by one axis, so it can be bound as a `Dep`.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn __unique<T: Any>(&self, _: &T) -> bool {
+        let ty = TypeId::of::<T>();
+        self.0.iter().filter(|(_, v)| (**v).type_id() == ty).count() == 1
+    }
+}
+
+impl std::fmt::Debug for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Matrix")
+            .field(&self.axes().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl FromTypeMap for Matrix {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
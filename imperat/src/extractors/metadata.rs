@@ -0,0 +1,52 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Key-value context describing the whole run, such as who triggered it or
+/// which commit it's for. Set entries with
+/// `ImperativeStepBuilder::with_metadata`. Every step can request it, and
+/// it's included wherever the run is described, such as verbose logs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunMetadata(Arc<BTreeMap<String, String>>);
+
+impl RunMetadata {
+    pub(crate) fn insert(&mut self, key: &str, value: &str) {
+        Arc::make_mut(&mut self.0).insert(key.to_string(), value.to_string());
+    }
+
+    /// Returns the value for `key`, if set.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Returns every entry, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns whether no entries are set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for RunMetadata {
+    /// Formats entries as space-separated `key=value` pairs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{k}={v}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromTypeMap for RunMetadata {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
@@ -0,0 +1,50 @@
+//! Types which steps can request as arguments. Each is resolved when its step
+//! runs, and a step which requests one that can't be resolved won't run.
+//!
+//! * `Dep<T>`, `DepMut<T>`, and `#[derive(Dependency)]` types are added to a builder with `add_dep`.
+//! * `PipeSender<T>` and `PipeReceiver<T>` are added to a builder with `pipe`.
+//! * `Option<T>` of any of these is `None` instead, and `DepOrDefault<T>` and `Preferred<T>`
+//!   fall back to `T::default()`, when nothing is bound; either way, the step still runs.
+//!   `Preferred<T>` also says which it was.
+//! * `StepInfo`, `Attempt`, `Cancelled`, `Progress`, `Counters`, and `StepSpawner` are
+//!   provided for each step by the executor and are always available.
+//! * `RunMetadata` is set on the builder and is always available.
+//! * `AuthContext<T>` is available once an `AuthProvider` of `T` is added to a builder with
+//!   `auth_provider`.
+//! * `Interaction` is available once an `Interact` is added to a builder with `interact`.
+//! * `RunRng` is seeded by the builder and is always available.
+//! * `Barriers` are added to a builder with `barrier` and are always available.
+//! * `WorkDir` is set on a builder or group with `work_dir`.
+//! * `DynamicSteps`, to add steps while the run is running, is always available.
+//! * `Matrix` is available to steps added by `matrix!`.
+//!
+//! Everything here is also in the prelude.
+mod auth;
+mod barrier;
+mod cancel;
+mod counters;
+mod interact;
+mod matrix;
+mod metadata;
+mod pipe;
+mod progress;
+mod rng;
+mod spawner;
+mod step;
+mod workdir;
+
+pub use auth::{AuthContext, AuthProvider};
+pub use barrier::Barriers;
+pub use cancel::{CancelHandle, Cancelled, RunController};
+pub use counters::Counters;
+pub use imperat_common::{Dep, DepMut, DepOrDefault, Preferred};
+pub use interact::{Interact, Interaction, TerminalInteract};
+pub use matrix::Matrix;
+pub use metadata::RunMetadata;
+pub(crate) use pipe::pipe;
+pub use pipe::{PipeReceiver, PipeSender};
+pub use progress::Progress;
+pub use rng::RunRng;
+pub use spawner::{StepSpawner, StepTask};
+pub use step::{Attempt, StepInfo};
+pub use workdir::WorkDir;
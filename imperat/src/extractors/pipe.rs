@@ -0,0 +1,60 @@
+use crate::{FromTypeMap, TypeMap};
+use std::sync::Arc;
+use tokio::sync::{Mutex, mpsc};
+
+/// Sends values to the steps receiving from a pipe. Create a pipe with
+/// `ImperativeStepBuilder::pipe`.
+///
+/// The pipe closes once every step requesting its sender has finished, and
+/// later steps can no longer request it.
+pub struct PipeSender<T>(mpsc::Sender<T>);
+
+impl<T> PipeSender<T> {
+    /// Waits for room in the pipe and then sends `value`. If every receiver
+    /// is gone, `value` is returned instead.
+    pub async fn send(&self, value: T) -> Result<(), T> {
+        self.0.send(value).await.map_err(|e| e.0)
+    }
+}
+
+impl<T> Clone for PipeSender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Send + 'static> FromTypeMap for PipeSender<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// Receives values sent to a pipe by other steps. Steps receiving from the
+/// same pipe share its values, each value going to only one of them.
+pub struct PipeReceiver<T>(Arc<Mutex<mpsc::Receiver<T>>>);
+
+impl<T> PipeReceiver<T> {
+    /// Waits for the next value, or returns `None` once the pipe is closed
+    /// and empty.
+    pub async fn recv(&self) -> Option<T> {
+        self.0.lock().await.recv().await
+    }
+}
+
+impl<T> Clone for PipeReceiver<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Send + 'static> FromTypeMap for PipeReceiver<T> {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// Creates a pipe holding up to `capacity` values before senders wait.
+pub(crate) fn pipe<T>(capacity: usize) -> (PipeSender<T>, PipeReceiver<T>) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+    (PipeSender(tx), PipeReceiver(Arc::new(Mutex::new(rx))))
+}
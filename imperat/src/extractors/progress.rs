@@ -0,0 +1,43 @@
+use crate::{FromTypeMap, StatusHandle, TypeMap};
+use std::sync::Arc;
+
+/// Lets a long-running step report how far through its work it is, such as
+/// how many of its items it has processed. While the step runs, its
+/// progress is included in the run's status. See `RunStatus::progress`.
+#[derive(Clone, Debug)]
+pub struct Progress {
+    step: Arc<str>,
+    status: StatusHandle,
+}
+
+impl Progress {
+    pub(crate) fn new(step: &str, status: &StatusHandle) -> Self {
+        Self {
+            step: step.into(),
+            status: status.clone(),
+        }
+    }
+
+    /// Set how many items this step will process in total, if known.
+    pub fn set_total(&self, total: u64) {
+        self.status
+            .update_progress(&self.step, |p| p.total = Some(total));
+    }
+
+    /// Record that `n` more items were processed.
+    pub fn advance(&self, n: u64) {
+        self.status
+            .update_progress(&self.step, |p| p.done = p.done.saturating_add(n));
+    }
+
+    /// Record that `done` items have been processed so far.
+    pub fn set(&self, done: u64) {
+        self.status.update_progress(&self.step, |p| p.done = done);
+    }
+}
+
+impl FromTypeMap for Progress {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
@@ -0,0 +1,79 @@
+use super::{Attempt, StepInfo};
+use crate::{FromTypeMap, TypeMap};
+
+/// A seeded random number generator, for steps which sample or add jitter
+/// but must be reproducible. Each attempt of each step gets its own stream,
+/// derived from the run's seed and the step's name and attempt, so a run is
+/// reproduced from its seed however its steps are scheduled.
+///
+/// The seed defaults to the run's id; set it with
+/// `ImperativeStepBuilder::seed`. It's recorded in `ExecutionReport::seed`.
+/// This isn't suitable for cryptography.
+#[derive(Clone, Debug)]
+pub struct RunRng {
+    seed: u64,
+    state: u64,
+}
+
+impl RunRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Returns the run's seed.
+    #[must_use]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the next random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        // SplitMix64
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random `f64` in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // An exponent of 0 with random mantissa bits is in [1, 2).
+        f64::from_bits(0x3ff0_0000_0000_0000 | (self.next_u64() >> 12)) - 1.0
+    }
+
+    /// Returns a random number below `n`.
+    ///
+    /// # Panics
+    /// If `n` is 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "RunRng::below requires a non-zero bound");
+        let wide = u128::from(self.next_u64()) * u128::from(n);
+        u64::try_from(wide >> 64).expect("high half of a u128 fits in a u64")
+    }
+
+    /// Shuffles `items` in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            // Both conversions are lossless as `j <= i`.
+            let bound = u64::try_from(i + 1).unwrap_or(u64::MAX);
+            let j = usize::try_from(self.below(bound)).unwrap_or(i);
+            items.swap(i, j);
+        }
+    }
+}
+
+impl FromTypeMap for RunRng {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        let seed = tm.get::<Self>()?.seed;
+        let name = tm.get::<StepInfo>().map_or("", StepInfo::name);
+        let attempt = tm.get::<Attempt>().map_or(1, |a| a.0);
+        let mut rng = Self::new(seed);
+        for b in name.bytes().chain(attempt.to_le_bytes()) {
+            rng.state ^= u64::from(b);
+            rng.state = rng.next_u64();
+        }
+        rng.state ^= seed;
+        Some(rng)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{FromTypeMap, TypeMap, builder::ExecutorHandle};
+use futures::{
+    channel::oneshot,
+    future::{self, AbortHandle, Aborted},
+};
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// Spawns tasks on behalf of a step, on the run's `Executor`. Request it as
+/// a step argument like any other dependency. Every task spawned through it
+/// is aborted once its step finishes, so nested work never outlives the
+/// step that started it.
+#[derive(Clone)]
+pub struct StepSpawner {
+    step: Arc<str>,
+    tasks: Arc<Mutex<Vec<AbortHandle>>>,
+    executor: ExecutorHandle,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl std::fmt::Debug for StepSpawner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StepSpawner")
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StepSpawner {
+    pub(crate) fn new(step: &str, executor: ExecutorHandle) -> Self {
+        Self {
+            step: step.into(),
+            tasks: Arc::default(),
+            executor,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+        }
+    }
+
+    /// Runs spawned tasks in `span`, the step's.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn in_span(mut self, span: &tracing::Span) -> Self {
+        self.span = span.clone();
+        self
+    }
+
+    /// Returns the name of the step this spawner belongs to.
+    #[must_use]
+    pub fn step(&self) -> &str {
+        &self.step
+    }
+
+    /// Spawns a task onto the run's executor. The task is aborted if it's
+    /// still running when the step finishes. With the `tracing` feature, it
+    /// runs in the step's span.
+    ///
+    /// # Panics
+    /// If the task list mutex is poisoned.
+    pub fn spawn<F>(&self, fut: F) -> StepTask<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, self.span.clone());
+        let (tx, rx) = oneshot::channel();
+        let (task, abort) = future::abortable(fut);
+        self.tasks
+            .lock()
+            .expect("imperat spawner mutex poisoned")
+            .push(abort.clone());
+        self.executor.detach(Box::pin(async move {
+            if let Ok(out) = task.await {
+                let _ = tx.send(out);
+            }
+        }));
+        StepTask { rx, abort }
+    }
+
+    /// Aborts every task spawned for this step.
+    pub(crate) fn abort_all(&self) {
+        for task in self
+            .tasks
+            .lock()
+            .expect("imperat spawner mutex poisoned")
+            .drain(..)
+        {
+            task.abort();
+        }
+    }
+}
+
+impl FromTypeMap for StepSpawner {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// A task spawned with `StepSpawner::spawn`. Await it for the task's output,
+/// or `Aborted` if it was aborted or panicked first. Dropping it doesn't
+/// stop the task.
+#[derive(Debug)]
+pub struct StepTask<T> {
+    rx: oneshot::Receiver<T>,
+    abort: AbortHandle,
+}
+
+impl<T> StepTask<T> {
+    /// Aborts the task, unless it's already finished.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+}
+
+impl<T> Future for StepTask<T> {
+    type Output = Result<T, Aborted>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|out| out.map_err(|_| Aborted))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{FromTypeMap, TypeMap};
+use std::sync::Arc;
+
+/// Describes the step which is currently running.
+#[derive(Clone, Debug)]
+pub struct StepInfo {
+    name: Arc<str>,
+    tags: Arc<[String]>,
+}
+
+impl StepInfo {
+    pub(crate) fn new(name: &str, tags: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            tags: tags.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Returns the name of the running step.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the running step's tags, after its group's. See
+    /// `StepBuilder::tag`.
+    #[must_use]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+impl FromTypeMap for StepInfo {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// Which attempt of the running step this is. The first attempt is 1,
+/// and each retry increments it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Attempt(pub usize);
+
+impl Attempt {
+    /// Returns whether this is a retry rather than the first attempt.
+    #[must_use]
+    pub fn is_retry(self) -> bool {
+        self.0 > 1
+    }
+}
+
+impl FromTypeMap for Attempt {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().copied()
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{FromTypeMap, TypeMap};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+/// The directory a step works in, such as one project's folder in a
+/// monorepo. Set it for the whole run with `ImperativeStepBuilder::work_dir`
+/// or for one group with `GroupBuilder::work_dir`, which takes precedence.
+///
+/// It's only injected into steps; the process's current directory is never
+/// changed, so groups in different directories can run at once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkDir(Arc<Path>);
+
+impl WorkDir {
+    /// Creates a working directory at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self(path.as_ref().into())
+    }
+
+    /// Returns the working directory's path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Resolves `path` against the working directory. Absolute paths are
+    /// returned as they are.
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+
+    /// Returns `path` relative to the working directory, or `None` if it's
+    /// outside of it.
+    #[must_use]
+    pub fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.0).ok()
+    }
+
+    /// Returns a working directory for `path` resolved against this one,
+    /// such as a subproject's folder.
+    #[must_use]
+    pub fn child(&self, path: impl AsRef<Path>) -> Self {
+        Self(self.join(path).into())
+    }
+
+    /// Returns a command for `program` which runs in the working directory.
+    pub fn command(&self, program: impl AsRef<std::ffi::OsStr>) -> Command {
+        let mut cmd = Command::new(program);
+        cmd.current_dir(&self.0);
+        cmd
+    }
+}
+
+impl AsRef<Path> for WorkDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl FromTypeMap for WorkDir {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
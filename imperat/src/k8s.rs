@@ -0,0 +1,109 @@
+//! Structured status output for running pipelines as Kubernetes Jobs, or
+//! under operators which wrap them.
+use crate::{BuilderError, IntoStepOutcome};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Which failures give a job a non-zero exit code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExitPolicy {
+    /// Exit non-zero if the run failed or any step failed, even if its
+    /// failure was tolerated.
+    #[default]
+    Strict,
+    /// Exit non-zero only if the run failed.
+    RunOnly,
+}
+
+/// The status of a run as Kubernetes-style conditions. The first condition
+/// is `Complete` or `Failed` for the run, followed by a condition per step
+/// result whose type is the step's result key.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub conditions: Vec<Condition>,
+    #[serde(rename = "exitCode")]
+    pub exit_code: i32,
+}
+
+/// A single status condition.
+#[derive(Clone, Debug, Serialize)]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// `"True"` or `"False"`.
+    pub status: String,
+    pub reason: String,
+    pub message: String,
+}
+
+impl Condition {
+    fn new(kind: &str, status: bool, reason: &str, message: String) -> Self {
+        Self {
+            kind: kind.to_string(),
+            status: if status { "True" } else { "False" }.to_string(),
+            reason: reason.to_string(),
+            message,
+        }
+    }
+}
+
+impl JobStatus {
+    /// Builds the status of a run from what `execute` returned.
+    #[must_use]
+    pub fn new<O: IntoStepOutcome>(
+        res: &Result<HashMap<String, O>, BuilderError>,
+        policy: ExitPolicy,
+    ) -> Self {
+        let results = match res {
+            Ok(results) => results,
+            Err(e) => {
+                return Self {
+                    conditions: vec![Condition::new("Failed", true, "RunFailed", e.to_string())],
+                    exit_code: 1,
+                };
+            }
+        };
+
+        // sorted for stable output
+        let steps: BTreeMap<_, _> = results.iter().map(|(k, r)| (k, r.success())).collect();
+        let failed = steps.values().filter(|s| !**s).count();
+        let mut conditions = vec![Condition::new(
+            "Complete",
+            true,
+            if failed == 0 {
+                "AllStepsSucceeded"
+            } else {
+                "StepFailuresTolerated"
+            },
+            format!(
+                "{} of {} steps succeeded",
+                steps.len() - failed,
+                steps.len()
+            ),
+        )];
+        conditions.extend(steps.into_iter().map(|(key, success)| {
+            let reason = if success { "Succeeded" } else { "Failed" };
+            Condition::new(key, success, reason, String::new())
+        }));
+
+        Self {
+            conditions,
+            exit_code: i32::from(failed > 0 && policy == ExitPolicy::Strict),
+        }
+    }
+
+    /// Returns this status as JSON.
+    ///
+    /// # Panics
+    /// Never, as every field is serializable.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("job status is always serializable")
+    }
+
+    /// Writes this status to `/dev/termination-log`, where Kubernetes reads
+    /// the termination message of a container.
+    pub fn write_termination_log(&self) -> std::io::Result<()> {
+        std::fs::write("/dev/termination-log", self.to_json())
+    }
+}
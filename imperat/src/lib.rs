@@ -2,17 +2,68 @@
 #![allow(clippy::missing_errors_doc)]
 mod builder;
 mod callable;
+mod current;
+pub mod extractors;
+#[cfg(feature = "k8s")]
+pub mod k8s;
+mod macros;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "test-util")]
+pub mod test;
+#[cfg(feature = "tui")]
+pub mod tui;
 
+#[cfg(feature = "inventory")]
+pub use builder::Registration;
+#[cfg(feature = "miette")]
+pub use builder::RunDiagnostic;
+#[cfg(feature = "tokio")]
+pub use builder::TokioExecutor;
 pub use builder::{
-    Error as BuilderError, ImperativeStepBuilder, IntoStepOutcome, new as new_builder,
+    Adaptive, AnyOutput, AnyStep, Approval, Approvals, Bounded, CallbackPanicPolicy, CancelReason,
+    Checkpoint, Checkpointer, Cipher, CircuitBreaker, CircuitState, DurationHistogram,
+    DynamicSteps, Error as BuilderError, ExecutionPlan, ExecutionReport, Executor, ExitCodes,
+    GroupBuilder, GroupOrder, GroupPlan, ImperativeStepBuilder, IntoStepOutcome, KeyStrategy, Lint,
+    OutputBudget, Outputs, PanicPolicy, Parallel, Phase, PipelineEvent, PipelineEvents,
+    PreparedRun, Profile, ProfileSettings, ProviderPlan, Refreshable, Registrar, Replay,
+    ReplaySpeed, RetryPolicy, RollbackScope, Rollout, RunDiff, RunResult, RunStatus, RunSummary,
+    ScheduledStep, Scheduler, Sequential, SingleFlight, Skipped, SlowerStep, StatusHandle,
+    StepBudget, StepBuilder, StepExtras, StepKey, StepOutcome, StepPlan, StepProgress, StepReport,
+    StepReturn, StepStats, StepSummary, SubPipeline, ThreadExecutor, any_output, define,
+    new as new_builder, new_any as new_any_builder, new_step, new_unit as new_unit_builder,
 };
-pub use callable::Callable;
-pub use imperat_common::{Dep, FromTypeMap, TypeMap};
-pub use imperat_macros::Dependency;
+#[cfg(feature = "failpoints")]
+pub use builder::{FailPoint, FailPoints};
+#[cfg(feature = "serde")]
+pub use builder::{JsonCheckpointer, OutputSerializers};
+pub use callable::{Callable, DynStep, SyncFn, sync_fn};
+pub use current::{CurrentRun, current_run};
+pub use extractors::{
+    Attempt, AuthContext, AuthProvider, Barriers, CancelHandle, Cancelled, Counters, Interact,
+    Interaction, Matrix, PipeReceiver, PipeSender, Progress, RunController, RunMetadata, RunRng,
+    StepInfo, StepSpawner, StepTask, TerminalInteract, WorkDir,
+};
+pub use imperat_common::{
+    Dep, DepInfo, DepMut, DepOrDefault, FromTypeMap, Preferred, ResolutionCache, SyncTypeMap,
+    TypeMap,
+};
+pub use imperat_macros::{Dependency, step};
+#[cfg(feature = "tower")]
+pub use service::PipelineService;
+
+#[cfg(feature = "inventory")]
+#[doc(hidden)]
+pub use inventory as __inventory;
+#[doc(hidden)]
+pub use macros::assert_unique_step_names as __assert_unique_step_names;
 
+/// Everything needed to build and run steps, in one import.
 pub mod prelude {
+    pub use super::extractors::*;
     pub use super::{
-        Callable, Dep, Dependency, ImperativeStepBuilder, IntoStepOutcome,
-        new_builder as new_imperative_builder,
+        Callable, Dependency, ImperativeStepBuilder, IntoStepOutcome, Outputs, Profile,
+        StepBuilder, any_output, matrix, new_any_builder, new_builder as new_imperative_builder,
+        new_step, new_unit_builder, step, steps, sync_fn,
     };
 }
@@ -0,0 +1,169 @@
+/// Adds many steps to a builder or group at once, optionally guarded by a
+/// condition. Step names must be literals. Expands into `add_step` calls,
+/// so unguarded steps behave exactly as if they were added by hand.
+///
+/// Guards are written like attributes before a step:
+///   * `#[cfg(...)]` adds the step if the `cfg!` predicate holds.
+///   * `#[env("VAR")]` adds the step if the environment variable is set
+///     when the macro runs.
+///
+/// Two unguarded steps with the same name fail to compile. Guarded steps
+/// may share a name, such as one step per platform.
+///
+/// ```
+/// # use imperat::prelude::*;
+/// let builder = steps! {
+///     new_imperative_builder(),
+///     "build" => async || Ok::<_, &str>(()),
+///     #[cfg(unix)]
+///     "set permissions" => async || Ok(()),
+///     #[env("CI")]
+///     "upload artifacts" => async || Ok(()),
+/// };
+/// ```
+#[macro_export]
+macro_rules! steps {
+    ($builder:expr, $($(#[$guard:ident $args:tt])? $name:literal => $func:expr),* $(,)?) => {{
+        const _: () = $crate::__assert_unique_step_names(&[
+            $(($name, $crate::steps!(@guarded $($guard)?))),*
+        ]);
+        let builder = $builder;
+        $(
+            let builder = if $crate::steps!(@guard $($guard $args)?) {
+                builder.add_step($name, $func)
+            } else {
+                builder
+            };
+        )*
+        builder
+    }};
+    (@guarded) => { false };
+    (@guarded $guard:ident) => { true };
+    (@guard) => { true };
+    (@guard cfg $args:tt) => { cfg! $args };
+    (@guard env ($var:expr)) => { ::std::env::var_os($var).is_some() };
+}
+
+/// Internal API for `steps!`: fails to compile, naming the step, if two
+/// unguarded steps share a name.
+///
+/// # Panics
+/// If two unguarded steps share a name.
+#[doc(hidden)]
+pub const fn assert_unique_step_names(names: &[(&str, bool)]) {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            let ((a, a_guarded), (b, b_guarded)) = (names[i], names[j]);
+            assert!(
+                a_guarded || b_guarded || !const_eq(a.as_bytes(), b.as_bytes()),
+                "{}",
+                a
+            );
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn const_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Adds a step per combination of values, like a GitHub Actions matrix.
+/// Each step is named after the template and its values, such as
+/// `test (os: Linux, version: 2)`, and each value is bound as a `Dep` only
+/// that step sees. Values must implement `Clone` and `Debug`.
+///
+/// Axes whose values share a type can't each be a `Dep`, so none of them
+/// is; steps read them by name from a `Matrix` instead.
+///
+/// ```
+/// # use imperat::prelude::*;
+/// #[derive(Clone, Debug)]
+/// enum Os {
+///     Linux,
+///     Mac,
+/// }
+///
+/// let builder = matrix! {
+///     new_imperative_builder(),
+///     "test" => async |os: Dep<Os>, version: Dep<u32>| Ok::<_, &str>(()),
+///     os: [Os::Linux, Os::Mac],
+///     version: [1u32, 2],
+/// };
+///
+/// let builder = matrix! {
+///     new_imperative_builder(),
+///     "load" => async |m: Matrix| {
+///         let (users, orders) = (m.get::<u32>("users"), m.get::<u32>("orders"));
+///         Ok::<_, &str>(())
+///     },
+///     users: [10u32, 100],
+///     orders: [1u32, 5],
+/// };
+/// ```
+#[macro_export]
+macro_rules! matrix {
+    ($builder:expr, $name:expr => $func:expr, $($axis:ident: [$($value:expr),* $(,)?]),+ $(,)?) => {{
+        let mut builder = $builder;
+        $crate::matrix!(@expand builder, $name, $func, [], $($axis [$($value),*])+);
+        builder
+    }};
+    (@expand $b:ident, $name:expr, $func:expr, [$($bound:ident)*], $axis:ident [$($value:expr),*] $($rest:tt)*) => {
+        for $axis in [$($value),*] {
+            $crate::matrix!(@expand $b, $name, $func, [$($bound)* $axis], $($rest)*);
+        }
+    };
+    (@expand $b:ident, $name:expr, $func:expr, [$($bound:ident)*], ) => {
+        let values: &[String] = &[$(format!("{}: {:?}", stringify!($bound), $bound)),*];
+        let name = format!("{} ({})", $name, values.join(", "));
+        let matrix = $crate::Matrix::default()$(.__with(stringify!($bound), $bound.clone()))*;
+        let step = $crate::new_step(&name, $func).scoped_dep(matrix.clone());
+        $(
+            let step = if matrix.__unique(&$bound) {
+                step.scoped_dep($crate::Dep::new($bound.clone()))
+            } else {
+                step.__hide_dep(&$bound)
+            };
+        )*
+        $b = $b.add(step);
+    };
+}
+
+/// Registers steps to the pipeline named `$pipeline` from anywhere in a
+/// binary, such as a library crate, to be added at startup by
+/// `ImperativeStepBuilder::add_registered`. Takes a function from the
+/// builder to the builder, which must name its output type.
+///
+/// ```
+/// use imperat::{ImperativeStepBuilder, prelude::*, register_steps};
+///
+/// register_steps!("deploy", |b: ImperativeStepBuilder<bool>| {
+///     b.add_step("migrate", async || true)
+/// });
+///
+/// let builder = new_imperative_builder::<bool>().add_registered("deploy");
+/// ```
+#[cfg(feature = "inventory")]
+#[macro_export]
+macro_rules! register_steps {
+    ($pipeline:expr, $register:expr $(,)?) => {
+        $crate::__inventory::submit! {
+            $crate::Registration::new($pipeline, module_path!(), line!(), |builder| {
+                $crate::Registration::apply(builder, $register)
+            })
+        }
+    };
+}
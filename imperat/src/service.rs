@@ -0,0 +1,39 @@
+use crate::{BuilderError, ImperativeStepBuilder, IntoStepOutcome};
+use futures::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    task::{Context, Poll},
+};
+
+/// Runs a pipeline per request as a `tower_service::Service`, so
+/// orchestration can be embedded in middleware stacks. Each request is
+/// passed to a function which builds the pipeline for it, such as by
+/// adding the request as a dependency. The response is the pipeline's results.
+pub struct PipelineService<F> {
+    build: F,
+}
+
+impl<F> PipelineService<F> {
+    /// Creates a service which builds a pipeline per request with `build`.
+    pub fn new(build: F) -> Self {
+        Self { build }
+    }
+}
+
+impl<F, Req, O> tower_service::Service<Req> for PipelineService<F>
+where
+    F: Fn(Req) -> ImperativeStepBuilder<O>,
+    O: IntoStepOutcome + Send + Sync + 'static,
+{
+    type Response = HashMap<String, O>;
+    type Error = BuilderError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        Box::pin((self.build)(req).execute())
+    }
+}
@@ -0,0 +1,807 @@
+//! Utilities for testing pipelines built with imperat. Add them as dependencies
+//! with `ImperativeStepBuilder::add_dep` and request them in steps to assert
+//! that steps really ran concurrently or serially, without relying on timing,
+//! or substitute mocks with a `TestHarness` to check how steps are wired.
+//! Run pipelines on a `DeterministicRunner` to assert the exact order their
+//! steps interleave in, and compare their reports to golden files with a
+//! `GoldenReport`. Answer interactive steps' questions from a script with a
+//! `ScriptedInteract`.
+use crate::{
+    Dep, DepInfo, ExecutionReport, Executor, FromTypeMap, ImperativeStepBuilder, Interact,
+    IntoStepOutcome, ThreadExecutor, TypeMap, builder::ExecutorHandle,
+};
+use futures::future::{self, BoxFuture};
+use std::{
+    any::TypeId,
+    collections::{HashMap, VecDeque},
+    io,
+    path::Path,
+    pin::pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+/// A barrier which steps wait on until `n` of them are waiting at once. If
+/// steps don't run concurrently, waiting panics after a timeout rather than
+/// hanging forever.
+#[derive(Clone, Debug)]
+pub struct TestBarrier {
+    barrier: Arc<tokio::sync::Barrier>,
+    limit: Duration,
+}
+
+impl TestBarrier {
+    /// Creates a barrier which releases once `n` steps are waiting on it.
+    /// Waiting panics if that doesn't happen within 5 seconds.
+    #[must_use]
+    pub fn new(n: usize) -> Self {
+        Self {
+            barrier: Arc::new(tokio::sync::Barrier::new(n)),
+            limit: Duration::from_secs(5),
+        }
+    }
+
+    /// Wait this long for every step before panicking.
+    #[must_use]
+    pub fn with_timeout(mut self, limit: Duration) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Wait until every step is waiting on this barrier.
+    ///
+    /// # Panics
+    /// If every step isn't waiting within the timeout.
+    pub async fn wait(&self) {
+        assert!(
+            // timed on its own thread so it works under any executor
+            ExecutorHandle::new(ThreadExecutor)
+                .timeout(self.limit, self.barrier.wait())
+                .await
+                .is_some(),
+            "steps did not reach the barrier concurrently within {:?}",
+            self.limit
+        );
+    }
+}
+
+impl FromTypeMap for TestBarrier {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// Records how many steps are running at once. Steps call `enter` when
+/// they start and hold the returned guard until they finish.
+#[derive(Clone, Debug, Default)]
+pub struct ConcurrencyRecorder(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    current: AtomicUsize,
+    max: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl ConcurrencyRecorder {
+    /// Creates a recorder which hasn't seen any steps.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a step as running until the returned guard is dropped.
+    #[must_use]
+    pub fn enter(&self) -> ConcurrencyGuard {
+        let current = self.0.current.fetch_add(1, Ordering::SeqCst) + 1;
+        self.0.max.fetch_max(current, Ordering::SeqCst);
+        self.0.total.fetch_add(1, Ordering::SeqCst);
+        ConcurrencyGuard(self.0.clone())
+    }
+
+    /// Returns the most steps which were running at once.
+    #[must_use]
+    pub fn max_concurrency(&self) -> usize {
+        self.0.max.load(Ordering::SeqCst)
+    }
+
+    /// Returns how many steps entered this recorder.
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.0.total.load(Ordering::SeqCst)
+    }
+
+    /// Asserts that no two steps ever ran at once.
+    ///
+    /// # Panics
+    /// If more than one step ran at once.
+    pub fn assert_serial(&self) {
+        assert!(
+            self.max_concurrency() <= 1,
+            "expected steps to run serially, but {} ran at once",
+            self.max_concurrency()
+        );
+    }
+
+    /// Asserts that at least `n` steps ran at once.
+    ///
+    /// # Panics
+    /// If fewer than `n` steps ran at once.
+    pub fn assert_concurrent(&self, n: usize) {
+        assert!(
+            self.max_concurrency() >= n,
+            "expected at least {n} steps to run at once, but at most {} did",
+            self.max_concurrency()
+        );
+    }
+}
+
+impl FromTypeMap for ConcurrencyRecorder {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+/// Marks a step as running in a `ConcurrencyRecorder` until dropped.
+#[derive(Debug)]
+pub struct ConcurrencyGuard(Arc<Counts>);
+
+impl Drop for ConcurrencyGuard {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A scripted answer to a question asked of a `ScriptedInteract`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Answer {
+    /// Answers `Interact::prompt`.
+    Text(String),
+    /// Answers `Interact::confirm`; `None` takes its default.
+    Confirm(Option<bool>),
+    /// Answers `Interact::select` with an option's index.
+    Select(usize),
+}
+
+/// Answers the questions steps ask through `Interaction` from a script,
+/// in order, so interactive pipelines can be tested or automated. Clones
+/// share their script. Add it with `ImperativeStepBuilder::interact`.
+///
+/// Questions fail if the script runs out of answers, or if the next answer
+/// is for another kind of question.
+///
+/// ```
+/// # use imperat::{prelude::*, test::ScriptedInteract};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let script = ScriptedInteract::new().text("corgi").confirm(true);
+/// let res = new_imperative_builder()
+///     .interact(script.clone())
+///     .add_step("provision", async |i: Interaction| {
+///         let name = i.prompt("name").await.unwrap();
+///         i.confirm(&format!("create {name}?"), false).await.unwrap()
+///     })
+///     .execute()
+///     .await
+///     .unwrap();
+/// assert!(res["provision"]);
+/// assert_eq!(script.asked(), ["name", "create corgi?"]);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedInteract(Arc<Mutex<Script>>);
+
+#[derive(Debug, Default)]
+struct Script {
+    answers: VecDeque<Answer>,
+    asked: Vec<String>,
+}
+
+impl ScriptedInteract {
+    /// Creates a script without any answers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `answer` to the end of the script.
+    ///
+    /// # Panics
+    /// If the script mutex is poisoned.
+    #[must_use]
+    pub fn answer(self, answer: Answer) -> Self {
+        self.0
+            .lock()
+            .expect("imperat script mutex poisoned")
+            .answers
+            .push_back(answer);
+        self
+    }
+
+    /// Adds an answer to a prompt.
+    #[must_use]
+    pub fn text(self, text: &str) -> Self {
+        self.answer(Answer::Text(text.to_string()))
+    }
+
+    /// Adds an answer to a yes or no question.
+    #[must_use]
+    pub fn confirm(self, yes: bool) -> Self {
+        self.answer(Answer::Confirm(Some(yes)))
+    }
+
+    /// Adds a choice of the option at `index`.
+    #[must_use]
+    pub fn select(self, index: usize) -> Self {
+        self.answer(Answer::Select(index))
+    }
+
+    /// Returns every question asked so far, in order.
+    ///
+    /// # Panics
+    /// If the script mutex is poisoned.
+    #[must_use]
+    pub fn asked(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .expect("imperat script mutex poisoned")
+            .asked
+            .clone()
+    }
+
+    /// Returns how many answers haven't been used yet.
+    ///
+    /// # Panics
+    /// If the script mutex is poisoned.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.0
+            .lock()
+            .expect("imperat script mutex poisoned")
+            .answers
+            .len()
+    }
+
+    /// Records `message` and takes the next answer, if `accept` takes it.
+    fn next<T>(
+        &self,
+        message: &str,
+        accept: impl FnOnce(Answer) -> Result<T, Answer>,
+    ) -> io::Result<T> {
+        let mut script = self.0.lock().expect("imperat script mutex poisoned");
+        script.asked.push(message.to_string());
+        let Some(answer) = script.answers.pop_front() else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("script has no answer for '{message}'"),
+            ));
+        };
+        accept(answer).map_err(|answer| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("script answers '{message}' with {answer:?}"),
+            )
+        })
+    }
+}
+
+impl Interact for ScriptedInteract {
+    fn prompt<'a>(&'a self, message: &'a str) -> BoxFuture<'a, io::Result<String>> {
+        let res = self.next(message, |a| match a {
+            Answer::Text(text) => Ok(text),
+            a => Err(a),
+        });
+        Box::pin(future::ready(res))
+    }
+
+    fn confirm<'a>(&'a self, message: &'a str, default: bool) -> BoxFuture<'a, io::Result<bool>> {
+        let res = self.next(message, |a| match a {
+            Answer::Confirm(yes) => Ok(yes.unwrap_or(default)),
+            a => Err(a),
+        });
+        Box::pin(future::ready(res))
+    }
+
+    fn select<'a>(
+        &'a self,
+        message: &'a str,
+        options: &'a [&'a str],
+    ) -> BoxFuture<'a, io::Result<usize>> {
+        let res = self.next(message, |a| match a {
+            Answer::Select(i) if i < options.len() => Ok(i),
+            a => Err(a),
+        });
+        Box::pin(future::ready(res))
+    }
+}
+
+// Binds a mock, returning whether it replaced a dependency.
+type MockFn = dyn Fn(&mut TypeMap) -> bool + Send + Sync;
+
+/// Substitutes mock dependencies into a builder and records which steps
+/// used them, to verify a pipeline's wiring in tests.
+///
+/// ```
+/// # use imperat::{prelude::*, test::TestHarness};
+/// # struct Db;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let harness = TestHarness::new().mock(Dep::new(Db));
+/// let builder = new_imperative_builder()
+///     .add_dep(Dep::new(Db))
+///     .add_step("migrate users", async |_db: Dep<Db>| true);
+/// harness.install(builder).execute().await.unwrap();
+/// harness.assert_dep_used::<Db>("migrate users");
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct TestHarness {
+    mocks: Vec<(DepInfo, Arc<MockFn>)>,
+    substituted: Arc<Mutex<Vec<DepInfo>>>,
+    // every dependency lookup, with the step which made it
+    uses: Arc<Mutex<Vec<(String, DepInfo)>>>,
+}
+
+impl std::fmt::Debug for TestHarness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mocks: Vec<_> = self.mocks.iter().map(|(dep, _)| dep.name).collect();
+        f.debug_struct("TestHarness")
+            .field("mocks", &mocks)
+            .field("substituted", &self.substituted)
+            .field("uses", &self.uses)
+            .finish()
+    }
+}
+
+impl TestHarness {
+    /// Creates a harness without any mocks.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Substitute `mock` for the dependency of the same type when installed,
+    /// or add it if the builder has none. Mocks replace dependencies added to
+    /// the builder, but not those only a group's steps see.
+    #[must_use]
+    pub fn mock<T: Clone + Send + Sync + 'static>(mut self, mock: T) -> Self {
+        let bind = move |tm: &mut TypeMap| tm.bind(mock.clone()).is_some();
+        self.mocks.push((DepInfo::of::<T>(), Arc::new(bind)));
+        self
+    }
+
+    /// Substitutes every mock into `builder`, and records the dependencies
+    /// its steps look up. Install after adding the dependencies to replace.
+    ///
+    /// # Panics
+    /// If the typemap or harness mutex is poisoned.
+    pub fn install<O: IntoStepOutcome + Send + 'static>(
+        &self,
+        builder: ImperativeStepBuilder<O>,
+    ) -> ImperativeStepBuilder<O> {
+        for (dep, bind) in &self.mocks {
+            if builder.with_typemap(|tm| bind(tm)) {
+                self.lock_substituted().push(*dep);
+            }
+        }
+        let uses = self.uses.clone();
+        builder.on_dep_access(move |step, dep| {
+            uses.lock()
+                .expect("imperat harness mutex poisoned")
+                .push((step.to_string(), *dep));
+        })
+    }
+
+    /// Returns the mocks which replaced a dependency, in the order they
+    /// were installed.
+    ///
+    /// # Panics
+    /// If the harness mutex is poisoned.
+    #[must_use]
+    pub fn substituted(&self) -> Vec<DepInfo> {
+        self.lock_substituted().clone()
+    }
+
+    /// Returns the steps which looked up a dependency of type `T`, or a
+    /// `Dep<T>`, in the order they first did.
+    ///
+    /// # Panics
+    /// If the harness mutex is poisoned.
+    #[must_use]
+    pub fn used_by<T: 'static>(&self) -> Vec<String> {
+        let ids = [TypeId::of::<T>(), TypeId::of::<Dep<T>>()];
+        let mut steps: Vec<String> = vec![];
+        let uses = self.uses.lock().expect("imperat harness mutex poisoned");
+        for (step, dep) in uses.iter() {
+            if ids.contains(&dep.id) && !steps.contains(step) {
+                steps.push(step.clone());
+            }
+        }
+        steps
+    }
+
+    /// Asserts that `step` looked up a dependency of type `T`, or a `Dep<T>`.
+    ///
+    /// # Panics
+    /// If it didn't.
+    pub fn assert_dep_used<T: 'static>(&self, step: &str) {
+        let steps = self.used_by::<T>();
+        assert!(
+            steps.iter().any(|s| s == step),
+            "expected step '{step}' to use {}, but only {steps:?} did",
+            std::any::type_name::<T>()
+        );
+    }
+
+    /// Asserts that `step` never looked up a dependency of type `T`, or a
+    /// `Dep<T>`.
+    ///
+    /// # Panics
+    /// If it did.
+    pub fn assert_dep_unused<T: 'static>(&self, step: &str) {
+        assert!(
+            !self.used_by::<T>().iter().any(|s| s == step),
+            "expected step '{step}' not to use {}",
+            std::any::type_name::<T>()
+        );
+    }
+
+    fn lock_substituted(&self) -> std::sync::MutexGuard<'_, Vec<DepInfo>> {
+        self.substituted
+            .lock()
+            .expect("imperat harness mutex poisoned")
+    }
+}
+
+/// Executes a run on the calling thread, polling the steps of parallel
+/// groups one at a time in the order they were started or woken, so every
+/// run of a pipeline interleaves its steps the same way. Timers run on
+/// virtual time: whenever no step can make progress, the clock jumps to the
+/// next timer, so retry delays and timeouts finish instantly and in order.
+/// Add it as a dependency so steps can `sleep` on the same clock.
+///
+/// Steps must only wait on this runner's timers and on work woken by other
+/// steps; real timers, threads and tokio tasks aren't driven by it.
+///
+/// ```
+/// # use imperat::{prelude::*, test::DeterministicRunner};
+/// # use std::time::Duration;
+/// let runner = DeterministicRunner::new();
+/// let builder = runner.install(new_imperative_builder()).new_group(|g| {
+///     g.parallel()
+///         .add_step("slow", async |r: DeterministicRunner| r.sleep(Duration::from_secs(60)).await)
+///         .add_step("fast", async |r: DeterministicRunner| r.sleep(Duration::from_secs(1)).await)
+/// });
+/// runner.run(builder.execute()).unwrap();
+/// assert_eq!(runner.now(), Duration::from_secs(60));
+/// ```
+#[derive(Clone, Default)]
+pub struct DeterministicRunner(Arc<Shared>);
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    // signalled when a task is woken from outside the runner's thread
+    woken: std::sync::Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    now: Duration,
+    // unfinished spawned tasks by id, taken out while being polled
+    tasks: HashMap<usize, BoxFuture<'static, ()>>,
+    next_task: usize,
+    // ids of woken tasks, in the order they were woken; 0 is the run itself
+    ready: VecDeque<usize>,
+    timers: Vec<Timer>,
+    next_timer: u64,
+}
+
+struct Timer {
+    deadline: Duration,
+    id: u64,
+    waker: Waker,
+}
+
+impl std::fmt::Debug for DeterministicRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let st = self.lock();
+        f.debug_struct("DeterministicRunner")
+            .field("now", &st.now)
+            .field("tasks", &st.tasks.len())
+            .field("timers", &st.timers.len())
+            .finish()
+    }
+}
+
+impl DeterministicRunner {
+    /// Creates a runner whose clock starts at zero.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `builder` to run on this runner, and adds it as a dependency
+    /// so steps can sleep on its clock.
+    #[must_use]
+    pub fn install<O: IntoStepOutcome + Send + 'static>(
+        &self,
+        builder: ImperativeStepBuilder<O>,
+    ) -> ImperativeStepBuilder<O> {
+        builder.executor(self.clone()).add_dep(self.clone())
+    }
+
+    /// Returns how much virtual time has passed.
+    ///
+    /// # Panics
+    /// If the runner's mutex is poisoned.
+    #[must_use]
+    pub fn now(&self) -> Duration {
+        self.lock().now
+    }
+
+    /// Waits for `limit` to pass on the virtual clock. If this is dropped
+    /// first, such as a timeout of a step which finished in time, its timer
+    /// is removed, so it no longer moves the clock.
+    pub async fn sleep(&self, limit: Duration) {
+        let deadline = self.now() + limit;
+        let mut timer = TimerGuard {
+            runner: self,
+            id: None,
+        };
+        future::poll_fn(|cx| {
+            let mut st = self.lock();
+            if st.now >= deadline {
+                return Poll::Ready(());
+            }
+            let id = *timer.id.get_or_insert_with(|| {
+                st.next_timer += 1;
+                st.next_timer
+            });
+            st.timers.retain(|t| t.id != id);
+            st.timers.push(Timer {
+                deadline,
+                id,
+                waker: cx.waker().clone(),
+            });
+            Poll::Pending
+        })
+        .await;
+    }
+
+    /// Drives `fut`, such as a builder's `execute`, and every task it spawns
+    /// on this runner until it completes.
+    ///
+    /// # Panics
+    /// If `fut` can't make progress: nothing is woken within 5 seconds and
+    /// no timer is pending.
+    pub fn run<F: Future>(&self, fut: F) -> F::Output {
+        let mut fut = pin!(fut);
+        self.wake(0);
+        loop {
+            let Some(id) = self.lock().ready.pop_front() else {
+                self.advance();
+                continue;
+            };
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                shared: Arc::downgrade(&self.0),
+            }));
+            let mut cx = Context::from_waker(&waker);
+            if id == 0 {
+                if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                    return out;
+                }
+                continue;
+            }
+            let Some(mut task) = self.lock().tasks.remove(&id) else {
+                continue;
+            };
+            if task.as_mut().poll(&mut cx).is_pending() {
+                self.lock().tasks.insert(id, task);
+            }
+        }
+    }
+
+    // Moves the clock to the next timer and wakes every task waiting on it,
+    // or waits for a task to be woken from another thread.
+    fn advance(&self) {
+        let mut st = self.lock();
+        let Some(deadline) = st.timers.iter().map(|t| t.deadline).min() else {
+            let (st, timeout) = self
+                .0
+                .woken
+                .wait_timeout_while(st, Duration::from_secs(5), |st| st.ready.is_empty())
+                .expect("imperat runner mutex poisoned");
+            assert!(
+                !timeout.timed_out() || !st.ready.is_empty(),
+                "run can't make progress: no step was woken and no timer is pending"
+            );
+            return;
+        };
+        st.now = deadline;
+        let (due, timers) = std::mem::take(&mut st.timers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|t| t.deadline <= deadline);
+        st.timers = timers;
+        drop(st);
+        // wake in the order the timers were set
+        let mut due = due;
+        due.sort_by_key(|t| t.id);
+        for timer in due {
+            timer.waker.wake();
+        }
+    }
+
+    fn wake(&self, id: usize) {
+        let mut st = self.lock();
+        if !st.ready.contains(&id) {
+            st.ready.push_back(id);
+        }
+        drop(st);
+        self.0.woken.notify_one();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.state.lock().expect("imperat runner mutex poisoned")
+    }
+}
+
+impl Executor for DeterministicRunner {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) {
+        let mut st = self.lock();
+        st.next_task += 1;
+        let id = st.next_task;
+        st.tasks.insert(id, fut);
+        drop(st);
+        self.wake(id);
+    }
+
+    fn sleep(&self, limit: Duration) -> BoxFuture<'static, ()> {
+        let runner = self.clone();
+        Box::pin(async move { runner.sleep(limit).await })
+    }
+}
+
+impl FromTypeMap for DeterministicRunner {
+    fn retrieve_from_map(tm: &TypeMap) -> Option<Self> {
+        tm.get::<Self>().cloned()
+    }
+}
+
+// Removes the timer of a `DeterministicRunner::sleep` when it's dropped.
+struct TimerGuard<'a> {
+    runner: &'a DeterministicRunner,
+    id: Option<u64>,
+}
+
+impl Drop for TimerGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.runner.lock().timers.retain(|t| t.id != id);
+        }
+    }
+}
+
+// Requeues a task of a `DeterministicRunner` when woken.
+struct TaskWaker {
+    id: usize,
+    shared: std::sync::Weak<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Some(shared) = self.shared.upgrade() {
+            DeterministicRunner(shared).wake(self.id);
+        }
+    }
+}
+
+/// Renders an `ExecutionReport` as text which stays the same across runs of
+/// the same pipeline, to compare against a golden file with `assert_golden`.
+/// Steps are listed in the order they were added rather than finished, and
+/// the run's id and start times are left out. Durations are too, unless
+/// rounded with `round_durations`.
+///
+/// ```text
+/// run: succeeded
+/// [0] fetch (fetch): Succeeded
+///   output: "config"
+/// [0] parse (parse): Failed
+///   error: step 'parse' failed to execute: bad config
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GoldenReport {
+    round: Option<Duration>,
+}
+
+impl GoldenReport {
+    /// Renders reports without durations.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include each step's duration, rounded to the nearest multiple of
+    /// `to`, so small differences in timing don't change the rendering.
+    #[must_use]
+    pub fn round_durations(mut self, to: Duration) -> Self {
+        self.round = Some(to);
+        self
+    }
+
+    /// Renders `report`, with each kept output's `Debug` representation.
+    #[must_use]
+    pub fn render<O: std::fmt::Debug>(&self, report: &ExecutionReport<O>) -> String {
+        use std::fmt::Write;
+
+        let mut out = match &report.error {
+            None => "run: succeeded\n".to_string(),
+            Some(e) => format!("run: failed: {e}\n"),
+        };
+        let mut steps: Vec<_> = report.steps.iter().collect();
+        steps.sort_by_key(|s| s.id);
+        for step in steps {
+            let _ = write!(
+                out,
+                "[{}] {} ({}): {:?}",
+                step.group, step.name, step.key, step.outcome
+            );
+            if let Some((to, duration)) = self.round.zip(step.duration) {
+                let _ = write!(out, " {:?}", round(duration, to));
+            }
+            out.push('\n');
+            if let Some(e) = &step.error {
+                let _ = writeln!(out, "  error: {e}");
+            }
+            if let Some(output) = report.output(step) {
+                let _ = writeln!(out, "  output: {output:?}");
+            }
+            for (name, n) in &step.counters {
+                let _ = writeln!(out, "  counter {name}: {n}");
+            }
+        }
+        out
+    }
+}
+
+// Rounds `d` to the nearest multiple of `to`.
+fn round(d: Duration, to: Duration) -> Duration {
+    let to = to.as_nanos().max(1);
+    let nanos = (d.as_nanos() + to / 2) / to * to;
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+/// Asserts that the golden file at `path` contains `actual`, such as a
+/// `GoldenReport`'s rendering. Set `IMPERAT_UPDATE_GOLDEN=1` to write
+/// `actual` to the file instead, creating it if needed.
+///
+/// # Panics
+/// If the file differs from `actual` or can't be read or written.
+pub fn assert_golden(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var_os("IMPERAT_UPDATE_GOLDEN").is_some_and(|v| v != "0") {
+        std::fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("failed to write golden file {}: {e}", path.display()));
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "failed to read golden file {}: {e}; set IMPERAT_UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "golden file {} differs; set IMPERAT_UPDATE_GOLDEN=1 to update it\n--- expected\n{expected}\n--- actual\n{actual}",
+        path.display()
+    );
+}
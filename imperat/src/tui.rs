@@ -0,0 +1,179 @@
+//! A minimal live terminal view of a run, drawn from its events, so long
+//! local runs can be followed without external tooling.
+use crate::{ExecutionPlan, PipelineEvent, PipelineEvents};
+use futures::StreamExt;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// Running, queued and finished steps per group, updated from a run's
+/// `PipelineEvent`s and redrawn in place on a terminal.
+///
+/// ```no_run
+/// # async fn run(builder: imperat::ImperativeStepBuilder<()>) {
+/// let report = builder.execute_live().await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LiveView {
+    groups: Vec<GroupView>,
+    started: Instant,
+    finished: Option<Result<(), String>>,
+    drawn: usize,
+}
+
+#[derive(Debug)]
+struct GroupView {
+    label: String,
+    total: usize,
+    running: BTreeMap<String, (usize, Instant)>,
+    done: usize,
+    failed: usize,
+    elapsed: Duration,
+}
+
+impl GroupView {
+    fn new(label: String, total: usize) -> Self {
+        Self {
+            label,
+            total,
+            running: BTreeMap::new(),
+            done: 0,
+            failed: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl LiveView {
+    /// Creates a view of a run of `plan`, with every step queued. Groups
+    /// appear in the order they run.
+    #[must_use]
+    pub fn new(plan: &ExecutionPlan) -> Self {
+        let groups = plan
+            .groups
+            .iter()
+            .filter(|g| !g.steps.is_empty())
+            // Top-level steps are reported as the group at position 0.
+            .map(|g| GroupView::new(g.label.clone().unwrap_or_else(|| "0".into()), g.steps.len()))
+            .collect();
+        Self {
+            groups,
+            started: Instant::now(),
+            finished: None,
+            drawn: 0,
+        }
+    }
+
+    fn group(&mut self, label: &str) -> &mut GroupView {
+        if let Some(i) = self.groups.iter().position(|g| g.label == label) {
+            return &mut self.groups[i];
+        }
+        self.groups.push(GroupView::new(label.to_string(), 0));
+        self.groups.last_mut().expect("group was just added")
+    }
+
+    /// Updates this view with an event from the run.
+    pub fn update(&mut self, event: &PipelineEvent) {
+        match event {
+            PipelineEvent::GroupStarted { group } => {
+                self.group(group);
+            }
+            PipelineEvent::StepStarted {
+                name,
+                group,
+                attempt,
+            } => {
+                let g = self.group(group);
+                let started = g.running.get(name).map_or_else(Instant::now, |r| r.1);
+                g.running.insert(name.clone(), (*attempt, started));
+            }
+            PipelineEvent::StepFinished {
+                name,
+                group,
+                duration,
+                success,
+                ..
+            } => {
+                let g = self.group(group);
+                g.running.remove(name);
+                g.done += 1;
+                g.failed += usize::from(!success);
+                g.elapsed += *duration;
+                // Steps which weren't planned, such as spawned ones, are
+                // counted once they finish.
+                g.total = g.total.max(g.done + g.running.len());
+            }
+            PipelineEvent::PipelineFinished { error, .. } => {
+                self.finished = Some(error.clone().map_or(Ok(()), Err));
+            }
+            PipelineEvent::StepProgress { .. } | PipelineEvent::CircuitChanged { .. } => {}
+        }
+    }
+
+    /// Renders this view as plain text: a line per group with its counts
+    /// and the total duration of its finished steps, followed by a line per
+    /// running step with how long it's been running.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for g in &self.groups {
+            let queued = g.total.saturating_sub(g.done + g.running.len());
+            let _ = write!(
+                out,
+                "{}: {} running, {queued} queued, {} done",
+                g.label,
+                g.running.len(),
+                g.done
+            );
+            if g.failed > 0 {
+                let _ = write!(out, " ({} failed)", g.failed);
+            }
+            let _ = writeln!(out, " [{:.1?}]", g.elapsed);
+            for (name, (attempt, started)) in &g.running {
+                let _ = write!(out, "  > {name} {:.1?}", started.elapsed());
+                if *attempt > 1 {
+                    let _ = write!(out, " (attempt {attempt})");
+                }
+                out.push('\n');
+            }
+        }
+        let elapsed = self.started.elapsed();
+        let _ = match &self.finished {
+            None => writeln!(out, "running for {elapsed:.1?}"),
+            Some(Ok(())) => writeln!(out, "succeeded in {elapsed:.1?}"),
+            Some(Err(e)) => writeln!(out, "failed in {elapsed:.1?}: {e}"),
+        };
+        out
+    }
+
+    /// Draws this view to `out`, replacing what it last drew there.
+    pub fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let text = self.render();
+        if self.drawn > 0 {
+            // Move to the start of the last drawing and clear it.
+            write!(out, "\x1b[{}F\x1b[J", self.drawn)?;
+        }
+        out.write_all(text.as_bytes())?;
+        out.flush()?;
+        self.drawn = text.lines().count();
+        Ok(())
+    }
+
+    /// Redraws this view to `out` after every event until the run finishes.
+    pub async fn follow(
+        mut self,
+        mut events: PipelineEvents,
+        mut out: impl Write,
+    ) -> io::Result<()> {
+        self.draw(&mut out)?;
+        while let Some(event) = events.next().await {
+            self.update(&event);
+            self.draw(&mut out)?;
+        }
+        Ok(())
+    }
+}
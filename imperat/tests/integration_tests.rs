@@ -1,8 +1,23 @@
-use imperat::{BuilderError, prelude::*};
+use imperat::{
+    Adaptive, Approval, Approvals, Bounded, BuilderError, CallbackPanicPolicy, CancelReason,
+    Checkpoint, Checkpointer, CircuitBreaker, CircuitState, Counters, DepInfo, DynamicSteps,
+    ExitCodes, GroupBuilder, GroupOrder, KeyStrategy, Lint, OutputBudget, PanicPolicy,
+    PipelineEvent, ProfileSettings, Refreshable, ReplaySpeed, RetryPolicy, RollbackScope, Rollout,
+    RunStatus, ScheduledStep, Scheduler, SingleFlight, Skipped, StepBudget, StepKey, StepOutcome,
+    StepProgress, StepReturn, StepStats, StepSummary, SubPipeline, SyncTypeMap, ThreadExecutor,
+    define,
+    prelude::*,
+    test::{
+        ConcurrencyRecorder, DeterministicRunner, GoldenReport, ScriptedInteract, TestBarrier,
+        TestHarness, assert_golden,
+    },
+};
 use std::{
+    collections::HashMap,
+    ops::ControlFlow,
     sync::{
-        LazyLock,
-        atomic::{AtomicUsize, Ordering},
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     time::{Duration, Instant},
 };
@@ -71,7 +86,7 @@ async fn test_missing_deps() {
         .execute()
         .await
         .expect_err("should have failed");
-    assert!(matches!(e, BuilderError::DepResolution(_)), "{e:?}");
+    assert!(matches!(e, BuilderError::MissingParam(_, 1, _)), "{e:?}");
 }
 
 #[derive(Error, Debug, PartialEq, Eq)]
@@ -264,3 +279,5355 @@ async fn test_callbacks_propagate_and_run() {
     b.execute().await.unwrap();
     assert_eq!(CNT.load(Ordering::Relaxed), (5 * 10 + 10) * 2);
 }
+
+// A group with retries should rerun a failing step until it succeeds.
+#[tokio::test]
+async fn test_retry_until_success() {
+    static CNT: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+
+    let res = new_imperative_builder()
+        .new_group(|gb| {
+            gb.add_step("flaky", async || CNT.fetch_add(1, Ordering::Relaxed) >= 2)
+                .retry(5, Duration::from_millis(1))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["flaky"]);
+    assert_eq!(CNT.load(Ordering::Relaxed), 3);
+}
+
+// Retries should stop once the run-wide budget is spent, even if
+// groups allow more.
+#[tokio::test]
+async fn test_retry_budget_is_shared() {
+    static CNT: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+
+    let res = new_imperative_builder()
+        .retry_budget(3)
+        .new_group(|gb| {
+            gb.add_step("one", async || {
+                CNT.fetch_add(1, Ordering::Relaxed);
+                false
+            })
+            .add_step("two", async || {
+                CNT.fetch_add(1, Ordering::Relaxed);
+                false
+            })
+            .retry(2, Duration::from_millis(1))
+            .tolerate_failure()
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(!res["one"] && !res["two"]);
+    // two first attempts plus three retries from the budget
+    assert_eq!(CNT.load(Ordering::Relaxed), 5);
+}
+
+// Steps should expose every dependency they're injected with.
+#[tokio::test]
+async fn test_step_dependencies_recorded() {
+    new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_dep(DeriveDataSource)
+        .add_step("no deps", async || {})
+        .add_step("two deps", async |_: Dep<Database>, _: DeriveDataSource| {})
+        .before_step(|s| {
+            let deps: Vec<_> = s.dependencies().iter().map(|d| d.id).collect();
+            match s.name() {
+                "no deps" => assert!(deps.is_empty()),
+                "two deps" => assert_eq!(
+                    deps,
+                    vec![
+                        DepInfo::of::<Dep<Database>>().id,
+                        DepInfo::of::<DeriveDataSource>().id
+                    ]
+                ),
+                other => panic!("unexpected step {other}"),
+            }
+        })
+        .execute()
+        .await
+        .unwrap();
+}
+
+// Tasks spawned by a step should be aborted once the step finishes.
+#[tokio::test]
+async fn test_step_spawner_aborts_with_step() {
+    static FINISHED: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+
+    let res = new_imperative_builder()
+        .add_step("spawns", async |spawner: StepSpawner| {
+            let joined = spawner.spawn(async { 1 }).await.unwrap();
+            spawner.spawn(async {
+                sleep(Duration::from_millis(20)).await;
+                FINISHED.fetch_add(1, Ordering::Relaxed);
+            });
+            joined
+        })
+        .add_step("waits", async || {
+            sleep(Duration::from_millis(40)).await;
+            0
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["spawns"], 1);
+    assert_eq!(FINISHED.load(Ordering::Relaxed), 0);
+
+    // tasks are spawned on the run's executor, even without a runtime
+    let res = futures::executor::block_on(
+        new_imperative_builder()
+            .executor(ThreadExecutor)
+            .add_step("spawns", async |spawner: StepSpawner| {
+                let task = spawner.spawn(futures::future::pending::<u32>());
+                task.abort();
+                let aborted = task.await.is_err();
+                spawner.spawn(async { 2 }).await == Ok(2) && aborted
+            })
+            .execute(),
+    )
+    .unwrap();
+    assert!(res["spawns"]);
+}
+
+// Cancelling a run should notify running steps and keep later steps
+// from starting.
+#[tokio::test]
+async fn test_cancel_notifies_steps() {
+    static OBSERVED: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+    static LATER: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+
+    let b = new_imperative_builder();
+    let handle = b.cancel_handle();
+    let e = b
+        .new_group(|gb| {
+            let handle = handle.clone();
+            gb.add_step("waits", async |c: Cancelled| {
+                tokio::select! {
+                    () = c.cancelled() => OBSERVED.fetch_add(1, Ordering::Relaxed),
+                    () = sleep(Duration::from_secs(5)) => 0,
+                };
+            })
+            .add_step("cancels", move || {
+                let handle = handle.clone();
+                async move {
+                    sleep(Duration::from_millis(5)).await;
+                    handle.cancel();
+                }
+            })
+            .parallel()
+        })
+        .new_group(|gb| {
+            gb.add_step("later", async || {
+                LATER.fetch_add(1, Ordering::Relaxed);
+            })
+        })
+        .execute()
+        .await
+        .expect_err("should have been cancelled");
+
+    assert!(
+        matches!(
+            e,
+            BuilderError::Cancelled { reason: CancelReason::Requested, ref during_step }
+                if during_step == "later"
+        ),
+        "{e:?}"
+    );
+    assert_eq!(OBSERVED.load(Ordering::Relaxed), 1);
+    assert_eq!(LATER.load(Ordering::Relaxed), 0);
+}
+
+// Timeouts should cascade from the builder to groups and steps, with the
+// most specific timeout winning.
+#[tokio::test]
+async fn test_timeout_inheritance() {
+    let slow = async || sleep(Duration::from_millis(20)).await;
+
+    // builder default applies to top-level steps
+    let e = new_imperative_builder()
+        .default_step_timeout(Duration::from_millis(5))
+        .add_step("slow", slow)
+        .execute()
+        .await
+        .expect_err("should have timed out");
+    assert!(
+        matches!(e, BuilderError::Cancelled { reason: CancelReason::Timeout, ref during_step } if during_step == "slow"),
+        "{e:?}"
+    );
+
+    // groups override the builder default
+    new_imperative_builder()
+        .default_step_timeout(Duration::from_millis(5))
+        .new_group(|gb| {
+            gb.add_step("slow", slow)
+                .step_timeout(Duration::from_millis(500))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    // steps override their group
+    let e = new_imperative_builder()
+        .new_group(|gb| {
+            gb.add(new_step("slow", slow).timeout(Duration::from_millis(5)))
+                .add_step("fast", async || ())
+                .step_timeout(Duration::from_millis(500))
+        })
+        .execute()
+        .await
+        .expect_err("should have timed out");
+    assert!(
+        matches!(e, BuilderError::Cancelled { reason: CancelReason::Timeout, ref during_step } if during_step == "slow"),
+        "{e:?}"
+    );
+}
+
+// Timeouts should be cancellations with their own reason, but still fail
+// the step and be retried like other failures.
+#[tokio::test]
+async fn test_timeout_cancel_reason() {
+    static CNT: AtomicUsize = AtomicUsize::new(0);
+
+    let report = new_imperative_builder()
+        .new_group(|gb| {
+            gb.add_step("slow", async || {
+                CNT.fetch_add(1, Ordering::Relaxed);
+                sleep(Duration::from_millis(20)).await;
+            })
+            .step_timeout(Duration::from_millis(5))
+            .retry(2, Duration::from_millis(1))
+        })
+        .execute_report()
+        .await;
+
+    assert_eq!(CNT.load(Ordering::Relaxed), 3);
+    assert_eq!(report.step("slow").unwrap().outcome, StepOutcome::Failed);
+    let e = report.error.unwrap();
+    assert_eq!(e.cancel_reason(), Some(&CancelReason::Timeout));
+    assert_eq!(e.to_string(), "step 'slow' timed out");
+}
+
+// A deterministic group should run steps concurrently but invoke after
+// step callbacks in declaration order.
+#[tokio::test]
+async fn test_deterministic_callback_order() {
+    static ORDER: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    let st = Instant::now();
+    new_imperative_builder()
+        .new_group(|mut gb| {
+            for i in 0..5 {
+                gb = gb.add_step(&format!("{i}"), move || {
+                    sleep(Duration::from_millis(25 - i * 5))
+                });
+            }
+            gb.deterministic().after_step(|name, _| {
+                ORDER.lock().unwrap().push(name.to_string());
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(
+        st.elapsed() < Duration::from_millis(100),
+        "{:?}",
+        st.elapsed()
+    );
+    assert_eq!(*ORDER.lock().unwrap(), vec!["0", "1", "2", "3", "4"]);
+}
+
+// Profiles should set run-wide defaults which can be overridden.
+#[tokio::test]
+async fn test_profiles() {
+    static CNT: LazyLock<AtomicUsize> = LazyLock::new(|| AtomicUsize::new(0));
+
+    // dev tolerates failures
+    let res = new_imperative_builder()
+        .with_profile(Profile::Dev)
+        .add_step("fails", async || false)
+        .add_step("runs anyway", async || true)
+        .execute()
+        .await
+        .unwrap();
+    assert!(!res["fails"] && res["runs anyway"]);
+
+    // ci retries, here with a quicker backoff
+    let res = new_imperative_builder()
+        .with_settings(ProfileSettings {
+            retry_backoff: Duration::from_millis(1),
+            ..Profile::Ci.settings()
+        })
+        .add_step("flaky", async || CNT.fetch_add(1, Ordering::Relaxed) >= 1)
+        .execute()
+        .await
+        .unwrap();
+    assert!(res["flaky"]);
+    assert_eq!(CNT.load(Ordering::Relaxed), 2);
+
+    // settings made before the profile still override it
+    let res = new_imperative_builder()
+        .default_step_timeout(Duration::from_millis(10))
+        .with_profile(Profile::Dev)
+        .add_step("slow", async || {
+            sleep(Duration::from_secs(1)).await;
+            true
+        })
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::Cancelled { reason: CancelReason::Timeout, during_step }) if during_step == "slow"),
+        "{res:?}"
+    );
+}
+
+// Steps should keep their deprecated names.
+#[tokio::test]
+async fn test_step_aliases() {
+    new_imperative_builder()
+        .add(
+            new_step("load config", async || ())
+                .alias("read config")
+                .alias("config"),
+        )
+        .before_step(|s| assert_eq!(s.aliases(), ["read config", "config"]))
+        .execute()
+        .await
+        .unwrap();
+}
+
+// Input hashes should only change when the hashed inputs do.
+#[cfg(feature = "serde")]
+#[test]
+fn test_input_hash() {
+    let a = new_imperative_builder::<()>()
+        .add_hashed_dep("db://prod".to_string())
+        .add_hashed_dep(3u32)
+        .add_dep(Dep::new(Database));
+    let b = new_imperative_builder::<()>()
+        .add_hashed_dep(3u32)
+        .add_hashed_dep("db://prod".to_string());
+    let c = new_imperative_builder::<()>()
+        .add_hashed_dep(3u32)
+        .add_hashed_dep("db://staging".to_string());
+
+    assert_eq!(a.input_hash(), b.input_hash());
+    assert_ne!(a.input_hash(), c.input_hash());
+}
+
+// Step-scoped extractors should describe the running step.
+#[tokio::test]
+async fn test_step_extractors() {
+    let res = new_imperative_builder()
+        .new_group(|gb| {
+            gb.add_step("flaky", async |info: StepInfo, attempt: Attempt| {
+                assert_eq!(info.name(), "flaky");
+                // fail the first attempt only
+                attempt.is_retry()
+            })
+            .retry(2, Duration::from_millis(1))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["flaky"]);
+}
+
+// Dependency lookups should be reported with the step which made them.
+#[tokio::test]
+async fn test_dep_access_audit() {
+    static ACCESSES: Mutex<Vec<(String, &'static str)>> = Mutex::new(vec![]);
+
+    new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_dep(DeriveDataSource)
+        .on_dep_access(|step, dep| {
+            ACCESSES.lock().unwrap().push((step.to_string(), dep.name));
+        })
+        .add_step("db", async |_: Dep<Database>| ())
+        .add_step("nothing", async || ())
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *ACCESSES.lock().unwrap(),
+        vec![("db".to_string(), DepInfo::of::<Dep<Database>>().name)]
+    );
+}
+
+// Reduced outputs should be kept in results while callbacks see the full output.
+#[tokio::test]
+async fn test_reduce_output() {
+    static FULL_LEN: AtomicUsize = AtomicUsize::new(0);
+
+    let res = new_imperative_builder()
+        .after_step(|_, out: &String| {
+            FULL_LEN.store(out.len(), Ordering::SeqCst);
+        })
+        .add(
+            new_step("bulk", async || "abcdefghij".repeat(100))
+                .reduce_output(|full| full.chars().step_by(100).collect()),
+        )
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(FULL_LEN.load(Ordering::SeqCst), 1000);
+    assert_eq!(res["bulk"], "aaaaaaaaaa");
+}
+
+// Preparing should surface build errors without running any steps.
+#[tokio::test]
+async fn test_prepare_then_run() {
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+    let count = async || {
+        RAN.fetch_add(1, Ordering::SeqCst);
+    };
+
+    let err = new_imperative_builder()
+        .add_step("counted", count)
+        .add_step("missing", async |_: Dep<Database>| ())
+        .prepare();
+    assert!(matches!(err, Err(BuilderError::MissingParam(..))));
+    assert_eq!(RAN.load(Ordering::SeqCst), 0);
+
+    let prepared = new_imperative_builder()
+        .add_step("counted", count)
+        .prepare()
+        .unwrap();
+    assert_eq!(RAN.load(Ordering::SeqCst), 0);
+    prepared.run().await.unwrap();
+    assert_eq!(RAN.load(Ordering::SeqCst), 1);
+}
+
+// Every failed preflight check should be reported and no steps should run.
+#[tokio::test]
+async fn test_preflight_checks() {
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+
+    let res = new_imperative_builder()
+        .add_preflight("disk space", async || Ok(()))
+        .add_preflight("network", async || Err("unreachable"))
+        .add_preflight("credentials", async || Err("expired"))
+        .add_step("deploy", async || {
+            RAN.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .execute()
+        .await;
+
+    let Err(BuilderError::Preflight(errors)) = res else {
+        panic!("expected preflight failure, got {res:?}");
+    };
+    let names: Vec<_> = errors
+        .iter()
+        .map(|e| match e {
+            BuilderError::Step(name, _) => name.as_str(),
+            e => panic!("unexpected error {e}"),
+        })
+        .collect();
+    assert_eq!(names, ["credentials", "network"]);
+    assert_eq!(RAN.load(Ordering::SeqCst), 0);
+
+    let res = new_imperative_builder()
+        .add_preflight("disk space", async || Ok::<_, &str>(()))
+        .add_step("deploy", async || Ok(()))
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 1);
+
+    // Checks which time out are collected with the rest.
+    let res = new_imperative_builder()
+        .default_step_timeout(Duration::from_millis(10))
+        .add_preflight("network", async || {
+            sleep(Duration::from_secs(1)).await;
+            Ok(())
+        })
+        .add_preflight("dns", async || {
+            sleep(Duration::from_secs(1)).await;
+            Ok(())
+        })
+        .add_preflight("credentials", async || Err("expired"))
+        .add_step("deploy", async || Ok(()))
+        .execute()
+        .await;
+    let Err(BuilderError::Preflight(errors)) = &res else {
+        panic!("expected preflight failure, got {res:?}");
+    };
+    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        [
+            "step 'credentials' failed to execute: expired",
+            "step 'dns' timed out",
+            "step 'network' timed out",
+        ]
+    );
+}
+
+// Every step in a phase should finish before a later phase starts.
+#[tokio::test]
+async fn test_phases() {
+    static ORDER: Mutex<Vec<String>> = Mutex::new(vec![]);
+    let record = |name: &'static str, ms| {
+        move || async move {
+            sleep(Duration::from_millis(ms)).await;
+            ORDER.lock().unwrap().push(name.to_string());
+        }
+    };
+
+    new_imperative_builder()
+        .new_group(|g| {
+            g.parallel()
+                .add(new_step("verify", record("verify", 1)).phase("verify"))
+                .add(new_step("deploy", record("deploy", 1)).phase("deploy"))
+                .add(new_step("build a", record("build a", 30)).phase(1))
+                .add(new_step("build b", record("build b", 10)).phase(1))
+                .add(new_step("fetch", record("fetch", 20)))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *ORDER.lock().unwrap(),
+        ["fetch", "build b", "build a", "verify", "deploy"]
+    );
+}
+
+// Guarded steps should only be added when their guard holds.
+#[tokio::test]
+async fn test_steps_macro() {
+    let res = steps! {
+        new_imperative_builder(),
+        "always" => async || (),
+        #[cfg(all())]
+        "cfg true" => async || (),
+        #[cfg(any())]
+        "cfg false" => async || (),
+        #[env("IMPERAT_TEST_UNSET_VARIABLE")]
+        "env unset" => async || (),
+    }
+    .execute()
+    .await
+    .unwrap();
+
+    let mut names: Vec<_> = res.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["always", "cfg true"]);
+}
+
+// The same function should be addable once per bound argument.
+#[tokio::test]
+async fn test_add_step_with_args() {
+    async fn process_table(table: &'static str, _: Dep<Database>) -> String {
+        format!("processed {table}")
+    }
+
+    let res = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_step_with_args("dogs", process_table, "dogs")
+        .new_group(|g| g.add_step_with_args("cats", process_table, "cats"))
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["dogs"], "processed dogs");
+    assert_eq!(res["cats"], "processed cats");
+}
+
+// A binding step's output should be injectable into later groups.
+#[tokio::test]
+async fn test_step_binding() {
+    #[derive(Debug, PartialEq)]
+    struct Token(String);
+
+    let res = new_imperative_builder()
+        .add_step_binding("login", async || {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Token("secret".to_string()))
+        })
+        .new_group(|g| {
+            g.add_step("use token", async |token: Dep<Token>| {
+                assert_eq!(**token, Token("secret".to_string()));
+                Ok(())
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), 2);
+    assert!(res.values().all(Result::is_ok));
+}
+
+// Steps which depend on each other's bindings should report the full cycle.
+#[tokio::test]
+async fn test_binding_cycle() {
+    struct A;
+    struct B;
+    struct C;
+    type Res<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    let res = new_imperative_builder::<Res<()>>()
+        .add_step_binding("a", async |_: Dep<C>| Res::Ok(A))
+        .add_step_binding("b", async |_: Dep<A>| Res::Ok(B))
+        .add_step_binding("c", async |_: Dep<B>| Res::Ok(C))
+        .execute()
+        .await;
+
+    let Err(BuilderError::Cycle(cycle)) = res else {
+        panic!("expected a cycle");
+    };
+    assert_eq!(cycle, ["a", "c", "b", "a"]);
+}
+
+// Test utilities should observe whether steps really ran concurrently.
+#[tokio::test]
+async fn test_concurrency_utilities() {
+    let recorder = ConcurrencyRecorder::new();
+    let step = async |barrier: TestBarrier, recorder: ConcurrencyRecorder| {
+        let _guard = recorder.enter();
+        barrier.wait().await;
+    };
+    new_imperative_builder()
+        .add_dep(TestBarrier::new(3))
+        .add_dep(recorder.clone())
+        .new_group(|g| {
+            g.parallel()
+                .add_step("a", step)
+                .add_step("b", step)
+                .add_step("c", step)
+        })
+        .execute()
+        .await
+        .unwrap();
+    recorder.assert_concurrent(3);
+
+    let recorder = ConcurrencyRecorder::new();
+    let step = async |recorder: ConcurrencyRecorder| {
+        let _guard = recorder.enter();
+        tokio::task::yield_now().await;
+    };
+    new_imperative_builder()
+        .add_dep(recorder.clone())
+        .add_step("a", step)
+        .add_step("b", step)
+        .execute()
+        .await
+        .unwrap();
+    recorder.assert_serial();
+    assert_eq!(recorder.total(), 2);
+}
+
+// CPU-bound steps in parallel groups should run on separate threads.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_cpu_bound_group() {
+    let recorder = ConcurrencyRecorder::new();
+    let step = async |recorder: ConcurrencyRecorder| {
+        let _guard = recorder.enter();
+        std::thread::sleep(Duration::from_millis(50));
+        (0..1000u64).sum::<u64>() == 499_500
+    };
+
+    let res = new_imperative_builder()
+        .add_dep(recorder.clone())
+        .new_group(|g| {
+            g.parallel()
+                .cpu_bound()
+                .add_step("a", step)
+                .add_step("b", step)
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res.values().all(|r| *r));
+    recorder.assert_concurrent(2);
+}
+
+// CPU-bound steps should leave the runtime's thread even on a current-thread
+// runtime, and their panics should still be caught.
+#[tokio::test]
+async fn test_cpu_bound_current_thread() {
+    let runtime = std::thread::current().id();
+    let res = new_imperative_builder()
+        .new_group(move |g| {
+            g.cpu_bound()
+                .on_panic(PanicPolicy::Tolerate)
+                .add_step("offloaded", move || async move {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    std::thread::current().id() != runtime
+                })
+                .add_step("panics", async || -> bool { panic!("bad step") })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["offloaded"]);
+    assert!(!res.contains_key("panics"));
+}
+
+// Steps in parallel groups should run on the runtime's worker threads.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_parallel_threads() {
+    type Threads = Mutex<Vec<std::thread::ThreadId>>;
+    let threads: Dep<Threads> = Dep::new(Mutex::new(vec![]));
+    let step = async |threads: Dep<Threads>| {
+        // blocks its thread, so the other step must run elsewhere
+        std::thread::sleep(Duration::from_millis(50));
+        threads.lock().unwrap().push(std::thread::current().id());
+    };
+
+    new_imperative_builder()
+        .add_dep(threads.clone())
+        .new_group(|g| g.parallel().add_step("a", step).add_step("b", step))
+        .execute()
+        .await
+        .unwrap();
+
+    let threads = threads.lock().unwrap();
+    assert_eq!(threads.len(), 2);
+    assert_ne!(threads[0], threads[1]);
+}
+
+// Runs should be spawnable on a multi-threaded runtime, callbacks and all.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_spawned_run() {
+    let seen = Arc::new(Mutex::new(vec![]));
+    let builder = new_imperative_builder()
+        .add_dep(Dep::new(2u32))
+        .after_step({
+            let seen = seen.clone();
+            move |name, _| seen.lock().unwrap().push(name.to_string())
+        })
+        .add_step("double", async |n: Dep<u32>| **n * 2);
+
+    let res = tokio::spawn(builder.execute()).await.unwrap().unwrap();
+    assert_eq!(res["double"], 4);
+    assert_eq!(*seen.lock().unwrap(), ["double"]);
+}
+
+// Results should be keyed according to the chosen strategy.
+#[tokio::test]
+async fn test_key_strategy() {
+    let build = |keys| {
+        new_imperative_builder()
+            .key_strategy(keys)
+            .add_step("a", async || ())
+            .new_group(|g| g.name("deploy").add_step("a", async || ()))
+            .new_group(|g| g.add_step("b", async || ()))
+    };
+    let keys = async |keys| {
+        let mut keys: Vec<_> = build(keys).execute().await.unwrap().into_keys().collect();
+        keys.sort();
+        keys
+    };
+
+    assert_eq!(keys(KeyStrategy::Name).await, ["a", "b"]);
+    assert_eq!(keys(KeyStrategy::Id).await, ["0", "1", "2"]);
+    assert_eq!(
+        keys(KeyStrategy::GroupQualified).await,
+        ["0/a", "2/b", "deploy/a"]
+    );
+    assert_eq!(
+        keys(KeyStrategy::Custom(Arc::new(|k: &StepKey| k
+            .name
+            .to_uppercase())))
+        .await,
+        ["A", "B"]
+    );
+}
+
+// Statistics should accumulate over many runs.
+#[tokio::test]
+async fn test_step_stats() {
+    let stats = StepStats::new().with_window(10);
+    for i in 0..20 {
+        new_imperative_builder()
+            .with_settings(ProfileSettings {
+                tolerate_failure: true,
+                ..ProfileSettings::default()
+            })
+            .record_stats(&stats)
+            .add_step("flaky", move || async move { i % 4 != 0 })
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(stats.attempts("flaky"), 10);
+    assert_eq!(stats.failure_rate("flaky"), Some(0.2));
+    assert!(stats.p50("flaky").unwrap() <= stats.p95("flaky").unwrap());
+    assert_eq!(stats.p50("missing"), None);
+}
+
+// Concurrent runs should share the output of a single-flight step.
+#[tokio::test]
+async fn test_single_flight() {
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+    let flight = SingleFlight::new();
+    let run = || {
+        new_imperative_builder()
+            .add(
+                new_step("fetch", async || {
+                    RAN.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(10)).await;
+                    "fetched".to_string()
+                })
+                .single_flight(&flight, "fetch config"),
+            )
+            .execute()
+    };
+
+    let (a, b) = tokio::join!(run(), run());
+    assert_eq!(a.unwrap()["fetch"], "fetched");
+    assert_eq!(b.unwrap()["fetch"], "fetched");
+    assert_eq!(RAN.load(Ordering::SeqCst), 1);
+
+    run().await.unwrap();
+    assert_eq!(RAN.load(Ordering::SeqCst), 2);
+}
+
+// A high priority step should preempt a preemptible step at the concurrency limit.
+#[tokio::test]
+async fn test_preemption() {
+    static ORDER: Mutex<Vec<String>> = Mutex::new(vec![]);
+    let record = |name: &'static str, ms| {
+        move || async move {
+            ORDER.lock().unwrap().push(format!("start {name}"));
+            sleep(Duration::from_millis(ms)).await;
+            ORDER.lock().unwrap().push(format!("end {name}"));
+        }
+    };
+
+    new_imperative_builder()
+        .new_group(|g| {
+            g.parallel()
+                .max_concurrency(1)
+                .add(new_step("background", record("background", 20)).preemptible())
+                .add(new_step("critical", record("critical", 1)).priority(10))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        *ORDER.lock().unwrap(),
+        [
+            "start background",
+            "start critical",
+            "end critical",
+            "start background",
+            "end background"
+        ]
+    );
+}
+
+// Panicking steps should be handled according to their group's panic policy.
+#[tokio::test]
+async fn test_panic_policy() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    let panics = async || -> bool { panic!("boom") };
+
+    let res = new_imperative_builder()
+        .add_step("panics", panics)
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::Panicked(name, msg)) if name == "panics" && msg == "boom")
+    );
+
+    let res = new_imperative_builder()
+        .new_group(|g| {
+            g.on_panic(PanicPolicy::Tolerate)
+                .add_step("panics", panics)
+                .add_step("fine", async || true)
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res.keys().collect::<Vec<_>>(), ["fine"]);
+
+    let res = new_imperative_builder()
+        .new_group(|g| {
+            g.on_panic(PanicPolicy::Retry)
+                .retry(2, Duration::ZERO)
+                .add_step("flaky", async || {
+                    assert!(ATTEMPTS.fetch_add(1, Ordering::SeqCst) > 0, "first attempt");
+                    true
+                })
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert!(res["flaky"]);
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+}
+
+// Panicking callbacks should be caught, failing the run or only warning per
+// the builder's policy, and always be reported.
+#[tokio::test]
+async fn test_callback_panic() {
+    let report = new_imperative_builder()
+        .after_step(|name, _| assert_ne!(name, "first", "bad callback"))
+        .add_step("first", async || true)
+        .add_step("second", async || true)
+        .execute_report()
+        .await;
+    assert!(matches!(
+        &report.error,
+        Some(BuilderError::CallbackFailed(cb, target, msg))
+            if cb == "after_step" && target == "first" && msg.contains("bad callback")
+    ));
+    assert_eq!(report.callback_failures.len(), 1);
+
+    let report = new_imperative_builder()
+        .on_callback_panic(CallbackPanicPolicy::Warn)
+        .on_group_start(|_| panic!("bad callback"))
+        .before_step(|s| {
+            if s.name() == "first" {
+                panic!("bad callback");
+            }
+        })
+        .add_step("first", async || true)
+        .add_step("second", async || true)
+        .execute_report()
+        .await;
+    assert!(report.error.is_none(), "{:?}", report.error);
+    assert_eq!(report.steps.len(), 2);
+    let failures: Vec<_> = report
+        .callback_failures
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        failures,
+        [
+            "on_group_start callback for '0' panicked: bad callback",
+            "before_step callback for 'first' panicked: bad callback",
+        ]
+    );
+
+    // Redactors which panic lose the output, so fail the step regardless.
+    let res = new_imperative_builder()
+        .on_callback_panic(CallbackPanicPolicy::Warn)
+        .redact_output(|_: bool| panic!("bad redactor"))
+        .add_step("first", async || true)
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::CallbackFailed(..))),
+        "{res:?}"
+    );
+
+    // Panicking message redactors and dependency hooks are caught too, and
+    // the messages they were redacting withheld.
+    let report = new_imperative_builder()
+        .on_callback_panic(CallbackPanicPolicy::Warn)
+        .redact(|_| panic!("bad redactor"))
+        .on_dep_access(|_, _| panic!("bad hook"))
+        .add_dep(Dep::new(1u32))
+        .add_step("first", async |_: Dep<u32>| {
+            Err::<(), _>(std::io::Error::other("hunter2"))
+        })
+        .execute_report()
+        .await;
+    assert!(
+        matches!(
+            &report.error,
+            Some(BuilderError::Step(name, e))
+                if name == "first" && e.to_string() == "<redaction failed>"
+        ),
+        "{:?}",
+        report.error
+    );
+    let callbacks: Vec<_> = report
+        .callback_failures
+        .iter()
+        .filter_map(|e| match e {
+            BuilderError::CallbackFailed(cb, ..) => Some(cb.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert!(callbacks.contains(&"on_dep_access"), "{callbacks:?}");
+    assert!(callbacks.contains(&"redact"), "{callbacks:?}");
+}
+
+// Steps should be able to add steps at runtime, which run after their group,
+// within the run's limit.
+#[tokio::test]
+async fn test_dynamic_steps() {
+    let res = new_imperative_builder()
+        .add_step("discover", async |steps: DynamicSteps<bool>| {
+            for item in ["a", "b"] {
+                steps.add_step(&format!("process {item}"), async || true);
+            }
+            steps.add_group(|g| {
+                g.name("report")
+                    .add_step("summarize", async |steps: DynamicSteps<bool>| {
+                        steps.add_step("notify", async || true);
+                        true
+                    })
+            });
+            true
+        })
+        .new_group(|g| g.name("last").add_step("cleanup", async || true))
+        .execute_report()
+        .await;
+    assert!(res.error.is_none(), "{:?}", res.error);
+    let steps: Vec<_> = res
+        .steps
+        .iter()
+        .map(|s| format!("{}/{}", s.group, s.name))
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            "0/discover",
+            "2/process a",
+            "2/process b",
+            "report/summarize",
+            "4/notify",
+            "last/cleanup"
+        ]
+    );
+
+    // steps may add steps with their own name once they opt in, such as
+    // to crawl links
+    let res = new_imperative_builder()
+        .add_step("crawl", async |steps: DynamicSteps<bool>| {
+            let steps = steps.allow_recursion();
+            steps.add_step("crawl", async |steps: DynamicSteps<bool>| {
+                steps.allow_recursion().add_step("crawl", async || true);
+                true
+            });
+            true
+        })
+        .execute_report()
+        .await;
+    assert!(res.error.is_none(), "{:?}", res.error);
+    let names: Vec<_> = res.steps.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["crawl", "crawl", "crawl"]);
+
+    // otherwise, adding steps in a cycle fails the run
+    let res = new_imperative_builder()
+        .add_step("ping", async |steps: DynamicSteps<bool>| {
+            steps.add_step("pong", async |steps: DynamicSteps<bool>| {
+                steps.add_step("ping", async || true);
+                true
+            });
+            true
+        })
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::Cycle(chain)) if chain == &["ping", "pong", "ping"]),
+        "{res:?}"
+    );
+
+    // steps asking for another output type can't add steps
+    let res = new_imperative_builder()
+        .add_step("discover", async |_: DynamicSteps<u32>| true)
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::MissingParam(name, 1, _)) if name == "discover"),
+        "{res:?}"
+    );
+
+    let res = new_imperative_builder()
+        .max_dynamic_steps(2)
+        .add_step("discover", async |steps: DynamicSteps<bool>| {
+            for i in 0..3 {
+                steps.add_step(&format!("process {i}"), async || true);
+            }
+            true
+        })
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::TooManySteps(name, 2)) if name == "discover"),
+        "{res:?}"
+    );
+}
+
+// Unit builders should never need their output type annotated.
+#[tokio::test]
+async fn test_unit_builder() {
+    let empty = new_unit_builder().execute().await.unwrap();
+    assert!(empty.is_empty());
+
+    let builder: ImperativeStepBuilder = new_unit_builder().add_step("effect", async || {});
+    let res = builder.execute().await.unwrap();
+    assert_eq!(res.len(), 1);
+}
+
+// A pipeline service should build and run a pipeline per request.
+#[cfg(feature = "tower")]
+#[tokio::test]
+async fn test_pipeline_service() {
+    use tower_service::Service;
+
+    struct Name(String);
+    let mut svc = imperat::PipelineService::new(|name: String| {
+        new_imperative_builder()
+            .add_dep(Dep::new(Name(name)))
+            .add_step("greet", async |name: Dep<Name>| format!("hello {}", name.0))
+    });
+
+    std::future::poll_fn(|cx| svc.poll_ready(cx)).await.unwrap();
+    let res = svc.call("corgi".to_string()).await.unwrap();
+    assert_eq!(res["greet"], "hello corgi");
+
+    // its futures may be spawned
+    let res = tokio::spawn(svc.call("pug".to_string()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(res["greet"], "hello pug");
+}
+
+// Job statuses should report each step and exit per the policy.
+#[cfg(feature = "k8s")]
+#[tokio::test]
+async fn test_job_status() {
+    use imperat::k8s::{ExitPolicy, JobStatus};
+
+    let res = new_imperative_builder()
+        .with_settings(ProfileSettings {
+            tolerate_failure: true,
+            ..ProfileSettings::default()
+        })
+        .add_step("a", async || true)
+        .add_step("b", async || false)
+        .execute()
+        .await;
+
+    let status = JobStatus::new(&res, ExitPolicy::Strict);
+    assert_eq!(status.exit_code, 1);
+    assert_eq!(JobStatus::new(&res, ExitPolicy::RunOnly).exit_code, 0);
+    assert_eq!(
+        status.to_json(),
+        r#"{"conditions":[{"type":"Complete","status":"True","reason":"StepFailuresTolerated","message":"1 of 2 steps succeeded"},{"type":"a","status":"True","reason":"Succeeded","message":""},{"type":"b","status":"False","reason":"Failed","message":""}],"exitCode":1}"#
+    );
+}
+
+// A matrix should add a step per combination with its values in scope.
+#[tokio::test]
+async fn test_matrix() {
+    #[derive(Clone, Debug)]
+    enum Os {
+        Linux,
+        Mac,
+    }
+    let test = async |os: Dep<Os>, version: Dep<u32>| format!("{:?} {}", **os, **version);
+
+    let res = matrix! {
+        new_imperative_builder(),
+        "test" => test,
+        os: [Os::Linux, Os::Mac],
+        version: [1u32, 2],
+    }
+    .execute()
+    .await
+    .unwrap();
+
+    assert_eq!(res.len(), 4);
+    assert_eq!(res["test (os: Linux, version: 1)"], "Linux 1");
+    assert_eq!(res["test (os: Mac, version: 2)"], "Mac 2");
+
+    // steps outside the matrix can't see its values
+    let res = matrix!(new_imperative_builder(), "test" => test, os: [Os::Linux], version: [1u32])
+        .add_step("outside", async |os: Dep<Os>| format!("{:?}", **os))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::MissingParam(s, ..)) if s == "outside"));
+
+    // axes sharing a type are only read by name, never as the wrong `Dep`
+    let res = matrix! {
+        new_imperative_builder(),
+        "test" => async |m: Matrix, os: Dep<Os>| {
+            let (retries, version) = (m.get::<u32>("retries"), m.get::<u32>("version"));
+            format!("{:?} {} {}", **os, retries.unwrap(), version.unwrap())
+        },
+        os: [Os::Linux],
+        retries: [1u32, 2],
+        version: [3u32],
+    }
+    .execute()
+    .await
+    .unwrap();
+    assert_eq!(res["test (os: Linux, retries: 2, version: 3)"], "Linux 2 3");
+
+    let res = matrix! {
+        new_imperative_builder().add_dep(Dep::new(0u32)),
+        "test" => async |version: Dep<u32>| **version,
+        retries: [1u32],
+        version: [3u32],
+    }
+    .execute()
+    .await;
+    assert!(
+        matches!(&res, Err(BuilderError::MissingParam(s, ..)) if s.starts_with("test")),
+        "{res:?}"
+    );
+}
+
+// Status snapshots should reflect the run's progress while it executes.
+#[tokio::test]
+async fn test_run_status() {
+    let builder = new_imperative_builder()
+        .add_step("first", async || true)
+        .add_step("second", async || false)
+        .add_step("third", async || true);
+    let status = builder.status_handle();
+    let seen = status.clone();
+
+    let res = builder
+        .with_settings(ProfileSettings {
+            tolerate_failure: true,
+            ..ProfileSettings::default()
+        })
+        .add_step("check", move || {
+            let snapshot = seen.snapshot();
+            async move {
+                snapshot
+                    == RunStatus {
+                        pending: 0,
+                        running: 1,
+                        succeeded: 2,
+                        failed: 1,
+                        current: vec!["check".to_string()],
+                        ..RunStatus::default()
+                    }
+            }
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["check"]);
+    let done = status.snapshot();
+    assert!(done.is_done());
+    assert_eq!((done.succeeded, done.failed), (3, 1));
+}
+
+// A run which fails early should still end done, with the steps it never
+// reached counted as skipped.
+#[tokio::test]
+async fn test_run_status_failed() {
+    let builder = new_imperative_builder()
+        .add_step("first", async || false)
+        .add_step("second", async || true)
+        .add_step("third", async || true);
+    let status = builder.status_handle();
+
+    let res = builder.execute().await;
+    assert!(res.is_err(), "{res:?}");
+    let done = status.snapshot();
+    assert!(done.is_done(), "{done:?}");
+    assert_eq!((done.succeeded, done.failed, done.skipped), (0, 1, 2));
+    assert!(done.current.is_empty());
+}
+
+// Steps exceeding their budget should fail with a distinct error.
+#[tokio::test]
+async fn test_step_budget() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    let res = new_imperative_builder()
+        .add(
+            new_step("slow", async || sleep(Duration::from_secs(5)).await)
+                .budget(StepBudget::new().max_duration(Duration::from_millis(5))),
+        )
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::BudgetExceeded(s, _)) if s == "slow"));
+
+    let res = new_imperative_builder()
+        .add(
+            new_step("large", async || "x".repeat(100))
+                .budget(StepBudget::new().max_output_size(10))
+                .output_size(String::len),
+        )
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::BudgetExceeded(s, _)) if s == "large"));
+
+    // output sizes can't be limited without measuring them
+    let res = new_imperative_builder()
+        .add(
+            new_step("large", async || "x".repeat(100))
+                .budget(StepBudget::new().max_output_size(10)),
+        )
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::NoOutputSize(s)) if s == "large"),
+        "{res:?}"
+    );
+
+    let res = new_imperative_builder()
+        .new_group(|g| {
+            g.retry(5, Duration::ZERO).tolerate_failure().add(
+                new_step("flaky", async || {
+                    ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+                    false
+                })
+                .budget(StepBudget::new().max_retries(1)),
+            )
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert!(!res["flaky"]);
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+}
+
+// Producing steps should keep their output and inject it into later steps.
+#[tokio::test]
+async fn test_producing_step() {
+    let res = new_imperative_builder()
+        .add_producing_step("fetch", async || "config".to_string())
+        .new_group(|g| {
+            g.add_step("parse", async |raw: Dep<String>| {
+                format!("parsed {}", **raw)
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["fetch"], "config");
+    assert_eq!(res["parse"], "parsed config");
+}
+
+// Producing steps should bind the type they choose, and two steps may not
+// bind the same type.
+#[tokio::test]
+async fn test_producing_types() {
+    struct Host(String);
+    struct Port(String);
+    impl From<String> for Host {
+        fn from(s: String) -> Self {
+            Host(s)
+        }
+    }
+    impl From<String> for Port {
+        fn from(s: String) -> Self {
+            Port(s)
+        }
+    }
+
+    let res = new_imperative_builder()
+        .add(new_step("host", async || "localhost".to_string()).produces::<Host>())
+        .add(new_step("port", async || "8080".to_string()).produces::<Port>())
+        .new_group(|g| {
+            g.add_step("url", async |host: Dep<Host>, port: Dep<Port>| {
+                format!("{}:{}", host.0, port.0)
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["url"], "localhost:8080");
+
+    let res = new_imperative_builder()
+        .add_producing_step("a", async || "a".to_string())
+        .add_producing_step("b", async || "b".to_string())
+        .execute()
+        .await;
+    assert!(
+        matches!(&res, Err(BuilderError::DuplicateBinding(_, first, second)) if first == "a" && second == "b"),
+        "{res:?}"
+    );
+
+    // steps added with `add_step_binding` count too
+    let res = new_imperative_builder::<Result<(), std::io::Error>>()
+        .add_step_binding("a", async || Ok(Host("a".to_string())))
+        .add_step_binding("b", async || Ok(Host("b".to_string())))
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::DuplicateBinding(..))),
+        "{res:?}"
+    );
+}
+
+// Steps should run after the steps they depend on, and independent steps
+// should still run concurrently.
+#[tokio::test]
+async fn test_depends_on() {
+    static ORDER: Mutex<Vec<&str>> = Mutex::new(vec![]);
+
+    let step = |name: &'static str| {
+        move |barrier: TestBarrier| async move {
+            if name != "a" && name != "d" {
+                barrier.wait().await;
+            }
+            ORDER.lock().unwrap().push(name);
+        }
+    };
+    new_imperative_builder()
+        .add_dep(TestBarrier::new(2))
+        .new_group(|g| {
+            g.parallel()
+                .add(new_step("d", step("d")).depends_on(["b", "c"]))
+                .add(new_step("b", step("b")).depends_on(["a"]))
+                .add(new_step("c", step("c")).depends_on(["a"]))
+                .add(new_step("a", step("a")))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    let order = ORDER.lock().unwrap();
+    assert_eq!(order[0], "a");
+    assert_eq!(order[3], "d");
+}
+
+// Steps should be skipped when a step they depend on fails, and may only
+// depend on steps in their group.
+#[tokio::test]
+async fn test_depends_on_errors() {
+    let res = new_imperative_builder()
+        .new_group(|g| {
+            g.parallel()
+                .add_step("a", async || false)
+                .add(new_step("b", async || true).depends_on(["a"]))
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert!(!res.contains_key("b"));
+
+    // unless the failure is tolerated, the group fails before the skip
+    let res = new_imperative_builder()
+        .add_step("a", async || false)
+        .add(new_step("b", async || true).depends_on(["a"]))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::UnknownStep(s)) if s == "a"));
+
+    let res = new_imperative_builder()
+        .add_step("a", async || true)
+        .new_group(|g| g.add(new_step("b", async || true).depends_on(["a"])))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::DependsOn(s, dep)) if s == "b" && dep == "a"));
+
+    let res = new_imperative_builder()
+        .add(new_step("a", async || true).depends_on(["b"]))
+        .add(new_step("b", async || true).depends_on(["a"]))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::Cycle(_))));
+}
+
+// Steps skipped as a step they depend on failed, in their phase or an
+// earlier one, should only fail groups which don't tolerate failure.
+#[tokio::test]
+async fn test_depends_on_tolerated() {
+    for parallel in [false, true] {
+        let report = new_imperative_builder()
+            .new_group(|g| {
+                let g = if parallel { g.parallel() } else { g };
+                g.tolerate_failure()
+                    .add_step("a", async || false)
+                    .add(new_step("b", async || true).depends_on(["a"]))
+                    .add_step("c", async || true)
+            })
+            .execute_report()
+            .await;
+        assert!(report.error.is_none(), "{:?}", report.error);
+        let b = report.step("b").unwrap();
+        assert_eq!(b.outcome, StepOutcome::Skipped);
+        assert_eq!(
+            b.error.as_deref(),
+            Some("step 'b' was skipped as 'a' didn't succeed")
+        );
+        assert_eq!(report.step("c").unwrap().outcome, StepOutcome::Succeeded);
+    }
+
+    for parallel in [false, true] {
+        let report = new_imperative_builder()
+            .new_group(|g| {
+                let g = if parallel { g.parallel() } else { g };
+                g.tolerate_failure()
+                    .add(new_step("a", async || false).phase(1))
+                    .add(new_step("b", async || true).phase(2).depends_on(["a"]))
+                    .add(new_step("c", async || true).phase(2))
+            })
+            .execute_report()
+            .await;
+        assert!(report.error.is_none(), "{:?}", report.error);
+        assert_eq!(report.step("b").unwrap().outcome, StepOutcome::Skipped);
+        assert_eq!(report.step("c").unwrap().outcome, StepOutcome::Succeeded);
+    }
+}
+
+// Missing dependencies should be reported by their parameter.
+#[tokio::test]
+async fn test_missing_parameter() {
+    struct HttpClient;
+
+    let res = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_step("sync", async |_: Dep<Database>, _: Dep<HttpClient>| ())
+        .execute()
+        .await;
+
+    let Err(e) = res else {
+        panic!("expected a missing parameter");
+    };
+    assert_eq!(
+        e.to_string(),
+        "step 'sync': parameter 2 (Dep<HttpClient>) missing"
+    );
+}
+
+// Groups should start from the builder's group defaults, and may override them.
+#[tokio::test]
+async fn test_group_defaults() {
+    let recorder = ConcurrencyRecorder::new();
+    let step = async |recorder: ConcurrencyRecorder| {
+        let _guard = recorder.enter();
+        sleep(Duration::from_millis(20)).await;
+    };
+
+    new_imperative_builder()
+        .add_dep(recorder.clone())
+        .group_defaults(|g| g.parallel().max_concurrency(2))
+        .new_group(|g| {
+            g.add_step("a", step)
+                .add_step("b", step)
+                .add_step("c", step)
+        })
+        .execute()
+        .await
+        .unwrap();
+    recorder.assert_concurrent(2);
+
+    let recorder = ConcurrencyRecorder::new();
+    new_imperative_builder()
+        .add_dep(recorder.clone())
+        .group_defaults(|g| g.parallel().max_concurrency(2))
+        .new_group(|g| g.max_concurrency(1).add_step("a", step).add_step("b", step))
+        .execute()
+        .await
+        .unwrap();
+    recorder.assert_serial();
+
+    // steps in the template fail the build, and bind nothing for real steps
+    let res = new_imperative_builder()
+        .group_defaults(|g| g.add(new_step("template", async || 1u32).produces::<u32>()))
+        .add(new_step("real", async || 2u32).produces::<u32>())
+        .add_step("reads", async |n: Dep<u32>| **n)
+        .execute()
+        .await;
+    let Err(BuilderError::DefaultSteps(names)) = &res else {
+        panic!("expected a build error, got {res:?}");
+    };
+    assert_eq!(names, &["template"]);
+}
+
+// Steps should retry per their own policy, and report each retry.
+#[tokio::test]
+async fn test_retry_policy() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    let retries = Arc::new(Mutex::new(vec![]));
+
+    let recorded = retries.clone();
+    let start = Instant::now();
+    let res = new_imperative_builder()
+        .on_retry(move |name, attempt| recorded.lock().unwrap().push((name.to_string(), attempt)))
+        .new_group(|g| {
+            g.retry(10, Duration::from_secs(10)).add(
+                new_step("flaky", async || {
+                    ATTEMPTS.fetch_add(1, Ordering::SeqCst) >= 2
+                })
+                .retry(
+                    RetryPolicy::new(5)
+                        .exponential(Duration::from_millis(5), Duration::from_millis(20))
+                        .jitter(false),
+                ),
+            )
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["flaky"]);
+    assert!(start.elapsed() >= Duration::from_millis(15));
+    assert_eq!(
+        *retries.lock().unwrap(),
+        [("flaky".to_string(), 2), ("flaky".to_string(), 3)]
+    );
+
+    // failures the policy doesn't accept aren't retried
+    let res = new_imperative_builder()
+        .add(
+            new_step("timeout", async || sleep(Duration::from_secs(5)).await)
+                .timeout(Duration::from_millis(1))
+                .retry(RetryPolicy::new(5).retry_if(|res| res.is_ok())),
+        )
+        .execute()
+        .await;
+    assert!(matches!(
+        res,
+        Err(BuilderError::Cancelled {
+            reason: CancelReason::Timeout,
+            ..
+        })
+    ));
+}
+
+// Groups built outside a closure should run once attached.
+#[tokio::test]
+async fn test_attach_group() {
+    fn checks(b: &ImperativeStepBuilder<bool>) -> GroupBuilder<bool> {
+        b.group("checks")
+            .parallel()
+            .add_step("lint", async || true)
+            .add_step("fmt", async || true)
+    }
+
+    let b = new_imperative_builder();
+    let group = checks(&b);
+    let skipped = b.group("skipped").add_step("never", async || true);
+    let res = b.attach(group).execute().await.unwrap();
+    drop(skipped);
+    assert_eq!(res.len(), 2);
+    assert!(res["lint"] && res["fmt"]);
+
+    // groups belong to the builder which created them
+    let other = new_imperative_builder::<bool>();
+    let group = checks(&other);
+    let res = new_imperative_builder().attach(group).execute().await;
+    assert!(matches!(res, Err(BuilderError::Group(name, _)) if name == "checks"));
+}
+
+// Steps running past the run's deadline should be cancelled, and later
+// steps shouldn't start.
+#[tokio::test]
+async fn test_deadline() {
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    let start = Instant::now();
+    let res = new_imperative_builder()
+        .deadline(Instant::now() + Duration::from_millis(20))
+        .new_group(|g| {
+            g.parallel()
+                .add_step("quick", async || {
+                    STARTED.fetch_add(1, Ordering::SeqCst);
+                })
+                .add_step("hung", async || {
+                    STARTED.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_secs(60)).await;
+                })
+        })
+        .new_group(|g| {
+            g.add_step("after", async || {
+                STARTED.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .execute()
+        .await;
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let e = res.unwrap_err();
+    assert_eq!(e.cancel_reason(), Some(&CancelReason::Deadline));
+    assert!(
+        matches!(e, BuilderError::Cancelled { ref during_step, .. } if during_step == "hung"),
+        "{e:?}"
+    );
+    assert_eq!(STARTED.load(Ordering::SeqCst), 2);
+}
+
+// Parallel groups which fail fast should cancel their other steps on the
+// first failure.
+#[tokio::test]
+async fn test_fail_fast() {
+    let start = Instant::now();
+    let res = new_imperative_builder()
+        .new_group(|g| {
+            g.parallel()
+                .fail_fast()
+                .add_step("slow", async || {
+                    sleep(Duration::from_secs(60)).await;
+                    true
+                })
+                .add_step("fails", async || {
+                    sleep(Duration::from_millis(5)).await;
+                    false
+                })
+                .add_step("quick", async || true)
+        })
+        .execute()
+        .await;
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let Err(BuilderError::FailFast(e, cancelled)) = res else {
+        panic!("expected the group to fail fast");
+    };
+    assert!(matches!(*e, BuilderError::UnknownStep(s) if s == "fails"));
+    assert_eq!(cancelled, ["slow"]);
+}
+
+// Steps cancelled by a failing sibling should report why, while the
+// failure itself isn't a cancellation.
+#[tokio::test]
+async fn test_cancel_reasons() {
+    let report = new_imperative_builder()
+        .new_group(|g| {
+            g.parallel()
+                .fail_fast()
+                .add_step("slow", async || {
+                    sleep(Duration::from_secs(60)).await;
+                    true
+                })
+                .add_step("fails", async || false)
+        })
+        .execute_report()
+        .await;
+
+    let slow = report.step("slow").unwrap();
+    assert_eq!(slow.outcome, StepOutcome::Cancelled);
+    assert_eq!(
+        slow.error.as_deref(),
+        Some("run was cancelled at step 'slow': step 'fails' failed")
+    );
+    assert_eq!(report.error.unwrap().cancel_reason(), None);
+}
+
+// Steps should see the run's metadata.
+#[tokio::test]
+async fn test_run_metadata() {
+    let res = new_imperative_builder()
+        .with_metadata("triggered_by", "cron")
+        .with_metadata("git_sha", "abc123")
+        .add_step("describe", async |meta: RunMetadata| {
+            assert_eq!(meta.get("git_sha"), Some("abc123"));
+            meta.to_string()
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["describe"], "git_sha=abc123 triggered_by=cron");
+
+    // metadata is always available, even when empty
+    let res = new_imperative_builder()
+        .add_step("empty", async |meta: RunMetadata| meta.is_empty())
+        .execute()
+        .await
+        .unwrap();
+    assert!(res["empty"]);
+}
+
+// Redactors should scrub step errors and outputs before they leave the run.
+#[tokio::test]
+async fn test_redaction() {
+    let scrub = |msg: &str| msg.replace("hunter2", "[redacted]");
+
+    let res = new_imperative_builder()
+        .redact(scrub)
+        .add_step("login", async || {
+            Err::<(), _>(std::io::Error::other("bad password hunter2"))
+        })
+        .execute()
+        .await;
+    let Err(e) = res else {
+        panic!("expected login to fail");
+    };
+    assert_eq!(
+        e.to_string(),
+        "step 'login' failed to execute: bad password [redacted]"
+    );
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let recorded = seen.clone();
+    let res = new_imperative_builder()
+        .redact_output(move |out: String| scrub(&out))
+        .after_step(move |_, out| recorded.lock().unwrap().push(out.clone()))
+        .add_step("echo", async || "hunter2".to_string())
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["echo"], "[redacted]");
+    assert_eq!(*seen.lock().unwrap(), ["[redacted]"]);
+}
+
+// Dependencies built by providers should be available to steps, and provider
+// failures should fail the run.
+#[tokio::test]
+async fn test_add_dep_with() {
+    struct Pool(String);
+
+    let res = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_dep_with(async |_: Dep<Database>| {
+            sleep(Duration::from_millis(1)).await;
+            Ok::<_, std::io::Error>(Pool("pool".to_string()))
+        })
+        .add_step("query", async |pool: Dep<Pool>| pool.0.clone())
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["query"], "pool");
+
+    let res = new_imperative_builder()
+        .add_dep_with(async || Err::<Pool, _>(std::io::Error::other("refused")))
+        .add_step("query", async |pool: Dep<Pool>| pool.0.clone())
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::DepInit(..))));
+}
+
+// Progress reported by a running step should appear in status snapshots.
+#[tokio::test]
+async fn test_step_progress() {
+    let builder = new_imperative_builder();
+    let status = builder.status_handle();
+    let seen = status.clone();
+
+    let res = builder
+        .add_step("import", move |progress: Progress| {
+            progress.set_total(10);
+            progress.advance(3);
+            progress.advance(1);
+            let snapshot = seen.snapshot();
+            async move {
+                snapshot.progress["import"]
+                    == StepProgress {
+                        done: 4,
+                        total: Some(10),
+                    }
+            }
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["import"]);
+    assert!(status.snapshot().progress.is_empty());
+}
+
+// Optional and defaulted dependencies should let a step run without them.
+#[tokio::test]
+async fn test_optional_deps() {
+    let res = new_imperative_builder()
+        .add_dep(Dep::new("bound".to_string()))
+        .add_step(
+            "branch",
+            |name: Option<Dep<String>>, retries: Option<Dep<u32>>, limit: DepOrDefault<u64>| async move {
+                format!(
+                    "{} {:?} {}",
+                    name.map_or("none".to_string(), |n| n.to_string()),
+                    retries.map(|r| **r),
+                    **limit
+                )
+            },
+        )
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["branch"], "bound None 0");
+}
+
+// Rollouts should enable steps and groups for a stable share of keys, and
+// record each decision in the run's metadata.
+#[tokio::test]
+async fn test_rollout() {
+    let half = Rollout::new(50, "tenant-a");
+    assert_eq!(half.enabled("new-step"), half.enabled("new-step"));
+    let enabled = (0..1000)
+        .filter(|i| Rollout::new(50, &format!("tenant-{i}")).enabled("new-step"))
+        .count();
+    assert!((400..600).contains(&enabled), "{enabled} of 1000 enabled");
+
+    let res = new_imperative_builder()
+        .add_step("existing", async || true)
+        .add(new_step("everyone", async || true).rollout(Rollout::new(100, "tenant-a")))
+        .add(new_step("no one", async || true).rollout(Rollout::new(0, "tenant-a")))
+        .new_group(|g| {
+            g.name("canary")
+                .rollout(Rollout::new(0, "tenant-a"))
+                .add_step("canary step", async || true)
+        })
+        .add_step("report", async |metadata: RunMetadata| {
+            metadata.get("rollout.everyone") == Some("enabled")
+                && metadata.get("rollout.no one") == Some("disabled")
+                && metadata.get("rollout.canary") == Some("disabled")
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), 3);
+    assert!(res["everyone"] && res["report"]);
+}
+
+// Groups ordered by history should run their flakiest, then fastest, steps first.
+#[tokio::test]
+async fn test_order_by_history() {
+    let stats = StepStats::new();
+    let ran = Arc::new(Mutex::new(vec![]));
+    for i in 0..2 {
+        let ran = ran.clone();
+        ran.lock().unwrap().clear();
+        new_imperative_builder()
+            .record_stats(&stats)
+            .new_group(|g| {
+                let log = |name: &'static str| {
+                    let ran = ran.clone();
+                    move || ran.lock().unwrap().push(name)
+                };
+                let (slow, fast, flaky) = (log("slow"), log("fast"), log("flaky"));
+                g.tolerate_failure()
+                    .order_by_history(&stats)
+                    .add_step("slow", move || {
+                        slow();
+                        async {
+                            sleep(Duration::from_millis(20)).await;
+                            true
+                        }
+                    })
+                    .add_step("fast", move || {
+                        fast();
+                        async { true }
+                    })
+                    .add_step("flaky", move || {
+                        flaky();
+                        async move { i > 0 }
+                    })
+            })
+            .execute()
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(*ran.lock().unwrap(), ["flaky", "fast", "slow"]);
+}
+
+// Steps should share and modify mutable state, even in parallel.
+#[tokio::test]
+async fn test_dep_mut() {
+    let res = new_imperative_builder()
+        .add_dep(DepMut::new(Vec::<usize>::new()))
+        .new_group(|g| {
+            (0..10).fold(g.parallel(), |g, i| {
+                g.add_step(
+                    &format!("push {i}"),
+                    move |seen: DepMut<Vec<usize>>| async move {
+                        let mut seen = seen.lock().await;
+                        let len = seen.len();
+                        sleep(Duration::from_millis(1)).await;
+                        seen.push(len);
+                        true
+                    },
+                )
+            })
+        })
+        .new_group(|g| {
+            g.add_step("check", async |seen: DepMut<Vec<usize>>| {
+                seen.update(|s| s.push(10)).await;
+                seen.get().await == (0..=10).collect::<Vec<_>>()
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert!(res["check"]);
+}
+
+// Group dependencies should shadow the builder's only within their group.
+#[tokio::test]
+async fn test_group_deps() {
+    async fn region(region: Dep<&'static str>) -> String {
+        region.to_string()
+    }
+
+    let res = new_imperative_builder()
+        .key_strategy(KeyStrategy::GroupQualified)
+        .add_dep(Dep::new("us-east"))
+        .new_group(|g| {
+            g.name("eu")
+                .add_dep(Dep::new("eu-west"))
+                .add_step("deploy", region)
+        })
+        .new_group(|g| g.name("us").add_step("deploy", region))
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["eu/deploy"], "eu-west");
+    assert_eq!(res["us/deploy"], "us-east");
+
+    let err = new_imperative_builder::<bool>()
+        .new_group(|g| g.add_dep(Dep::new(1)).add_dep(Dep::new(2)))
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, BuilderError::InGroup(g, e) if g == "1" && matches!(**e, BuilderError::AddDep(_))),
+        "{err}"
+    );
+}
+
+// Builders should display every step and group in a readable tree.
+#[tokio::test]
+async fn test_display_builder() {
+    let b = new_imperative_builder()
+        .add_dep(Dep::new(1_u32))
+        .add_step("count", async |_: Dep<u32>| true)
+        .new_group(|g| {
+            g.name("deploy")
+                .parallel()
+                .fail_fast()
+                .add(new_step("push", async || true).timeout(Duration::from_secs(5)))
+                .add(new_step("notify", async || true).depends_on(["push"]))
+        })
+        .new_group(|g| g.tolerate_failure().add_step("cleanup", async || true));
+
+    assert_eq!(
+        b.to_string(),
+        "steps:\n  count(Dep<u32>)\n\
+         group 'deploy' [parallel, fails fast]:\n  push() [timeout 5s]\n  notify() [after push]\n\
+         group 2 [tolerates failure]:\n  cleanup()\n"
+    );
+    assert!(format!("{b:?}").contains("name: \"notify\""));
+}
+
+// Helpers called from a step should see the current run without extractors.
+#[tokio::test]
+async fn test_current_run() {
+    fn describe() -> String {
+        let run = imperat::current_run().unwrap();
+        format!(
+            "{} {} {:?}",
+            run.step().name(),
+            run.attempt().0,
+            run.metadata().get("env")
+        )
+    }
+
+    assert!(imperat::current_run().is_none());
+    let run = || {
+        new_imperative_builder()
+            .with_metadata("env", "prod")
+            .add_step("describe", async || describe())
+            .add_step("id", async || {
+                imperat::current_run().unwrap().run_id().to_string()
+            })
+            .execute()
+    };
+    let (first, second) = (run().await.unwrap(), run().await.unwrap());
+
+    assert_eq!(first["describe"], "describe 1 Some(\"prod\")");
+    assert_ne!(first["id"], second["id"]);
+}
+
+// Steps returning different types should be queryable by name and by type.
+#[tokio::test]
+async fn test_heterogeneous_outputs() {
+    #[derive(Debug, PartialEq)]
+    struct Release(u32);
+    impl IntoStepOutcome for Release {
+        fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+            None
+        }
+
+        fn success(&self) -> bool {
+            true
+        }
+    }
+
+    let mut outputs = Outputs::from(
+        new_any_builder()
+            .add_step("count", any_output(async || 3_usize))
+            .add_step("name", any_output(async || "imperat".to_string()))
+            .add_step("release", any_output(async || Release(7)))
+            .execute()
+            .await
+            .unwrap(),
+    );
+
+    assert_eq!(outputs.get::<usize>("count"), Some(&3));
+    assert_eq!(outputs.get::<String>("count"), None);
+    assert_eq!(outputs.by_type::<Release>(), Some(&Release(7)));
+    assert_eq!(outputs.take::<String>("name").unwrap(), "imperat");
+    assert_eq!(outputs.len(), 2);
+
+    // failures are still decided by each step's own output type
+    let err = new_any_builder()
+        .add_step("ok", any_output(async || 1_u8))
+        .add_step(
+            "fails",
+            any_output(async || {
+                Err::<(), _>(Box::<dyn std::error::Error + Send + Sync>::from("nope"))
+            }),
+        )
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("nope"), "{err}");
+}
+
+// Run results should look outputs up by key and type, and report which
+// steps succeeded or were tolerated failures.
+#[tokio::test]
+async fn test_run_result() {
+    let res = new_imperative_builder()
+        .with_settings(ProfileSettings {
+            tolerate_failure: true,
+            ..ProfileSettings::default()
+        })
+        .add_step("ok", async || {
+            sleep(Duration::from_millis(5)).await;
+            true
+        })
+        .add_step("fails", async || false)
+        .execute_result()
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), 2);
+    assert!(res["ok"]);
+    assert_eq!(res.get_as::<bool>("fails"), Some(&false));
+    assert_eq!(res.get_as::<u32>("ok"), None);
+    let names = |steps: Vec<&imperat::StepReport>| -> Vec<String> {
+        steps.into_iter().map(|s| s.name.clone()).collect()
+    };
+    assert_eq!(names(res.successes().collect()), ["ok"]);
+    assert_eq!(names(res.failures().collect()), ["fails"]);
+    assert!(res.duration() >= Duration::from_millis(5));
+    assert_eq!(res.into_map().len(), 2);
+
+    let res = new_any_builder()
+        .add_step("count", any_output(async || 3_usize))
+        .execute_result()
+        .await
+        .unwrap();
+    assert_eq!(res.get_as::<usize>("count"), Some(&3));
+}
+
+// Pipes should stream values between parallel steps and close once their
+// senders finish.
+#[tokio::test]
+async fn test_pipe() {
+    let res = new_imperative_builder()
+        .pipe::<u32>(2)
+        .new_group(|g| {
+            g.parallel()
+                .add_step("produce", async |tx: PipeSender<u32>| {
+                    for i in 1..=10 {
+                        if tx.send(i).await.is_err() {
+                            return 0;
+                        }
+                    }
+                    10
+                })
+                .add_step("consume", async |rx: PipeReceiver<u32>| {
+                    let mut sum = 0;
+                    while let Some(i) = rx.recv().await {
+                        sum += i;
+                    }
+                    sum
+                })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res["produce"], 10);
+    assert_eq!(res["consume"], 55);
+}
+
+// Parallel steps waiting on a barrier should all finish one stage before any
+// starts the next.
+#[tokio::test]
+async fn test_barriers() {
+    let stages = Arc::new(Mutex::new(vec![]));
+    let res = new_imperative_builder()
+        .barrier("stage a", 3)
+        .new_group(|g| {
+            (0..3).fold(g.parallel().fail_fast(), |g, shard| {
+                let stages = stages.clone();
+                g.add_step(&format!("shard {shard}"), move |barriers: Barriers| {
+                    let stages = stages.clone();
+                    async move {
+                        sleep(Duration::from_millis(shard * 5)).await;
+                        stages.lock().unwrap().push('a');
+                        let leader = barriers.wait("stage a").await;
+                        stages.lock().unwrap().push('b');
+                        usize::from(leader)
+                    }
+                })
+            })
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(*stages.lock().unwrap(), ['a', 'a', 'a', 'b', 'b', 'b']);
+    assert_eq!(res.values().sum::<usize>(), 1);
+}
+
+// Steps whose condition isn't met should be skipped with a distinct output.
+#[tokio::test]
+async fn test_conditional_steps() {
+    struct Flags {
+        migrate: bool,
+    }
+
+    let builder = new_any_builder();
+    let status = builder.status_handle();
+    let outputs = Outputs::from(
+        builder
+            .add_dep(Dep::new(Flags { migrate: false }))
+            .add_step_if(
+                "migrate",
+                sync_fn(|flags: Dep<Flags>| flags.migrate),
+                any_output(async || 1_u32),
+            )
+            .add_step_if("deploy", async || true, any_output(async || 2_u32))
+            .add(new_step("notify", any_output(async || 3_u32)).skip_if(sync_fn(|| true)))
+            .execute()
+            .await
+            .unwrap(),
+    );
+
+    assert_eq!(outputs.get::<Skipped>("migrate"), Some(&Skipped));
+    assert_eq!(outputs.get::<u32>("deploy"), Some(&2));
+    assert_eq!(outputs.get::<Skipped>("notify"), Some(&Skipped));
+    assert_eq!(status.snapshot().skipped, 2);
+}
+
+// Reports should describe every step, including those which failed.
+#[tokio::test]
+async fn test_execution_report() {
+    let report = new_imperative_builder()
+        .add(new_step("build", async || true).alias("compile"))
+        .new_group(|g| {
+            g.name("checks")
+                .tolerate_failure()
+                .add_step("lint", async || false)
+                .add_step("test", async || true)
+        })
+        .execute_report()
+        .await;
+
+    assert!(report.is_success());
+    assert_eq!(report.steps.len(), 3);
+    assert!(report["build"]);
+    assert_eq!(report.get("compile"), Some(&true));
+
+    let lint = report.step("lint").unwrap();
+    assert_eq!(lint.outcome, StepOutcome::Failed);
+    assert_eq!(lint.group, "checks");
+    assert!(lint.finished().is_some());
+    assert_eq!(report.output(lint), Some(&false));
+    assert_eq!(report.step("compile").unwrap().name, "build");
+    assert_eq!(report.with_outcome(StepOutcome::Succeeded).count(), 2);
+
+    let report = new_imperative_builder()
+        .add_step("build", async || false)
+        .add_step("deploy", async || true)
+        .execute_report()
+        .await;
+
+    assert!(!report.is_success());
+    assert!(report.error.is_some());
+    assert_eq!(report.step("build").unwrap().outcome, StepOutcome::Failed);
+    assert!(report.get("build").is_none());
+    assert!(report.into_result().is_err());
+}
+
+// Every build error should be returned, not only the last.
+#[tokio::test]
+async fn test_multiple_build_errors() {
+    let res = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_dep(Dep::new(Database))
+        .add_step("first", async |_: Dep<String>| ())
+        .new_group(|g| g.add_step("second", async |_: Dep<u32>| ()))
+        .execute()
+        .await;
+
+    let err = res.unwrap_err();
+    assert!(err.to_string().starts_with("3 build error(s): "), "{err}");
+    let BuilderError::Build(errors) = err else {
+        panic!("expected every build error, got {err:?}");
+    };
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    assert!(matches!(errors[0], BuilderError::AddDep(_)));
+    assert!(matches!(&errors[1], BuilderError::MissingParam(s, ..) if s == "first"));
+    assert!(
+        matches!(&errors[2], BuilderError::InGroup(g, e) if g == "1" && matches!(&**e, BuilderError::MissingParam(s, ..) if s == "second")),
+        "{errors:?}"
+    );
+}
+
+// Build errors should be attributed to where they occurred, in the same order
+// however they were added.
+#[tokio::test]
+async fn test_build_error_origins() {
+    let errors = new_imperative_builder::<bool>()
+        .new_group(|g| g.name("deploy").add_step("push", async |_: Dep<u32>| true))
+        .add_step("build", async |_: Dep<String>| true)
+        .new_group(|g| g.add_dep(Dep::new(1)).add_dep(Dep::new(2)))
+        .add_dep(Dep::new(Database))
+        .add_dep(Dep::new(Database))
+        .validate()
+        .unwrap_err();
+
+    let origins: Vec<_> = errors
+        .iter()
+        .map(|e| match e {
+            BuilderError::InGroup(g, e) => {
+                format!("{g}: {}", e.to_string().split(' ').next().unwrap())
+            }
+            e => e.to_string().split(' ').next().unwrap().to_string(),
+        })
+        .collect();
+    assert_eq!(origins, ["failed", "step", "deploy: step", "2: failed"]);
+}
+
+// Validating should report every build error without consuming the builder.
+#[tokio::test]
+async fn test_validate() {
+    let builder = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_step("ok", async |_: Dep<Database>| ());
+    assert!(builder.validate().is_ok());
+    builder.execute().await.unwrap();
+
+    let builder = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_dep(Dep::new(Database))
+        .add_step("first", async |_: Dep<String>| ())
+        .add_step("second", async |_: Dep<u32>| ());
+    let errors = builder.validate().unwrap_err();
+    assert_eq!(errors.len(), 3, "{errors:?}");
+    assert!(matches!(errors[0], BuilderError::AddDep(_)));
+    assert!(matches!(&errors[2], BuilderError::MissingParam(s, ..) if s == "second"));
+
+    // validating doesn't clear the errors
+    assert_eq!(builder.validate().unwrap_err().len(), 3);
+    assert!(matches!(builder.execute().await, Err(BuilderError::Build(e)) if e.len() == 3));
+}
+
+// Callbacks should see failed steps in sequential groups before the run ends.
+#[tokio::test]
+async fn test_step_result_callbacks() {
+    let after = Arc::new(Mutex::new(vec![]));
+    let results = Arc::new(Mutex::new(vec![]));
+    let (a, r) = (after.clone(), results.clone());
+    let res = new_imperative_builder()
+        .add_step("build", async || true)
+        .add_step("check", async || false)
+        .add_step("deploy", async || true)
+        .after_step(move |name, out| a.lock().unwrap().push((name.to_string(), *out)))
+        .on_step_result(move |name, outcome, out| {
+            r.lock()
+                .unwrap()
+                .push((name.to_string(), outcome, out.copied()));
+        })
+        .execute()
+        .await;
+
+    assert!(matches!(res, Err(BuilderError::UnknownStep(s)) if s == "check"));
+    assert_eq!(
+        *after.lock().unwrap(),
+        [("build".to_string(), true), ("check".to_string(), false)]
+    );
+    assert_eq!(
+        *results.lock().unwrap(),
+        [
+            ("build".to_string(), StepOutcome::Succeeded, Some(true)),
+            ("check".to_string(), StepOutcome::Failed, Some(false)),
+        ]
+    );
+
+    // steps without an output still report how they ended
+    let results = Arc::new(Mutex::new(vec![]));
+    let r = results.clone();
+    let res = new_imperative_builder()
+        .add(
+            new_step("hung", async || sleep(Duration::from_secs(5)).await)
+                .timeout(Duration::from_millis(10)),
+        )
+        .on_step_result(move |name, outcome, out| {
+            r.lock()
+                .unwrap()
+                .push((name.to_string(), outcome, out.is_some()));
+        })
+        .execute()
+        .await;
+
+    assert!(res.is_err());
+    assert_eq!(
+        *results.lock().unwrap(),
+        [("hung".to_string(), StepOutcome::Failed, false)]
+    );
+}
+
+// Plans should describe every step in order without running any.
+#[tokio::test]
+async fn test_plan() {
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+    let builder = new_imperative_builder()
+        .add_dep(Dep::new(Database))
+        .add_preflight("reachable", async || true)
+        .add_step("migrate", async |_: Dep<Database>| {
+            RAN.fetch_add(1, Ordering::SeqCst);
+            true
+        })
+        .new_group(|g| {
+            g.name("checks")
+                .parallel()
+                .add(new_step("lint", async || true).phase(1))
+                .add_step("build", async || true)
+                .add_step("test", async |_: Dep<Database>, _: Dep<u32>| true)
+        });
+    let plan = builder.plan();
+
+    let groups: Vec<_> = plan.groups.iter().map(|g| g.label.as_deref()).collect();
+    assert_eq!(groups, [Some("preflight"), None, Some("checks")]);
+    let steps: Vec<_> = plan
+        .steps()
+        .map(|(_, s)| (s.name.as_str(), s.phase))
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            ("reachable", Some(0)),
+            ("migrate", Some(0)),
+            ("build", Some(0)),
+            ("lint", Some(1)),
+            ("test", None),
+        ]
+    );
+    assert!(plan.groups[2].parallel);
+    assert_eq!(
+        plan.groups[1].steps[0].dependencies,
+        [DepInfo::of::<Dep<Database>>()]
+    );
+
+    assert!(!plan.is_resolved());
+    let test = plan.unresolved().next().unwrap();
+    assert_eq!(test.name, "test");
+    assert_eq!(test.dependencies.len(), 2);
+    assert_eq!(test.unresolved, [DepInfo::of::<Dep<u32>>()]);
+    assert_eq!(RAN.load(Ordering::SeqCst), 0);
+}
+
+// Plans should render as graphs with edges from providers to consumers.
+#[tokio::test]
+async fn test_plan_graphs() {
+    struct Pool;
+
+    let plan = new_imperative_builder()
+        .add_dep_with(async || Ok::<_, std::io::Error>(Pool))
+        .add_producing_step("version", async |_: Dep<Pool>| "1.0".to_string())
+        .new_group(|g| {
+            g.name("release")
+                .add_step("deploy", async |v: Dep<String>| v.to_string())
+                .add(new_step("notify", async || String::new()).depends_on(["deploy"]))
+        })
+        .plan();
+
+    assert_eq!(
+        plan.to_dot(),
+        r#"digraph pipeline {
+  p0 [label="provider of Dep<Pool>", shape=box];
+  subgraph cluster_0 {
+    label="steps";
+    s0 [label="version"];
+  }
+  subgraph cluster_1 {
+    label="release";
+    s1 [label="deploy"];
+    s2 [label="notify"];
+  }
+  p0 -> s0 [label="Dep<Pool>"];
+  s0 -> s1 [label="Dep<String>"];
+  s1 -> s2 [style=dashed];
+}
+"#
+    );
+    assert_eq!(
+        plan.to_mermaid(),
+        r#"flowchart TD
+  p0[("provider of Dep#lt;Pool#gt;")]
+  subgraph g0 ["steps"]
+    s0["version"]
+  end
+  subgraph g1 ["release"]
+    s1["deploy"]
+    s2["notify"]
+  end
+  p0 -- "Dep#lt;Pool#gt;" --> s0
+  s0 -- "Dep#lt;String#gt;" --> s1
+  s1 -.-> s2
+  classDef unresolved stroke:red
+"#
+    );
+}
+
+// Break should halt the run, and Continue should continue it.
+#[tokio::test]
+async fn test_control_flow_outcome() {
+    let res = new_imperative_builder()
+        .add_step("poll", async || ControlFlow::<(), u32>::Continue(3))
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["poll"], ControlFlow::Continue(3));
+
+    let res = new_imperative_builder()
+        .add_step("done", async || ControlFlow::<(), u32>::Break(()))
+        .add_step("after", async || ControlFlow::Continue(1))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::UnknownStep(s)) if s == "done"));
+
+    let res = new_imperative_builder()
+        .add_step("fails", async || {
+            ControlFlow::<std::io::Error, ()>::Break(std::io::Error::other("stop"))
+        })
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::Step(s, e)) if s == "fails" && e.to_string() == "stop")
+    );
+}
+
+// Completed steps should be rolled back newest first when a later step fails.
+#[tokio::test]
+async fn test_rollback() {
+    let undone = Arc::new(Mutex::new(vec![]));
+    let undo = |name: &'static str| {
+        let undone = undone.clone();
+        move || {
+            let undone = undone.clone();
+            async move {
+                undone.lock().unwrap().push(name);
+                true
+            }
+        }
+    };
+
+    let res = new_imperative_builder()
+        .add_step_with_rollback("network", async || true, undo("network"))
+        .new_group(|g| {
+            g.add_step_with_rollback("disk", async || true, undo("disk"))
+                .add_step_with_rollback("vm", async || true, undo("vm"))
+                .add_step("boot", async || false)
+        })
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::UnknownStep(s)) if s == "boot"));
+    assert_eq!(*undone.lock().unwrap(), ["vm", "disk"]);
+
+    // the whole run may be rolled back, reporting failed rollbacks
+    undone.lock().unwrap().clear();
+    let res = new_imperative_builder()
+        .rollback_scope(RollbackScope::Run)
+        .add_step_with_rollback("network", async || true, undo("network"))
+        .new_group(|g| {
+            g.add_step_with_rollback(
+                "disk",
+                async || true,
+                async || Err::<(), _>(std::io::Error::other("busy")),
+            )
+            .add_step("boot", async || false)
+        })
+        .execute()
+        .await;
+    let Err(BuilderError::Rollback(e, failed)) = res else {
+        panic!("expected a failed rollback, got {res:?}");
+    };
+    assert!(matches!(*e, BuilderError::UnknownStep(s) if s == "boot"));
+    assert!(matches!(&failed[..], [BuilderError::Step(s, _)] if s == "disk"));
+    assert_eq!(*undone.lock().unwrap(), ["network"]);
+}
+
+// Hardened steps should retry panics and failures, keeping explicit settings.
+#[tokio::test]
+async fn test_hardened_step() {
+    static CNT: AtomicUsize = AtomicUsize::new(0);
+    let step = new_step("flaky", async || {
+        if CNT.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("boom");
+        }
+        true
+    })
+    .retry(RetryPolicy::new(3))
+    .hardened();
+    let builder = new_imperative_builder().add(step);
+    assert_eq!(
+        builder.to_string(),
+        "steps:\n  flaky() [timeout 300s, 3 retries, retry panics]\n"
+    );
+
+    let res = builder.execute().await.unwrap();
+    assert!(res["flaky"]);
+    assert_eq!(CNT.load(Ordering::SeqCst), 2);
+}
+
+// Resumed runs should skip steps which completed before and keep their outputs.
+#[tokio::test]
+async fn test_checkpoint_resume() {
+    #[derive(Clone, Default)]
+    struct Memory(Arc<Mutex<HashMap<String, u32>>>);
+
+    impl Checkpointer<u32> for Memory {
+        fn save(
+            &self,
+            key: &str,
+            output: &u32,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0.lock().unwrap().insert(key.to_string(), *output);
+            Ok(())
+        }
+
+        fn load(&self) -> Result<Checkpoint<u32>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(self.0.lock().unwrap().clone().into())
+        }
+    }
+
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+    let pipeline = |crash: bool| {
+        new_imperative_builder()
+            .add_producing_step("fetch", async || {
+                RAN.fetch_add(1, Ordering::SeqCst);
+                2_u32
+            })
+            .new_group(move |g| {
+                g.add_step("deploy", move |v: Dep<u32>| async move {
+                    assert!(!crash, "crashed");
+                    **v + 1
+                })
+            })
+    };
+
+    let memory = Memory::default();
+    let res = pipeline(true).checkpoint(memory.clone()).execute().await;
+    assert!(matches!(res, Err(BuilderError::Panicked(..))));
+    let checkpoint = memory.load().unwrap();
+    assert_eq!(checkpoint.get("fetch"), Some(&2));
+    assert_eq!(checkpoint.len(), 1);
+
+    let report = pipeline(false)
+        .checkpoint(memory.clone())
+        .resume_from(checkpoint)
+        .execute_report()
+        .await;
+    assert!(report.is_success());
+    assert_eq!(report["fetch"], 2);
+    assert_eq!(report["deploy"], 3);
+    assert_eq!(report.step("fetch").unwrap().outcome, StepOutcome::Skipped);
+    assert_eq!(RAN.load(Ordering::SeqCst), 1);
+    assert_eq!(memory.load().unwrap().get("deploy"), Some(&3));
+}
+
+// JSON checkpoints should survive a round trip through their file.
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_json_checkpointer() {
+    let path = std::env::temp_dir().join(format!("imperat-{}.json", std::process::id()));
+    let checkpointer = imperat::JsonCheckpointer::new(&path);
+    let empty: Checkpoint<String> = checkpointer.load().unwrap();
+    assert!(empty.is_empty());
+
+    new_imperative_builder()
+        .add_step("build", async || "built".to_string())
+        .checkpoint(checkpointer.clone())
+        .execute()
+        .await
+        .unwrap();
+    let checkpoint: Checkpoint<String> = checkpointer.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(checkpoint.get("build").map(String::as_str), Some("built"));
+}
+
+// Encrypted checkpoints should be sealed at rest, and shouldn't load with
+// another key.
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_encrypted_checkpointer() {
+    // not a real cipher: XORs with the key and authenticates by prefixing
+    // the associated data and key
+    struct XorCipher(u8);
+
+    impl imperat::Cipher for XorCipher {
+        fn seal(
+            &self,
+            plaintext: &[u8],
+            aad: &[u8],
+        ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+            let tag = aad.iter().chain([&self.0]);
+            Ok(tag.chain(plaintext).map(|b| b ^ self.0).collect())
+        }
+
+        fn open(
+            &self,
+            ciphertext: &[u8],
+            aad: &[u8],
+        ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+            let plain: Vec<_> = ciphertext.iter().map(|b| b ^ self.0).collect();
+            plain
+                .strip_prefix(aad)
+                .and_then(|p| p.strip_prefix(&[self.0]))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| "failed to authenticate".into())
+        }
+    }
+
+    let path = std::env::temp_dir().join(format!("imperat-sealed-{}.json", std::process::id()));
+    let checkpointer = imperat::JsonCheckpointer::new(&path).encrypted(XorCipher(0x5a));
+    new_imperative_builder()
+        .add_step("build", async || "secret output".to_string())
+        .checkpoint(checkpointer.clone())
+        .execute()
+        .await
+        .unwrap();
+
+    let raw = std::fs::read(&path).unwrap();
+    let loaded: Result<Checkpoint<String>, _> = checkpointer.load();
+    let wrong_key: Result<Checkpoint<String>, _> = imperat::JsonCheckpointer::new(&path)
+        .encrypted(XorCipher(0x17))
+        .load();
+    let plain: Result<Checkpoint<String>, _> = imperat::JsonCheckpointer::new(&path).load();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(!String::from_utf8_lossy(&raw).contains("secret output"));
+    assert_eq!(
+        loaded.unwrap().get("build").map(String::as_str),
+        Some("secret output")
+    );
+    assert!(wrong_key.is_err());
+    assert!(plain.is_err());
+}
+
+struct Token {
+    serial: usize,
+    // a token is only good for one use
+    used: AtomicUsize,
+    revoked: bool,
+}
+
+impl Token {
+    fn new(serial: usize) -> Self {
+        Token {
+            serial,
+            used: AtomicUsize::new(0),
+            revoked: false,
+        }
+    }
+
+    fn take(&self) -> usize {
+        self.used.fetch_add(1, Ordering::SeqCst);
+        self.serial
+    }
+}
+
+impl Refreshable for Token {
+    fn is_stale(&self) -> bool {
+        self.used.load(Ordering::SeqCst) > 0
+    }
+
+    async fn refresh(&self) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if self.revoked {
+            return Err("token was revoked".into());
+        }
+        Ok(Token::new(self.serial + 1))
+    }
+}
+
+// Stale refreshable deps should be refreshed before each step which uses them.
+#[tokio::test]
+async fn test_refreshable_dep() {
+    let report = new_imperative_builder()
+        .add_refreshable_dep(Token::new(0))
+        .add_step("first", async |t: Dep<Token>| t.take())
+        .add_step("second", async |t: Dep<Token>| t.take())
+        .add_step("unused", async || 10)
+        .add_step("third", async |t: Dep<Token>| t.take())
+        .execute_report()
+        .await;
+    assert!(report.is_success());
+    assert_eq!(report["first"], 0);
+    assert_eq!(report["second"], 1);
+    assert_eq!(report["third"], 2);
+
+    let revoked = Token {
+        revoked: true,
+        ..Token::new(0)
+    };
+    let res = new_imperative_builder()
+        .add_refreshable_dep(revoked)
+        .add_step("first", async |t: Dep<Token>| t.take())
+        .add_step("second", async |t: Dep<Token>| t.take())
+        .execute()
+        .await;
+    assert!(matches!(
+        res.unwrap_err(),
+        BuilderError::DepRefresh(_, e) if e.to_string() == "token was revoked"
+    ));
+}
+
+// Groups should inject their own working directory over the builder's.
+#[tokio::test]
+async fn test_work_dir() {
+    let root = WorkDir::new("/repo");
+    let manifest = async |dir: WorkDir| dir.join("Cargo.toml").display().to_string();
+
+    let res = new_imperative_builder()
+        .work_dir(root.path())
+        .add_step("root", manifest)
+        .new_group(|g| {
+            g.name("api")
+                .work_dir(root.join("services/api"))
+                .add_step("api", manifest)
+        })
+        .new_group(|g| g.work_dir("/elsewhere").add_step("other", manifest))
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["root"], "/repo/Cargo.toml");
+    assert_eq!(res["api"], "/repo/services/api/Cargo.toml");
+    assert_eq!(res["other"], "/elsewhere/Cargo.toml");
+
+    let api = root.child("services/api");
+    assert_eq!(api.path(), std::path::Path::new("/repo/services/api"));
+    assert_eq!(
+        root.relative(api.path()),
+        Some(std::path::Path::new("services/api"))
+    );
+    assert_eq!(api.relative(root.path()), None);
+    assert_eq!(api.command("cargo").get_current_dir(), Some(api.path()));
+}
+
+// Steps' counters should be reported per step and totalled per group.
+#[tokio::test]
+async fn test_counters() {
+    let report = new_imperative_builder()
+        .add_step("extract", async |counters: Counters| {
+            counters.add("rows", 100);
+            true
+        })
+        .new_group(|g| {
+            g.name("load")
+                .add(
+                    new_step("upload", async |counters: Counters, attempt: Attempt| {
+                        counters.add("bytes", 512);
+                        counters.increment("uploads");
+                        attempt.0 > 1
+                    })
+                    .retry(RetryPolicy::new(2)),
+                )
+                .add_step("index", async |counters: Counters| {
+                    counters.add("rows", 100);
+                    counters.get("rows") == 100
+                })
+        })
+        .execute_report()
+        .await;
+    assert!(report.is_success());
+
+    let upload = report.step("upload").unwrap();
+    assert_eq!(upload.counters["bytes"], 1024);
+    assert_eq!(upload.counters["uploads"], 2);
+    let extract = report.step("extract").unwrap();
+    assert_eq!(extract.counters.keys().collect::<Vec<_>>(), ["rows"]);
+
+    let load = report.counters(Some("load"));
+    assert_eq!(load["rows"], 100);
+    assert_eq!(load["bytes"], 1024);
+    let all = report.counters(None);
+    assert_eq!(all["rows"], 200);
+    assert_eq!(all["uploads"], 2);
+}
+
+// Records the spans and events the executor emits, with their fields sorted.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+struct TraceRecorder(Arc<Mutex<Vec<String>>>);
+
+#[cfg(feature = "tracing")]
+struct TraceFields(Vec<String>);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for TraceFields {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        // run ids and durations vary
+        if !matches!(field.name(), "run_id" | "metadata" | "duration") {
+            self.0.push(format!("{}={value:?}", field.name()));
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl TraceRecorder {
+    fn push(&self, kind: &str, mut fields: TraceFields) -> u64 {
+        fields.0.sort();
+        let mut log = self.0.lock().unwrap();
+        log.push(format!("{kind} {}", fields.0.join(" ")));
+        log.len() as u64
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for TraceRecorder {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut fields = TraceFields(vec![]);
+        span.record(&mut fields);
+        let kind = format!("span {}", span.metadata().name());
+        tracing::span::Id::from_u64(self.push(&kind, fields))
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = TraceFields(vec![]);
+        event.record(&mut fields);
+        self.push("event", fields);
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+// Steps and groups should be traced, with an event as each step starts and ends.
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn test_tracing() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    let recorder = TraceRecorder::default();
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let report = new_imperative_builder()
+        .new_group(|g| {
+            g.name("ci")
+                .add(new_step("build", async || true).alias("compile"))
+                .add(
+                    new_step("flaky", async || {
+                        ATTEMPTS.fetch_add(1, Ordering::SeqCst) > 0
+                    })
+                    .retry(RetryPolicy::new(1)),
+                )
+        })
+        .new_group(|g| {
+            g.parallel()
+                .add_step("test", async || false)
+                .add(new_step("deploy", async || true).depends_on(["test"]))
+        })
+        .execute_report()
+        .await;
+    assert!(report.step("compile").is_some());
+
+    let log = recorder.0.lock().unwrap().clone();
+    for line in [
+        "span group group=ci",
+        r#"span step attempt=1 group="ci" id="03535349" step="build""#,
+        "event message=step started",
+        "event message=step succeeded",
+        r#"span step attempt=2 group="ci" id="a790a61c" step="flaky""#,
+        "event message=step attempt failed",
+        r#"event group="2" message=step failed step="test""#,
+        r#"event group="2" message=step skipped reason="step 'deploy' was skipped as 'test' didn't succeed" step="deploy""#,
+        "event message=step 'compile' is deprecated, use 'build' instead",
+    ] {
+        assert!(
+            log.iter().any(|l| l == line),
+            "missing {line:?} in {log:#?}"
+        );
+    }
+}
+
+// Retry policies should only retry the errors they classify as transient.
+#[tokio::test]
+async fn test_retry_on_error() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+    let transient = |e: &(dyn std::error::Error + 'static)| {
+        e.downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::ConnectionReset)
+    };
+    let res = new_imperative_builder()
+        .add(
+            new_step("fetch", async || {
+                let kind = match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+                    0 => std::io::ErrorKind::ConnectionReset,
+                    _ => std::io::ErrorKind::InvalidData,
+                };
+                Err::<(), _>(std::io::Error::from(kind))
+            })
+            .retry(RetryPolicy::new(5).retry_on_error(transient)),
+        )
+        .execute()
+        .await;
+    assert!(res.is_err());
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+
+    // executor errors, such as timeouts, may be retried by type
+    let res = new_imperative_builder()
+        .add(
+            new_step("slow", async |attempt: Attempt| {
+                if attempt.0 == 1 {
+                    sleep(Duration::from_secs(10)).await;
+                }
+                Ok::<_, std::io::Error>(())
+            })
+            .timeout(Duration::from_millis(10))
+            .retry(RetryPolicy::new(1).retry_on::<BuilderError>()),
+        )
+        .add(
+            new_step("invalid", async || {
+                Err::<(), _>(std::io::Error::from(std::io::ErrorKind::InvalidData))
+            })
+            .retry(RetryPolicy::new(1).retry_on::<BuilderError>()),
+        )
+        .execute()
+        .await;
+    assert!(matches!(res.unwrap_err(), BuilderError::Step(name, _) if name == "invalid"));
+}
+
+// Streamed events should follow the run live and end once it finishes.
+#[tokio::test]
+async fn test_execute_streaming() {
+    use futures::StreamExt;
+
+    let (events, run) = new_imperative_builder()
+        .add_step("import", |progress: Progress| async move {
+            progress.advance(2);
+            true
+        })
+        .new_group(|gb| gb.add_step("broken", async || false))
+        .execute_streaming();
+    let (events, report) = tokio::join!(events.collect::<Vec<_>>(), run);
+
+    assert!(!report.is_success());
+    let started = |group: &str| PipelineEvent::GroupStarted {
+        group: group.to_string(),
+    };
+    assert_eq!(events[0], started("0"));
+    assert_eq!(
+        events[1],
+        PipelineEvent::StepStarted {
+            name: "import".to_string(),
+            group: "0".to_string(),
+            attempt: 1,
+        }
+    );
+    assert_eq!(
+        events[2],
+        PipelineEvent::StepProgress {
+            name: "import".to_string(),
+            progress: StepProgress {
+                done: 2,
+                total: None,
+            },
+        }
+    );
+    assert!(matches!(
+        &events[3],
+        PipelineEvent::StepFinished { name, success: true, outcome: StepOutcome::Succeeded, .. }
+            if name == "import"
+    ));
+    assert_eq!(events[4], started("1"));
+    assert!(matches!(
+        &events[6],
+        PipelineEvent::StepFinished { name, group, success: false, .. }
+            if name == "broken" && group == "1"
+    ));
+    assert!(matches!(
+        events.last(),
+        Some(PipelineEvent::PipelineFinished {
+            success: false,
+            error: Some(_)
+        })
+    ));
+    assert_eq!(events.len(), 8);
+}
+
+// Async, error, and group callbacks should run around every step whatever
+// its outcome.
+#[tokio::test]
+async fn test_lifecycle_callbacks() {
+    let audit = Arc::new(Mutex::new(vec![]));
+    let log = |audit: &Arc<Mutex<Vec<String>>>| {
+        let audit = audit.clone();
+        move |entry: String| audit.lock().unwrap().push(entry)
+    };
+    let (before, after, error, start, end) = (
+        log(&audit),
+        log(&audit),
+        log(&audit),
+        log(&audit),
+        log(&audit),
+    );
+
+    let res = new_imperative_builder()
+        .before_step_async(move |s| {
+            let entry = format!("before {}", s.name());
+            let before = before.clone();
+            async move {
+                sleep(Duration::from_millis(1)).await;
+                before(entry);
+            }
+        })
+        .after_step_async(move |name, outcome, _| {
+            let entry = format!("after {name} {outcome:?}");
+            let after = after.clone();
+            async move { after(entry) }
+        })
+        .on_step_error(move |name, e| error(format!("error {name}: {e}")))
+        .on_group_start(move |group| start(format!("start {group}")))
+        .on_group_end(move |group, e| end(format!("end {group} {}", e.is_some())))
+        .add_step("ok", async || Ok::<_, std::io::Error>(()))
+        .add(
+            new_step("slow", async || {
+                sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .timeout(Duration::from_millis(10)),
+        )
+        .execute()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(BuilderError::Cancelled {
+            reason: CancelReason::Timeout,
+            ..
+        })
+    ));
+    assert_eq!(
+        *audit.lock().unwrap(),
+        [
+            "start 0",
+            "before ok",
+            "after ok Succeeded",
+            "before slow",
+            "error slow: step 'slow' timed out",
+            "after slow Failed",
+            "end 0 true",
+        ]
+    );
+}
+
+// Failed runs should render as diagnostics labeling each failed step.
+#[cfg(feature = "miette")]
+#[tokio::test]
+async fn test_run_diagnostic() {
+    use miette::{GraphicalReportHandler, GraphicalTheme};
+
+    let report = new_imperative_builder()
+        .add_step("fetch", async || Ok::<_, miette::Report>(()))
+        .add_step("parse", async || {
+            Err::<(), _>(miette::miette!("unexpected token"))
+        })
+        .execute_report()
+        .await;
+
+    let diagnostic = report.diagnostic().unwrap();
+    let mut out = String::new();
+    GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+        .render_report(&mut out, &diagnostic)
+        .unwrap();
+    assert!(out.contains("imperat::run_failed"), "{out}");
+    assert!(out.contains("[0] fetch (Succeeded)"), "{out}");
+    assert!(out.contains("[0] parse (Failed)"), "{out}");
+    assert!(out.contains("unexpected token"), "{out}");
+    assert!(out.contains("1 of 2 steps succeeded"), "{out}");
+
+    // run errors convert into reports
+    let res: miette::Result<_> = new_imperative_builder()
+        .add_step("parse", async || false)
+        .execute()
+        .await
+        .map_err(Into::into);
+    assert!(res.is_err());
+}
+
+// Child groups and embedded pipelines should run in place with their own
+// dependencies.
+#[tokio::test]
+async fn test_nested_groups() {
+    fn migrate_database() -> SubPipeline<bool> {
+        new_imperative_builder()
+            .add_dep(Dep::new("postgres://migrations".to_string()))
+            .add_step("connect", async |url: Dep<String>| {
+                url.as_str() == "postgres://migrations"
+            })
+            .new_group(|gb| gb.name("schema").add_step("migrate", async || true))
+            .into_group("migrate database")
+    }
+
+    let order = Arc::new(Mutex::new(vec![]));
+    let track = |name: &'static str| {
+        let order = order.clone();
+        move || {
+            order.lock().unwrap().push(name);
+            async { true }
+        }
+    };
+    let report = new_imperative_builder()
+        .add_dep(Dep::new("postgres://app".to_string()))
+        .new_group(|gb| {
+            gb.name("build")
+                .add_step("compile", track("compile"))
+                .new_group(|gb| gb.name("test").add_step("unit", track("unit")))
+        })
+        .add_pipeline(migrate_database())
+        .add_step("deploy", async |url: Dep<String>| {
+            url.as_str() == "postgres://app"
+        })
+        .execute_report()
+        .await;
+
+    assert!(report.is_success(), "{:?}", report.error);
+    assert_eq!(*order.lock().unwrap(), ["compile", "unit"]);
+    assert!(report["connect"] && report["migrate"] && report["deploy"]);
+    let group = |name: &str| report.step(name).unwrap().group.clone();
+    assert_eq!(group("unit"), "test");
+    assert_eq!(group("connect"), "migrate database");
+    assert_eq!(group("migrate"), "schema");
+}
+
+// Step durations should be summarized per group and for the whole run.
+#[tokio::test]
+async fn test_duration_histograms() {
+    let report = new_imperative_builder()
+        .add_step("fast", async || true)
+        .new_group(|g| {
+            g.name("slow")
+                .add_step("sleep", async || {
+                    sleep(Duration::from_millis(20)).await;
+                    true
+                })
+                .add_step("fail", async || false)
+        })
+        .execute_report()
+        .await;
+
+    assert_eq!(report.durations.count, 3);
+    assert_eq!(report.durations.buckets.iter().sum::<u64>(), 3);
+    assert!(report.durations.max.unwrap() >= Duration::from_millis(20));
+    assert_eq!(report.durations.quantile(1.0), report.durations.max);
+    assert!(report.durations.quantile(0.5).unwrap() < Duration::from_millis(20));
+
+    let slow = &report.group_durations["slow"];
+    assert_eq!(slow.count, 2);
+    assert_eq!(report.group_durations["0"].count, 1);
+    let mut merged = report.group_durations["0"].clone();
+    merged.merge(slow);
+    assert_eq!(merged, report.durations);
+}
+
+#[step(
+    name = "load config",
+    retries = 2,
+    timeout_ms = 1000,
+    tags("setup", "config")
+)]
+async fn load_config(attempt: Attempt) -> Result<usize, std::io::Error> {
+    match attempt.0 {
+        1 => Err(std::io::ErrorKind::NotFound.into()),
+        n => Ok(n),
+    }
+}
+
+/// Checks nothing, quickly.
+#[step]
+async fn check() -> Result<usize, std::io::Error> {
+    Ok(0)
+}
+
+// Steps declared with `#[step]` should carry their name and options.
+#[tokio::test]
+async fn test_step_attribute() {
+    let tags = Arc::new(Mutex::new(vec![]));
+    let seen = tags.clone();
+    let res = new_imperative_builder()
+        .before_step(move |s| seen.lock().unwrap().push(s.tags().join(",")))
+        .add(load_config)
+        .add(check)
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(load_config::NAME, "load config");
+    assert_eq!(*res["load config"].as_ref().unwrap(), 2);
+    assert_eq!(*res["check"].as_ref().unwrap(), 0);
+    assert_eq!(*tags.lock().unwrap(), ["setup,config", "setup,config", ""]);
+    assert_eq!(check::call().await.unwrap(), 0);
+}
+
+// The test harness should substitute mocks and record which steps used them.
+#[tokio::test]
+async fn test_harness_substitution() {
+    struct Database(&'static str);
+    struct Mailer;
+
+    let harness = TestHarness::new()
+        .mock(Dep::new(Database("mock")))
+        .mock(Dep::new(Mailer));
+    let builder = new_imperative_builder()
+        .add_dep(Dep::new(Database("postgres")))
+        .add_step("migrate users", async |db: Dep<Database>| db.0 == "mock")
+        .add_step("report", async || true);
+    let res = harness.install(builder).execute().await.unwrap();
+
+    assert!(res["migrate users"]);
+    assert_eq!(harness.substituted(), [DepInfo::of::<Dep<Database>>()]);
+    harness.assert_dep_used::<Database>("migrate users");
+    harness.assert_dep_unused::<Database>("report");
+    assert!(harness.used_by::<Mailer>().is_empty());
+}
+
+// Tag filters should only run matching steps and report the rest as skipped.
+#[tokio::test]
+async fn test_filter_tags() {
+    let report = new_imperative_builder()
+        .add(new_step("build", async || true).tag("ci"))
+        .add(new_step("bench", async || true).tag("ci").tag("slow"))
+        .add_step("lint", async || true)
+        .new_group(|gb| {
+            gb.tag("migrations")
+                .add_step("migrate users", async || true)
+                .add(new_step("migrate orders", async || true).tag("slow"))
+        })
+        .filter_tags(&["ci", "migrations"], &["slow"])
+        .execute_report()
+        .await;
+
+    assert!(report.is_success());
+    assert!(report["build"]);
+    assert!(report["migrate users"]);
+    for name in ["bench", "lint", "migrate orders"] {
+        assert_eq!(report.step(name).unwrap().outcome, StepOutcome::Skipped);
+    }
+}
+
+// Runs should spawn and time out steps without tokio given another executor.
+#[test]
+fn test_thread_executor() {
+    let barrier = TestBarrier::new(2);
+    let pipeline = new_imperative_builder()
+        .executor(ThreadExecutor)
+        .add_dep(barrier)
+        .new_group(|gb| {
+            gb.parallel()
+                .add_step("left", async |b: TestBarrier| b.wait().await)
+                .add_step("right", async |b: TestBarrier| b.wait().await)
+        })
+        .new_group(|gb| {
+            gb.add(
+                new_step("hang", futures::future::pending::<()>).timeout(Duration::from_millis(5)),
+            )
+        });
+
+    let report = futures::executor::block_on(pipeline.execute_report());
+    assert_eq!(report.step("left").unwrap().outcome, StepOutcome::Succeeded);
+    assert_eq!(
+        report.step("right").unwrap().outcome,
+        StepOutcome::Succeeded
+    );
+    assert!(
+        matches!(report.error, Some(BuilderError::Cancelled { reason: CancelReason::Timeout, ref during_step }) if during_step == "hang"),
+        "{:?}",
+        report.error
+    );
+
+    // timers fire in deadline order, and abandoned ones never fire
+    use futures::future::{self, Either};
+    use imperat::Executor;
+    let order = Mutex::new(vec![]);
+    let sleep = async |ms, n| {
+        ThreadExecutor.sleep(Duration::from_millis(ms)).await;
+        order.lock().unwrap().push(n);
+    };
+    let hour = Duration::from_secs(3600);
+    futures::executor::block_on(async {
+        drop(ThreadExecutor.sleep(hour));
+        let raced = future::select(
+            ThreadExecutor.sleep(hour),
+            ThreadExecutor.sleep(Duration::ZERO),
+        );
+        assert!(matches!(raced.await, Either::Right(_)));
+        future::join(sleep(20, 2), sleep(5, 1)).await;
+    });
+    assert_eq!(*order.lock().unwrap(), [1, 2]);
+}
+
+// Fail points should fail steps' attempts where they're set.
+#[cfg(feature = "failpoints")]
+#[tokio::test]
+async fn test_fail_points() {
+    use imperat::{FailPoint, FailPoints};
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+
+    let points = FailPoints::new()
+        .fail_times(FailPoint::BeforeAwait, "flaky", 1)
+        .fail(FailPoint::BeforeResolve, "broken")
+        .fail(FailPoint::AfterStep, "finished");
+    let report = new_imperative_builder()
+        .fail_points(points.clone())
+        .add(new_step("flaky", async || true).retry(RetryPolicy::new(1)))
+        .add_step("finished", async || RAN.fetch_add(1, Ordering::SeqCst) == 0)
+        .execute_report()
+        .await;
+    assert_eq!(
+        report.step("flaky").unwrap().outcome,
+        StepOutcome::Succeeded
+    );
+    assert_eq!(
+        report.step("finished").unwrap().outcome,
+        StepOutcome::Failed
+    );
+    assert_eq!(RAN.load(Ordering::SeqCst), 1);
+    assert!(
+        matches!(
+            report.error,
+            Some(BuilderError::FailPoint(ref s, FailPoint::AfterStep)) if s == "finished"
+        ),
+        "{:?}",
+        report.error
+    );
+
+    let e = new_imperative_builder()
+        .fail_points(points)
+        .add_step("broken", async || true)
+        .execute()
+        .await
+        .expect_err("should fail before resolving");
+    assert!(
+        matches!(e, BuilderError::FailPoint(ref s, FailPoint::BeforeResolve) if s == "broken"),
+        "{e:?}"
+    );
+}
+
+// Reports should map how the run ended to an exit code.
+#[tokio::test]
+async fn test_exit_code() {
+    use std::process::ExitCode;
+
+    let run = async |fail: bool, tolerate: bool| {
+        new_imperative_builder()
+            .add_step("ok", async || true)
+            .new_group(move |gb| {
+                let gb = gb.add_step("check", move || async move { !fail });
+                if tolerate { gb.tolerate_failure() } else { gb }
+            })
+            .execute_report()
+            .await
+    };
+
+    let codes = ExitCodes::default();
+    assert_eq!(run(false, false).await.exit_code(codes), ExitCode::SUCCESS);
+    assert_eq!(run(true, false).await.exit_code(codes), ExitCode::from(1));
+    assert_eq!(run(true, true).await.exit_code(codes), ExitCode::from(2));
+    let lenient = codes.partial(0).failure(3);
+    assert_eq!(run(true, true).await.exit_code(lenient), ExitCode::SUCCESS);
+    assert_eq!(run(true, false).await.exit_code(lenient), ExitCode::from(3));
+}
+
+// Groups should start steps as their scheduler decides.
+#[tokio::test]
+async fn test_custom_scheduler() {
+    struct ByPriority;
+
+    impl Scheduler for ByPriority {
+        fn next(&self, ready: &[ScheduledStep<'_>], running: usize) -> Option<usize> {
+            let (i, _) = ready.iter().enumerate().max_by_key(|(_, s)| s.priority)?;
+            (running == 0).then_some(i)
+        }
+    }
+
+    let order = Arc::new(Mutex::new(vec![]));
+    let seen = order.clone();
+    new_imperative_builder()
+        .new_group(|gb| {
+            gb.scheduler(ByPriority)
+                .add_step("low", async || true)
+                .add(new_step("high", async || true).priority(10))
+                .add(
+                    new_step("after", async || true)
+                        .depends_on(["low"])
+                        .priority(20),
+                )
+                .add(new_step("mid", async || true).priority(5))
+        })
+        .before_step(move |s| seen.lock().unwrap().push(s.name().to_string()))
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(*order.lock().unwrap(), ["high", "mid", "low", "after"]);
+
+    let recorder = ConcurrencyRecorder::new();
+    let step = async |recorder: ConcurrencyRecorder| {
+        let _guard = recorder.enter();
+        sleep(Duration::from_millis(5)).await;
+    };
+    new_imperative_builder()
+        .add_dep(recorder.clone())
+        .new_group(|mut gb| {
+            for i in 0..6 {
+                gb = gb.add_step(&format!("step #{i}"), step);
+            }
+            gb.scheduler(Bounded(2))
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(recorder.max_concurrency(), 2);
+    assert_eq!(recorder.total(), 6);
+}
+
+// Schedulers should hear how each step they started fared, and adaptive
+// ones should grow their limit as steps succeed and back off as they fail
+// or run slowly.
+#[tokio::test]
+async fn test_adaptive_scheduler() {
+    #[derive(Clone, Default)]
+    struct Recording(Arc<Mutex<Vec<String>>>);
+
+    impl Scheduler for Recording {
+        fn next(&self, _: &[ScheduledStep<'_>], _: usize) -> Option<usize> {
+            Some(0)
+        }
+
+        fn finished(&self, name: &str, _: Duration, success: bool) {
+            self.0.lock().unwrap().push(format!("{name} {success}"));
+        }
+    }
+
+    let recording = Recording::default();
+    let res = new_imperative_builder()
+        .new_group(|gb| {
+            gb.scheduler(recording.clone())
+                .add_step("ok", async || true)
+                .add(new_step("fails", async || false).depends_on(["ok"]))
+                .add(new_step("skipped", async || true).depends_on(["fails"]))
+        })
+        .execute()
+        .await;
+    assert!(!res.unwrap().contains_key("skipped"));
+    assert_eq!(*recording.0.lock().unwrap(), ["ok true", "fails false"]);
+
+    let adaptive = Adaptive::new(4).max(6);
+    assert_eq!(adaptive.limit(), 4);
+    assert_eq!(adaptive.next(&[], 3), Some(0));
+    assert_eq!(adaptive.next(&[], 4), None);
+    for _ in 0..5 {
+        adaptive.finished("step", Duration::from_millis(1), true);
+    }
+    assert_eq!(adaptive.limit(), 5);
+    for _ in 0..20 {
+        adaptive.finished("step", Duration::from_millis(1), true);
+    }
+    assert_eq!(adaptive.limit(), 6);
+    adaptive.finished("step", Duration::from_millis(1), false);
+    assert_eq!(adaptive.limit(), 3);
+
+    let adaptive = Adaptive::new(4)
+        .min(2)
+        .latency_target(Duration::from_millis(100));
+    adaptive.finished("step", Duration::from_millis(200), true);
+    assert_eq!(adaptive.limit(), 2);
+    adaptive.finished("step", Duration::from_millis(200), true);
+    assert_eq!(adaptive.limit(), 2);
+}
+
+// Steps should be skipped when a step they require didn't succeed, even in
+// groups which tolerate failure.
+#[tokio::test]
+async fn test_requires_success_of() {
+    let report = new_any_builder()
+        .new_group(|gb| {
+            gb.tolerate_failure()
+                .add_step("fetch", any_output(async || false))
+                .add(new_step("parse", any_output(async || 1_u32)).requires_success_of(["fetch"]))
+                .add_step("report", any_output(async || 2_u32))
+        })
+        .new_group(|gb| {
+            gb.add(new_step("index", any_output(async || 3_u32)).requires_success_of(["parse"]))
+                .add(new_step("notify", any_output(async || 4_u32)).requires_success_of(["report"]))
+        })
+        .execute_report()
+        .await;
+
+    assert!(report.is_success(), "{:?}", report.error);
+    let outcome = |name| report.step(name).unwrap().outcome;
+    assert_eq!(outcome("fetch"), StepOutcome::Failed);
+    assert_eq!(outcome("parse"), StepOutcome::Skipped);
+    assert_eq!(outcome("report"), StepOutcome::Succeeded);
+    assert_eq!(outcome("index"), StepOutcome::Skipped);
+    assert_eq!(outcome("notify"), StepOutcome::Succeeded);
+    assert_eq!(
+        report.step("index").unwrap().error.as_deref(),
+        Some("step 'index' was skipped as 'parse' didn't succeed")
+    );
+    let outputs = Outputs::from(report.into_outputs());
+    assert_eq!(outputs.get::<Skipped>("parse"), Some(&Skipped));
+    assert_eq!(outputs.get::<u32>("notify"), Some(&4));
+}
+
+// Diffs between runs should flag new failures, fixed steps, and slowdowns.
+#[tokio::test]
+async fn test_run_diff() {
+    let run = async |broken: &'static str| {
+        new_imperative_builder()
+            .new_group(move |gb| {
+                gb.name("ci")
+                    .tolerate_failure()
+                    .add_step("build", move || async move { broken != "build" })
+                    .add_step("test", move || async move { broken != "test" })
+            })
+            .execute_report()
+            .await
+            .summary()
+    };
+
+    let before = run("build").await;
+    let mut after = run("test").await;
+    after.steps.remove("ci/build");
+    after.steps.insert(
+        "ci/lint".to_string(),
+        StepSummary {
+            outcome: StepOutcome::Succeeded,
+            duration: None,
+        },
+    );
+    let diff = before.diff(&after);
+    assert_eq!(diff.new_failures, ["ci/test"]);
+    assert!(diff.fixed.is_empty());
+    assert_eq!(diff.added, ["ci/lint"]);
+    assert_eq!(diff.removed, ["ci/build"]);
+    assert!(diff.is_regression());
+
+    let mut slow = before.clone();
+    let step = slow.steps.get_mut("ci/test").unwrap();
+    step.duration = step.duration.map(|d| d * 2 + Duration::from_millis(1));
+    let diff = before.diff(&slow);
+    assert_eq!(diff.slower.len(), 1);
+    assert_eq!(diff.slower[0].name, "ci/test");
+    assert!(slow.diff(&before).slower.is_empty());
+
+    let diff = run("test").await.diff(&before);
+    assert_eq!(diff.new_failures, ["ci/build"]);
+    assert_eq!(diff.fixed, ["ci/test"]);
+    assert_eq!(
+        diff.to_string().lines().next(),
+        Some("new failure: ci/build")
+    );
+}
+
+// Steps with the same name in different groups should each have their own
+// entry in a run's summary.
+#[tokio::test]
+async fn test_run_diff_duplicate_names() {
+    let run = async |broken: &'static str| {
+        new_imperative_builder()
+            .new_group(move |gb| {
+                gb.name("staging")
+                    .tolerate_failure()
+                    .add_step("deploy", move || async move { broken != "staging" })
+            })
+            .new_group(move |gb| {
+                gb.name("prod")
+                    .tolerate_failure()
+                    .add_step("deploy", move || async move { broken != "prod" })
+            })
+            .execute_report()
+            .await
+            .summary()
+    };
+
+    let before = run("staging").await;
+    assert_eq!(
+        before.steps.keys().collect::<Vec<_>>(),
+        ["prod/deploy", "staging/deploy"]
+    );
+    assert_eq!(before.steps["staging/deploy"].outcome, StepOutcome::Failed);
+    assert_eq!(before.steps["prod/deploy"].outcome, StepOutcome::Succeeded);
+
+    let diff = before.diff(&run("prod").await);
+    assert_eq!(diff.new_failures, ["prod/deploy"]);
+    assert_eq!(diff.fixed, ["staging/deploy"]);
+}
+
+// Reports should snapshot the environment variables a run was configured with.
+#[tokio::test]
+async fn test_snapshot_env() {
+    let path = std::env::var("PATH").unwrap();
+    let report = new_imperative_builder()
+        .snapshot_env(&["PATH", "IMPERAT_UNSET_VARIABLE"])
+        .redact(|msg| msg.replace("bin", "***"))
+        .new_group(|gb| {
+            gb.name("deploy")
+                .snapshot_env(&["PATH"])
+                .add_step("release", async || true)
+        })
+        .add_step("build", async || true)
+        .execute_report()
+        .await;
+
+    let redacted = path.replace("bin", "***");
+    assert_eq!(report.env.len(), 1);
+    assert_eq!(report.env["PATH"], redacted);
+    assert_eq!(report.group_env.len(), 1);
+    assert_eq!(report.group_env["deploy"]["PATH"], redacted);
+}
+
+// Failed health checks should skip degradable steps and fail critical ones.
+#[tokio::test]
+async fn test_degradable_steps() {
+    struct Search;
+    struct Database;
+
+    let pipeline = |critical: bool| {
+        let index = new_step("index", any_output(async |_: Dep<Search>| 1_u32));
+        new_any_builder()
+            .add_dep(Dep::new(Search))
+            .add_dep(Dep::new(Database))
+            .health_check::<Search, _, _>(async |_: Dep<Search>| false)
+            .health_check::<Database, _, _>(async || true)
+            .add(if critical { index } else { index.degradable() })
+            .add_step("save", any_output(async |_: Dep<Database>| 2_u32))
+    };
+
+    let report = pipeline(false).execute_report().await;
+    assert!(report.is_success(), "{:?}", report.error);
+    assert_eq!(report.unhealthy, ["Dep<Search>"]);
+    assert_eq!(report.step("index").unwrap().outcome, StepOutcome::Skipped);
+    assert_eq!(report.step("save").unwrap().outcome, StepOutcome::Succeeded);
+
+    let report = pipeline(true).execute_report().await;
+    assert_eq!(report.step("index").unwrap().outcome, StepOutcome::Failed);
+    assert!(report.step("save").is_none());
+    assert!(
+        matches!(
+            report.error,
+            Some(BuilderError::Unhealthy(ref s, ref dep)) if s == "index" && dep == "Dep<Search>"
+        ),
+        "{:?}",
+        report.error
+    );
+}
+
+// Steps should wait for their dependencies to become ready, probing them
+// once per run, and fail clearly if they don't in time.
+#[tokio::test]
+async fn test_wait_for_dep() {
+    struct Service(AtomicUsize);
+
+    let report = new_imperative_builder()
+        .add_dep(Dep::new(Service(AtomicUsize::new(0))))
+        .wait_for_dep::<Service, _, _>(Duration::from_secs(5), async |s: Dep<Service>| {
+            s.0.fetch_add(1, Ordering::Relaxed) >= 2
+        })
+        .add_step("call", async |s: Dep<Service>| {
+            s.0.load(Ordering::Relaxed) == 3
+        })
+        .add_step("call again", async |s: Dep<Service>| {
+            s.0.load(Ordering::Relaxed) == 3
+        })
+        .execute()
+        .await
+        .unwrap();
+    assert!(report["call"] && report["call again"]);
+
+    let report = new_imperative_builder()
+        .add_dep(Dep::new(Service(AtomicUsize::new(0))))
+        .wait_for_dep::<Service, _, _>(Duration::from_millis(100), async || false)
+        .add_step("unrelated", async || true)
+        .add_step("call", async |_: Dep<Service>| true)
+        .execute_report()
+        .await;
+    assert_eq!(
+        report.step("unrelated").unwrap().outcome,
+        StepOutcome::Succeeded
+    );
+    assert_eq!(report.step("call").unwrap().outcome, StepOutcome::Failed);
+    assert!(
+        matches!(
+            report.error,
+            Some(BuilderError::NotReady(ref s, ref dep, timeout))
+                if s == "call" && dep == "Dep<Service>" && timeout == Duration::from_millis(100)
+        ),
+        "{:?}",
+        report.error
+    );
+}
+
+// Groups which succeed if any step does should try their steps in turn,
+// skipping the rest once one succeeds, and fail only if none do.
+#[tokio::test]
+async fn test_any_of() {
+    let report = new_imperative_builder()
+        .new_group(|gb| {
+            gb.name("fetch")
+                .any_of()
+                .add_step("mirror a", async || false)
+                .add_step("mirror b", async || true)
+                .add_step("mirror c", async || true)
+        })
+        .new_group(|gb| {
+            gb.parallel()
+                .any_of()
+                .add_step("race a", async || false)
+                .add_step("race b", async || true)
+        })
+        .execute_report()
+        .await;
+    assert!(report.is_success(), "{:?}", report.error);
+    let outcome = |name| report.step(name).unwrap().outcome;
+    assert_eq!(outcome("mirror a"), StepOutcome::Failed);
+    assert_eq!(outcome("mirror b"), StepOutcome::Succeeded);
+    assert_eq!(outcome("mirror c"), StepOutcome::Skipped);
+    let mut outputs: Vec<_> = report.into_outputs().into_keys().collect();
+    outputs.sort();
+    assert_eq!(outputs, ["mirror b", "race b"]);
+
+    for parallel in [false, true] {
+        let e = new_imperative_builder()
+            .new_group(|gb| {
+                let gb = gb
+                    .any_of()
+                    .add_step("mirror a", async || false)
+                    .add_step("mirror b", async || false);
+                if parallel { gb.parallel() } else { gb }
+            })
+            .execute()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&e, BuilderError::NoneSucceeded(errors) if errors.len() == 2),
+            "{e:?}"
+        );
+    }
+
+    // all_of is the default, failing on the first failure
+    let e = new_imperative_builder()
+        .new_group(|gb| {
+            gb.any_of()
+                .all_of()
+                .add_step("mirror a", async || false)
+                .add_step("mirror b", async || true)
+        })
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&e, BuilderError::UnknownStep(name) if name == "mirror a"),
+        "{e:?}"
+    );
+}
+
+// Fallbacks should only run when their primary fails, and the pair should
+// succeed if either does, in sequential and parallel groups alike.
+#[tokio::test]
+async fn test_fallback_for() {
+    for parallel in [false, true] {
+        let report = new_any_builder()
+            .new_group(|gb| {
+                let gb = gb
+                    .add_step("primary cdn", any_output(async || false))
+                    .add(
+                        new_step("backup cdn", any_output(async || 1_u32))
+                            .fallback_for("primary cdn"),
+                    )
+                    .add_step("cache", any_output(async || 2_u32))
+                    .add(new_step("no cache", any_output(async || 3_u32)).fallback_for("cache"));
+                if parallel { gb.parallel() } else { gb }
+            })
+            .new_group(|gb| {
+                gb.add(
+                    new_step("publish", any_output(async || 4_u32))
+                        .requires_success_of(["primary cdn"]),
+                )
+            })
+            .execute_report()
+            .await;
+        assert!(report.is_success(), "{:?}", report.error);
+        let outcome = |name| report.step(name).unwrap().outcome;
+        assert_eq!(outcome("primary cdn"), StepOutcome::Failed);
+        assert_eq!(outcome("backup cdn"), StepOutcome::Succeeded);
+        assert_eq!(outcome("no cache"), StepOutcome::Skipped);
+        assert_eq!(outcome("publish"), StepOutcome::Succeeded);
+        assert_eq!(
+            report.step("backup cdn").unwrap().fallback_for.as_deref(),
+            Some("primary cdn")
+        );
+        let outputs = Outputs::from(report.into_outputs());
+        assert_eq!(outputs.get::<u32>("backup cdn"), Some(&1));
+        assert_eq!(outputs.get::<Skipped>("no cache"), Some(&Skipped));
+    }
+
+    // if both fail, so does the group, with the fallback's error
+    let e = new_any_builder()
+        .new_group(|gb| {
+            gb.add_step("primary cdn", any_output(async || false))
+                .add(new_step("backup cdn", any_output(async || false)).fallback_for("primary cdn"))
+        })
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&e, BuilderError::UnknownStep(name) if name == "backup cdn"),
+        "{e:?}"
+    );
+
+    let res = new_any_builder()
+        .new_group(|gb| {
+            gb.add(new_step("backup cdn", any_output(async || true)).fallback_for("primary cdn"))
+        })
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::DependsOn(s, dep)) if s == "backup cdn" && dep == "primary cdn")
+    );
+}
+
+// Once steps using a dependency spend its retry limit, its circuit breaker
+// should open, skipping degradable steps and failing the rest.
+#[tokio::test]
+async fn test_dep_retry_limit() {
+    struct Payments;
+    static CNT: AtomicUsize = AtomicUsize::new(0);
+
+    let report = new_any_builder()
+        .add_dep(Dep::new(Payments))
+        .dep_retry_limit::<Payments>(2)
+        .new_group(|gb| {
+            gb.add_step(
+                "charge",
+                any_output(async |_: Dep<Payments>| {
+                    CNT.fetch_add(1, Ordering::Relaxed);
+                    false
+                }),
+            )
+            .add(new_step("refund", any_output(async |_: Dep<Payments>| 1_u32)).degradable())
+            .add_step("notify", any_output(async || 2_u32))
+            .add_step("audit", any_output(async |_: Dep<Payments>| 3_u32))
+            .retry(5, Duration::from_millis(1))
+            .tolerate_failure()
+        })
+        .execute_report()
+        .await;
+
+    // the first attempt plus the two retries the breaker allows
+    assert_eq!(CNT.load(Ordering::Relaxed), 3);
+    assert_eq!(report.step("charge").unwrap().outcome, StepOutcome::Failed);
+    assert_eq!(report.step("refund").unwrap().outcome, StepOutcome::Skipped);
+    assert_eq!(
+        report.step("notify").unwrap().outcome,
+        StepOutcome::Succeeded
+    );
+    assert_eq!(report.step("audit").unwrap().outcome, StepOutcome::Failed);
+    assert!(
+        matches!(
+            report.error,
+            Some(BuilderError::CircuitOpen(ref s, ref dep)) if s == "audit" && dep == "Dep<Payments>"
+        ),
+        "{:?}",
+        report.error
+    );
+}
+
+// A retry denied by one dependency's limit shouldn't spend the limits of
+// the step's other dependencies.
+#[tokio::test]
+async fn test_dep_retry_limit_denied() {
+    struct Payments;
+    struct Ledger;
+    static CNT: AtomicUsize = AtomicUsize::new(0);
+
+    let report = new_any_builder()
+        .add_dep(Dep::new(Payments))
+        .add_dep(Dep::new(Ledger))
+        .dep_retry_limit::<Payments>(1)
+        .dep_retry_limit::<Ledger>(0)
+        .new_group(|gb| {
+            gb.add_step(
+                "charge",
+                any_output(async |_: Dep<Payments>, _: Dep<Ledger>| false),
+            )
+            .retry(5, Duration::from_millis(1))
+            .tolerate_failure()
+        })
+        .new_group(|gb| {
+            gb.add_step(
+                "capture",
+                any_output(async |_: Dep<Payments>| {
+                    CNT.fetch_add(1, Ordering::Relaxed);
+                    false
+                }),
+            )
+            .retry(5, Duration::from_millis(1))
+            .tolerate_failure()
+        })
+        .execute_report()
+        .await;
+
+    // Payments' one retry is still left for "capture"
+    assert_eq!(CNT.load(Ordering::Relaxed), 2);
+    assert_eq!(report.step("charge").unwrap().outcome, StepOutcome::Failed);
+    assert_eq!(report.step("capture").unwrap().outcome, StepOutcome::Failed);
+}
+
+// Circuit breakers should stop steps after repeated failures, across runs,
+// and close again once a probe succeeds after their cooldown.
+#[tokio::test]
+async fn test_circuit_breaker() {
+    use futures::StreamExt;
+
+    static CNT: AtomicUsize = AtomicUsize::new(0);
+    static HEALTHY: AtomicBool = AtomicBool::new(false);
+
+    let breaker = CircuitBreaker::for_tag("payments")
+        .failures(2)
+        .cooldown(Duration::from_millis(50));
+    let pipeline = || {
+        new_imperative_builder()
+            .circuit_breaker(breaker.clone())
+            .new_group(|gb| {
+                gb.add(
+                    new_step("charge", async || {
+                        CNT.fetch_add(1, Ordering::Relaxed);
+                        HEALTHY.load(Ordering::Relaxed)
+                    })
+                    .tag("payments"),
+                )
+                .add_step("notify", async || true)
+                .retry(5, Duration::from_millis(1))
+                .tolerate_failure()
+            })
+    };
+    let changes = |events: Vec<PipelineEvent>| -> Vec<_> {
+        events
+            .into_iter()
+            .filter_map(|e| match e {
+                PipelineEvent::CircuitChanged { breaker, state } => Some((breaker, state)),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // retries stop once the breaker opens
+    let (events, run) = pipeline().execute_streaming();
+    let (events, report) = tokio::join!(events.collect::<Vec<_>>(), run);
+    assert_eq!(report.step("charge").unwrap().outcome, StepOutcome::Failed);
+    assert_eq!(CNT.load(Ordering::Relaxed), 2);
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert_eq!(
+        changes(events),
+        [("payments".to_string(), CircuitState::Open)]
+    );
+
+    // while open, later runs don't call it
+    let report = pipeline().execute_report().await;
+    assert_eq!(CNT.load(Ordering::Relaxed), 2);
+    assert!(
+        matches!(
+            report.error,
+            Some(BuilderError::CircuitOpen(ref s, ref b)) if s == "charge" && b == "payments"
+        ),
+        "{:?}",
+        report.error
+    );
+
+    // after the cooldown, a successful probe closes it
+    sleep(Duration::from_millis(60)).await;
+    HEALTHY.store(true, Ordering::Relaxed);
+    let (events, run) = pipeline().execute_streaming();
+    let (events, report) = tokio::join!(events.collect::<Vec<_>>(), run);
+    assert!(report.is_success(), "{:?}", report.error);
+    assert_eq!(CNT.load(Ordering::Relaxed), 3);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+    assert_eq!(
+        changes(events),
+        [
+            ("payments".to_string(), CircuitState::HalfOpen),
+            ("payments".to_string(), CircuitState::Closed),
+        ]
+    );
+}
+
+// Groups over their output budget should start no more steps until sinks
+// drain enough output.
+#[tokio::test]
+async fn test_output_budget() {
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+    let budget = OutputBudget::new(10);
+    let drainer = {
+        let budget = budget.clone();
+        tokio::spawn(async move {
+            while budget.retained() <= 10 {
+                sleep(Duration::from_millis(5)).await;
+            }
+            // the group is paused until output is released
+            sleep(Duration::from_millis(20)).await;
+            assert_eq!(STARTED.load(Ordering::Relaxed), 2);
+            budget.release(16);
+        })
+    };
+    let step = |name: &str| {
+        let budget = budget.clone();
+        new_step(name, move || {
+            let budget = budget.clone();
+            async move {
+                // the third step only starts once the first two are drained
+                if STARTED.fetch_add(1, Ordering::Relaxed) == 2 {
+                    assert_eq!(budget.retained(), 0);
+                }
+                true
+            }
+        })
+        .output_size(|_| 8)
+    };
+
+    let res = new_imperative_builder()
+        .new_group(|gb| {
+            gb.add(step("a"))
+                .add(step("b"))
+                .add(step("c"))
+                .add(step("d"))
+                .scheduler(Bounded(1))
+                .output_budget(&budget)
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    drainer.await.unwrap();
+    assert!(res.values().all(|&r| r));
+    assert_eq!(STARTED.load(Ordering::Relaxed), 4);
+    assert_eq!(budget.retained(), 16);
+}
+
+// Runners defined asynchronously should have every step registered across
+// awaits, and fail if their definition does.
+#[tokio::test]
+async fn test_define() {
+    async fn list_tables() -> Vec<&'static str> {
+        sleep(Duration::from_millis(1)).await;
+        vec!["users", "orders"]
+    }
+
+    let builder = define(|reg| async move {
+        reg.dep(Dep::new(Database));
+        for table in list_tables().await {
+            reg.step(&format!("migrate {table}"), async |_: Dep<Database>| true);
+        }
+        reg.group(|gb| gb.add_step("verify", async || true));
+        Ok::<_, std::io::Error>(())
+    })
+    .await
+    .unwrap();
+    let res = builder.execute().await.unwrap();
+    assert_eq!(res.len(), 3);
+    assert!(res["migrate users"] && res["migrate orders"] && res["verify"]);
+
+    let res = define(|reg| async move {
+        reg.step("unreachable", async || true);
+        Err::<(), _>(std::io::Error::other("no tables"))
+    })
+    .await;
+    assert!(
+        matches!(&res, Err(BuilderError::Define(e)) if e.to_string() == "no tables"),
+        "{res:?}"
+    );
+}
+
+// Exported reports should serialize outputs with the serializer for their
+// type, falling back to `Debug`.
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_report_to_json() {
+    use imperat::OutputSerializers;
+    use serde_json::json;
+
+    #[derive(Debug)]
+    struct Rows(Vec<u32>);
+
+    impl IntoStepOutcome for Rows {
+        fn error(self) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+            None
+        }
+
+        fn success(&self) -> bool {
+            true
+        }
+    }
+
+    let report = new_any_builder()
+        .add_step("count", any_output(async || 3_u32))
+        .add_step("load", any_output(async || Rows(vec![1, 2])))
+        .add_step("label", any_output(async || "done".to_string()))
+        .execute_report()
+        .await;
+    let serializers = OutputSerializers::new()
+        .register::<u32>()
+        .register_with(|rows: &Rows| json!({ "rows": rows.0.len() }));
+    let export = report.to_json(&serializers);
+
+    assert_eq!(export["success"], true);
+    let output = |name: &str| {
+        let steps = export["steps"].as_array().unwrap();
+        steps.iter().find(|s| s["name"] == name).unwrap()["output"].clone()
+    };
+    assert_eq!(output("count"), json!(3));
+    assert_eq!(output("load"), json!({ "rows": 2 }));
+    assert!(output("label").as_str().unwrap().contains("String"));
+
+    let report = new_imperative_builder()
+        .add_step("check", async || true)
+        .execute_report()
+        .await;
+    let export = report.to_json(&OutputSerializers::new());
+    assert_eq!(export["steps"][0]["outcome"], "Succeeded");
+    assert_eq!(export["steps"][0]["output"], "true");
+}
+
+// JUnit exports should have a suite per group and a test case per step,
+// with failed and skipped steps marked as such.
+#[tokio::test]
+async fn test_report_to_junit() {
+    let b = new_imperative_builder();
+    let controller = b.controller();
+    let report = b
+        .with_settings(ProfileSettings {
+            tolerate_failure: true,
+            ..ProfileSettings::default()
+        })
+        .add_step("build", async || true)
+        .new_group(|gb| {
+            let controller = controller.clone();
+            gb.name("checks")
+                .add_step("lint <fast>", async || false)
+                .add_step("drains", move || {
+                    controller.drain();
+                    async { true }
+                })
+                .add_step("test", async || true)
+        })
+        .execute_report()
+        .await;
+
+    let xml = report.to_junit();
+    assert!(xml.starts_with("<?xml"), "{xml}");
+    assert!(
+        xml.contains(r#"tests="4" failures="1" errors="0" skipped="1""#),
+        "{xml}"
+    );
+    assert!(xml.contains(r#"<testsuite name="0" tests="1""#), "{xml}");
+    assert!(
+        xml.contains(r#"<testsuite name="checks" tests="3""#),
+        "{xml}"
+    );
+    assert!(
+        xml.contains(r#"<testcase name="lint &lt;fast&gt;" classname="checks""#),
+        "{xml}"
+    );
+    assert!(xml.contains("<failure"), "{xml}");
+    assert!(xml.contains("<skipped"), "{xml}");
+    assert!(xml.trim_end().ends_with("</testsuites>"), "{xml}");
+}
+
+// Markdown and HTML summaries should show each step's outcome, the errors
+// of those which failed, and a timing chart, escaping step names.
+#[tokio::test]
+async fn test_report_to_markdown_and_html() {
+    type Res = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    let report = new_imperative_builder::<Res>()
+        .with_settings(ProfileSettings {
+            tolerate_failure: true,
+            ..ProfileSettings::default()
+        })
+        .add_step("build", async || {
+            sleep(Duration::from_millis(5)).await;
+            Res::Ok(())
+        })
+        .add_step("lint <a|b>", async || Res::Err("2 warnings".into()))
+        .execute_report()
+        .await;
+
+    let md = report.to_markdown();
+    assert!(
+        md.starts_with(&format!("## Run {} succeeded", report.run_id)),
+        "{md}"
+    );
+    assert!(md.contains("| build | 0 | ✅ succeeded |"), "{md}");
+    assert!(md.contains("| lint <a\\|b> | 0 | ❌ failed |"), "{md}");
+    assert!(md.contains("### Failures"), "{md}");
+    assert!(md.contains("2 warnings"), "{md}");
+    assert!(md.contains("### Timing"), "{md}");
+    assert!(
+        md.contains(&format!("build       {}", "█".repeat(30))),
+        "{md}"
+    );
+
+    let html = report.to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+    assert!(html.contains("<td>lint &lt;a|b&gt;</td>"), "{html}");
+    assert!(html.contains("<tr class=\"failed\">"), "{html}");
+    assert!(html.contains("2 warnings"), "{html}");
+    assert!(html.contains("width: 100%"), "{html}");
+    assert!(html.trim_end().ends_with("</html>"), "{html}");
+}
+
+// A deterministic runner should interleave parallel steps the same way on
+// every run, and finish virtual sleeps in order without waiting for them.
+#[test]
+fn test_deterministic_runner() {
+    let run = || {
+        let runner = DeterministicRunner::new();
+        let order = Dep::new(Mutex::new(vec![]));
+        let step = |name: &'static str, ms: u64| {
+            move |r: DeterministicRunner, order: Dep<Mutex<Vec<String>>>| async move {
+                order.lock().unwrap().push(format!("{name} start"));
+                tokio::task::yield_now().await;
+                r.sleep(Duration::from_millis(ms)).await;
+                order.lock().unwrap().push(format!("{name} @{:?}", r.now()));
+            }
+        };
+        let builder = runner
+            .install(new_unit_builder())
+            .add_dep(order.clone())
+            .new_group(|g| {
+                g.parallel()
+                    .add_step("a", step("a", 30))
+                    .add_step("b", step("b", 10))
+                    .add_step("c", step("c", 20))
+            });
+        let st = Instant::now();
+        runner.run(builder.execute()).unwrap();
+        assert!(st.elapsed() < Duration::from_secs(1));
+        assert_eq!(runner.now(), Duration::from_millis(30));
+        let order: Vec<String> = order.lock().unwrap().clone();
+        order
+    };
+
+    let order = run();
+    assert_eq!(
+        order,
+        [
+            "a start", "b start", "c start", "b @10ms", "c @20ms", "a @30ms"
+        ]
+    );
+    assert_eq!(order, run());
+
+    // timeouts elapse on the virtual clock too
+    let runner = DeterministicRunner::new();
+    let builder = runner.install(new_unit_builder()).add(
+        new_step("hang", async |r: DeterministicRunner| {
+            r.sleep(Duration::from_secs(3600)).await;
+        })
+        .timeout(Duration::from_secs(60)),
+    );
+    assert!(runner.run(builder.execute()).is_err());
+    assert_eq!(runner.now(), Duration::from_secs(60));
+
+    // timeouts of steps which finish in time are removed, as are finished
+    // tasks
+    let runner = DeterministicRunner::new();
+    let builder = runner.install(new_unit_builder()).new_group(|g| {
+        g.parallel().add(
+            new_step("quick", async |r: DeterministicRunner| {
+                r.sleep(Duration::from_secs(1)).await;
+            })
+            .timeout(Duration::from_secs(3600)),
+        )
+    });
+    runner.run(builder.execute()).unwrap();
+    assert_eq!(runner.now(), Duration::from_secs(1));
+    let state = format!("{runner:?}");
+    assert!(state.contains("tasks: 0, timers: 0"), "{state}");
+}
+
+// Golden reports should list steps in the order they were added, without
+// timings unless rounded, and match the golden file they were written to.
+#[tokio::test]
+async fn test_golden_report() {
+    let report = new_imperative_builder()
+        .add_step("build", async || true)
+        .new_group(|g| {
+            g.name("checks")
+                .parallel()
+                .tolerate_failure()
+                .add_step("lint", async || {
+                    sleep(Duration::from_millis(20)).await;
+                    false
+                })
+                .add_step("test", async |counters: Counters| {
+                    counters.add("cases", 3);
+                    true
+                })
+        })
+        .execute_report()
+        .await;
+
+    let golden = GoldenReport::new().render(&report);
+    assert_eq!(
+        golden,
+        "run: succeeded\n\
+         [0] build (build): Succeeded\n  output: true\n\
+         [checks] lint (lint): Failed\n  output: false\n\
+         [checks] test (test): Succeeded\n  output: true\n  counter cases: 3\n"
+    );
+    let rounded = GoldenReport::new()
+        .round_durations(Duration::from_secs(60))
+        .render(&report);
+    assert!(rounded.contains("[checks] lint (lint): Failed 0ns\n"));
+
+    let path = std::env::temp_dir().join(format!("imperat-golden-{}.txt", std::process::id()));
+    std::fs::write(&path, &golden).unwrap();
+    assert_golden(&path, &golden);
+    let mismatch = std::panic::catch_unwind(|| assert_golden(&path, "run: failed\n"));
+    std::fs::remove_file(&path).unwrap();
+    assert!(mismatch.is_err());
+}
+
+// Linting should find reused names, single step parallel groups, bindings
+// in tolerant groups and unused dependencies, and nothing in a clean runner.
+#[test]
+fn test_lint() {
+    struct Unused;
+    struct Token;
+    type Res<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    let builder = new_imperative_builder::<Res<()>>()
+        .add_dep(Dep::new(Database))
+        .add_dep(Dep::new(Unused))
+        .add_step("migrate", async |_: Dep<Database>| Ok(()))
+        .new_group(|g| {
+            g.name("auth")
+                .tolerate_failure()
+                .add_step_binding("login", async || Res::Ok(Token))
+                .add_step("migrate", async |_: Dep<Token>| Ok(()))
+        })
+        .new_group(|g| {
+            g.name("lonely")
+                .parallel()
+                .add_step("only", async || Ok(()))
+        });
+
+    let lints = builder.lint();
+    assert_eq!(
+        lints,
+        [
+            Lint::DuplicateName("migrate".to_string()),
+            Lint::TolerantBinding {
+                group: "auth".to_string(),
+                step: "login".to_string(),
+            },
+            Lint::SingleStepParallel("lonely".to_string()),
+            Lint::UnusedDep(DepInfo::of::<Dep<Unused>>()),
+        ]
+    );
+    assert_eq!(
+        lints[3].to_string(),
+        "dependency Dep<Unused> is added but never used"
+    );
+
+    let builder = new_unit_builder()
+        .add_dep(Dep::new(Database))
+        .add_step("migrate", async |_: Dep<Database>| {});
+    assert!(builder.lint().is_empty());
+}
+
+// Finalizers should run in order once the run is over, whether it succeeded
+// or failed, and fail runs which otherwise succeeded.
+#[tokio::test]
+async fn test_finalizers() {
+    type Log = Dep<Mutex<Vec<&'static str>>>;
+
+    let log: Log = Dep::new(Mutex::new(vec![]));
+    let finalizer = |name: &'static str, ok: bool| {
+        move |log: Log| async move {
+            log.lock().unwrap().push(name);
+            ok
+        }
+    };
+    let res = new_imperative_builder()
+        .add_dep(log.clone())
+        .add_step("build", async |log: Log| {
+            log.lock().unwrap().push("build");
+            false
+        })
+        .add_step("deploy", async || true)
+        .finalizer("first", finalizer("first", false))
+        .finalizer("second", finalizer("second", true))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::UnknownStep(name)) if name == "build"));
+    assert_eq!(*log.lock().unwrap(), ["build", "first", "second"]);
+
+    let res = new_imperative_builder()
+        .add_step("build", async || true)
+        .finalizer("clean up", async || false)
+        .execute()
+        .await;
+    assert!(matches!(res, Err(BuilderError::UnknownStep(name)) if name == "clean up"));
+}
+
+// Draining a run should let running steps finish and skip the rest, then
+// run finalizers and succeed with the outputs of the steps which ran.
+#[tokio::test]
+async fn test_drain() {
+    let cleaned_up = Arc::new(AtomicBool::new(false));
+    let b = new_imperative_builder();
+    let controller = b.controller();
+    let report = b
+        .add_step("first", async || true)
+        .add_step("drains", move || {
+            controller.drain();
+            async { true }
+        })
+        .add_step("skipped", async || true)
+        .new_group(|gb| {
+            gb.add_step("later", async || true)
+                .add_step("also later", async || true)
+                .parallel()
+        })
+        .finalizer("clean up", {
+            let cleaned_up = cleaned_up.clone();
+            move || {
+                cleaned_up.store(true, Ordering::Relaxed);
+                async {}
+            }
+        })
+        .execute_report()
+        .await;
+
+    assert!(report.error.is_none(), "{:?}", report.error);
+    assert!(report.drained);
+    assert!(cleaned_up.load(Ordering::Relaxed));
+    let outcomes: Vec<_> = report
+        .steps
+        .iter()
+        .map(|s| (s.name.as_str(), s.outcome))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("first", StepOutcome::Succeeded),
+            ("drains", StepOutcome::Succeeded),
+            ("skipped", StepOutcome::Skipped),
+            ("later", StepOutcome::Skipped),
+            ("also later", StepOutcome::Skipped),
+        ]
+    );
+    let mut outputs: Vec<_> = report.into_outputs().into_keys().collect();
+    outputs.sort();
+    assert_eq!(outputs, ["drains", "first"]);
+}
+
+// Finalizers should still run, with what steps bound, when the run is
+// dropped partway.
+#[tokio::test]
+async fn test_finalizers_on_drop() {
+    struct Lease(&'static str);
+    type Res<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+    let run = new_imperative_builder::<Res<()>>()
+        .add_step_binding("lease", async || Res::Ok(Lease("db")))
+        .add_step("hang", async |_: Dep<Lease>| {
+            sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        })
+        .finalizer("release", move |lease: Dep<Lease>| {
+            let tx = tx.lock().unwrap().take();
+            async move {
+                tx.unwrap().send(lease.0).unwrap();
+            }
+        })
+        .execute();
+
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), run)
+            .await
+            .is_err()
+    );
+    let released = tokio::time::timeout(Duration::from_secs(5), rx).await;
+    assert_eq!(released.unwrap().unwrap(), "db");
+
+    // a finalizer which was running when the run was dropped still finishes
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+    let run = new_imperative_builder()
+        .add_step("quick", async || true)
+        .finalizer("flush", move || {
+            let tx = tx.lock().unwrap().take();
+            async move {
+                sleep(Duration::from_millis(100)).await;
+                tx.unwrap().send("flushed").unwrap();
+            }
+        })
+        .execute();
+
+    assert!(
+        tokio::time::timeout(Duration::from_millis(20), run)
+            .await
+            .is_err()
+    );
+    let flushed = tokio::time::timeout(Duration::from_secs(5), rx).await;
+    assert_eq!(flushed.unwrap().unwrap(), "flushed");
+}
+
+// Steps returning a `StepReturn` should add its metrics to their counters,
+// list its artifacts, and publish its dependencies, only if they succeed.
+#[tokio::test]
+async fn test_step_return() {
+    #[derive(Debug)]
+    struct Digest(&'static str);
+
+    let report = new_any_builder()
+        .add_step(
+            "build",
+            any_output(async || {
+                StepReturn::new(true)
+                    .metric("files", 2)
+                    .metric("files", 3)
+                    .artifact("binary", "target/app")
+                    .publish(Digest("abc"))
+            }),
+        )
+        .add_step(
+            "upload",
+            any_output(async |digest: Option<Dep<Digest>>| {
+                digest.map_or_else(String::new, |d| d.0.to_string())
+            }),
+        )
+        .new_group(|g| {
+            g.tolerate_failure().add_step(
+                "lint",
+                any_output(async || StepReturn::new(false).metric("warnings", 1)),
+            )
+        })
+        .execute_report()
+        .await;
+
+    let build = report.step("build").unwrap();
+    assert_eq!(build.counters["files"], 5);
+    assert_eq!(
+        build.artifacts["binary"],
+        std::path::PathBuf::from("target/app")
+    );
+    let out = report.output(build).unwrap();
+    assert!(out.downcast_ref::<StepReturn<bool>>().unwrap().value);
+    assert!(report.step("lint").unwrap().counters.is_empty());
+    let upload = report.output(report.step("upload").unwrap()).unwrap();
+    assert_eq!(upload.downcast_ref::<String>().unwrap(), "abc");
+}
+
+// Steps sharing arguments should see dependencies published or scoped after
+// another step resolved them, as shared arguments are only cached until the
+// dependencies they were resolved from change.
+#[tokio::test]
+async fn test_shared_resolution() {
+    #[derive(Debug)]
+    struct Region(&'static str);
+
+    let region = async |region: Dep<Region>| region.0;
+    let report = new_any_builder()
+        .add_dep(Dep::new(Region("us")))
+        .new_group(|gb| {
+            gb.add_step("first", any_output(region))
+                .add_step("second", any_output(region))
+                .add_step(
+                    "move",
+                    any_output(async || StepReturn::new(()).publish(Region("eu"))),
+                )
+                .add_step("third", any_output(region))
+                .add(new_step("scoped", any_output(region)).scoped_dep(Dep::new(Region("ap"))))
+                .add_step("fourth", any_output(region))
+        })
+        .execute_report()
+        .await;
+    assert!(report.is_success(), "{:?}", report.error);
+    let outputs = Outputs::from(report.into_outputs());
+    let region = |name| *outputs.get::<&str>(name).unwrap();
+    assert_eq!(region("first"), "us");
+    assert_eq!(region("second"), "us");
+    assert_eq!(region("third"), "eu");
+    assert_eq!(region("scoped"), "ap");
+    assert_eq!(region("fourth"), "eu");
+}
+
+// Steps added without a name should be named after their fn, and closures,
+// which have no name, should be rejected.
+#[tokio::test]
+async fn test_add_step_auto() {
+    async fn load_config() -> bool {
+        true
+    }
+    async fn check<T: Default + PartialEq>() -> bool {
+        T::default() == T::default()
+    }
+
+    let res = new_imperative_builder()
+        .add_step_auto(load_config)
+        .new_group(|gb| gb.add_step_auto(check::<Vec<u8>>))
+        .execute()
+        .await
+        .unwrap();
+    let mut names: Vec<_> = res.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["check", "load_config"]);
+
+    // both closures would be named `test_add_step_auto`
+    let res = new_imperative_builder()
+        .add_step_auto(async || true)
+        .new_group(|gb| gb.add_step_auto(async || false))
+        .execute()
+        .await;
+    let Err(BuilderError::Build(errors)) = res else {
+        panic!("expected build errors, got {res:?}");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(
+        matches!(&errors[0], BuilderError::ClosureName(name)
+            if name.contains("test_add_step_auto::{{closure}}")),
+        "{errors:?}"
+    );
+    assert!(matches!(&errors[1], BuilderError::InGroup(_, e)
+        if matches!(**e, BuilderError::ClosureName(_))));
+}
+
+// Gated steps should wait for approval, and a run resumed from a checkpoint
+// should return to the same gate without running earlier steps again.
+#[tokio::test]
+async fn test_approval_resume() {
+    type Saved = (HashMap<String, u32>, HashMap<String, Approval>);
+    #[derive(Clone, Default)]
+    struct Memory(Arc<Mutex<Saved>>);
+
+    impl Checkpointer<u32> for Memory {
+        fn save(
+            &self,
+            key: &str,
+            output: &u32,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0.lock().unwrap().0.insert(key.to_string(), *output);
+            Ok(())
+        }
+
+        fn save_approval(
+            &self,
+            key: &str,
+            approval: &Approval,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            let mut state = self.0.lock().unwrap();
+            state.1.insert(key.to_string(), approval.clone());
+            Ok(())
+        }
+
+        fn load(&self) -> Result<Checkpoint<u32>, Box<dyn std::error::Error + Send + Sync>> {
+            let (outputs, approvals) = self.0.lock().unwrap().clone();
+            Ok(Checkpoint::from(outputs).with_approvals(approvals))
+        }
+    }
+
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+    let memory = Memory::default();
+    let pipeline = |approvals: &Approvals| {
+        new_imperative_builder()
+            .add_step("fetch", async || {
+                RAN.fetch_add(1, Ordering::SeqCst);
+                2_u32
+            })
+            .add(new_step("deploy", async || 3_u32).requires_approval())
+            .checkpoint(memory.clone())
+            .approvals(approvals.clone())
+    };
+
+    // the process stops while the gate is waiting
+    let approvals = Approvals::new();
+    let run = pipeline(&approvals).execute();
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), run)
+            .await
+            .is_err()
+    );
+    assert_eq!(approvals.waiting(), ["deploy"]);
+    let checkpoint = memory.load().unwrap();
+    assert_eq!(checkpoint.approval("deploy"), Some(&Approval::Pending));
+
+    // resuming returns to the gate, which is approved there
+    let approvals = Approvals::new();
+    let run = pipeline(&approvals).resume_from(checkpoint).execute();
+    let approve = async {
+        while approvals.waiting().is_empty() {
+            sleep(Duration::from_millis(1)).await;
+        }
+        approvals.approve("deploy");
+    };
+    let (res, ()) = tokio::join!(run, approve);
+    let res = res.unwrap();
+    assert_eq!((res["fetch"], res["deploy"]), (2, 3));
+    assert_eq!(RAN.load(Ordering::SeqCst), 1);
+    let checkpoint = memory.load().unwrap();
+    assert_eq!(checkpoint.approval("deploy"), Some(&Approval::Approved));
+
+    // an approval recorded before a crash isn't asked for again
+    let mut outputs = HashMap::new();
+    outputs.insert("fetch".to_string(), 2);
+    let checkpoint = Checkpoint::from(outputs).with_approvals(
+        [("deploy".to_string(), Approval::Approved)]
+            .into_iter()
+            .collect(),
+    );
+    let run = pipeline(&Approvals::new())
+        .resume_from(checkpoint)
+        .execute();
+    let res = tokio::time::timeout(Duration::from_secs(5), run).await;
+    assert_eq!(res.unwrap().unwrap()["deploy"], 3);
+
+    let approvals = Approvals::new();
+    approvals.reject("deploy", "change freeze");
+    let res = pipeline(&approvals).execute().await;
+    assert!(matches!(
+        res,
+        Err(BuilderError::Rejected(name, reason)) if name == "deploy" && reason == "change freeze"
+    ));
+}
+
+// Replays should play a run's events back in order, waiting as long as the
+// run did unless fast-forwarded or stepped through.
+#[tokio::test]
+async fn test_replay() {
+    let report = new_imperative_builder()
+        .add_step("setup", async || true)
+        .new_group(|gb| {
+            gb.name("slow").add_step("wait", async || {
+                sleep(Duration::from_millis(200)).await;
+                false
+            })
+        })
+        .execute_report()
+        .await;
+
+    let mut replay = report.replay().speed(ReplaySpeed::StepByStep);
+    let mut events = vec![];
+    while let Some(event) = replay.next_event().await {
+        events.push(event);
+    }
+    let names: Vec<_> = events
+        .iter()
+        .map(|e| match e {
+            PipelineEvent::GroupStarted { group } => format!("group {group}"),
+            PipelineEvent::StepStarted { name, .. } => format!("start {name}"),
+            PipelineEvent::StepFinished { name, success, .. } => format!("{name} {success}"),
+            PipelineEvent::PipelineFinished { success, .. } => format!("finished {success}"),
+            e => panic!("unexpected event {e:?}"),
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "group 0",
+            "start setup",
+            "setup true",
+            "group slow",
+            "start wait",
+            "wait false",
+            "finished false"
+        ]
+    );
+
+    let start = Instant::now();
+    let mut replay = report.replay().speed(ReplaySpeed::FastForward(100.0));
+    while replay.next_event().await.is_some() {}
+    assert!(start.elapsed() < Duration::from_millis(150));
+
+    let start = Instant::now();
+    let mut replay = report.replay();
+    while replay.next_event().await.is_some() {}
+    assert!(start.elapsed() >= Duration::from_millis(150));
+}
+
+// Steps should resolve credentials scoped to them from the builder's auth
+// provider, such as by a tenant tag on the step or its group.
+#[tokio::test]
+async fn test_auth_context() {
+    struct TenantTokens;
+
+    impl AuthProvider for TenantTokens {
+        type Token = String;
+
+        fn token<'a>(
+            &'a self,
+            step: &'a StepInfo,
+        ) -> futures::future::BoxFuture<'a, Result<String, Box<dyn std::error::Error + Send + Sync>>>
+        {
+            Box::pin(async move {
+                let tenant = step
+                    .tags()
+                    .iter()
+                    .find_map(|t| t.strip_prefix("tenant:"))
+                    .ok_or("no tenant")?;
+                Ok(format!("{tenant}-{}", step.name()))
+            })
+        }
+    }
+
+    let token =
+        async |auth: AuthContext<String>| auth.token().await.unwrap_or_else(|e| e.to_string());
+    let res = new_imperative_builder()
+        .auth_provider(TenantTokens)
+        .add(new_step("acme", token).tag("tenant:acme"))
+        .add_step("untagged", token)
+        .new_group(|gb| gb.tag("tenant:globex").add_step("sync", token))
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["acme"], "acme-acme");
+    assert_eq!(res["sync"], "globex-sync");
+    assert_eq!(res["untagged"], "no tenant");
+
+    // steps can't request credentials without a provider
+    let res = new_imperative_builder()
+        .add_step("acme", async |_: AuthContext<String>| {})
+        .execute()
+        .await;
+    assert!(
+        matches!(res, Err(BuilderError::MissingParam(..))),
+        "{res:?}"
+    );
+}
+
+// Interactive steps should be answered from a script in tests, and the
+// terminal implementation should ask again until it gets a valid answer.
+#[tokio::test]
+async fn test_interact() {
+    let provision = async |i: Interaction| {
+        let name = i.prompt("name").await?;
+        let size = i.select("size", &["small", "large"]).await?;
+        let create = i.confirm(&format!("create {name}?"), false).await?;
+        Ok::<_, std::io::Error>(create.then(|| format!("{name} ({size})")))
+    };
+
+    let script = ScriptedInteract::new()
+        .text("corgi")
+        .select(1)
+        .confirm(true);
+    let res = new_imperative_builder()
+        .interact(script.clone())
+        .add_step("provision", provision)
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(
+        res["provision"].as_ref().unwrap().as_deref(),
+        Some("corgi (1)")
+    );
+    assert_eq!(script.asked(), ["name", "size", "create corgi?"]);
+    assert_eq!(script.remaining(), 0);
+
+    // answers for the wrong question, or running out, fail the step
+    let script = ScriptedInteract::new().confirm(true);
+    let e = new_imperative_builder()
+        .interact(script)
+        .add_step("provision", provision)
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("script answers 'name'"), "{e}");
+
+    let input = std::io::Cursor::new("corgi\n9\n2\nmaybe\n\n");
+    let res = new_imperative_builder()
+        .interact(TerminalInteract::with_io(input, std::io::sink()))
+        .add_step("provision", provision)
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(res["provision"].as_ref().unwrap(), &None);
+
+    let input = std::io::Cursor::new("");
+    let e = new_imperative_builder()
+        .interact(TerminalInteract::with_io(input, std::io::sink()))
+        .add_step("provision", provision)
+        .execute()
+        .await
+        .unwrap_err();
+    assert!(e.to_string().contains("provision"), "{e}");
+}
+
+// A live view should count each group's steps as they run, and redraw in
+// place.
+#[cfg(feature = "tui")]
+#[tokio::test]
+async fn test_live_view() {
+    use imperat::tui::LiveView;
+
+    let builder = new_imperative_builder()
+        .add_step("fetch", async || true)
+        .new_group(|gb| {
+            gb.name("checks")
+                .add_step("lint", async || true)
+                .add_step("audit", async || false)
+        });
+    let view = LiveView::new(&builder.plan());
+    assert!(view.render().starts_with(
+        "0: 0 running, 1 queued, 0 done [0.0ns]\nchecks: 0 running, 2 queued, 0 done [0.0ns]\n"
+    ));
+
+    let (events, run) = builder.execute_streaming();
+    let mut out = vec![];
+    let (res, _) = tokio::join!(view.follow(events, &mut out), run);
+    res.unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let last = out.rsplit("\x1b[J").next().unwrap();
+    assert!(last.starts_with("0: 0 running, 0 queued, 1 done ["));
+    assert!(last.contains("\nchecks: 0 running, 0 queued, 2 done (1 failed) ["));
+    assert!(last.contains("\nfailed in "));
+}
+
+// Short ids should be stable hashes of a step's group and name, shared by
+// plans and reports, and usable to look steps up and as result keys.
+#[tokio::test]
+async fn test_short_ids() {
+    let build = || {
+        new_imperative_builder()
+            .add_step("a", async || true)
+            .new_group(|g| g.name("deploy").add_step("a", async || true))
+    };
+
+    let plan = build().plan();
+    let ids: Vec<_> = plan.steps().map(|(_, s)| s.short_id.clone()).collect();
+    assert_eq!(ids, ["4eb54118", "35187928"]);
+
+    let report = build().execute_report().await;
+    assert_eq!(report.steps[0].short_id, "4eb54118");
+    assert_eq!(report.step("35187928").unwrap().group, "deploy");
+
+    let mut keys: Vec<_> = build()
+        .key_strategy(KeyStrategy::ShortId)
+        .execute()
+        .await
+        .unwrap()
+        .into_keys()
+        .collect();
+    keys.sort();
+    assert_eq!(keys, ["35187928", "4eb54118"]);
+}
+
+// Boxed steps should run a future built outside the builder, and fail if
+// retried as it can only run once.
+#[tokio::test]
+async fn test_boxed_step() {
+    use futures::FutureExt;
+
+    let report = new_imperative_builder()
+        .add_boxed_step("prebuilt", async { true }.boxed())
+        .new_group(|gb| {
+            gb.tolerate_failure()
+                .retry(1, Duration::ZERO)
+                .add_boxed_step("flaky", async { false }.boxed())
+        })
+        .execute_report()
+        .await;
+
+    assert_eq!(report.get("prebuilt"), Some(&true));
+    let flaky = report.step("flaky").unwrap();
+    assert_eq!(flaky.outcome, StepOutcome::Failed);
+    assert!(
+        flaky
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("can't run more than once")
+    );
+}
+
+// Trait object steps should run under their own name, and again on each
+// retry.
+#[tokio::test]
+async fn test_dyn_step() {
+    use futures::future::BoxFuture;
+    use imperat::DynStep;
+
+    struct Plugin {
+        name: String,
+        runs: AtomicUsize,
+        failures: usize,
+    }
+
+    impl DynStep<bool> for Plugin {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn run(&self) -> BoxFuture<'_, bool> {
+            Box::pin(async { self.runs.fetch_add(1, Ordering::Relaxed) >= self.failures })
+        }
+    }
+
+    let plugin = |name: &str, failures| {
+        Box::new(Plugin {
+            name: name.to_string(),
+            runs: AtomicUsize::new(0),
+            failures,
+        })
+    };
+    let res = new_imperative_builder()
+        .add_dyn_step(plugin("loaded", 0))
+        .new_group(|gb| {
+            gb.retry(1, Duration::ZERO)
+                .add_dyn_step(plugin("retried", 1))
+        })
+        .execute()
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), 2);
+    assert!(res["loaded"] && res["retried"]);
+}
+
+// Dependencies in a sync type map should be shareable by pipelines built on
+// other threads.
+#[test]
+fn test_sync_deps() {
+    let mut deps = SyncTypeMap::new();
+    deps.bind(Dep::new("prod".to_string()));
+
+    let handles: Vec<_> = (0..2)
+        .map(|i| {
+            let deps = deps.clone();
+            std::thread::spawn(move || {
+                let builder = new_imperative_builder()
+                    .executor(ThreadExecutor)
+                    .add_sync_deps(&deps)
+                    .add_dep(Dep::new(i))
+                    .add_step("deploy", async |env: Dep<String>, i: Dep<i32>| {
+                        format!("{}-{}", *env, *i)
+                    });
+                futures::executor::block_on(builder.execute()).unwrap()["deploy"].clone()
+            })
+        })
+        .collect();
+    let out: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(out, ["prod-0", "prod-1"]);
+
+    // dependencies already added aren't replaced
+    let res = new_imperative_builder()
+        .add_dep(Dep::new("dev".to_string()))
+        .add_sync_deps(&deps)
+        .add_step("deploy", async |env: Dep<String>| env.to_string())
+        .validate();
+    assert!(matches!(&res.unwrap_err()[..], [BuilderError::AddDep(_)]));
+}
+
+// Steps sharing a name should all keep their outputs when collected.
+#[tokio::test]
+async fn test_execute_all() {
+    async fn shard(i: usize) -> usize {
+        i * 10
+    }
+
+    let build = || {
+        new_imperative_builder()
+            .add_step("merge", async || 100)
+            .new_group(|gb| {
+                (0..3).fold(gb.parallel(), |gb, i| {
+                    gb.add_step_with_args("shard", shard, i)
+                })
+            })
+    };
+
+    let res = build().execute_all().await.unwrap();
+    assert_eq!(res["shard"], [0, 10, 20]);
+    assert_eq!(res["merge"], [100]);
+
+    // `execute` keeps the last
+    assert_eq!(build().execute().await.unwrap()["shard"], 20);
+}
+
+// Groups should run in the configured order, with preflight checks first.
+#[tokio::test]
+async fn test_group_order() {
+    let build = |order| {
+        let log: Dep<Mutex<Vec<&str>>> = Dep::new(Mutex::new(vec![]));
+        let step = |name: &'static str| {
+            move |log: Dep<Mutex<Vec<&'static str>>>| async move {
+                log.lock().unwrap().push(name);
+                true
+            }
+        };
+        let builder = new_imperative_builder()
+            .add_dep(log.clone())
+            .group_order(order)
+            .add_preflight("check", step("check"))
+            .add_step("teardown", step("teardown"))
+            .new_group(|gb| gb.name("build").add_step("build", step("build")))
+            .new_group(|gb| gb.add_step("test", step("test")));
+        (builder, log)
+    };
+    let order = async |order| {
+        let (builder, log) = build(order);
+        builder.execute().await.unwrap();
+        log.lock().unwrap().clone()
+    };
+
+    assert_eq!(
+        order(GroupOrder::default()).await,
+        ["check", "teardown", "build", "test"]
+    );
+    assert_eq!(
+        order(GroupOrder::DefaultLast).await,
+        ["check", "build", "test", "teardown"]
+    );
+    let custom = GroupOrder::Labels(vec!["2".into(), "0".into()]);
+    let (builder, _) = build(custom.clone());
+    let plan: Vec<_> = builder
+        .plan()
+        .steps()
+        .map(|(_, s)| s.name.clone())
+        .collect();
+    assert_eq!(plan, ["check", "test", "teardown", "build"]);
+    assert_eq!(order(custom).await, ["check", "test", "teardown", "build"]);
+
+    let (builder, _) = build(GroupOrder::Labels(vec!["deploy".into()]));
+    assert!(matches!(
+        builder.execute().await,
+        Err(BuilderError::UnknownGroup(label)) if label == "deploy"
+    ));
+}
+
+// Steps' random numbers should be reproducible from the run's seed, and
+// differ between steps.
+#[tokio::test]
+async fn test_run_rng() {
+    let run = |seed| {
+        new_imperative_builder()
+            .seed(seed)
+            .add_step("sample", async |mut rng: RunRng| rng.next_u64())
+            .add_step("jitter", async |mut rng: RunRng| rng.next_u64())
+            .execute_report()
+    };
+
+    let report = run(7).await;
+    assert_eq!(report.seed, 7);
+    let again = run(7).await;
+    assert_eq!(report.get("sample"), again.get("sample"));
+    assert_ne!(report.get("sample"), report.get("jitter"));
+    assert_ne!(report.get("sample"), run(8).await.get("sample"));
+
+    let report = new_imperative_builder()
+        .add_step("shuffle", async |mut rng: RunRng| {
+            let mut items = [1, 2, 3, 4, 5];
+            rng.shuffle(&mut items);
+            assert!((0.0..1.0).contains(&rng.next_f64()));
+            assert!(rng.below(3) < 3);
+            items.iter().sum::<i32>()
+        })
+        .execute_report()
+        .await;
+    assert_eq!(report.seed, report.run_id);
+    assert_eq!(report.get("shuffle"), Some(&15));
+}
+
+// Tolerant groups should still fail on failures their policy escalates.
+#[tokio::test]
+async fn test_escalate_if() {
+    use std::io::{Error as IoError, ErrorKind};
+
+    let build = |parallel: bool| {
+        new_imperative_builder().new_group(move |gb| {
+            let gb = if parallel { gb.parallel() } else { gb };
+            gb.tolerate_failure()
+                .escalate_if(|_, out: &Result<(), IoError>| {
+                    matches!(out, Err(e) if e.kind() == ErrorKind::InvalidData)
+                })
+                .add_step("flaky", async || Err(IoError::from(ErrorKind::TimedOut)))
+                .add_step("checksum", async || {
+                    Err(IoError::new(ErrorKind::InvalidData, "corrupt row"))
+                })
+                .add_step("after", async || Ok(()))
+        })
+    };
+
+    for parallel in [false, true] {
+        let report = build(parallel).execute_report().await;
+        assert!(matches!(
+            &report.error,
+            Some(BuilderError::Step(name, e)) if name == "checksum" && e.to_string() == "corrupt row"
+        ));
+        assert_eq!(report.step("flaky").unwrap().outcome, StepOutcome::Failed);
+        // sequential groups stop at the escalated failure
+        assert_eq!(report.step("after").is_some(), parallel);
+    }
+}
+
+// Steps registered to a pipeline from anywhere in the binary should be
+// added in a stable order, and registrations for another output type
+// should fail the build.
+#[cfg(feature = "inventory")]
+mod registered {
+    use imperat::{BuilderError, ImperativeStepBuilder, prelude::*, register_steps};
+
+    register_steps!("registered", |b: ImperativeStepBuilder<u32>| {
+        b.add_step("first", async || 1)
+    });
+    register_steps!("registered", |b: ImperativeStepBuilder<u32>| {
+        b.add_step("second", async || 2)
+    });
+    register_steps!("mismatched", |b: ImperativeStepBuilder<u32>| b);
+
+    #[tokio::test]
+    async fn test_registered_steps() {
+        let builder = new_imperative_builder::<u32>().add_registered("registered");
+        let names: Vec<_> = builder.plan().groups[0]
+            .steps
+            .iter()
+            .map(|s| s.name.clone())
+            .collect();
+        assert_eq!(names, ["first", "second"]);
+        let res = builder.execute().await.unwrap();
+        assert_eq!(res["first"] + res["second"], 3);
+
+        // unknown pipelines add nothing
+        let res = new_imperative_builder::<u32>()
+            .add_registered("unknown")
+            .execute()
+            .await
+            .unwrap();
+        assert!(res.is_empty());
+
+        let res = new_imperative_builder::<bool>()
+            .add_registered("mismatched")
+            .execute()
+            .await;
+        assert!(matches!(res, Err(BuilderError::Define(_))), "{res:?}");
+    }
+}